@@ -0,0 +1,104 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use solana_deposit_withdraw::{DepositAccount, DepositInstruction};
+use solana_geyser_subscription::{
+    filter,
+    geyser::{subscribe_update::Update, Block, Ping, SubscribeUpdate},
+};
+use solana_sdk::{
+    hash::Hash,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use solana_token_transfer::{load_keypair_from_secret, transfer::transfer_instructions};
+
+/// Base58 secret key as found in task2/task3 configs
+fn keypair_decode(c: &mut Criterion) {
+    let secret = bs58::encode(Keypair::new().to_bytes()).into_string();
+
+    c.bench_function("keypair_decode", |b| {
+        b.iter(|| load_keypair_from_secret(black_box(&secret)).unwrap())
+    });
+}
+
+/// What task3 does for every new block: build the transfer (with memo) and sign it
+fn transaction_build_sign(c: &mut Criterion) {
+    let source = Keypair::new();
+    let destination = Pubkey::new_unique();
+    let blockhash = Hash::new_unique();
+
+    c.bench_function("transaction_build_sign", |b| {
+        b.iter(|| {
+            let instructions =
+                transfer_instructions(&source.pubkey(), &destination, 1_000, Some("bench"));
+            Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&source.pubkey()),
+                &[&source],
+                black_box(blockhash),
+            )
+        })
+    });
+}
+
+fn deposit_state(c: &mut Criterion) {
+    let account = DepositAccount {
+        owner: Pubkey::new_unique(),
+        balance: 42_000_000,
+        deposit_count: 3,
+        withdraw_count: 1,
+        last_activity_ts: 1_700_000_000,
+        recovery: Pubkey::new_unique(),
+        recovery_after_slots: 216_000,
+        last_owner_slot: 250_000_000,
+    };
+    let data = account.try_to_vec().unwrap();
+
+    c.bench_function("deposit_account_serialize", |b| {
+        b.iter(|| black_box(&account).try_to_vec().unwrap())
+    });
+    c.bench_function("deposit_account_deserialize", |b| {
+        b.iter(|| DepositAccount::try_from_slice(black_box(&data)).unwrap())
+    });
+    c.bench_function("deposit_instruction_roundtrip", |b| {
+        b.iter(|| {
+            let data = DepositInstruction::Withdraw { amount: black_box(1_000) }
+                .try_to_vec()
+                .unwrap();
+            DepositInstruction::try_from_slice(&data).unwrap()
+        })
+    });
+}
+
+/// Runs on every streamed Geyser update, blocks and everything else alike
+fn block_filter(c: &mut Criterion) {
+    let block = SubscribeUpdate {
+        update: Some(Update::Block(Block {
+            slot: 250_000_000,
+            parent_slot: 249_999_999,
+            blockhash: Hash::new_unique().to_string(),
+            previous_blockhash: Hash::new_unique().to_string(),
+            ..Default::default()
+        })),
+    };
+    let ping = SubscribeUpdate {
+        update: Some(Update::Ping(Ping { seq: 1 })),
+    };
+
+    c.bench_function("block_filter_block", |b| {
+        b.iter(|| filter::block_slot(black_box(&block)))
+    });
+    c.bench_function("block_filter_ping", |b| {
+        b.iter(|| filter::block_slot(black_box(&ping)))
+    });
+}
+
+criterion_group!(
+    hot_paths,
+    keypair_decode,
+    transaction_build_sign,
+    deposit_state,
+    block_filter
+);
+criterion_main!(hot_paths);
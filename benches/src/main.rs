@@ -0,0 +1,65 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{collections::BTreeMap, env, fs, path::PathBuf};
+
+/// Overrides the directory criterion writes its results to
+const CRITERION_DIR_ENV: &str = "CRITERION_HOME";
+
+#[derive(Debug, Deserialize)]
+struct Estimates {
+    mean: Estimate,
+}
+
+#[derive(Debug, Deserialize)]
+struct Estimate {
+    point_estimate: f64,
+}
+
+/// Compare the latest `cargo bench` results against the bounds in thresholds.yaml.
+/// Exits non-zero when any benchmark is slower than its bound or has no results.
+fn main() -> Result<()> {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let thresholds_path = manifest_dir.join("thresholds.yaml");
+    let thresholds: BTreeMap<String, f64> = serde_yaml::from_str(
+        &fs::read_to_string(&thresholds_path)
+            .with_context(|| format!("Failed to read {}", thresholds_path.display()))?,
+    )
+    .with_context(|| format!("Failed to parse {}", thresholds_path.display()))?;
+
+    let criterion_dir = env::var_os(CRITERION_DIR_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| manifest_dir.join("target").join("criterion"));
+
+    let mut failures = 0;
+    for (name, max_ns) in &thresholds {
+        let path = criterion_dir.join(name).join("new").join("estimates.json");
+        let mean_ns = match fs::read_to_string(&path) {
+            Ok(json) => {
+                let estimates: Estimates = serde_json::from_str(&json)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                estimates.mean.point_estimate
+            }
+            Err(_) => {
+                println!("{:<32} no results (run `cargo bench` first)", name);
+                failures += 1;
+                continue;
+            }
+        };
+
+        let status = if mean_ns > *max_ns {
+            failures += 1;
+            "REGRESSED"
+        } else {
+            "ok"
+        };
+        println!(
+            "{:<32} {:>12.1} ns  (max {:>10.0} ns)  {}",
+            name, mean_ns, max_ns, status
+        );
+    }
+
+    if failures > 0 {
+        bail!("{} of {} benchmarks over threshold or missing", failures, thresholds.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use crate::error::ConfigError;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+type Result<T> = std::result::Result<T, ConfigError>;
+
+/// Environment variable overriding the address book location
+pub const ADDRESS_BOOK_ENV: &str = "SOLANA_ADDRESS_BOOK";
+
+/// Persistent alias -> address mapping shared by every binary in the workspace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AddressBook {
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl AddressBook {
+    /// Default location: `$SOLANA_ADDRESS_BOOK`, else `<config dir>/solana-toolkit/address_book.yaml`
+    pub fn default_path() -> Result<PathBuf> {
+        if let Ok(path) = std::env::var(ADDRESS_BOOK_ENV) {
+            return Ok(PathBuf::from(path));
+        }
+
+        let config_dir = dirs::config_dir().ok_or(ConfigError::NoConfigDir)?;
+        Ok(config_dir.join("solana-toolkit").join("address_book.yaml"))
+    }
+
+    /// Load the address book from its default location
+    pub fn load() -> Result<Self> {
+        Self::load_from(&Self::default_path()?)
+    }
+
+    /// Load the address book from `path`, starting empty if the file does not exist yet
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let mut book = if path.exists() {
+            let file = File::open(path).map_err(|source| ConfigError::Read {
+                path: path.to_path_buf(),
+                source,
+            })?;
+            serde_yaml::from_reader::<_, AddressBook>(file).map_err(|source| {
+                ConfigError::Parse {
+                    path: path.to_path_buf(),
+                    source,
+                }
+            })?
+        } else {
+            AddressBook::default()
+        };
+
+        book.path = path.to_path_buf();
+        Ok(book)
+    }
+
+    /// Write the address book back to the file it was loaded from
+    pub fn save(&self) -> Result<()> {
+        let write_error = |source| ConfigError::Write {
+            path: self.path.clone(),
+            source,
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(write_error)?;
+        }
+
+        let file = File::create(&self.path).map_err(write_error)?;
+        serde_yaml::to_writer(file, self).map_err(|source| ConfigError::Serialize {
+            path: self.path.clone(),
+            source,
+        })?;
+
+        Ok(())
+    }
+
+    /// Register `alias` for `address`, rejecting malformed aliases and invalid addresses
+    pub fn add(&mut self, alias: &str, address: &str) -> Result<Pubkey> {
+        let invalid_alias = |reason| ConfigError::InvalidAlias {
+            alias: alias.to_string(),
+            reason,
+        };
+
+        if alias.is_empty()
+            || !alias
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(invalid_alias(
+                "may only contain letters, digits, '_' and '-'",
+            ));
+        }
+
+        if Pubkey::from_str(alias).is_ok() {
+            return Err(invalid_alias("is itself a valid address"));
+        }
+
+        let pubkey = Pubkey::from_str(address).map_err(|_| ConfigError::InvalidAddress {
+            value: address.to_string(),
+        })?;
+
+        if let Some(existing) = self.aliases.get(alias) {
+            if *existing != pubkey.to_string() {
+                return Err(ConfigError::AliasConflict {
+                    alias: alias.to_string(),
+                    existing: existing.clone(),
+                });
+            }
+        }
+
+        self.aliases.insert(alias.to_string(), pubkey.to_string());
+        Ok(pubkey)
+    }
+
+    /// Remove `alias`, returning the address it pointed to
+    pub fn remove(&mut self, alias: &str) -> Result<String> {
+        self.aliases
+            .remove(alias)
+            .ok_or_else(|| ConfigError::UnknownAlias {
+                alias: alias.to_string(),
+            })
+    }
+
+    /// Point every alias of `old` at `new` (e.g. after a key rotation), returning the aliases moved
+    pub fn repoint(&mut self, old: &str, new: &Pubkey) -> Vec<String> {
+        let mut moved = Vec::new();
+
+        for (alias, address) in self.aliases.iter_mut() {
+            if address == old {
+                *address = new.to_string();
+                moved.push(alias.clone());
+            }
+        }
+
+        moved
+    }
+
+    /// Resolve an alias or raw base58 address to a pubkey
+    pub fn resolve(&self, value: &str) -> Result<Pubkey> {
+        let address = self.aliases.get(value).map(String::as_str).unwrap_or(value);
+
+        Pubkey::from_str(address).map_err(|_| ConfigError::InvalidAddress {
+            value: value.to_string(),
+        })
+    }
+
+    /// Reverse lookup: the alias registered for `address`, if any
+    pub fn label(&self, address: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(_, a)| a.as_str() == address)
+            .map(|(alias, _)| alias.as_str())
+    }
+
+    /// Render `address` for reports, prefixed with its alias when one exists
+    pub fn display(&self, address: &str) -> String {
+        match self.label(address) {
+            Some(alias) => format!("{} ({})", alias, address),
+            None => address.to_string(),
+        }
+    }
+
+    /// All registered (alias, address) pairs in alphabetical order
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases
+            .iter()
+            .map(|(alias, address)| (alias.as_str(), address.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "Vote111111111111111111111111111111111111111";
+
+    #[test]
+    fn resolve_accepts_aliases_and_raw_addresses() {
+        let mut book = AddressBook::default();
+        book.add("treasury", ADDRESS).unwrap();
+
+        let expected = Pubkey::from_str(ADDRESS).unwrap();
+        assert_eq!(book.resolve("treasury").unwrap(), expected);
+        assert_eq!(book.resolve(ADDRESS).unwrap(), expected);
+    }
+
+    #[test]
+    fn resolve_rejects_unknown_aliases_and_malformed_addresses() {
+        let book = AddressBook::default();
+
+        for value in ["treasury", "not-base58-0OIl", ""] {
+            match book.resolve(value) {
+                Err(ConfigError::InvalidAddress { value: reported }) => {
+                    assert_eq!(reported, value)
+                }
+                other => panic!("expected InvalidAddress for {:?}, got {:?}", value, other),
+            }
+        }
+    }
+
+    #[test]
+    fn add_rejects_malformed_aliases_and_conflicts() {
+        let mut book = AddressBook::default();
+
+        assert!(matches!(
+            book.add("has space", ADDRESS),
+            Err(ConfigError::InvalidAlias { .. })
+        ));
+        assert!(matches!(
+            book.add(ADDRESS, ADDRESS),
+            Err(ConfigError::InvalidAlias { .. })
+        ));
+
+        book.add("treasury", ADDRESS).unwrap();
+        // Re-adding the same address is a no-op, another one is a conflict
+        book.add("treasury", ADDRESS).unwrap();
+        assert!(matches!(
+            book.add("treasury", "11111111111111111111111111111111"),
+            Err(ConfigError::AliasConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn repoint_moves_every_alias_of_the_old_address() {
+        let mut book = AddressBook::default();
+        book.add("treasury", ADDRESS).unwrap();
+        book.add("ops", ADDRESS).unwrap();
+
+        let new = Pubkey::new_unique();
+        assert_eq!(book.repoint(ADDRESS, &new), vec!["ops", "treasury"]);
+        assert_eq!(book.resolve("ops").unwrap(), new);
+        assert_eq!(book.label(&new.to_string()), Some("ops"));
+    }
+}
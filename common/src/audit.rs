@@ -0,0 +1,368 @@
+use crate::error::AuditError;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    hash::{hash, Hash},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+/// Where the audit log lives and which key signs it; auditing is off unless `log` is set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Append-only JSON lines file
+    pub log: Option<PathBuf>,
+    /// Keypair file of the audit key signing every entry
+    pub key: Option<PathBuf>,
+}
+
+impl AuditConfig {
+    pub fn open(&self) -> Result<Option<AuditLog>, AuditError> {
+        let Some(log) = &self.log else {
+            return Ok(None);
+        };
+
+        let key_path = self.key.as_ref().ok_or_else(|| AuditError::Key {
+            path: PathBuf::new(),
+            reason: "audit.key must be set when audit.log is".to_string(),
+        })?;
+        let key = read_keypair_file(key_path).map_err(|e| AuditError::Key {
+            path: key_path.clone(),
+            reason: e.to_string(),
+        })?;
+
+        AuditLog::open(log, key).map(Some)
+    }
+}
+
+/// A submitted fund movement as reported by the binary that sent it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Binary that submitted the transaction (`task2`, `task3`, ...)
+    pub origin: String,
+    pub source: String,
+    pub destination: String,
+    pub lamports: u64,
+    pub signature: String,
+    /// Hash of the signed run manifest that approved the transfer, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<String>,
+}
+
+/// One line of the log. `hash` covers every field before it, including the previous
+/// entry's hash, and `audit_signature` is the audit key's signature over `hash`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub record: AuditRecord,
+    pub prev_hash: String,
+    pub hash: String,
+    pub auditor: String,
+    pub audit_signature: String,
+}
+
+/// The hashed part of an entry, serialized in a fixed field order
+#[derive(Serialize)]
+struct HashedFields<'a> {
+    seq: u64,
+    timestamp: &'a str,
+    #[serde(flatten)]
+    record: &'a AuditRecord,
+    prev_hash: &'a str,
+}
+
+impl AuditEntry {
+    fn compute_hash(&self) -> Hash {
+        let fields = HashedFields {
+            seq: self.seq,
+            timestamp: &self.timestamp,
+            record: &self.record,
+            prev_hash: &self.prev_hash,
+        };
+
+        hash(&serde_json::to_vec(&fields).expect("audit entry fields serialize"))
+    }
+}
+
+struct ChainHead {
+    next_seq: u64,
+    prev_hash: String,
+}
+
+/// Append-only, hash-chained audit log signed by an audit key. Safe to share across tasks.
+pub struct AuditLog {
+    path: PathBuf,
+    key: Keypair,
+    head: Mutex<ChainHead>,
+}
+
+impl AuditLog {
+    /// Open `path` (created on first append) and continue the chain from its last entry
+    pub fn open(path: &Path, key: Keypair) -> Result<Self, AuditError> {
+        let mut head = ChainHead {
+            next_seq: 0,
+            prev_hash: Hash::default().to_string(),
+        };
+
+        if path.exists() {
+            if let Some(last) = read_entries(path)?.pop() {
+                head.next_seq = last.seq + 1;
+                head.prev_hash = last.hash;
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            key,
+            head: Mutex::new(head),
+        })
+    }
+
+    pub fn auditor(&self) -> Pubkey {
+        self.key.pubkey()
+    }
+
+    /// Chain, sign and durably append `record`
+    pub fn append(&self, record: AuditRecord) -> Result<AuditEntry, AuditError> {
+        let mut head = self.head.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut entry = AuditEntry {
+            seq: head.next_seq,
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            record,
+            prev_hash: head.prev_hash.clone(),
+            hash: String::new(),
+            auditor: self.key.pubkey().to_string(),
+            audit_signature: String::new(),
+        };
+        let entry_hash = entry.compute_hash();
+        entry.hash = entry_hash.to_string();
+        entry.audit_signature = self.key.sign_message(entry_hash.as_ref()).to_string();
+
+        let io_error = |source| AuditError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(io_error)?;
+        let line = serde_json::to_string(&entry).expect("audit entry serializes");
+        writeln!(file, "{}", line).map_err(io_error)?;
+        file.sync_data().map_err(io_error)?;
+
+        head.next_seq += 1;
+        head.prev_hash = entry.hash.clone();
+
+        Ok(entry)
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, AuditError> {
+    let io_error = |source| AuditError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = File::open(path).map_err(io_error)?;
+
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = serde_json::from_str(&line).map_err(|e| AuditError::Corrupt {
+            line: index + 1,
+            reason: e.to_string(),
+        })?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// Check every entry's sequence number, chain link, hash and signature. When `auditor` is
+/// given, entries must also be signed by that key. Returns the verified entries.
+pub fn verify(path: &Path, auditor: Option<&Pubkey>) -> Result<Vec<AuditEntry>, AuditError> {
+    let entries = read_entries(path)?;
+    let mut prev_hash = Hash::default().to_string();
+
+    for (expected_seq, entry) in (0u64..).zip(&entries) {
+        if entry.seq != expected_seq || entry.prev_hash != prev_hash {
+            return Err(AuditError::ChainBroken { seq: entry.seq });
+        }
+
+        let entry_hash = entry.compute_hash();
+        if entry_hash.to_string() != entry.hash {
+            return Err(AuditError::HashMismatch { seq: entry.seq });
+        }
+
+        let signer = Pubkey::from_str(&entry.auditor)
+            .map_err(|_| AuditError::BadSignature { seq: entry.seq })?;
+        if auditor.is_some_and(|auditor| *auditor != signer) {
+            return Err(AuditError::UnexpectedAuditor {
+                seq: entry.seq,
+                auditor: entry.auditor.clone(),
+            });
+        }
+
+        let signature = Signature::from_str(&entry.audit_signature)
+            .map_err(|_| AuditError::BadSignature { seq: entry.seq })?;
+        if !signature.verify(signer.as_ref(), entry_hash.as_ref()) {
+            return Err(AuditError::BadSignature { seq: entry.seq });
+        }
+
+        prev_hash = entry.hash.clone();
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// A fresh log path under the system temp dir, removed when dropped
+    struct TempLog(PathBuf);
+
+    impl TempLog {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "solana-common-audit-{}-{}.jsonl",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_file(&path);
+            TempLog(path)
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn record(lamports: u64) -> AuditRecord {
+        AuditRecord {
+            origin: "task2".to_string(),
+            source: Pubkey::new_unique().to_string(),
+            destination: Pubkey::new_unique().to_string(),
+            lamports,
+            signature: Signature::default().to_string(),
+            manifest: None,
+        }
+    }
+
+    /// Write a log of `count` entries signed by `key`
+    fn write_log(path: &Path, key: &Keypair, count: u64) {
+        let log = AuditLog::open(path, key.insecure_clone()).unwrap();
+        for lamports in 0..count {
+            log.append(record(lamports)).unwrap();
+        }
+    }
+
+    /// Rewrite line `index` of the log through `edit`
+    fn tamper(path: &Path, index: usize, edit: impl FnOnce(&mut AuditEntry)) {
+        let mut entries = read_entries(path).unwrap();
+        edit(&mut entries[index]);
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect();
+        fs::write(path, lines.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn verify_accepts_an_intact_chain_continued_across_opens() {
+        let log = TempLog::new("intact");
+        let key = Keypair::new();
+        write_log(&log.0, &key, 2);
+        // Reopening continues the chain instead of restarting it
+        write_log(&log.0, &key, 1);
+
+        let entries = verify(&log.0, Some(&key.pubkey())).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].prev_hash, Hash::default().to_string());
+        assert_eq!(entries[2].seq, 2);
+        assert_eq!(entries[2].prev_hash, entries[1].hash);
+    }
+
+    #[test]
+    fn verify_detects_an_edited_record() {
+        let log = TempLog::new("edited");
+        let key = Keypair::new();
+        write_log(&log.0, &key, 3);
+        tamper(&log.0, 1, |entry| entry.record.lamports = 1_000_000);
+
+        assert!(matches!(
+            verify(&log.0, None),
+            Err(AuditError::HashMismatch { seq: 1 })
+        ));
+    }
+
+    #[test]
+    fn verify_detects_removed_and_relinked_entries() {
+        let log = TempLog::new("removed");
+        let key = Keypair::new();
+        write_log(&log.0, &key, 3);
+
+        let mut entries = read_entries(&log.0).unwrap();
+        entries.remove(1);
+        let lines: Vec<String> = entries
+            .iter()
+            .map(|entry| serde_json::to_string(entry).unwrap())
+            .collect();
+        fs::write(&log.0, lines.join("\n")).unwrap();
+        assert!(matches!(
+            verify(&log.0, None),
+            Err(AuditError::ChainBroken { seq: 2 })
+        ));
+
+        tamper(&log.0, 1, |entry| entry.seq = 1);
+        assert!(matches!(
+            verify(&log.0, None),
+            Err(AuditError::ChainBroken { seq: 1 })
+        ));
+    }
+
+    #[test]
+    fn verify_detects_a_rehashed_entry_without_the_audit_key() {
+        let log = TempLog::new("rehashed");
+        let key = Keypair::new();
+        write_log(&log.0, &key, 1);
+        tamper(&log.0, 0, |entry| {
+            entry.record.lamports = 1_000_000;
+            entry.hash = entry.compute_hash().to_string();
+        });
+
+        assert!(matches!(
+            verify(&log.0, None),
+            Err(AuditError::BadSignature { seq: 0 })
+        ));
+    }
+
+    #[test]
+    fn verify_rejects_entries_signed_by_another_auditor() {
+        let log = TempLog::new("auditor");
+        write_log(&log.0, &Keypair::new(), 1);
+
+        assert!(verify(&log.0, None).is_ok());
+        assert!(matches!(
+            verify(&log.0, Some(&Pubkey::new_unique())),
+            Err(AuditError::UnexpectedAuditor { seq: 0, .. })
+        ));
+    }
+}
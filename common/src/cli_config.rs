@@ -0,0 +1,125 @@
+use crate::error::{ConfigError, SigningError};
+use serde::Deserialize;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::{read_keypair_file, Keypair},
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+type Result<T> = std::result::Result<T, ConfigError>;
+
+/// Environment variable overriding the solana-cli config location
+pub const CLI_CONFIG_ENV: &str = "SOLANA_CLI_CONFIG";
+
+/// The subset of solana-cli's `config.yml` used as fallback defaults by every binary.
+/// Explicit flags and config files always win; built-in defaults apply when neither
+/// they nor the solana-cli config set a value.
+#[derive(Debug, Default, Deserialize)]
+pub struct CliConfig {
+    #[serde(default)]
+    pub json_rpc_url: Option<String>,
+    /// Empty in solana-cli configs unless set explicitly; then derived from `json_rpc_url`
+    #[serde(default)]
+    pub websocket_url: Option<String>,
+    #[serde(default)]
+    pub keypair_path: Option<String>,
+    #[serde(default)]
+    pub commitment: Option<String>,
+}
+
+impl CliConfig {
+    /// Default location: `$SOLANA_CLI_CONFIG`, else `~/.config/solana/cli/config.yml`
+    pub fn default_path() -> Option<PathBuf> {
+        if let Ok(path) = std::env::var(CLI_CONFIG_ENV) {
+            return Some(PathBuf::from(path));
+        }
+
+        dirs::home_dir().map(|home| {
+            home.join(".config")
+                .join("solana")
+                .join("cli")
+                .join("config.yml")
+        })
+    }
+
+    /// Load the solana-cli config from its default location, empty when there is none
+    pub fn load() -> Result<Self> {
+        match Self::default_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Load the solana-cli config from `path`, empty if the file does not exist
+    pub fn load_from(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let file = File::open(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_yaml::from_reader(file).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// `explicit` if given, else the solana-cli RPC URL, else `fallback`
+    pub fn rpc_url(&self, explicit: Option<&str>, fallback: &str) -> String {
+        explicit
+            .or(self.json_rpc_url.as_deref())
+            .unwrap_or(fallback)
+            .to_string()
+    }
+
+    /// `explicit` if given, else the solana-cli websocket URL, else the pubsub endpoint
+    /// of `rpc_url` the way solana-cli derives it
+    pub fn websocket_url(&self, explicit: Option<&str>, rpc_url: &str) -> String {
+        explicit
+            .or(self.websocket_url.as_deref().filter(|url| !url.is_empty()))
+            .map(str::to_string)
+            .unwrap_or_else(|| websocket_url_for(rpc_url))
+    }
+
+    /// The solana-cli commitment level, or `fallback` when unset or unrecognized
+    pub fn commitment(&self, fallback: CommitmentConfig) -> CommitmentConfig {
+        self.commitment
+            .as_deref()
+            .and_then(|level| CommitmentConfig::from_str(level).ok())
+            .unwrap_or(fallback)
+    }
+
+    /// Read the solana-cli default keypair, if one is configured
+    pub fn keypair(&self) -> std::result::Result<Option<Keypair>, SigningError> {
+        let Some(path) = self.keypair_path.as_deref() else {
+            return Ok(None);
+        };
+
+        read_keypair_file(path)
+            .map(Some)
+            .map_err(|e| SigningError::InvalidKeypair {
+                reason: format!("{}: {}", path, e),
+            })
+    }
+}
+
+/// ws(s):// on the same host; an explicit port moves to the next one, where
+/// solana-test-validator and most self-hosted nodes serve pubsub
+pub fn websocket_url_for(rpc_url: &str) -> String {
+    let Ok(mut url) = reqwest::Url::parse(rpc_url) else {
+        return rpc_url.to_string();
+    };
+
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    let _ = url.set_scheme(scheme);
+    if let Some(port) = url.port() {
+        let _ = url.set_port(Some(port.saturating_add(1)));
+    }
+    url.to_string()
+}
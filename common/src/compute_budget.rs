@@ -0,0 +1,100 @@
+use crate::error::RpcError;
+use serde::Deserialize;
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, message::Message,
+    pubkey::Pubkey, transaction::Transaction,
+};
+
+/// Highest compute unit limit a transaction may request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// How the compute unit limit is derived from simulation
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ComputeBudgetConfig {
+    /// Headroom added on top of the simulated compute units, in percent
+    pub margin_percent: u32,
+    /// Lower bound for the requested limit
+    pub min_units: u32,
+}
+
+impl Default for ComputeBudgetConfig {
+    fn default() -> Self {
+        Self {
+            margin_percent: 20,
+            min_units: 1_000,
+        }
+    }
+}
+
+/// ComputeBudget instructions to prepend to a transaction
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ComputeBudget {
+    /// Compute unit price in micro-lamports (priority fee)
+    pub unit_price: Option<u64>,
+    /// Size a compute unit limit from simulation when set
+    pub limit: Option<ComputeBudgetConfig>,
+}
+
+impl ComputeBudget {
+    /// Prepend the configured ComputeBudget instructions to `instructions` paid by `payer`.
+    /// The limit is sized by simulating the final transaction; when the simulation fails or
+    /// reports no usage, no limit is requested and the runtime default applies.
+    pub fn apply(
+        &self,
+        client: &RpcClient,
+        payer: &Pubkey,
+        instructions: Vec<Instruction>,
+    ) -> Result<Vec<Instruction>, RpcError> {
+        let mut budgeted = Vec::with_capacity(instructions.len() + 2);
+        if let Some(micro_lamports) = self.unit_price {
+            budgeted.push(ComputeBudgetInstruction::set_compute_unit_price(micro_lamports));
+        }
+
+        if let Some(config) = self.limit {
+            if let Some(units) = simulate_units(client, payer, &budgeted, &instructions)? {
+                let limit = units
+                    .saturating_mul(100 + config.margin_percent as u64)
+                    .div_ceil(100)
+                    .max(config.min_units as u64)
+                    .min(MAX_COMPUTE_UNIT_LIMIT as u64);
+                budgeted.push(ComputeBudgetInstruction::set_compute_unit_limit(limit as u32));
+            }
+        }
+
+        budgeted.extend(instructions);
+        Ok(budgeted)
+    }
+}
+
+/// Compute units consumed by `prefix` + `instructions` under the maximum limit
+fn simulate_units(
+    client: &RpcClient,
+    payer: &Pubkey,
+    prefix: &[Instruction],
+    instructions: &[Instruction],
+) -> Result<Option<u64>, RpcError> {
+    // Simulate with the limit instruction in place so its own cost is counted
+    let mut simulated = prefix.to_vec();
+    simulated.push(ComputeBudgetInstruction::set_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT));
+    simulated.extend_from_slice(instructions);
+
+    let message = Message::new(&simulated, Some(payer));
+    let simulation = client
+        .simulate_transaction_with_config(
+            &Transaction::new_unsigned(message),
+            RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                ..RpcSimulateTransactionConfig::default()
+            },
+        )
+        .map_err(|e| RpcError::request("simulateTransaction", &client.url(), e))?
+        .value;
+
+    if simulation.err.is_some() {
+        return Ok(None);
+    }
+    Ok(simulation.units_consumed)
+}
@@ -0,0 +1,74 @@
+use crate::error::{BoxError, ConfigError};
+use serde::de::DeserializeOwned;
+use std::{fmt, path::Path, str::FromStr};
+
+/// Syntax of a config file; every format deserializes into the same config types
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    /// Format implied by the extension of `path`: `.yaml`/`.yml`, `.toml` or `.json`
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        extension.parse().ok()
+    }
+
+    /// Deserialize `text` written in this format
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> Result<T, BoxError> {
+        Ok(match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(text)?,
+            ConfigFormat::Toml => toml::from_str(text)?,
+            ConfigFormat::Json => serde_json::from_str(text)?,
+        })
+    }
+}
+
+impl FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "toml" => Ok(ConfigFormat::Toml),
+            "json" => Ok(ConfigFormat::Json),
+            _ => Err(format!(
+                "unknown config format '{}', expected yaml, toml or json",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ConfigFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Json => "JSON",
+        })
+    }
+}
+
+/// Read the config at `path` in `format`, else in the format its extension implies, else
+/// as YAML
+pub fn load<T: DeserializeOwned>(
+    path: &Path,
+    format: Option<ConfigFormat>,
+) -> Result<T, ConfigError> {
+    let format = format
+        .or_else(|| ConfigFormat::from_extension(path))
+        .unwrap_or(ConfigFormat::Yaml);
+    let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    format.parse(&text).map_err(|source| ConfigError::Decode {
+        path: path.to_path_buf(),
+        format,
+        source,
+    })
+}
@@ -0,0 +1,358 @@
+//! Typed errors for the library crates. Binaries wrap these in `anyhow` at their edges;
+//! library callers can match on the variant or on the stable `code()` string.
+
+use crate::config_file::ConfigFormat;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_request::RpcError as ClientRpcError,
+};
+use solana_sdk::{signer::SignerError, transaction::TransactionError};
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Loading, parsing or validating configuration files and the address book
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Failed to read {}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+
+    #[error("Failed to parse {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[error("Failed to parse {} as {format}: {source}", path.display())]
+    Decode {
+        path: PathBuf,
+        format: ConfigFormat,
+        source: BoxError,
+    },
+
+    #[error("Failed to write {}: {source}", path.display())]
+    Write { path: PathBuf, source: io::Error },
+
+    #[error("Failed to serialize {}: {source}", path.display())]
+    Serialize {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+
+    #[error("Failed to locate user config directory")]
+    NoConfigDir,
+
+    #[error("'{value}' is neither a known alias nor a valid address")]
+    InvalidAddress { value: String },
+
+    #[error("Invalid alias '{alias}': {reason}")]
+    InvalidAlias { alias: String, reason: &'static str },
+
+    #[error("Alias '{alias}' already points to {existing}")]
+    AliasConflict { alias: String, existing: String },
+
+    #[error("Unknown alias '{alias}'")]
+    UnknownAlias { alias: String },
+
+    #[error("Private submission profile requires at least one relay in submission.relays")]
+    NoPrivateRelays,
+}
+
+impl ConfigError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConfigError::Read { .. } => "CONFIG_READ",
+            ConfigError::Parse { .. } => "CONFIG_PARSE",
+            ConfigError::Decode { .. } => "CONFIG_DECODE",
+            ConfigError::Write { .. } => "CONFIG_WRITE",
+            ConfigError::Serialize { .. } => "CONFIG_SERIALIZE",
+            ConfigError::NoConfigDir => "CONFIG_NO_DIR",
+            ConfigError::InvalidAddress { .. } => "CONFIG_INVALID_ADDRESS",
+            ConfigError::InvalidAlias { .. } => "CONFIG_INVALID_ALIAS",
+            ConfigError::AliasConflict { .. } => "CONFIG_ALIAS_CONFLICT",
+            ConfigError::UnknownAlias { .. } => "CONFIG_UNKNOWN_ALIAS",
+            ConfigError::NoPrivateRelays => "CONFIG_NO_PRIVATE_RELAYS",
+        }
+    }
+}
+
+/// Failures talking to a JSON-RPC endpoint
+#[derive(Debug, Error)]
+pub enum RpcError {
+    #[error("{method} request to {endpoint} failed: {source}")]
+    Request {
+        method: String,
+        endpoint: String,
+        source: Box<ClientError>,
+    },
+
+    #[error("Circuit breaker open for {endpoint}")]
+    CircuitOpen { endpoint: String },
+
+    #[error("Unexpected {method} response from {endpoint}: {reason}")]
+    InvalidResponse {
+        method: String,
+        endpoint: String,
+        reason: String,
+    },
+
+    #[error("TPU client on {endpoint} failed: {reason}")]
+    Tpu { endpoint: String, reason: String },
+}
+
+impl RpcError {
+    pub fn request(method: &str, endpoint: &str, source: ClientError) -> Self {
+        RpcError::Request {
+            method: method.to_string(),
+            endpoint: endpoint.to_string(),
+            source: Box::new(source),
+        }
+    }
+
+    /// Whether a node rejected a transaction because it already processed it, i.e. an
+    /// earlier broadcast of the same signed transaction landed
+    pub fn is_already_processed(&self) -> bool {
+        match self {
+            RpcError::Request { source, .. } => {
+                source.get_transaction_error() == Some(TransactionError::AlreadyProcessed)
+                    || source.to_string().contains("already been processed")
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the request certainly had no effect: the node answered it with an error, turned
+    /// it away with a 429 or could not be reached, or its circuit was open. A timeout, a dropped
+    /// connection or a 5xx from a gateway is no such proof, as the node may have acted on the
+    /// request without its answer getting through.
+    pub fn is_rejection(&self) -> bool {
+        match self {
+            RpcError::Request { source, .. } => match source.kind() {
+                ClientErrorKind::RpcError(ClientRpcError::RpcResponseError { .. }) => true,
+                ClientErrorKind::RpcError(ClientRpcError::RpcRequestError(message)) => {
+                    message.starts_with("Circuit breaker open")
+                }
+                ClientErrorKind::Reqwest(e) => {
+                    e.is_connect() || e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS)
+                }
+                _ => false,
+            },
+            RpcError::CircuitOpen { .. } => true,
+            RpcError::InvalidResponse { .. } | RpcError::Tpu { .. } => false,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            RpcError::Request { .. } => "RPC_REQUEST",
+            RpcError::CircuitOpen { .. } => "RPC_CIRCUIT_OPEN",
+            RpcError::InvalidResponse { .. } => "RPC_INVALID_RESPONSE",
+            RpcError::Tpu { .. } => "RPC_TPU",
+        }
+    }
+}
+
+/// Problems turning configured secrets into signers or signing with them
+#[derive(Debug, Error)]
+pub enum SigningError {
+    #[error("Failed to decode secret key: {reason}")]
+    InvalidSecretKey { reason: String },
+
+    #[error("Failed to create keypair from secret bytes: {reason}")]
+    InvalidKeypair { reason: String },
+
+    #[error("Failed to sign transaction: {0}")]
+    Signer(#[from] SignerError),
+
+    #[error("Wallet {address} needs exactly one of secret_key and secret_key_path")]
+    SecretSource { address: String },
+}
+
+impl SigningError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SigningError::InvalidSecretKey { .. } => "SIGNING_INVALID_SECRET",
+            SigningError::InvalidKeypair { .. } => "SIGNING_INVALID_KEYPAIR",
+            SigningError::Signer(_) => "SIGNING_FAILED",
+            SigningError::SecretSource { .. } => "SIGNING_SECRET_SOURCE",
+        }
+    }
+}
+
+/// Transactions refused by safe mode
+#[derive(Debug, Error)]
+pub enum SafetyError {
+    #[error("Transaction moves {value} lamports, above the safe mode cap of {cap}")]
+    ValueCapExceeded { value: u64, cap: u64 },
+
+    #[error("Simulation failed: {reason}")]
+    SimulationFailed { reason: String, logs: Vec<String> },
+
+    #[error("Could not simulate transaction: {0}")]
+    Simulation(#[from] RpcError),
+}
+
+impl SafetyError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            SafetyError::ValueCapExceeded { .. } => "SAFETY_VALUE_CAP",
+            SafetyError::SimulationFailed { .. } => "SAFETY_SIMULATION_FAILED",
+            SafetyError::Simulation(_) => "SAFETY_SIMULATION_UNAVAILABLE",
+        }
+    }
+}
+
+/// Audit log I/O and tamper detection
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("Audit log {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Failed to load audit key {}: {reason}", path.display())]
+    Key { path: PathBuf, reason: String },
+
+    #[error("Audit log line {line} is not a valid entry: {reason}")]
+    Corrupt { line: usize, reason: String },
+
+    #[error("Audit chain broken at entry {seq}")]
+    ChainBroken { seq: u64 },
+
+    #[error("Audit entry {seq} was modified (hash mismatch)")]
+    HashMismatch { seq: u64 },
+
+    #[error("Audit entry {seq} has an invalid signature")]
+    BadSignature { seq: u64 },
+
+    #[error("Audit entry {seq} was signed by unexpected key {auditor}")]
+    UnexpectedAuditor { seq: u64, auditor: String },
+}
+
+impl AuditError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuditError::Io { .. } => "AUDIT_IO",
+            AuditError::Key { .. } => "AUDIT_KEY",
+            AuditError::Corrupt { .. } => "AUDIT_CORRUPT",
+            AuditError::ChainBroken { .. } => "AUDIT_CHAIN_BROKEN",
+            AuditError::HashMismatch { .. } => "AUDIT_HASH_MISMATCH",
+            AuditError::BadSignature { .. } => "AUDIT_BAD_SIGNATURE",
+            AuditError::UnexpectedAuditor { .. } => "AUDIT_UNEXPECTED_AUDITOR",
+        }
+    }
+}
+
+/// Writing or verifying signed action receipts
+#[derive(Debug, Error)]
+pub enum ReceiptError {
+    #[error("Receipt log {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Failed to load operator key {}: {reason}", path.display())]
+    Key { path: PathBuf, reason: String },
+
+    #[error("Receipt log line {line} is not a valid receipt: {reason}")]
+    Corrupt { line: usize, reason: String },
+
+    #[error("Receipt on line {line} was modified (hash mismatch)")]
+    HashMismatch { line: usize },
+
+    #[error("Receipt on line {line} has an invalid signature")]
+    BadSignature { line: usize },
+
+    #[error("Receipt on line {line} was signed by unexpected key {operator}")]
+    UnexpectedOperator { line: usize, operator: String },
+}
+
+impl ReceiptError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ReceiptError::Io { .. } => "RECEIPT_IO",
+            ReceiptError::Key { .. } => "RECEIPT_KEY",
+            ReceiptError::Corrupt { .. } => "RECEIPT_CORRUPT",
+            ReceiptError::HashMismatch { .. } => "RECEIPT_HASH_MISMATCH",
+            ReceiptError::BadSignature { .. } => "RECEIPT_BAD_SIGNATURE",
+            ReceiptError::UnexpectedOperator { .. } => "RECEIPT_UNEXPECTED_OPERATOR",
+        }
+    }
+}
+
+/// Failures of long-lived gRPC subscriptions
+#[derive(Debug, Error)]
+pub enum StreamError {
+    #[error("Failed to connect to {endpoint}: {source}")]
+    Connect { endpoint: String, source: BoxError },
+
+    #[error("Failed to subscribe on {endpoint}: {source}")]
+    Subscribe { endpoint: String, source: BoxError },
+
+    #[error("Circuit breaker open for {endpoint}")]
+    CircuitOpen { endpoint: String },
+
+    #[error("Stream from {endpoint} closed")]
+    Closed { endpoint: String },
+}
+
+impl StreamError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            StreamError::Connect { .. } => "STREAM_CONNECT",
+            StreamError::Subscribe { .. } => "STREAM_SUBSCRIBE",
+            StreamError::CircuitOpen { .. } => "STREAM_CIRCUIT_OPEN",
+            StreamError::Closed { .. } => "STREAM_CLOSED",
+        }
+    }
+}
+
+/// Failures delivering run reports to notification sinks
+#[derive(Debug, Error)]
+pub enum NotifyError {
+    #[error("Invalid email address '{address}'")]
+    InvalidAddress { address: String },
+
+    #[error("Failed to build report email: {reason}")]
+    Message { reason: String },
+
+    #[error("SMTP password variable {var} is not set")]
+    MissingPassword { var: String },
+
+    #[error("SMTP delivery via {host} failed: {source}")]
+    Smtp {
+        host: String,
+        source: lettre::transport::smtp::Error,
+    },
+
+    #[error("Webhook delivery to {url} failed: {source}")]
+    Webhook { url: String, source: reqwest::Error },
+}
+
+impl NotifyError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            NotifyError::InvalidAddress { .. } => "NOTIFY_INVALID_ADDRESS",
+            NotifyError::Message { .. } => "NOTIFY_MESSAGE",
+            NotifyError::MissingPassword { .. } => "NOTIFY_MISSING_PASSWORD",
+            NotifyError::Smtp { .. } => "NOTIFY_SMTP",
+            NotifyError::Webhook { .. } => "NOTIFY_WEBHOOK",
+        }
+    }
+}
+
+/// Rendering a report in an output format
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error("Failed to render JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Failed to render CSV: {0}")]
+    Csv(#[from] csv::Error),
+}
+
+impl RenderError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RenderError::Json(_) => "RENDER_JSON",
+            RenderError::Csv(_) => "RENDER_CSV",
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use std::{io, net::SocketAddr};
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::broadcast,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Events buffered per subscriber before slow dashboards start missing some
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// Something a dashboard may want to react to
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A watched wallet's balance moved
+    BalanceChanged {
+        address: String,
+        previous_lamports: Option<u64>,
+        lamports: u64,
+    },
+    /// A transfer was sent or failed to send
+    TransferStatus {
+        source: String,
+        destination: String,
+        lamports: u64,
+        signature: Option<String>,
+        status: TransferState,
+        error: Option<String>,
+        /// Name of the task3 pipeline that sent it
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pipeline: Option<String>,
+    },
+    /// A geyser update that triggers work (e.g. a new block in task3)
+    GeyserTrigger {
+        slot: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pipeline: Option<String>,
+    },
+    /// A geyser update that was received but triggers nothing
+    TriggerSkipped {
+        slot: u64,
+        reason: SkipReason,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pipeline: Option<String>,
+    },
+    /// A trigger wallet fell below its reserve; triggers are skipped until it is funded again
+    BudgetExhausted {
+        source: String,
+        lamports: u64,
+        reserve_lamports: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pipeline: Option<String>,
+    },
+    /// A streamed block
+    BlockUpdate {
+        slot: u64,
+        transactions: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pipeline: Option<String>,
+    },
+    /// A new state of a watched account
+    AccountUpdate {
+        pubkey: String,
+        lamports: u64,
+        data_len: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pipeline: Option<String>,
+    },
+    /// A streamed transaction
+    TransactionUpdate {
+        signature: String,
+        slot: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pipeline: Option<String>,
+    },
+}
+
+/// Why a geyser update did not fire
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// Too few slots since the last trigger
+    SlotInterval,
+    /// Still cooling down from the last fired trigger
+    Cooldown,
+    /// The amount strategy gave 0 lamports
+    ZeroAmount,
+    /// The amount strategy's account has no usable state yet
+    NoAccountState,
+    /// None of the transaction's log lines matched
+    LogsUnmatched,
+    /// The transaction's logs could not be fetched
+    LogsUnavailable,
+    /// The transfer would leave the source wallet below its reserve
+    BelowReserve,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferState {
+    Sent,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Envelope {
+    timestamp: String,
+    #[serde(flatten)]
+    event: Event,
+}
+
+/// Fan-out of JSON-encoded events to every connected WebSocket client
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<String>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+
+    /// Events published from now on, JSON-encoded
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.sender.subscribe()
+    }
+
+    /// Publish `event`; it is dropped silently when nobody is subscribed
+    pub fn publish(&self, event: Event) {
+        let envelope = Envelope {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            event,
+        };
+        let json = serde_json::to_string(&envelope).expect("events serialize");
+        let _ = self.sender.send(json);
+    }
+}
+
+/// Accept WebSocket clients on `addr` and push every published event to them as a JSON text
+/// frame. Runs until the listener fails.
+pub async fn serve(addr: SocketAddr, bus: EventBus) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let events = bus.sender.subscribe();
+
+        tokio::spawn(async move {
+            if let Err(e) = push_events(stream, events).await {
+                eprintln!("Event stream to {} closed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Write every event from `events` to `out` as one JSON line, flushed right away so pipes see
+/// it, until the bus is dropped or writing fails
+pub async fn write_ndjson(
+    mut events: broadcast::Receiver<String>,
+    mut out: impl AsyncWrite + Unpin,
+) -> io::Result<()> {
+    loop {
+        let line = match events.recv().await {
+            Ok(json) => json,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                serde_json::json!({ "type": "lagged", "skipped": skipped }).to_string()
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        };
+        out.write_all(line.as_bytes()).await?;
+        out.write_all(b"\n").await?;
+        out.flush().await?;
+    }
+}
+
+async fn push_events(
+    stream: TcpStream,
+    mut events: broadcast::Receiver<String>,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    let socket = tokio_tungstenite::accept_async(stream).await?;
+    let (mut sink, mut incoming) = socket.split();
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(json) => sink.send(Message::Text(json)).await?,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    let notice = serde_json::json!({ "type": "lagged", "skipped": skipped });
+                    sink.send(Message::Text(notice.to_string())).await?;
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+            // Clients only listen; reading lets us notice them going away and answer pings
+            message = incoming.next() => match message {
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => return Err(e),
+            },
+        }
+    }
+}
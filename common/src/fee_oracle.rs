@@ -0,0 +1,470 @@
+use crate::{
+    error::RpcError,
+    rpc::{RpcLimits, RpcTransport},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Mutex;
+
+/// How quickly a transaction needs to land; picks the percentile of recent fees to match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl Urgency {
+    /// Percentile of recent prioritization fees targeted at this urgency
+    fn percentile(self) -> usize {
+        match self {
+            Urgency::Low => 25,
+            Urgency::Medium => 50,
+            Urgency::High => 90,
+        }
+    }
+
+    /// Priority level names used by Helius' getPriorityFeeEstimate
+    fn helius_level(self) -> &'static str {
+        match self {
+            Urgency::Low => "Low",
+            Urgency::Medium => "Medium",
+            Urgency::High => "VeryHigh",
+        }
+    }
+}
+
+/// Third-party fee estimation API queried alongside the RPC node
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum FeeProvider {
+    /// Helius `getPriorityFeeEstimate`
+    Helius { url: String },
+    /// Triton's percentile-aware `getRecentPrioritizationFees`
+    Triton { url: String },
+}
+
+/// Priority fee oracle settings
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FeeOracleConfig {
+    /// Urgency used when the caller has no specific need
+    pub urgency: Urgency,
+    /// Optional provider API consulted in addition to getRecentPrioritizationFees
+    pub provider: Option<FeeProvider>,
+    /// Weight of the newest sample in the local moving average (0..=1)
+    pub ewma_alpha: f64,
+    /// Never suggest more than this many micro-lamports per compute unit
+    pub max_micro_lamports: Option<u64>,
+    pub rpc_limits: RpcLimits,
+}
+
+impl Default for FeeOracleConfig {
+    fn default() -> Self {
+        Self {
+            urgency: Urgency::default(),
+            provider: None,
+            ewma_alpha: 0.3,
+            max_micro_lamports: None,
+            rpc_limits: RpcLimits::default(),
+        }
+    }
+}
+
+/// Suggests compute unit prices by combining the RPC node's recent prioritization fees,
+/// an optional provider estimate and an exponentially weighted average of past samples.
+pub struct FeeOracle {
+    rpc: RpcTransport,
+    provider: Option<(FeeProvider, RpcTransport)>,
+    config: FeeOracleConfig,
+    ewma: Mutex<Option<f64>>,
+}
+
+impl FeeOracle {
+    pub fn new(rpc_url: &str, config: FeeOracleConfig) -> Self {
+        let provider = config.provider.clone().map(|provider| {
+            let url = match &provider {
+                FeeProvider::Helius { url } | FeeProvider::Triton { url } => url.clone(),
+            };
+            (provider, RpcTransport::new(&url, config.rpc_limits))
+        });
+
+        Self {
+            rpc: RpcTransport::new(rpc_url, config.rpc_limits),
+            provider,
+            config,
+            ewma: Mutex::new(None),
+        }
+    }
+
+    /// The configured default urgency
+    pub fn urgency(&self) -> Urgency {
+        self.config.urgency
+    }
+
+    /// Suggested priority fee in micro-lamports per compute unit for a transaction
+    /// writing `accounts`. Provider failures are logged and skipped; only an RPC
+    /// failure without any earlier sample is an error.
+    pub async fn suggest_fee(
+        &self,
+        accounts: &[Pubkey],
+        urgency: Urgency,
+    ) -> Result<u64, RpcError> {
+        let rpc_sample = self.rpc_fee(accounts, urgency).await;
+        let provider_sample = match self.provider_fee(accounts, urgency).await {
+            Ok(fee) => fee,
+            Err(e) => {
+                eprintln!("Priority fee provider unavailable: {}", e);
+                None
+            }
+        };
+
+        let sample = match (rpc_sample, provider_sample) {
+            (Ok(rpc), Some(provider)) => Some(rpc.max(provider)),
+            (Ok(rpc), None) => Some(rpc),
+            (Err(_), Some(provider)) => Some(provider),
+            (Err(e), None) => {
+                if self.current_average().is_none() {
+                    return Err(e);
+                }
+                eprintln!("Using averaged priority fee: {}", e);
+                None
+            }
+        };
+
+        let average = self.update_average(sample);
+        let fee = match (urgency, sample) {
+            (_, None) | (Urgency::Medium, _) => average,
+            (Urgency::Low, Some(sample)) => average.min(sample as f64),
+            (Urgency::High, Some(sample)) => average.max(sample as f64),
+        };
+
+        let fee = fee.ceil() as u64;
+        Ok(match self.config.max_micro_lamports {
+            Some(cap) => fee.min(cap),
+            None => fee,
+        })
+    }
+
+    /// Percentile of the node's recent prioritization fees for `accounts`
+    async fn rpc_fee(&self, accounts: &[Pubkey], urgency: Urgency) -> Result<u64, RpcError> {
+        let result = self
+            .rpc
+            .call(
+                "getRecentPrioritizationFees",
+                json!([account_strings(accounts)]),
+            )
+            .await?;
+
+        let mut fees = prioritization_fees(&result).ok_or_else(|| RpcError::InvalidResponse {
+            method: "getRecentPrioritizationFees".to_string(),
+            endpoint: self.rpc.url().to_string(),
+            reason: "expected an array of prioritization fees".to_string(),
+        })?;
+        fees.sort_unstable();
+
+        Ok(percentile(&fees, urgency.percentile()))
+    }
+
+    async fn provider_fee(
+        &self,
+        accounts: &[Pubkey],
+        urgency: Urgency,
+    ) -> Result<Option<u64>, RpcError> {
+        let Some((provider, transport)) = &self.provider else {
+            return Ok(None);
+        };
+
+        match provider {
+            FeeProvider::Helius { .. } => {
+                let result = transport
+                    .call(
+                        "getPriorityFeeEstimate",
+                        json!([{
+                            "accountKeys": account_strings(accounts),
+                            "options": { "priorityLevel": urgency.helius_level() },
+                        }]),
+                    )
+                    .await?;
+
+                let fee = result["priorityFeeEstimate"].as_f64().ok_or_else(|| {
+                    RpcError::InvalidResponse {
+                        method: "getPriorityFeeEstimate".to_string(),
+                        endpoint: transport.url().to_string(),
+                        reason: "missing priorityFeeEstimate".to_string(),
+                    }
+                })?;
+                Ok(Some(fee.ceil() as u64))
+            }
+            FeeProvider::Triton { .. } => {
+                // Triton computes the percentile server side, in basis points
+                let result = transport
+                    .call(
+                        "getRecentPrioritizationFees",
+                        json!([
+                            account_strings(accounts),
+                            { "percentile": urgency.percentile() * 100 },
+                        ]),
+                    )
+                    .await?;
+
+                let mut fees =
+                    prioritization_fees(&result).ok_or_else(|| RpcError::InvalidResponse {
+                        method: "getRecentPrioritizationFees".to_string(),
+                        endpoint: transport.url().to_string(),
+                        reason: "expected an array of prioritization fees".to_string(),
+                    })?;
+                // One value per slot, like the node's own fees: take the same percentile
+                // across slots, not the single highest slot
+                fees.sort_unstable();
+                Ok((!fees.is_empty()).then(|| percentile(&fees, urgency.percentile())))
+            }
+        }
+    }
+
+    fn current_average(&self) -> Option<f64> {
+        *self.ewma.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Fold `sample` into the moving average and return the new average
+    fn update_average(&self, sample: Option<u64>) -> f64 {
+        let mut ewma = self.ewma.lock().unwrap_or_else(|e| e.into_inner());
+        let alpha = self.config.ewma_alpha.clamp(0.0, 1.0);
+
+        let average = match (*ewma, sample) {
+            (Some(average), Some(sample)) => alpha * sample as f64 + (1.0 - alpha) * average,
+            (None, Some(sample)) => sample as f64,
+            (Some(average), None) => average,
+            (None, None) => 0.0,
+        };
+        *ewma = Some(average);
+        average
+    }
+}
+
+fn account_strings(accounts: &[Pubkey]) -> Vec<String> {
+    accounts.iter().map(Pubkey::to_string).collect()
+}
+
+fn prioritization_fees(result: &Value) -> Option<Vec<u64>> {
+    result
+        .as_array()?
+        .iter()
+        .map(|entry| entry["prioritizationFee"].as_u64())
+        .collect()
+}
+
+/// `pct`-th percentile of sorted `values`, 0 when there are none
+fn percentile(values: &[u64], pct: usize) -> u64 {
+    if values.is_empty() {
+        return 0;
+    }
+    let index = (values.len() - 1) * pct / 100;
+    values[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{collections::HashMap, sync::Arc};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    /// JSON-RPC endpoint on localhost answering each method with a canned `result` or
+    /// `error` object, which tests may swap between calls
+    struct MockRpc {
+        url: String,
+        replies: Arc<Mutex<HashMap<String, Value>>>,
+    }
+
+    impl MockRpc {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let replies = Arc::new(Mutex::new(HashMap::new()));
+            let shared = replies.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(answer(stream, shared.clone()));
+                }
+            });
+            Self { url, replies }
+        }
+
+        fn reply(&self, method: &str, result: Value) {
+            self.set(method, json!({ "result": result }));
+        }
+
+        fn fail(&self, method: &str) {
+            self.set(
+                method,
+                json!({ "error": { "code": -32000, "message": "unavailable" } }),
+            );
+        }
+
+        fn set(&self, method: &str, reply: Value) {
+            self.replies
+                .lock()
+                .unwrap()
+                .insert(method.to_string(), reply);
+        }
+    }
+
+    /// Serve one HTTP request with the reply configured for its JSON-RPC method
+    async fn answer(mut stream: TcpStream, replies: Arc<Mutex<HashMap<String, Value>>>) {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 4096];
+        let body = loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request);
+            let Some(header_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let length: usize = text[..header_end]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse().ok())?
+                })
+                .unwrap_or(0);
+            if request.len() >= header_end + 4 + length {
+                break request[header_end + 4..header_end + 4 + length].to_vec();
+            }
+        };
+
+        let request: Value = serde_json::from_slice(&body).unwrap();
+        let method = request["method"].as_str().unwrap();
+        let mut reply = replies.lock().unwrap()[method].clone();
+        reply["jsonrpc"] = json!("2.0");
+        reply["id"] = request["id"].clone();
+        let reply = reply.to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            reply.len(),
+            reply
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    /// Recent fees of one slot each, as getRecentPrioritizationFees returns them
+    fn slot_fees(fees: &[u64]) -> Value {
+        fees.iter()
+            .enumerate()
+            .map(|(slot, fee)| json!({ "slot": slot, "prioritizationFee": fee }))
+            .collect()
+    }
+
+    fn oracle(rpc: &MockRpc, provider: Option<FeeProvider>) -> FeeOracle {
+        FeeOracle::new(
+            &rpc.url,
+            FeeOracleConfig {
+                provider,
+                ewma_alpha: 0.5,
+                // Failed calls fail at once instead of backing off
+                rpc_limits: RpcLimits {
+                    retry: crate::RetryPolicy {
+                        max_attempts: 1,
+                        ..crate::RetryPolicy::default()
+                    },
+                    ..RpcLimits::default()
+                },
+                ..FeeOracleConfig::default()
+            },
+        )
+    }
+
+    #[test]
+    fn percentiles_pick_from_sorted_values() {
+        let values: Vec<u64> = (1..=10).collect();
+        assert_eq!(percentile(&values, 25), 3);
+        assert_eq!(percentile(&values, 50), 5);
+        assert_eq!(percentile(&values, 90), 9);
+        assert_eq!(percentile(&values, 100), 10);
+        assert_eq!(percentile(&[7], 90), 7);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[tokio::test]
+    async fn the_average_weighs_new_samples_by_alpha() {
+        let rpc = MockRpc::start().await;
+        let oracle = oracle(&rpc, None);
+        assert_eq!(oracle.update_average(Some(100)), 100.0);
+        assert_eq!(oracle.update_average(Some(200)), 150.0);
+        assert_eq!(oracle.update_average(None), 150.0);
+        assert_eq!(oracle.update_average(Some(50)), 100.0);
+    }
+
+    #[tokio::test]
+    async fn urgency_bounds_the_average_by_the_latest_sample() {
+        let rpc = MockRpc::start().await;
+        rpc.reply(
+            "getRecentPrioritizationFees",
+            slot_fees(&[100, 200, 300, 400, 500]),
+        );
+
+        // The average moves halfway to the sample first; Low then takes the lower of it and
+        // the sample (200), High the higher (sample 400)
+        let cases = [
+            (Urgency::Low, 1_000, 200),
+            (Urgency::Low, 0, 100),
+            (Urgency::Medium, 1_000, 650),
+            (Urgency::High, 0, 400),
+            (Urgency::High, 1_000, 700),
+        ];
+        for (urgency, average, expected) in cases {
+            let oracle = oracle(&rpc, None);
+            oracle.update_average(Some(average));
+            let fee = oracle.suggest_fee(&[], urgency).await.unwrap();
+            assert_eq!(
+                fee, expected,
+                "{:?} with an average of {}",
+                urgency, average
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rpc_failures_fall_back_to_the_average() {
+        let rpc = MockRpc::start().await;
+        rpc.fail("getRecentPrioritizationFees");
+
+        let oracle = oracle(&rpc, None);
+        assert!(oracle.suggest_fee(&[], Urgency::High).await.is_err());
+        oracle.update_average(Some(120));
+        assert_eq!(oracle.suggest_fee(&[], Urgency::High).await.unwrap(), 120);
+    }
+
+    #[tokio::test]
+    async fn triton_fees_take_the_percentile_across_slots() {
+        let rpc = MockRpc::start().await;
+        rpc.reply("getRecentPrioritizationFees", slot_fees(&[0]));
+        let triton = MockRpc::start().await;
+        triton.reply(
+            "getRecentPrioritizationFees",
+            slot_fees(&[10, 9_000, 30, 50, 40, 20]),
+        );
+
+        let provider = FeeProvider::Triton {
+            url: triton.url.clone(),
+        };
+        let oracle = oracle(&rpc, Some(provider));
+        assert_eq!(
+            oracle.provider_fee(&[], Urgency::Medium).await.unwrap(),
+            Some(30)
+        );
+        assert_eq!(
+            oracle.provider_fee(&[], Urgency::High).await.unwrap(),
+            Some(50)
+        );
+
+        triton.reply("getRecentPrioritizationFees", slot_fees(&[]));
+        assert_eq!(oracle.provider_fee(&[], Urgency::High).await.unwrap(), None);
+    }
+}
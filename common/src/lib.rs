@@ -0,0 +1,36 @@
+//! Shared building blocks for the task binaries and the workspace toolkit.
+
+pub mod address_book;
+pub mod audit;
+pub mod cli_config;
+pub mod compute_budget;
+pub mod config_file;
+pub mod error;
+pub mod events;
+pub mod fee_oracle;
+pub mod notify;
+pub mod receipt;
+pub mod render;
+pub mod resilience;
+pub mod rpc;
+pub mod safety;
+pub mod submission;
+
+pub use address_book::AddressBook;
+pub use audit::{AuditConfig, AuditLog, AuditRecord};
+pub use cli_config::CliConfig;
+pub use compute_budget::{ComputeBudget, ComputeBudgetConfig};
+pub use config_file::ConfigFormat;
+pub use error::{
+    AuditError, ConfigError, NotifyError, ReceiptError, RenderError, RpcError, SafetyError,
+    SigningError, StreamError,
+};
+pub use events::{Event, EventBus, SkipReason, TransferState};
+pub use fee_oracle::{FeeOracle, FeeOracleConfig, FeeProvider, Urgency};
+pub use resilience::{BreakerConfig, CallError, Resilient, RetryPolicy};
+pub use notify::{EmailConfig, NotificationConfig, ReportAttachment, WebhookConfig};
+pub use receipt::{ActionReceipt, ReceiptConfig, ReceiptLog};
+pub use render::{OutputFormat, Render};
+pub use rpc::{EndpointPool, FailoverSender, RpcLimits, RpcTransport};
+pub use safety::SafetyConfig;
+pub use submission::{Submission, SubmissionConfig, SubmissionProfile, Submitter, TpuConfig};
@@ -0,0 +1,174 @@
+use crate::error::NotifyError;
+use crate::resilience::RetryPolicy;
+use lettre::{
+    message::{header::ContentType, Attachment, Mailbox, MultiPart, SinglePart},
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+/// The `notifications` config section shared by the binaries: email receives the report
+/// of a finished run, the webhook per-event callbacks (e.g. every task2 transfer)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    pub email: Option<EmailConfig>,
+    pub webhook: Option<WebhookConfig>,
+}
+
+/// SMTP delivery of run reports
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    /// Submission port; 465 uses implicit TLS, anything else STARTTLS
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    pub username: Option<String>,
+    /// Environment variable holding the SMTP password, so it stays out of the config file
+    pub password_env: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/// A file attached to a report email
+#[derive(Debug, Clone)]
+pub struct ReportAttachment {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl EmailConfig {
+    /// Send `text` as the body of an email with `subject` and the given attachments to
+    /// every recipient. Blocks until the SMTP server accepted the message.
+    pub fn send(
+        &self,
+        subject: &str,
+        text: &str,
+        attachments: &[ReportAttachment],
+    ) -> Result<(), NotifyError> {
+        let mut builder = Message::builder()
+            .from(parse_mailbox(&self.from)?)
+            .subject(subject);
+        for recipient in &self.to {
+            builder = builder.to(parse_mailbox(recipient)?);
+        }
+
+        let mut body = MultiPart::mixed().singlepart(SinglePart::plain(text.to_string()));
+        for attachment in attachments {
+            let content_type =
+                ContentType::parse(attachment.content_type).map_err(|e| NotifyError::Message {
+                    reason: format!("invalid content type '{}': {}", attachment.content_type, e),
+                })?;
+            body = body.singlepart(
+                Attachment::new(attachment.filename.clone())
+                    .body(attachment.body.clone(), content_type),
+            );
+        }
+
+        let message = builder.multipart(body).map_err(|e| NotifyError::Message {
+            reason: e.to_string(),
+        })?;
+
+        let transport = if self.smtp_port == 465 {
+            SmtpTransport::relay(&self.smtp_host)
+        } else {
+            SmtpTransport::starttls_relay(&self.smtp_host)
+        }
+        .map_err(|e| NotifyError::Smtp {
+            host: self.smtp_host.clone(),
+            source: e,
+        })?
+        .port(self.smtp_port);
+
+        let transport = match &self.username {
+            Some(username) => {
+                let password = match &self.password_env {
+                    Some(var) => std::env::var(var)
+                        .map_err(|_| NotifyError::MissingPassword { var: var.clone() })?,
+                    None => String::new(),
+                };
+                transport.credentials(Credentials::new(username.clone(), password))
+            }
+            None => transport,
+        };
+
+        transport
+            .build()
+            .send(&message)
+            .map_err(|e| NotifyError::Smtp {
+                host: self.smtp_host.clone(),
+                source: e,
+            })?;
+        Ok(())
+    }
+}
+
+/// HTTP endpoint receiving JSON event callbacks
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Extra request headers, e.g. `Authorization`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_webhook_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Backoff for failed deliveries (connection errors and non-2xx responses)
+    #[serde(default)]
+    pub retry: RetryPolicy,
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    10
+}
+
+impl WebhookConfig {
+    /// POST `payload` as JSON, retrying until the endpoint answers with a 2xx status
+    pub async fn post<T: Serialize>(&self, payload: &T) -> Result<(), NotifyError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(self.timeout_secs))
+            .build()
+            .map_err(|e| self.webhook_error(e))?;
+
+        let mut attempt = 0;
+        loop {
+            let mut request = client.post(&self.url).json(payload);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+
+            let result = match request.send().await {
+                Ok(response) => response.error_for_status().map(drop),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    attempt += 1;
+                    if attempt >= self.retry.max_attempts {
+                        return Err(self.webhook_error(e));
+                    }
+                    tokio::time::sleep(self.retry.delay(attempt)).await;
+                }
+            }
+        }
+    }
+
+    fn webhook_error(&self, source: reqwest::Error) -> NotifyError {
+        NotifyError::Webhook {
+            url: self.url.clone(),
+            source,
+        }
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, NotifyError> {
+    address.parse().map_err(|_| NotifyError::InvalidAddress {
+        address: address.to_string(),
+    })
+}
@@ -0,0 +1,172 @@
+use crate::error::ReceiptError;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    hash::{hash, Hash},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+};
+
+/// Where receipts are written and which operator key signs them; off unless `log` is set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ReceiptConfig {
+    /// Append-only JSON lines file
+    pub log: Option<PathBuf>,
+    /// Keypair file of the deployment's operator key
+    pub key: Option<PathBuf>,
+}
+
+impl ReceiptConfig {
+    pub fn open(&self) -> Result<Option<ReceiptLog>, ReceiptError> {
+        let Some(log) = &self.log else {
+            return Ok(None);
+        };
+
+        let key_path = self.key.as_ref().ok_or_else(|| ReceiptError::Key {
+            path: PathBuf::new(),
+            reason: "receipts.key must be set when receipts.log is".to_string(),
+        })?;
+        let key = read_keypair_file(key_path).map_err(|e| ReceiptError::Key {
+            path: key_path.clone(),
+            reason: e.to_string(),
+        })?;
+
+        Ok(Some(ReceiptLog::new(log, key)))
+    }
+}
+
+/// A transaction sent because a rule triggered, as reported by the deployment that sent it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionReceipt {
+    /// Rule (pipeline) that triggered
+    pub rule: String,
+    pub trigger_slot: u64,
+    pub signature: String,
+    pub source: String,
+    pub destination: String,
+    pub lamports: u64,
+    /// When the trigger fired and when its transaction was accepted, RFC 3339
+    pub triggered_at: String,
+    pub sent_at: String,
+}
+
+/// One line of the receipt log. `hash` covers the receipt and `operator_signature` is the
+/// operator key's signature over `hash`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedReceipt {
+    #[serde(flatten)]
+    pub receipt: ActionReceipt,
+    pub hash: String,
+    pub operator: String,
+    pub operator_signature: String,
+}
+
+fn receipt_hash(receipt: &ActionReceipt) -> Hash {
+    hash(&serde_json::to_vec(receipt).expect("receipt serializes"))
+}
+
+/// Append-only log of signed action receipts. Each receipt stands on its own, so a
+/// downstream system can check any one of them without the rest of the log.
+pub struct ReceiptLog {
+    path: PathBuf,
+    key: Keypair,
+    write: Mutex<()>,
+}
+
+impl ReceiptLog {
+    /// Append to `path`, created on the first receipt
+    pub fn new(path: &Path, key: Keypair) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            key,
+            write: Mutex::new(()),
+        }
+    }
+
+    pub fn operator(&self) -> Pubkey {
+        self.key.pubkey()
+    }
+
+    /// Sign and durably append `receipt`
+    pub fn append(&self, receipt: ActionReceipt) -> Result<SignedReceipt, ReceiptError> {
+        let receipt_hash = receipt_hash(&receipt);
+        let signed = SignedReceipt {
+            receipt,
+            hash: receipt_hash.to_string(),
+            operator: self.key.pubkey().to_string(),
+            operator_signature: self.key.sign_message(receipt_hash.as_ref()).to_string(),
+        };
+
+        let io_error = |source| ReceiptError::Io {
+            path: self.path.clone(),
+            source,
+        };
+        let line = serde_json::to_string(&signed).expect("signed receipt serializes");
+        let _write = self.write.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(io_error)?;
+        writeln!(file, "{}", line).map_err(io_error)?;
+        file.sync_data().map_err(io_error)?;
+
+        Ok(signed)
+    }
+}
+
+/// Check every receipt's hash and signature. When `operator` is given, receipts must also
+/// be signed by that key. Returns the verified receipts.
+pub fn verify(path: &Path, operator: Option<&Pubkey>) -> Result<Vec<SignedReceipt>, ReceiptError> {
+    let io_error = |source| ReceiptError::Io {
+        path: path.to_path_buf(),
+        source,
+    };
+    let file = File::open(path).map_err(io_error)?;
+
+    let mut receipts = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(io_error)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let number = index + 1;
+
+        let signed: SignedReceipt =
+            serde_json::from_str(&line).map_err(|e| ReceiptError::Corrupt {
+                line: number,
+                reason: e.to_string(),
+            })?;
+
+        let receipt_hash = receipt_hash(&signed.receipt);
+        if receipt_hash.to_string() != signed.hash {
+            return Err(ReceiptError::HashMismatch { line: number });
+        }
+
+        let signer = Pubkey::from_str(&signed.operator)
+            .map_err(|_| ReceiptError::BadSignature { line: number })?;
+        if operator.is_some_and(|operator| *operator != signer) {
+            return Err(ReceiptError::UnexpectedOperator {
+                line: number,
+                operator: signed.operator.clone(),
+            });
+        }
+
+        let signature = Signature::from_str(&signed.operator_signature)
+            .map_err(|_| ReceiptError::BadSignature { line: number })?;
+        if !signature.verify(signer.as_ref(), receipt_hash.as_ref()) {
+            return Err(ReceiptError::BadSignature { line: number });
+        }
+
+        receipts.push(signed);
+    }
+
+    Ok(receipts)
+}
@@ -0,0 +1,213 @@
+//! One rendering of every report (task1 balances, task2 results, task3 pipeline summaries)
+//! in the formats `--output` accepts.
+
+use crate::error::RenderError;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{fmt, path::Path, str::FromStr};
+
+/// Format a report is rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns for people, or the report's own human-readable layout
+    Table,
+    /// The whole report as one pretty-printed document
+    Json,
+    /// One row per record, then a blank line and `metric,value` rows with the summary
+    Csv,
+    /// One JSON object per record and line, then the summary as a `{"summary": ...}` line
+    Ndjson,
+    /// A pipe table of the records, then one of the summary
+    Markdown,
+}
+
+impl OutputFormat {
+    /// Format implied by the extension of `path`: `.csv`, `.json`, `.ndjson`/`.jsonl`,
+    /// `.md`/`.markdown` or `.txt` for a table
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "txt" => Some(OutputFormat::Table),
+            "jsonl" => Some(OutputFormat::Ndjson),
+            "md" => Some(OutputFormat::Markdown),
+            other => other.parse().ok(),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "markdown" => Ok(OutputFormat::Markdown),
+            _ => Err(format!(
+                "unknown output format '{}', expected table, json, csv, ndjson or markdown",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Json => "json",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Markdown => "markdown",
+        })
+    }
+}
+
+/// A report as records of named columns plus summary metrics. JSON serializes the report
+/// itself; every other format is built from `columns`, `rows` and `summary`.
+pub trait Render: Serialize {
+    /// Names of the columns of every row
+    fn columns(&self) -> &'static [&'static str];
+
+    /// One cell per column for every record
+    fn rows(&self) -> Vec<Vec<String>>;
+
+    /// Totals following the records, as metric,value pairs
+    fn summary(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// The records written by NDJSON; by default each row keyed by its column names
+    fn records(&self) -> Result<Vec<Value>, RenderError> {
+        let columns = self.columns();
+        Ok(self
+            .rows()
+            .into_iter()
+            .map(|row| {
+                let record: Map<String, Value> = columns
+                    .iter()
+                    .map(|column| column.to_string())
+                    .zip(row.into_iter().map(Value::String))
+                    .collect();
+                Value::Object(record)
+            })
+            .collect())
+    }
+
+    /// The report for `--output table`; by default the rows as aligned columns followed by
+    /// the summary
+    fn table(&self) -> String {
+        let mut table = aligned(self.columns(), &self.rows());
+        for (metric, value) in self.summary() {
+            table.push_str(&format!("{}: {}\n", metric, value));
+        }
+        table
+    }
+}
+
+/// Render `report` in `format`, ending with a newline
+pub fn render<R: Render + ?Sized>(report: &R, format: OutputFormat) -> Result<String, RenderError> {
+    match format {
+        OutputFormat::Table => Ok(report.table()),
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(report)? + "\n"),
+        OutputFormat::Csv => csv(report),
+        OutputFormat::Ndjson => {
+            let mut lines = String::new();
+            for record in report.records()? {
+                lines.push_str(&serde_json::to_string(&record)?);
+                lines.push('\n');
+            }
+            let summary = report.summary();
+            if !summary.is_empty() {
+                let summary: Map<String, Value> = summary
+                    .into_iter()
+                    .map(|(metric, value)| (metric, Value::String(value)))
+                    .collect();
+                lines.push_str(&serde_json::to_string(
+                    &serde_json::json!({ "summary": summary }),
+                )?);
+                lines.push('\n');
+            }
+            Ok(lines)
+        }
+        OutputFormat::Markdown => {
+            let mut markdown = markdown_table(report.columns(), &report.rows());
+            let summary = report.summary();
+            if !summary.is_empty() {
+                let rows: Vec<Vec<String>> = summary
+                    .into_iter()
+                    .map(|(metric, value)| vec![metric, value])
+                    .collect();
+                markdown.push('\n');
+                markdown.push_str(&markdown_table(&["metric", "value"], &rows));
+            }
+            Ok(markdown)
+        }
+    }
+}
+
+fn csv<R: Render + ?Sized>(report: &R) -> Result<String, RenderError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(report.columns())?;
+    for row in report.rows() {
+        writer.write_record(row)?;
+    }
+    let mut bytes = writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?;
+
+    let summary = report.summary();
+    if !summary.is_empty() {
+        // A bare empty line; an empty record would be written as `""`
+        bytes.push(b'\n');
+        let mut writer = csv::Writer::from_writer(bytes);
+        writer.write_record(["metric", "value"])?;
+        for (metric, value) in summary {
+            writer.write_record([metric, value])?;
+        }
+        bytes = writer
+            .into_inner()
+            .map_err(|e| csv::Error::from(e.into_error()))?;
+    }
+    Ok(String::from_utf8(bytes).expect("CSV of strings is UTF-8"))
+}
+
+/// `rows` under `columns`, each column as wide as its widest cell
+fn aligned(columns: &[&str], rows: &[Vec<String>]) -> String {
+    let single_line = |cell: &str| cell.replace('\n', " ");
+    let mut widths: Vec<usize> = columns.iter().map(|column| column.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(single_line(cell).chars().count());
+        }
+    }
+
+    let line = |cells: Vec<String>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        padded.join("  ").trim_end().to_string() + "\n"
+    };
+    let mut table = line(columns.iter().map(|column| column.to_string()).collect());
+    for row in rows {
+        table.push_str(&line(row.iter().map(|cell| single_line(cell)).collect()));
+    }
+    table
+}
+
+/// A Markdown pipe table; pipes in cells are escaped and line breaks become `<br>`
+fn markdown_table(columns: &[&str], rows: &[Vec<String>]) -> String {
+    let escape = |cell: &str| cell.replace('|', "\\|").replace('\n', "<br>");
+    let line = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+
+    let mut table = line(columns.iter().map(|column| escape(column)).collect());
+    table.push_str(&line(columns.iter().map(|_| "---".to_string()).collect()));
+    for row in rows {
+        table.push_str(&line(row.iter().map(|cell| escape(cell)).collect()));
+    }
+    table
+}
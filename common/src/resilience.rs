@@ -0,0 +1,508 @@
+use rand::Rng;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// Exponential backoff retry policy for transient failures
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts including the first one
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    /// Wait a random 50-100% of each backoff so clients that failed together do not
+    /// retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 200,
+            max_backoff_ms: 5_000,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before retry number `attempt` (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+        Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+
+    /// Backoff before retry number `attempt`, with jitter applied when enabled
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        if !self.jitter {
+            return backoff;
+        }
+        backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// When a circuit breaker trips and how long it stays open
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default)]
+pub struct BreakerConfig {
+    /// Consecutive transient failures that open the circuit
+    pub failure_threshold: u32,
+    /// Seconds the circuit stays open before a half-open probe is allowed
+    pub open_secs: u64,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen,
+}
+
+/// Per-endpoint circuit breaker: closed -> open after repeated failures -> half-open
+/// single probe once the open period elapses -> closed again on success.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may go out now, and as what; while half-open only the probe call is
+    /// admitted
+    fn try_acquire(&self) -> Option<Admission> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        match *state {
+            BreakerState::Closed { .. } => Some(Admission::Call),
+            BreakerState::Open { until } if Instant::now() >= until => {
+                *state = BreakerState::HalfOpen;
+                Some(Admission::Probe)
+            }
+            BreakerState::Open { .. } | BreakerState::HalfOpen => None,
+        }
+    }
+
+    /// Let the next call probe again: the probe ended without telling whether the endpoint
+    /// recovered
+    fn release_probe(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if *state == BreakerState::HalfOpen {
+            *state = BreakerState::Open {
+                until: Instant::now(),
+            };
+        }
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) =
+            BreakerState::Closed { failures: 0 };
+    }
+
+    pub(crate) fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let open = BreakerState::Open {
+            until: Instant::now() + Duration::from_secs(self.config.open_secs),
+        };
+
+        *state = match *state {
+            BreakerState::Closed { failures } if failures + 1 < self.config.failure_threshold => {
+                BreakerState::Closed {
+                    failures: failures + 1,
+                }
+            }
+            _ => open,
+        };
+    }
+
+    /// Time until an open circuit admits its next probe
+    pub fn retry_in(&self) -> Duration {
+        match *self.state.lock().unwrap_or_else(|e| e.into_inner()) {
+            BreakerState::Open { until } => until.saturating_duration_since(Instant::now()),
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(
+            *self.state.lock().unwrap_or_else(|e| e.into_inner()),
+            BreakerState::Closed { .. }
+        )
+    }
+}
+
+/// How [`CircuitBreaker::try_acquire`] admitted a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Admission {
+    Call,
+    /// The single call of a half-open circuit
+    Probe,
+}
+
+/// Re-opens the circuit when a probe is dropped before it settled, e.g. cancelled by a
+/// timeout, so the breaker does not stay half-open with no probe in flight
+struct ProbeGuard<'a> {
+    breaker: &'a CircuitBreaker,
+    settled: bool,
+}
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        if !self.settled {
+            self.breaker.record_failure();
+        }
+    }
+}
+
+/// Process-wide breakers keyed by endpoint and configuration, so every client of a provider
+/// configured the same way shares its state; a client with other thresholds gets its own
+/// breaker rather than silently inheriting the first one's
+pub(crate) fn circuit_breaker(endpoint: &str, config: BreakerConfig) -> Arc<CircuitBreaker> {
+    type Breakers = HashMap<(String, BreakerConfig), Arc<CircuitBreaker>>;
+    static BREAKERS: OnceLock<Mutex<Breakers>> = OnceLock::new();
+
+    BREAKERS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry((endpoint.to_string(), config))
+        .or_insert_with(|| Arc::new(CircuitBreaker::new(config)))
+        .clone()
+}
+
+/// Failure of a call made through [`Resilient`]
+#[derive(Debug)]
+pub enum CallError<E> {
+    /// The endpoint's circuit is open; the call was not attempted
+    CircuitOpen { endpoint: String },
+    /// The call failed permanently or ran out of retries
+    Failed(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CallError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::CircuitOpen { endpoint } => {
+                write!(f, "Circuit breaker open for {}", endpoint)
+            }
+            CallError::Failed(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for CallError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CallError::CircuitOpen { .. } => None,
+            CallError::Failed(e) => Some(e),
+        }
+    }
+}
+
+/// Retry + circuit breaker layer wrapped around calls to one endpoint
+#[derive(Debug, Clone)]
+pub struct Resilient {
+    endpoint: String,
+    retry: RetryPolicy,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl Resilient {
+    pub fn new(endpoint: &str, retry: RetryPolicy, breaker: BreakerConfig) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            retry,
+            breaker: circuit_breaker(endpoint, breaker),
+        }
+    }
+
+    pub fn breaker(&self) -> &CircuitBreaker {
+        &self.breaker
+    }
+
+    /// Run `op`, retrying failures classified as transient by `is_transient` with backoff.
+    /// Transient failures count against the endpoint's breaker; once it is open calls fail
+    /// fast until a half-open probe succeeds. Permanent errors mean the endpoint answered,
+    /// so they count as healthy responses, except that a probe failing permanently only lets
+    /// the next call probe again. A probe cancelled mid-flight counts as a failure.
+    pub async fn call<T, E, F, Fut>(
+        &self,
+        mut op: F,
+        is_transient: impl Fn(&E) -> bool,
+    ) -> Result<T, CallError<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let Some(admission) = self.breaker.try_acquire() else {
+                return Err(CallError::CircuitOpen {
+                    endpoint: self.endpoint.clone(),
+                });
+            };
+
+            // Built lazily: an unused guard would count as a cancelled probe when dropped
+            let mut probe = (admission == Admission::Probe).then(|| ProbeGuard {
+                breaker: &self.breaker,
+                settled: false,
+            });
+            let result = op().await;
+            if let Some(probe) = &mut probe {
+                probe.settled = true;
+            }
+
+            match result {
+                Ok(value) => {
+                    self.breaker.record_success();
+                    return Ok(value);
+                }
+                Err(e) if is_transient(&e) => {
+                    self.breaker.record_failure();
+                    attempt += 1;
+                    if attempt >= self.retry.max_attempts {
+                        return Err(CallError::Failed(e));
+                    }
+                    tokio::time::sleep(self.retry.delay(attempt)).await;
+                }
+                Err(e) => {
+                    if probe.is_some() {
+                        self.breaker.release_probe();
+                    } else {
+                        self.breaker.record_success();
+                    }
+                    return Err(CallError::Failed(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(failure_threshold: u32, open_secs: u64) -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig {
+            failure_threshold,
+            open_secs,
+        })
+    }
+
+    fn state(breaker: &CircuitBreaker) -> BreakerState {
+        *breaker.state.lock().unwrap()
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+    }
+
+    /// A `Resilient` with its own breaker, so tests do not share the process-wide registry
+    fn resilient(breaker: CircuitBreaker) -> Resilient {
+        Resilient {
+            endpoint: "test".to_string(),
+            retry: RetryPolicy {
+                max_attempts: 1,
+                ..RetryPolicy::default()
+            },
+            breaker: Arc::new(breaker),
+        }
+    }
+
+    #[test]
+    fn breaker_opens_after_threshold_consecutive_failures() {
+        let breaker = breaker(3, 60);
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(state(&breaker), BreakerState::Closed { failures: 2 });
+        assert_eq!(breaker.try_acquire(), Some(Admission::Call));
+
+        breaker.record_failure();
+        assert!(breaker.is_open());
+        assert_eq!(breaker.try_acquire(), None);
+        assert!(breaker.retry_in() > Duration::from_secs(59));
+    }
+
+    #[test]
+    fn breaker_success_resets_the_failure_count() {
+        let breaker = breaker(2, 60);
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(state(&breaker), BreakerState::Closed { failures: 1 });
+    }
+
+    #[test]
+    fn breaker_goes_half_open_with_a_single_probe_then_closes_on_success() {
+        let breaker = breaker(1, 0);
+        breaker.record_failure();
+
+        assert_eq!(breaker.try_acquire(), Some(Admission::Probe));
+        assert_eq!(state(&breaker), BreakerState::HalfOpen);
+        assert_eq!(breaker.try_acquire(), None);
+
+        breaker.record_success();
+        assert_eq!(state(&breaker), BreakerState::Closed { failures: 0 });
+        assert_eq!(breaker.try_acquire(), Some(Admission::Call));
+    }
+
+    #[test]
+    fn breaker_reopens_when_the_probe_fails() {
+        let breaker = breaker(3, 60);
+        *breaker.state.lock().unwrap() = BreakerState::HalfOpen;
+
+        breaker.record_failure();
+        assert!(matches!(state(&breaker), BreakerState::Open { .. }));
+        assert_eq!(breaker.try_acquire(), None);
+    }
+
+    #[test]
+    fn cancelled_probe_reopens_the_circuit() {
+        let resilient = resilient(breaker(1, 0));
+        resilient.breaker.record_failure();
+
+        runtime().block_on(async {
+            let probe = resilient.call(std::future::pending::<Result<(), ()>>, |_: &()| true);
+            let timed_out = tokio::time::timeout(Duration::from_millis(1), probe).await;
+            assert!(timed_out.is_err());
+        });
+
+        assert!(matches!(
+            state(&resilient.breaker),
+            BreakerState::Open { .. }
+        ));
+        // With open_secs 0 the next call is admitted as a new probe
+        assert_eq!(resilient.breaker.try_acquire(), Some(Admission::Probe));
+    }
+
+    #[test]
+    fn permanent_error_from_a_probe_does_not_close_the_circuit() {
+        let resilient = resilient(breaker(1, 60));
+        *resilient.breaker.state.lock().unwrap() = BreakerState::Open {
+            until: Instant::now(),
+        };
+
+        let result =
+            runtime().block_on(resilient.call(|| async { Err::<(), _>("bad") }, |_| false));
+        assert!(matches!(result, Err(CallError::Failed("bad"))));
+        assert!(resilient.breaker.is_open());
+        assert_eq!(resilient.breaker.try_acquire(), Some(Admission::Probe));
+    }
+
+    #[test]
+    fn transient_failures_are_retried_and_counted() {
+        let resilient = Resilient {
+            retry: RetryPolicy {
+                max_attempts: 3,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 1,
+                jitter: false,
+            },
+            ..resilient(breaker(10, 60))
+        };
+        let mut calls = 0;
+
+        let result = runtime().block_on(resilient.call(
+            || {
+                calls += 1;
+                async { Err::<(), _>("timeout") }
+            },
+            |_| true,
+        ));
+        assert!(matches!(result, Err(CallError::Failed("timeout"))));
+        assert_eq!(calls, 3);
+        assert_eq!(
+            state(&resilient.breaker),
+            BreakerState::Closed { failures: 3 }
+        );
+    }
+
+    #[test]
+    fn breakers_are_shared_per_endpoint_and_configuration() {
+        let config = BreakerConfig {
+            failure_threshold: 2,
+            open_secs: 60,
+        };
+        let strict = BreakerConfig {
+            failure_threshold: 1,
+            ..config
+        };
+        let endpoint = "https://breaker-registry.test";
+
+        let shared = circuit_breaker(endpoint, config);
+        assert!(Arc::ptr_eq(&shared, &circuit_breaker(endpoint, config)));
+        assert!(!Arc::ptr_eq(&shared, &circuit_breaker(endpoint, strict)));
+        assert!(!Arc::ptr_eq(
+            &shared,
+            &circuit_breaker("https://other.test", config)
+        ));
+
+        // The strict breaker opens on its own threshold, not the first registered one
+        circuit_breaker(endpoint, strict).record_failure();
+        assert!(circuit_breaker(endpoint, strict).is_open());
+        assert!(!shared.is_open());
+    }
+
+    #[test]
+    fn backoff_doubles_and_is_capped() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff_ms: 200,
+            max_backoff_ms: 1_000,
+            jitter: false,
+        };
+
+        let backoffs: Vec<u128> = (1..=5)
+            .map(|attempt| policy.backoff(attempt).as_millis())
+            .collect();
+        assert_eq!(backoffs, [200, 400, 800, 1_000, 1_000]);
+        assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(1_000));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+    }
+
+    #[test]
+    fn jittered_delay_stays_within_half_to_full_backoff() {
+        let policy = RetryPolicy {
+            jitter: true,
+            ..RetryPolicy::default()
+        };
+
+        for _ in 0..100 {
+            let delay = policy.delay(3);
+            assert!(delay >= policy.backoff(3) / 2 && delay <= policy.backoff(3));
+        }
+    }
+}
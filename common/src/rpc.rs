@@ -0,0 +1,740 @@
+use crate::{
+    error::RpcError,
+    resilience::{
+        circuit_breaker, BreakerConfig, CallError, CircuitBreaker, Resilient, RetryPolicy,
+    },
+};
+use async_trait::async_trait;
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    nonblocking,
+    rpc_client::{RpcClient, RpcClientConfig},
+    rpc_custom_error,
+    rpc_request::{RpcError as ClientRpcError, RpcRequest, RpcResponseErrorData},
+    rpc_response::RpcSimulateTransactionResult,
+    rpc_sender::{RpcSender, RpcTransportStats},
+};
+use solana_sdk::commitment_config::CommitmentConfig;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// Default HTTP timeout for a single RPC request
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Retry-After values above this are treated as bogus and replaced by the default pause
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// Pause applied on 429 responses without a usable Retry-After header
+const DEFAULT_RATE_LIMIT_PAUSE: Duration = Duration::from_millis(500);
+
+/// Rate limiting and retry knobs for one RPC endpoint
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RpcLimits {
+    /// Sustained request rate allowed towards the endpoint
+    pub requests_per_second: f64,
+    /// Requests that may be sent back-to-back before throttling kicks in
+    pub burst: u32,
+    /// How many 429 responses a single request tolerates before failing
+    pub max_rate_limit_retries: u32,
+    /// Backoff for connection errors, timeouts and 5xx responses
+    pub retry: RetryPolicy,
+    /// Fail fast once the endpoint keeps failing instead of waiting on every request
+    pub circuit_breaker: BreakerConfig,
+}
+
+impl Default for RpcLimits {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 10.0,
+            burst: 20,
+            max_rate_limit_retries: 5,
+            retry: RetryPolicy::default(),
+            circuit_breaker: BreakerConfig::default(),
+        }
+    }
+}
+
+/// Request counters for one endpoint, shared by every client talking to it
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointMetrics {
+    pub endpoint: String,
+    pub requests: u64,
+    pub rate_limited: u64,
+    pub errors: u64,
+    pub avg_latency_ms: f64,
+    pub throttled_ms: u64,
+    /// Requests a [`FailoverSender`] moved on to another endpoint after this one failed them
+    pub failovers: u64,
+    /// Whether the endpoint's circuit breaker admits requests
+    pub healthy: bool,
+}
+
+/// Token bucket that hands out reservations: the returned duration is how long
+/// the caller has to wait before its request may go out.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl TokenBucket {
+    fn new(limits: &RpcLimits) -> Self {
+        let capacity = limits.burst.max(1) as f64;
+        Self {
+            rate: limits.requests_per_second.max(0.001),
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now(),
+            paused_until: None,
+        }
+    }
+
+    fn reserve(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+
+        // Tokens may go negative: each queued caller waits for its own refill slot
+        self.tokens -= 1.0;
+        let mut wait = if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        };
+
+        if let Some(paused_until) = self.paused_until {
+            wait = wait.max(paused_until.saturating_duration_since(now));
+        }
+
+        wait
+    }
+
+    /// Hold back every caller until `duration` has passed (server asked us to slow down)
+    fn pause(&mut self, duration: Duration) {
+        let until = Instant::now() + duration;
+        self.paused_until = Some(
+            self.paused_until
+                .map_or(until, |current| current.max(until)),
+        );
+    }
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    bucket: Mutex<TokenBucket>,
+    max_rate_limit_retries: u32,
+    requests: AtomicU64,
+    rate_limited: AtomicU64,
+    errors: AtomicU64,
+    latency_us: AtomicU64,
+    throttled_us: AtomicU64,
+    failovers: AtomicU64,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl Endpoint {
+    fn metrics(&self) -> EndpointMetrics {
+        let requests = self.requests.load(Ordering::Relaxed);
+        let latency_us = self.latency_us.load(Ordering::Relaxed);
+
+        EndpointMetrics {
+            endpoint: self.url.clone(),
+            requests,
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            avg_latency_ms: if requests > 0 {
+                latency_us as f64 / requests as f64 / 1000.0
+            } else {
+                0.0
+            },
+            throttled_ms: self.throttled_us.load(Ordering::Relaxed) / 1000,
+            failovers: self.failovers.load(Ordering::Relaxed),
+            healthy: !self.breaker.is_open(),
+        }
+    }
+}
+
+/// What makes two clients share an endpoint's bucket and breaker: the URL and the limits
+/// they enforce. Retry policies are per client and do not take part.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct EndpointKey {
+    url: String,
+    requests_per_second: u64,
+    burst: u32,
+    max_rate_limit_retries: u32,
+    circuit_breaker: BreakerConfig,
+}
+
+impl EndpointKey {
+    fn new(url: &str, limits: &RpcLimits) -> Self {
+        Self {
+            url: url.to_string(),
+            requests_per_second: limits.requests_per_second.to_bits(),
+            burst: limits.burst,
+            max_rate_limit_retries: limits.max_rate_limit_retries,
+            circuit_breaker: limits.circuit_breaker,
+        }
+    }
+}
+
+/// Process-wide registry so every client for the same URL and limits shares one bucket; a
+/// client configured differently gets its own instead of the first client's limits
+fn endpoints() -> &'static Mutex<HashMap<EndpointKey, Arc<Endpoint>>> {
+    static ENDPOINTS: OnceLock<Mutex<HashMap<EndpointKey, Arc<Endpoint>>>> = OnceLock::new();
+    ENDPOINTS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn endpoint(url: &str, limits: RpcLimits) -> Arc<Endpoint> {
+    let mut endpoints = endpoints().lock().unwrap_or_else(|e| e.into_inner());
+
+    endpoints
+        .entry(EndpointKey::new(url, &limits))
+        .or_insert_with(|| {
+            Arc::new(Endpoint {
+                url: url.to_string(),
+                bucket: Mutex::new(TokenBucket::new(&limits)),
+                max_rate_limit_retries: limits.max_rate_limit_retries,
+                requests: AtomicU64::new(0),
+                rate_limited: AtomicU64::new(0),
+                errors: AtomicU64::new(0),
+                latency_us: AtomicU64::new(0),
+                throttled_us: AtomicU64::new(0),
+                failovers: AtomicU64::new(0),
+                breaker: circuit_breaker(url, limits.circuit_breaker),
+            })
+        })
+        .clone()
+}
+
+/// Metrics for every endpoint contacted so far in this process, one entry per URL and limits
+pub fn metrics() -> Vec<EndpointMetrics> {
+    let endpoints = endpoints().lock().unwrap_or_else(|e| e.into_inner());
+
+    let mut metrics: Vec<EndpointMetrics> = endpoints.values().map(|e| e.metrics()).collect();
+    metrics.sort_by(|a, b| a.endpoint.cmp(&b.endpoint));
+    metrics
+}
+
+/// JSON-RPC over HTTP with per-endpoint token-bucket limiting, Retry-After aware
+/// 429 handling, retries behind a circuit breaker and request metrics.
+#[derive(Clone)]
+pub struct RpcTransport {
+    http: reqwest::Client,
+    endpoint: Arc<Endpoint>,
+    resilience: Resilient,
+    next_id: Arc<AtomicU64>,
+}
+
+impl RpcTransport {
+    pub fn new(url: &str, limits: RpcLimits) -> Self {
+        Self::with_timeout(url, limits, DEFAULT_TIMEOUT)
+    }
+
+    pub fn with_timeout(url: &str, limits: RpcLimits, timeout: Duration) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .expect("HTTP client configuration is valid");
+
+        Self {
+            http,
+            endpoint: endpoint(url, limits),
+            resilience: Resilient::new(url, limits.retry, limits.circuit_breaker),
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.endpoint.url
+    }
+
+    /// Call `method` and return its `result`, for callers building raw JSON-RPC requests
+    pub async fn call(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        self.execute(request).await.map_err(|e| match e {
+            CallError::Failed(e) => RpcError::request(method, self.url(), e),
+            CallError::CircuitOpen { endpoint } => RpcError::CircuitOpen { endpoint },
+        })
+    }
+
+    async fn execute(&self, request: Value) -> Result<Value, CallError<ClientError>> {
+        // Resending the same request is safe: signed transactions keep their signature
+        let result = self
+            .resilience
+            .call(|| self.execute_inner(&request), is_transient)
+            .await;
+
+        if result.is_err() {
+            self.endpoint.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    async fn execute_inner(&self, request: &Value) -> ClientResult<Value> {
+        let body = request.to_string();
+        let mut rate_limit_retries = self.endpoint.max_rate_limit_retries;
+
+        loop {
+            let wait = self
+                .endpoint
+                .bucket
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .reserve();
+            if !wait.is_zero() {
+                self.endpoint
+                    .throttled_us
+                    .fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+                tokio::time::sleep(wait).await;
+            }
+
+            let start = Instant::now();
+            let response = self
+                .http
+                .post(&self.endpoint.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body.clone())
+                .send()
+                .await;
+            self.endpoint.requests.fetch_add(1, Ordering::Relaxed);
+            self.endpoint
+                .latency_us
+                .fetch_add(start.elapsed().as_micros() as u64, Ordering::Relaxed);
+            let response = response?;
+
+            if response.status() == StatusCode::TOO_MANY_REQUESTS && rate_limit_retries > 0 {
+                rate_limit_retries -= 1;
+                self.endpoint.rate_limited.fetch_add(1, Ordering::Relaxed);
+
+                let pause = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .map(Duration::from_secs)
+                    .filter(|pause| *pause <= MAX_RETRY_AFTER)
+                    .unwrap_or(DEFAULT_RATE_LIMIT_PAUSE);
+
+                // Throttle every caller of this endpoint, not just this request
+                self.endpoint
+                    .bucket
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .pause(pause);
+                continue;
+            }
+
+            let mut json: Value = response.error_for_status()?.json().await?;
+            if json["error"].is_object() {
+                return Err(rpc_error(&json["error"]).into());
+            }
+
+            return Ok(json["result"].take());
+        }
+    }
+}
+
+/// Failures worth retrying: the endpoint was unreachable, slow, overloaded or behind
+fn is_transient(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.is_request()
+                || e.status().is_some_and(|status| {
+                    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+                })
+        }
+        ClientErrorKind::RpcError(ClientRpcError::RpcResponseError { code, .. }) => {
+            *code == rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+        }
+        _ => false,
+    }
+}
+
+/// Map a JSON-RPC error object the same way solana-client's own HTTP sender does
+fn rpc_error(error: &Value) -> ClientRpcError {
+    let code = error["code"].as_i64().unwrap_or_default();
+    let message = error["message"].as_str().unwrap_or_default().to_string();
+
+    let data = match code {
+        rpc_custom_error::JSON_RPC_SERVER_ERROR_SEND_TRANSACTION_PREFLIGHT_FAILURE => {
+            serde_json::from_value::<RpcSimulateTransactionResult>(error["data"].clone())
+                .map(RpcResponseErrorData::SendTransactionPreflightFailure)
+                .unwrap_or(RpcResponseErrorData::Empty)
+        }
+        rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY => error["data"]["numSlotsBehind"]
+            .as_u64()
+            .map(|slots| RpcResponseErrorData::NodeUnhealthy {
+                num_slots_behind: Some(slots),
+            })
+            .unwrap_or(RpcResponseErrorData::Empty),
+        _ => RpcResponseErrorData::Empty,
+    };
+
+    ClientRpcError::RpcResponseError {
+        code,
+        message,
+        data,
+    }
+}
+
+/// `RpcSender` plugging the shared transport into solana-client's `RpcClient`
+pub struct RateLimitedSender {
+    transport: RpcTransport,
+}
+
+#[async_trait]
+impl RpcSender for RateLimitedSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let id = self.transport.next_id.fetch_add(1, Ordering::Relaxed);
+        self.transport
+            .execute(request.build_request_json(id, params))
+            .await
+            .map_err(|e| match e {
+                CallError::Failed(e) => e,
+                open @ CallError::CircuitOpen { .. } => {
+                    ClientRpcError::RpcRequestError(open.to_string()).into()
+                }
+            })
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        let metrics = self.transport.endpoint.metrics();
+        RpcTransportStats {
+            request_count: metrics.requests as usize,
+            elapsed_time: Duration::from_secs_f64(
+                metrics.avg_latency_ms * metrics.requests as f64 / 1000.0,
+            ),
+            rate_limited_time: Duration::from_millis(metrics.throttled_ms),
+        }
+    }
+
+    fn url(&self) -> String {
+        self.transport.url().to_string()
+    }
+}
+
+/// `RpcSender` spreading requests round-robin over several endpoints. A request that fails
+/// transiently on one endpoint, or finds its circuit open, moves on to the next; each endpoint
+/// is tried once per round and the rounds follow the retry policy's attempts and backoff.
+/// Errors the endpoint answered with, such as a failed preflight, are returned as they are.
+pub struct FailoverSender {
+    transports: Vec<RpcTransport>,
+    retry: RetryPolicy,
+    next: AtomicUsize,
+    next_id: AtomicU64,
+}
+
+impl FailoverSender {
+    /// # Panics
+    ///
+    /// When `urls` is empty
+    pub fn new(urls: &[String], limits: RpcLimits, timeout: Duration) -> Self {
+        assert!(!urls.is_empty(), "failover needs at least one RPC endpoint");
+        // Retrying is done across endpoints, so each endpoint gets a single attempt per round
+        let single_attempt = RpcLimits {
+            retry: RetryPolicy {
+                max_attempts: 1,
+                ..limits.retry
+            },
+            ..limits
+        };
+        Self {
+            transports: urls
+                .iter()
+                .map(|url| RpcTransport::with_timeout(url, single_attempt, timeout))
+                .collect(),
+            retry: limits.retry,
+            next: AtomicUsize::new(0),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+#[async_trait]
+impl RpcSender for FailoverSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = request.build_request_json(id, params);
+        let endpoints = self.transports.len();
+        let first = self.next.fetch_add(1, Ordering::Relaxed);
+
+        let mut last_error = None;
+        for attempt in 0..endpoints * self.retry.max_attempts.max(1) as usize {
+            let round = attempt / endpoints;
+            if round > 0 && attempt % endpoints == 0 {
+                tokio::time::sleep(self.retry.delay(round as u32)).await;
+            }
+
+            let transport = &self.transports[(first + attempt) % endpoints];
+            match transport.execute(request.clone()).await {
+                Ok(result) => return Ok(result),
+                Err(CallError::Failed(e)) if !is_transient(&e) => return Err(e),
+                Err(e) => {
+                    transport.endpoint.failovers.fetch_add(1, Ordering::Relaxed);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(match last_error.expect("at least one attempt was made") {
+            CallError::Failed(e) => e,
+            open @ CallError::CircuitOpen { .. } => {
+                ClientRpcError::RpcRequestError(open.to_string()).into()
+            }
+        })
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        let mut stats = RpcTransportStats::default();
+        for transport in &self.transports {
+            let metrics = transport.endpoint.metrics();
+            stats.request_count += metrics.requests as usize;
+            stats.elapsed_time +=
+                Duration::from_secs_f64(metrics.avg_latency_ms * metrics.requests as f64 / 1000.0);
+            stats.rate_limited_time += Duration::from_millis(metrics.throttled_ms);
+        }
+        stats
+    }
+
+    fn url(&self) -> String {
+        self.transports
+            .iter()
+            .map(RpcTransport::url)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Blocking `RpcClient` whose requests go through the shared rate-limited transport
+pub fn rpc_client(url: &str, commitment: CommitmentConfig, limits: RpcLimits) -> RpcClient {
+    RpcClient::new_sender(
+        RateLimitedSender {
+            transport: RpcTransport::new(url, limits),
+        },
+        RpcClientConfig::with_commitment(commitment),
+    )
+}
+
+/// Blocking `RpcClient` failing over between `urls`, see [`FailoverSender`]; with a single
+/// URL this is [`rpc_client`]
+///
+/// # Panics
+///
+/// When `urls` is empty
+pub fn failover_rpc_client(
+    urls: &[String],
+    commitment: CommitmentConfig,
+    limits: RpcLimits,
+) -> RpcClient {
+    if let [url] = urls {
+        return rpc_client(url, commitment, limits);
+    }
+    RpcClient::new_sender(
+        FailoverSender::new(urls, limits, DEFAULT_TIMEOUT),
+        RpcClientConfig::with_commitment(commitment),
+    )
+}
+
+/// Blocking clients for each of several endpoints, for work that has to stay on one node: a
+/// transaction's blockhash, its broadcast and the polls confirming it. A node that lags behind
+/// the one that accepted a transaction may not know its blockhash or its status yet, which a
+/// [`FailoverSender`] spreading those requests over every endpoint would mistake for expiry.
+/// The clients share rate limits, breakers and metrics with other clients of the same URLs.
+pub struct EndpointPool {
+    clients: Vec<(RpcClient, Option<Arc<CircuitBreaker>>)>,
+    next: AtomicUsize,
+}
+
+impl EndpointPool {
+    /// # Panics
+    ///
+    /// When `urls` is empty
+    pub fn new(urls: &[String], commitment: CommitmentConfig, limits: RpcLimits) -> Self {
+        assert!(
+            !urls.is_empty(),
+            "an endpoint pool needs at least one RPC endpoint"
+        );
+        Self {
+            clients: urls
+                .iter()
+                .map(|url| {
+                    let breaker = endpoint(url, limits).breaker.clone();
+                    (rpc_client(url, commitment, limits), Some(breaker))
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pool of clients built elsewhere, e.g. on a custom sender; they count as always healthy
+    ///
+    /// # Panics
+    ///
+    /// When `clients` is empty
+    pub fn from_clients(clients: Vec<RpcClient>) -> Self {
+        assert!(
+            !clients.is_empty(),
+            "an endpoint pool needs at least one RPC client"
+        );
+        Self {
+            clients: clients.into_iter().map(|client| (client, None)).collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// The next endpoint in turn whose circuit is closed, or just the next one when every
+    /// circuit is open
+    pub fn pick(&self) -> &RpcClient {
+        let first = self.next.fetch_add(1, Ordering::Relaxed);
+        let endpoints = self.clients.len();
+        let healthy = (0..endpoints)
+            .map(|offset| &self.clients[(first + offset) % endpoints])
+            .find(|(_, breaker)| breaker.as_ref().is_none_or(|breaker| !breaker.is_open()));
+        &healthy.unwrap_or(&self.clients[first % endpoints]).0
+    }
+}
+
+/// Async `RpcClient` whose requests go through the shared rate-limited transport
+pub fn nonblocking_rpc_client(
+    url: &str,
+    commitment: CommitmentConfig,
+    limits: RpcLimits,
+) -> nonblocking::rpc_client::RpcClient {
+    nonblocking_rpc_client_with_timeout(url, commitment, limits, DEFAULT_TIMEOUT)
+}
+
+/// [`nonblocking_rpc_client`] with a custom per-request timeout
+pub fn nonblocking_rpc_client_with_timeout(
+    url: &str,
+    commitment: CommitmentConfig,
+    limits: RpcLimits,
+    timeout: Duration,
+) -> nonblocking::rpc_client::RpcClient {
+    nonblocking::rpc_client::RpcClient::new_sender(
+        RateLimitedSender {
+            transport: RpcTransport::with_timeout(url, limits, timeout),
+        },
+        RpcClientConfig::with_commitment(commitment),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(requests_per_second: f64, burst: u32) -> TokenBucket {
+        TokenBucket::new(&RpcLimits {
+            requests_per_second,
+            burst,
+            ..RpcLimits::default()
+        })
+    }
+
+    #[test]
+    fn endpoints_are_shared_per_url_and_limits() {
+        let url = "https://endpoint-registry.test";
+        let limits = RpcLimits::default();
+        let single_attempt = RpcLimits {
+            retry: RetryPolicy {
+                max_attempts: 1,
+                ..limits.retry
+            },
+            ..limits
+        };
+        let slower = RpcLimits {
+            requests_per_second: 1.0,
+            ..limits
+        };
+
+        let shared = endpoint(url, limits);
+        assert!(Arc::ptr_eq(&shared, &endpoint(url, single_attempt)));
+        assert!(!Arc::ptr_eq(&shared, &endpoint(url, slower)));
+        assert!(Arc::ptr_eq(
+            &shared.breaker,
+            &endpoint(url, single_attempt).breaker
+        ));
+    }
+
+    #[test]
+    fn endpoint_pool_rotates_and_skips_open_circuits() {
+        let urls: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|name| format!("https://pool-{}.test", name))
+            .collect();
+        let limits = RpcLimits {
+            circuit_breaker: BreakerConfig {
+                failure_threshold: 1,
+                open_secs: 60,
+            },
+            ..RpcLimits::default()
+        };
+        let pool = EndpointPool::new(&urls, CommitmentConfig::confirmed(), limits);
+        let picks = |count| -> Vec<String> { (0..count).map(|_| pool.pick().url()).collect() };
+        assert_eq!(picks(4), [&*urls[0], &urls[1], &urls[2], &urls[0]]);
+
+        endpoint(&urls[2], limits).breaker.record_failure();
+        assert_eq!(picks(3), [&*urls[1], &urls[0], &urls[0]]);
+    }
+
+    #[test]
+    fn token_bucket_admits_a_burst_then_spaces_requests_at_the_rate() {
+        let mut bucket = bucket(10.0, 3);
+
+        for _ in 0..3 {
+            assert_eq!(bucket.reserve(), Duration::ZERO);
+        }
+        // Each queued caller waits for its own refill slot, 100 ms apart at 10 rps
+        let first = bucket.reserve();
+        let second = bucket.reserve();
+        assert!(first > Duration::from_millis(90) && first <= Duration::from_millis(100));
+        assert!(second > Duration::from_millis(190) && second <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn token_bucket_refills_up_to_its_capacity() {
+        let mut bucket = bucket(1_000.0, 2);
+        bucket.reserve();
+        bucket.reserve();
+
+        std::thread::sleep(Duration::from_millis(20));
+        // 20 refilled tokens are capped at the burst of 2
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        assert!(bucket.reserve() > Duration::ZERO);
+    }
+
+    #[test]
+    fn token_bucket_pause_holds_back_every_caller_and_keeps_the_longest() {
+        let mut bucket = bucket(100.0, 10);
+        bucket.pause(Duration::from_secs(2));
+        bucket.pause(Duration::from_secs(1));
+
+        let wait = bucket.reserve();
+        assert!(wait > Duration::from_millis(1_900) && wait <= Duration::from_secs(2));
+    }
+}
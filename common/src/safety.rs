@@ -0,0 +1,54 @@
+use crate::error::{RpcError, SafetyError};
+use serde::Deserialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::transaction::Transaction;
+
+/// Simulation-first safety mode settings, set from config and forced on by `--safe-mode`
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct SafetyConfig {
+    /// Simulate every transaction and only broadcast the ones that succeed
+    pub safe_mode: bool,
+    /// In safe mode, refuse transactions moving more than this many lamports
+    pub max_lamports_per_tx: Option<u64>,
+}
+
+impl SafetyConfig {
+    /// Apply the command line flag on top of the config file default
+    pub fn with_flag(mut self, safe_mode: bool) -> Self {
+        self.safe_mode |= safe_mode;
+        self
+    }
+
+    /// Gate a signed `transaction` moving `value` lamports before it is broadcast.
+    /// Does nothing unless safe mode is enabled.
+    pub fn check(
+        &self,
+        client: &RpcClient,
+        transaction: &Transaction,
+        value: u64,
+    ) -> Result<(), SafetyError> {
+        if !self.safe_mode {
+            return Ok(());
+        }
+
+        if let Some(cap) = self.max_lamports_per_tx {
+            if value > cap {
+                return Err(SafetyError::ValueCapExceeded { value, cap });
+            }
+        }
+
+        let simulation = client
+            .simulate_transaction(transaction)
+            .map_err(|e| RpcError::request("simulateTransaction", &client.url(), e))?
+            .value;
+
+        match simulation.err {
+            Some(err) => Err(SafetyError::SimulationFailed {
+                reason: err.to_string(),
+                logs: simulation.logs.unwrap_or_default(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
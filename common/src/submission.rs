@@ -0,0 +1,245 @@
+use crate::{
+    error::{ConfigError, RpcError},
+    rpc::{RpcLimits, RpcTransport},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Deserialize;
+use serde_json::json;
+use solana_client::{
+    nonblocking::{rpc_client::RpcClient as AsyncRpcClient, tpu_client::TpuClient},
+    rpc_client::RpcClient,
+    tpu_client::{TpuClientConfig, DEFAULT_FANOUT_SLOTS, MAX_FANOUT_SLOTS},
+};
+use solana_quic_client::{QuicConfig, QuicConnectionManager, QuicPool};
+use solana_sdk::{signature::Signature, transaction::Transaction};
+use std::{str::FromStr, sync::Arc};
+
+type QuicTpuClient = TpuClient<QuicPool, QuicConnectionManager, QuicConfig>;
+
+/// Where signed transactions are broadcast
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubmissionProfile {
+    /// Through the regular RPC endpoint, visible to anyone watching its forwarding
+    #[default]
+    Public,
+    /// Only through the configured private relays / Jito block engines, never public RPC
+    Private,
+}
+
+impl FromStr for SubmissionProfile {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "public" => Ok(SubmissionProfile::Public),
+            "private" => Ok(SubmissionProfile::Private),
+            other => Err(format!(
+                "unknown submission profile '{}' (public, private)",
+                other
+            )),
+        }
+    }
+}
+
+/// Submission settings; `relays` are JSON-RPC endpoints accepting `sendTransaction`,
+/// e.g. `https://mainnet.block-engine.jito.wtf/api/v1/transactions`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SubmissionConfig {
+    pub profile: SubmissionProfile,
+    pub relays: Vec<String>,
+    pub rpc_limits: RpcLimits,
+    /// Public sends go straight to the upcoming leaders' TPU ports first
+    pub tpu: Option<TpuConfig>,
+}
+
+/// Direct sends to leader TPU ports over QUIC, skipping the RPC node's forwarding hop
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct TpuConfig {
+    /// Upcoming leader slots whose leaders each transaction is sent to (1-100)
+    pub fanout_slots: u64,
+}
+
+impl Default for TpuConfig {
+    fn default() -> Self {
+        Self {
+            fanout_slots: DEFAULT_FANOUT_SLOTS,
+        }
+    }
+}
+
+impl SubmissionConfig {
+    /// Apply the command line profile on top of the config file default
+    pub fn with_profile(mut self, profile: Option<SubmissionProfile>) -> Self {
+        if let Some(profile) = profile {
+            self.profile = profile;
+        }
+        self
+    }
+}
+
+/// A signed transaction and the last block height it can land at. Until then a retry resends
+/// this very transaction rather than a newly signed one: every copy carries the same
+/// signature, so it can land at most once however often it is sent.
+#[derive(Debug, Clone)]
+pub struct Submission {
+    transaction: Transaction,
+    /// None for durable nonce transactions, which stay valid until their nonce advances
+    last_valid_block_height: Option<u64>,
+}
+
+impl Submission {
+    pub fn new(transaction: Transaction, last_valid_block_height: Option<u64>) -> Self {
+        Self {
+            transaction,
+            last_valid_block_height,
+        }
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    pub fn signature(&self) -> Signature {
+        self.transaction.signatures[0]
+    }
+
+    pub fn last_valid_block_height(&self) -> Option<u64> {
+        self.last_valid_block_height
+    }
+
+    /// Whether the transaction can still land according to `client`, so that a retry must
+    /// resend it instead of signing a new one. A durable nonce transaction that did not land
+    /// left its nonce unchanged and stays valid.
+    pub fn is_live(&self, client: &RpcClient) -> Result<bool, RpcError> {
+        let Some(last_valid) = self.last_valid_block_height else {
+            return Ok(true);
+        };
+        let height = client
+            .get_block_height()
+            .map_err(|e| RpcError::request("getBlockHeight", &client.url(), e))?;
+        Ok(height <= last_valid)
+    }
+}
+
+/// Broadcasts signed transactions according to the submission profile
+#[derive(Clone)]
+pub struct Submitter {
+    profile: SubmissionProfile,
+    relays: Vec<RpcTransport>,
+    tpu: Option<Arc<QuicTpuClient>>,
+}
+
+impl Submitter {
+    /// A private profile without relays is refused rather than silently going public
+    pub fn new(config: &SubmissionConfig) -> Result<Self, ConfigError> {
+        if config.profile == SubmissionProfile::Private && config.relays.is_empty() {
+            return Err(ConfigError::NoPrivateRelays);
+        }
+
+        Ok(Self {
+            profile: config.profile,
+            relays: config
+                .relays
+                .iter()
+                .map(|url| RpcTransport::new(url, config.rpc_limits))
+                .collect(),
+            tpu: None,
+        })
+    }
+
+    /// Send public-profile transactions to the TPU ports of the leaders of the next
+    /// `fanout_slots` slots before falling back to `sendTransaction`. The leader schedule and
+    /// cluster nodes come from `rpc_client`, slot progress from the pubsub `websocket_url`.
+    pub async fn connect_tpu(
+        &mut self,
+        rpc_client: Arc<AsyncRpcClient>,
+        websocket_url: &str,
+        config: TpuConfig,
+    ) -> Result<(), RpcError> {
+        let tpu_config = TpuClientConfig {
+            fanout_slots: config.fanout_slots.clamp(1, MAX_FANOUT_SLOTS),
+        };
+        let client = QuicTpuClient::new("solana_common", rpc_client, websocket_url, tpu_config)
+            .await
+            .map_err(|e| RpcError::Tpu {
+                endpoint: websocket_url.to_string(),
+                reason: e.to_string(),
+            })?;
+        self.tpu = Some(Arc::new(client));
+        Ok(())
+    }
+
+    pub fn profile(&self) -> SubmissionProfile {
+        self.profile
+    }
+
+    /// Broadcast `transaction`: via `client` for the public profile (to the leaders' TPU
+    /// ports first when connected), otherwise through each relay in turn until one accepts
+    /// it. A private send never touches `client`.
+    /// An "already processed" rejection counts as success, so retries that resend the
+    /// same signed transaction never turn a landed transfer into an error.
+    pub async fn send(
+        &self,
+        client: &RpcClient,
+        transaction: &Transaction,
+    ) -> Result<Signature, RpcError> {
+        // Rebroadcasting a transaction that already landed must not look like a failure
+        let signature = transaction.signatures[0];
+
+        if self.profile == SubmissionProfile::Public {
+            if let Some(tpu) = &self.tpu {
+                match tpu.try_send_transaction(transaction).await {
+                    Ok(()) => return Ok(signature),
+                    Err(e) => eprintln!(
+                        "TPU send of {} failed, falling back to RPC: {}",
+                        signature, e
+                    ),
+                }
+            }
+            return match client.send_transaction(transaction) {
+                Ok(signature) => Ok(signature),
+                Err(e) => {
+                    let e = RpcError::request("sendTransaction", &client.url(), e);
+                    if e.is_already_processed() {
+                        Ok(signature)
+                    } else {
+                        Err(e)
+                    }
+                }
+            };
+        }
+
+        let encoded = BASE64.encode(
+            bincode::serialize(transaction).expect("transaction serialization cannot fail"),
+        );
+        let params = json!([encoded, { "encoding": "base64" }]);
+
+        let mut last_error = None;
+        for relay in &self.relays {
+            match relay.call("sendTransaction", params.clone()).await {
+                Ok(_) => return Ok(signature),
+                Err(e) if e.is_already_processed() => return Ok(signature),
+                Err(e) => {
+                    eprintln!("Private relay {} rejected transaction: {}", relay.url(), e);
+                    // A relay that may have taken it decides the outcome over later rejections
+                    if last_error.as_ref().is_none_or(RpcError::is_rejection) {
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+
+        Err(last_error.expect("private profile has at least one relay"))
+    }
+
+    /// Whether `error`, returned by [`Submitter::send`], proves the transaction was not
+    /// broadcast, so that signing it again cannot pay twice. A failed TPU send may still have
+    /// reached some leaders, so with TPU sends nothing is proof.
+    pub fn rejected(&self, error: &RpcError) -> bool {
+        let tpu = self.profile == SubmissionProfile::Public && self.tpu.is_some();
+        !tpu && error.is_rejection()
+    }
+}
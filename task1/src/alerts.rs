@@ -0,0 +1,183 @@
+use crate::BalanceResults;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_common::{AddressBook, WebhookConfig};
+use std::collections::{BTreeMap, HashMap};
+
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+/// Balance thresholds and where to send alerts when a wallet is outside them
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AlertConfig {
+    /// Thresholds of every wallet without its own entry in `wallets`
+    #[serde(flatten)]
+    pub thresholds: Thresholds,
+    /// Per-wallet thresholds, keyed by address or address book alias
+    pub wallets: BTreeMap<String, Thresholds>,
+    /// Defaults to `notifications.webhook`
+    pub webhook: Option<WebhookConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct Thresholds {
+    pub min_sol: Option<f64>,
+    pub max_sol: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    BelowMin,
+    AboveMax,
+}
+
+/// A wallet whose balance is outside its thresholds
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceAlert {
+    pub address: String,
+    pub label: Option<String>,
+    pub kind: AlertKind,
+    pub balance_sol: f64,
+    pub threshold_sol: f64,
+}
+
+/// Alert body; `text` and `content` carry the same summary so Slack and Discord incoming
+/// webhooks can render it as is
+#[derive(Debug, Serialize)]
+struct AlertPayload<'a> {
+    text: String,
+    content: String,
+    alerts: &'a [BalanceAlert],
+}
+
+/// Thresholds resolved to addresses, ready to check balances against
+#[derive(Debug, Clone)]
+pub struct Alerting {
+    webhook: WebhookConfig,
+    thresholds: Thresholds,
+    wallets: HashMap<String, Thresholds>,
+}
+
+impl AlertConfig {
+    /// `None` when no threshold is set; a threshold without any webhook is a config error
+    pub fn resolve(
+        &self,
+        book: &AddressBook,
+        fallback_webhook: Option<&WebhookConfig>,
+    ) -> Result<Option<Alerting>> {
+        let has_thresholds =
+            self.thresholds.is_set() || self.wallets.values().any(Thresholds::is_set);
+        if !has_thresholds {
+            return Ok(None);
+        }
+
+        let webhook = self
+            .webhook
+            .as_ref()
+            .or(fallback_webhook)
+            .context("Balance alerts need alerts.webhook or notifications.webhook")?
+            .clone();
+        let wallets = self
+            .wallets
+            .iter()
+            .map(|(wallet, thresholds)| {
+                let address = book
+                    .resolve(wallet)
+                    .with_context(|| format!("Invalid wallet {} in alerts", wallet))?;
+                Ok((address.to_string(), *thresholds))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Some(Alerting {
+            webhook,
+            thresholds: self.thresholds,
+            wallets,
+        }))
+    }
+}
+
+impl Thresholds {
+    fn is_set(&self) -> bool {
+        self.min_sol.is_some() || self.max_sol.is_some()
+    }
+
+    /// Which threshold `lamports` breaks, and its value
+    fn breach(&self, lamports: u64) -> Option<(AlertKind, f64)> {
+        let balance = lamports as f64 / LAMPORTS_PER_SOL;
+        match (self.min_sol, self.max_sol) {
+            (Some(min), _) if balance < min => Some((AlertKind::BelowMin, min)),
+            (_, Some(max)) if balance > max => Some((AlertKind::AboveMax, max)),
+            _ => None,
+        }
+    }
+}
+
+impl Alerting {
+    /// Alert for `address` when `lamports` is outside its thresholds
+    pub fn check(&self, address: &str, label: Option<&str>, lamports: u64) -> Option<BalanceAlert> {
+        let thresholds = self.wallets.get(address).unwrap_or(&self.thresholds);
+        let (kind, threshold_sol) = thresholds.breach(lamports)?;
+        Some(BalanceAlert {
+            address: address.to_string(),
+            label: label.map(str::to_string),
+            kind,
+            balance_sol: lamports as f64 / LAMPORTS_PER_SOL,
+            threshold_sol,
+        })
+    }
+
+    /// Whether a change from `previous` to `lamports` moves the wallet across a threshold
+    /// (into a breach, or from one breach into the other)
+    pub fn crossed(&self, address: &str, previous: Option<u64>, lamports: u64) -> bool {
+        let thresholds = self.wallets.get(address).unwrap_or(&self.thresholds);
+        let now = thresholds.breach(lamports).map(|(kind, _)| kind);
+        let before =
+            previous.and_then(|previous| thresholds.breach(previous).map(|(kind, _)| kind));
+        now.is_some() && now != before
+    }
+
+    /// Alerts for every reported wallet outside its thresholds
+    pub fn check_results(&self, results: &BalanceResults) -> Vec<BalanceAlert> {
+        results
+            .balances
+            .iter()
+            .filter_map(|wb| {
+                let lamports = (wb.balance * LAMPORTS_PER_SOL).round() as u64;
+                self.check(&wb.address, wb.label.as_deref(), lamports)
+            })
+            .collect()
+    }
+
+    /// POST all `alerts` to the webhook in one request
+    pub async fn send(&self, alerts: &[BalanceAlert]) -> Result<()> {
+        let text = alerts
+            .iter()
+            .map(|alert| {
+                let name = match &alert.label {
+                    Some(label) => format!("{} ({})", label, alert.address),
+                    None => alert.address.clone(),
+                };
+                let bound = match alert.kind {
+                    AlertKind::BelowMin => "below minimum",
+                    AlertKind::AboveMax => "above maximum",
+                };
+                format!(
+                    "{}: {} SOL, {} {} SOL",
+                    name, alert.balance_sol, bound, alert.threshold_sol
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let payload = AlertPayload {
+            content: text.clone(),
+            text,
+            alerts,
+        };
+        self.webhook
+            .post(&payload)
+            .await
+            .context("Failed to send balance alert")
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_common::Render;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+
+/// Largest page getAssetsByOwner serves
+const PAGE_LIMIT: usize = 1000;
+
+/// One NFT, regular or compressed, as reported by a DAS endpoint
+#[derive(Debug, Clone, Serialize)]
+pub struct Asset {
+    pub id: String,
+    pub name: Option<String>,
+    /// DAS interface, e.g. `V1_NFT`, `ProgrammableNFT` or `MplCoreAsset`
+    pub interface: String,
+    /// Collection address, for assets grouped under one
+    pub collection: Option<String>,
+    pub compressed: bool,
+}
+
+/// The assets of one wallet
+#[derive(Debug, Serialize)]
+pub struct WalletAssets {
+    pub address: String,
+    pub label: Option<String>,
+    pub assets: Vec<Asset>,
+}
+
+/// The assets of every wallet, as rendered for `--assets`
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+pub struct AssetReport(pub Vec<WalletAssets>);
+
+/// The parts of a DAS asset read here
+#[derive(Debug, Deserialize)]
+struct DasAsset {
+    id: String,
+    #[serde(default)]
+    interface: String,
+    #[serde(default)]
+    content: Option<DasContent>,
+    #[serde(default)]
+    grouping: Vec<DasGroup>,
+    #[serde(default)]
+    compression: Option<DasCompression>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DasContent {
+    #[serde(default)]
+    metadata: Option<DasMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DasMetadata {
+    #[serde(default)]
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DasGroup {
+    group_key: String,
+    group_value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DasCompression {
+    #[serde(default)]
+    compressed: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct DasPage {
+    items: Vec<DasAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DasResponse {
+    Result { result: DasPage },
+    Error { error: DasError },
+}
+
+#[derive(Debug, Deserialize)]
+struct DasError {
+    message: String,
+}
+
+impl From<DasAsset> for Asset {
+    fn from(asset: DasAsset) -> Self {
+        Asset {
+            id: asset.id,
+            name: asset
+                .content
+                .and_then(|content| content.metadata?.name)
+                .filter(|name| !name.is_empty()),
+            interface: asset.interface,
+            collection: asset
+                .grouping
+                .into_iter()
+                .find(|group| group.group_key == "collection")
+                .map(|group| group.group_value),
+            compressed: asset.compression.is_some_and(|c| c.compressed),
+        }
+    }
+}
+
+/// Every NFT `owner` holds, compressed ones included, page by page from the DAS-compatible
+/// endpoint at `rpc_url`. Plain RPC nodes do not serve getAssetsByOwner.
+pub async fn assets_by_owner(
+    http: &reqwest::Client,
+    rpc_url: &str,
+    owner: &Pubkey,
+) -> Result<Vec<Asset>> {
+    let mut assets = Vec::new();
+    for page in 1.. {
+        // DAS takes named params, which solana-client's RpcClient refuses to send
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": page,
+            "method": "getAssetsByOwner",
+            "params": {
+                "ownerAddress": owner.to_string(),
+                "page": page,
+                "limit": PAGE_LIMIT,
+            },
+        });
+        let response: DasResponse = http
+            .post(rpc_url)
+            .json(&request)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("getAssetsByOwner at {} failed", rpc_url))?
+            .json()
+            .await
+            .with_context(|| format!("Invalid getAssetsByOwner response from {}", rpc_url))?;
+        let response = match response {
+            DasResponse::Result { result } => result,
+            DasResponse::Error { error } => {
+                bail!("getAssetsByOwner at {} failed: {}", rpc_url, error.message)
+            }
+        };
+
+        let last = response.items.len() < PAGE_LIMIT;
+        assets.extend(response.items.into_iter().map(Asset::from));
+        if last {
+            break;
+        }
+    }
+    Ok(assets)
+}
+
+/// Assets of each wallet grouped by collection, largest first, with counts
+pub fn render_assets(wallets: &[WalletAssets]) -> String {
+    let mut report = String::from("NFT Holdings:\n");
+    for wallet in wallets {
+        let compressed = wallet
+            .assets
+            .iter()
+            .filter(|asset| asset.compressed)
+            .count();
+        let name = match &wallet.label {
+            Some(label) => format!("{} ({})", label, wallet.address),
+            None => wallet.address.clone(),
+        };
+        report.push_str(&format!(
+            "{}: {} NFT(s), {} compressed\n",
+            name,
+            wallet.assets.len(),
+            compressed
+        ));
+
+        let mut collections: BTreeMap<Option<&str>, Vec<&Asset>> = BTreeMap::new();
+        for asset in &wallet.assets {
+            collections
+                .entry(asset.collection.as_deref())
+                .or_default()
+                .push(asset);
+        }
+        let mut collections: Vec<_> = collections.into_iter().collect();
+        collections.sort_by_key(|(_, assets)| std::cmp::Reverse(assets.len()));
+
+        for (collection, assets) in collections {
+            report.push_str(&format!(
+                "    {}: {}\n",
+                collection.unwrap_or("no collection"),
+                assets.len()
+            ));
+            for asset in assets {
+                report.push_str(&format!(
+                    "        {} {}{}\n",
+                    asset.id,
+                    asset.name.as_deref().unwrap_or("(unnamed)"),
+                    if asset.compressed {
+                        " [compressed]"
+                    } else {
+                        ""
+                    }
+                ));
+            }
+        }
+    }
+    report
+}
+
+/// One row per asset
+impl Render for AssetReport {
+    fn columns(&self) -> &'static [&'static str] {
+        &[
+            "address",
+            "label",
+            "asset",
+            "name",
+            "interface",
+            "collection",
+            "compressed",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        for wallet in &self.0 {
+            for asset in &wallet.assets {
+                rows.push(vec![
+                    wallet.address.clone(),
+                    wallet.label.clone().unwrap_or_default(),
+                    asset.id.clone(),
+                    asset.name.clone().unwrap_or_default(),
+                    asset.interface.clone(),
+                    asset.collection.clone().unwrap_or_default(),
+                    asset.compressed.to_string(),
+                ]);
+            }
+        }
+        rows
+    }
+
+    fn table(&self) -> String {
+        render_assets(&self.0)
+    }
+}
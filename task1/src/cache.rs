@@ -0,0 +1,128 @@
+use crate::fetcher::WalletBalance;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Serve recently fetched wallets from a cache instead of the RPC
+#[derive(Debug, Clone, Deserialize)]
+pub struct CacheConfig {
+    /// How long a fetched balance is served before it is fetched again
+    pub ttl_secs: u64,
+    /// JSON file keeping the cache between runs; in memory only without it, which only helps
+    /// processes that keep running, e.g. `--serve-metrics`
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+/// What a cached balance was fetched with; an entry only serves fetches asking for the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheScope {
+    pub tokens: bool,
+    pub details: bool,
+    /// Epoch the stake was split into active and inactive against, only with stake
+    pub stake_epoch: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedBalance {
+    /// Unix time of the fetch
+    fetched_at: i64,
+    scope: CacheScope,
+    balance: WalletBalance,
+}
+
+/// Balances by cluster and wallet, each served until its TTL runs out
+#[derive(Debug)]
+pub struct BalanceCache {
+    ttl: Duration,
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, CachedBalance>>,
+}
+
+/// One entry per wallet and cluster, as the same address can be queried on several
+fn key(cluster: Option<&str>, address: &str) -> String {
+    format!("{}/{}", cluster.unwrap_or_default(), address)
+}
+
+impl BalanceCache {
+    /// An empty cache, or the one saved at `config.path` with its expired entries dropped
+    pub fn open(config: &CacheConfig) -> Result<Self> {
+        let cache = Self {
+            ttl: Duration::from_secs(config.ttl_secs),
+            path: config.path.clone(),
+            entries: Mutex::default(),
+        };
+        let Some(path) = &config.path else {
+            return Ok(cache);
+        };
+        if !path.exists() {
+            return Ok(cache);
+        }
+
+        let file = std::fs::read(path)
+            .with_context(|| format!("Failed to read balance cache {}", path.display()))?;
+        let mut entries: HashMap<String, CachedBalance> = serde_json::from_slice(&file)
+            .with_context(|| format!("Invalid balance cache {}", path.display()))?;
+        let now = chrono::Utc::now().timestamp();
+        entries.retain(|_, entry| cache.is_fresh(entry, now));
+        *cache.entries.lock().expect("balance cache poisoned") = entries;
+        Ok(cache)
+    }
+
+    fn is_fresh(&self, entry: &CachedBalance, now: i64) -> bool {
+        now.saturating_sub(entry.fetched_at) < self.ttl.as_secs() as i64
+    }
+
+    /// The cached balance of `address`, unless it expired or was fetched with another scope
+    pub fn get(
+        &self,
+        cluster: Option<&str>,
+        address: &str,
+        scope: CacheScope,
+    ) -> Option<WalletBalance> {
+        let entries = self.entries.lock().expect("balance cache poisoned");
+        let entry = entries.get(&key(cluster, address))?;
+        (entry.scope == scope && self.is_fresh(entry, chrono::Utc::now().timestamp()))
+            .then(|| entry.balance.clone())
+    }
+
+    /// Remember freshly fetched balances
+    pub fn insert(&self, balances: &[WalletBalance], scope: CacheScope) {
+        let now = chrono::Utc::now().timestamp();
+        let mut entries = self.entries.lock().expect("balance cache poisoned");
+        for balance in balances {
+            entries.insert(
+                key(balance.cluster.as_deref(), &balance.address),
+                CachedBalance {
+                    fetched_at: now,
+                    scope,
+                    balance: balance.clone(),
+                },
+            );
+        }
+    }
+
+    /// Write the cache to its file, if it has one; the file is replaced in one rename so a
+    /// concurrent run never reads half of it
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = {
+            let entries = self.entries.lock().expect("balance cache poisoned");
+            serde_json::to_vec(&*entries).context("Failed to serialize balance cache")?
+        };
+        write_atomically(path, &json)
+            .with_context(|| format!("Failed to write balance cache {}", path.display()))
+    }
+}
+
+fn write_atomically(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", std::process::id()));
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)
+}
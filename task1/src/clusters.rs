@@ -0,0 +1,50 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Wallets of one cluster, either just the list or with the endpoint to query them on
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum ClusterConfig {
+    Wallets(Vec<String>),
+    Endpoint {
+        /// Defaults to the public endpoint of a well-known cluster name
+        #[serde(default)]
+        rpc_url: Option<String>,
+        wallets: Vec<String>,
+    },
+}
+
+/// Public RPC endpoint of a well-known cluster name
+pub fn well_known_url(name: &str) -> Option<&'static str> {
+    match name {
+        "mainnet" | "mainnet-beta" => Some("https://api.mainnet-beta.solana.com"),
+        "devnet" => Some("https://api.devnet.solana.com"),
+        "testnet" => Some("https://api.testnet.solana.com"),
+        "localnet" | "localhost" => Some("http://127.0.0.1:8899"),
+        _ => None,
+    }
+}
+
+impl ClusterConfig {
+    pub fn wallets(&self) -> &[String] {
+        match self {
+            ClusterConfig::Wallets(wallets) | ClusterConfig::Endpoint { wallets, .. } => wallets,
+        }
+    }
+
+    /// The configured endpoint, else the public one of cluster `name`
+    pub fn rpc_url(&self, name: &str) -> Result<String> {
+        let configured = match self {
+            ClusterConfig::Endpoint { rpc_url, .. } => rpc_url.clone(),
+            ClusterConfig::Wallets(_) => None,
+        };
+        configured
+            .or_else(|| well_known_url(name).map(str::to_string))
+            .with_context(|| {
+                format!(
+                    "Cluster {} needs an rpc_url: it is not mainnet, devnet, testnet or localnet",
+                    name
+                )
+            })
+    }
+}
@@ -0,0 +1,196 @@
+use crate::report::cluster_prefix;
+use crate::BalanceResults;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_common::Render;
+use std::collections::HashMap;
+use std::path::Path;
+
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Inflow,
+    Outflow,
+}
+
+/// A wallet whose SOL balance moved since the previous run
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceChange {
+    pub address: String,
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    pub previous_sol: f64,
+    pub current_sol: f64,
+    /// Signed change, negative for outflows
+    pub change_sol: f64,
+    pub direction: Direction,
+}
+
+/// A previous run's balances against this one's, changed wallets only
+#[derive(Debug, Serialize)]
+pub struct BalanceDiff {
+    pub changes: Vec<BalanceChange>,
+    /// Wallets present in both runs
+    pub compared: usize,
+    /// Wallets of this run missing from the previous one, e.g. newly added or failed then
+    pub unmatched: usize,
+}
+
+/// The parts of a previous `--output json` report read here
+#[derive(Debug, Deserialize)]
+struct PreviousReport {
+    balances: Vec<PreviousBalance>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviousBalance {
+    address: String,
+    #[serde(default)]
+    cluster: Option<String>,
+    balance: f64,
+}
+
+fn lamports(sol: f64) -> i64 {
+    (sol * LAMPORTS_PER_SOL).round() as i64
+}
+
+/// Compare `results` with the JSON report at `path`, matching wallets by cluster and
+/// address; outflows come first, largest first
+pub fn diff_against(path: &Path, results: &BalanceResults) -> Result<BalanceDiff> {
+    let file = std::fs::read(path)
+        .with_context(|| format!("Failed to read previous report {}", path.display()))?;
+    let previous: PreviousReport = serde_json::from_slice(&file).with_context(|| {
+        format!(
+            "{} is not a balance report written with --output json",
+            path.display()
+        )
+    })?;
+    let previous: HashMap<(Option<String>, String), f64> = previous
+        .balances
+        .into_iter()
+        .map(|wallet| ((wallet.cluster, wallet.address), wallet.balance))
+        .collect();
+
+    let mut diff = BalanceDiff {
+        changes: Vec::new(),
+        compared: 0,
+        unmatched: 0,
+    };
+    for wallet in &results.balances {
+        let Some(&previous_sol) = previous.get(&(wallet.cluster.clone(), wallet.address.clone()))
+        else {
+            diff.unmatched += 1;
+            continue;
+        };
+        diff.compared += 1;
+
+        let change = lamports(wallet.balance) - lamports(previous_sol);
+        if change == 0 {
+            continue;
+        }
+        diff.changes.push(BalanceChange {
+            address: wallet.address.clone(),
+            label: wallet.label.clone(),
+            cluster: wallet.cluster.clone(),
+            previous_sol,
+            current_sol: wallet.balance,
+            change_sol: change as f64 / LAMPORTS_PER_SOL,
+            direction: if change > 0 {
+                Direction::Inflow
+            } else {
+                Direction::Outflow
+            },
+        });
+    }
+    diff.changes.sort_by_key(|change| {
+        (
+            change.direction == Direction::Inflow,
+            std::cmp::Reverse(lamports(change.change_sol).abs()),
+        )
+    });
+    Ok(diff)
+}
+
+/// The changed wallets as printed for `--output table`
+pub fn render_diff(diff: &BalanceDiff) -> String {
+    let mut report = format!(
+        "Balance changes: {} of {} wallet(s)\n",
+        diff.changes.len(),
+        diff.compared
+    );
+    for change in &diff.changes {
+        let name = match &change.label {
+            Some(label) => format!("{} ({})", label, change.address),
+            None => change.address.clone(),
+        };
+        report.push_str(&format!(
+            "{}{}: {} -> {} SOL ({:+} SOL, {})\n",
+            cluster_prefix(change.cluster.as_deref()),
+            name,
+            change.previous_sol,
+            change.current_sol,
+            change.change_sol,
+            match change.direction {
+                Direction::Inflow => "inflow",
+                Direction::Outflow => "outflow",
+            }
+        ));
+    }
+    if diff.unmatched > 0 {
+        report.push_str(&format!(
+            "{} wallet(s) not in the previous report\n",
+            diff.unmatched
+        ));
+    }
+    report
+}
+
+/// One row per changed wallet, then how many wallets were compared and unmatched
+impl Render for BalanceDiff {
+    fn columns(&self) -> &'static [&'static str] {
+        &[
+            "address",
+            "label",
+            "cluster",
+            "previous_sol",
+            "current_sol",
+            "change_sol",
+            "direction",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.changes
+            .iter()
+            .map(|change| {
+                vec![
+                    change.address.clone(),
+                    change.label.clone().unwrap_or_default(),
+                    change.cluster.clone().unwrap_or_default(),
+                    change.previous_sol.to_string(),
+                    change.current_sol.to_string(),
+                    change.change_sol.to_string(),
+                    match change.direction {
+                        Direction::Inflow => "inflow",
+                        Direction::Outflow => "outflow",
+                    }
+                    .to_string(),
+                ]
+            })
+            .collect()
+    }
+
+    fn summary(&self) -> Vec<(String, String)> {
+        vec![
+            ("compared".to_string(), self.compared.to_string()),
+            ("unmatched".to_string(), self.unmatched.to_string()),
+        ]
+    }
+
+    fn table(&self) -> String {
+        render_diff(self)
+    }
+}
@@ -0,0 +1,165 @@
+use crate::fetcher::BalanceResults;
+use std::fmt::Write;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Largest request head read before answering; scrapers send far less
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// The latest rendered metrics, swapped in after every refresh
+pub type MetricsPage = Arc<RwLock<String>>;
+
+/// `key="value"` label pairs, skipping unset values
+fn labels(pairs: &[(&str, Option<&str>)]) -> String {
+    let pairs: Vec<String> = pairs
+        .iter()
+        .filter_map(|(key, value)| Some(format!("{}=\"{}\"", key, escape(value.as_ref()?))))
+        .collect();
+    pairs.join(",")
+}
+
+/// Escape a label value as the Prometheus text format requires
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// A balance report as Prometheus text exposition: one lamports gauge per wallet, one token
+/// gauge per holding, and whether each wallet's last refresh succeeded
+pub fn render_metrics(results: &BalanceResults, refreshed_at: i64) -> String {
+    let mut page = String::new();
+
+    page.push_str("# HELP solana_wallet_balance_lamports SOL balance of the wallet in lamports\n");
+    page.push_str("# TYPE solana_wallet_balance_lamports gauge\n");
+    for wb in &results.balances {
+        let lamports = (wb.balance * 1_000_000_000.0).round() as u64;
+        let _ = writeln!(
+            page,
+            "solana_wallet_balance_lamports{{{}}} {}",
+            labels(&[
+                ("address", Some(&wb.address)),
+                ("label", wb.label.as_deref()),
+                ("cluster", wb.cluster.as_deref()),
+            ]),
+            lamports
+        );
+    }
+
+    page.push_str("# HELP solana_wallet_token_balance Token holdings of the wallet per mint, scaled by the mint's decimals\n");
+    page.push_str("# TYPE solana_wallet_token_balance gauge\n");
+    for wb in &results.balances {
+        for token in &wb.tokens {
+            let _ = writeln!(
+                page,
+                "solana_wallet_token_balance{{{}}} {}",
+                labels(&[
+                    ("address", Some(&wb.address)),
+                    ("label", wb.label.as_deref()),
+                    ("cluster", wb.cluster.as_deref()),
+                    ("mint", Some(&token.mint)),
+                    ("symbol", token.symbol.as_deref()),
+                ]),
+                token.amount
+            );
+        }
+    }
+
+    page.push_str(
+        "# HELP solana_wallet_up Whether the last refresh fetched the wallet's balance\n",
+    );
+    page.push_str("# TYPE solana_wallet_up gauge\n");
+    let up = results
+        .balances
+        .iter()
+        .map(|wb| (&wb.address, wb.label.as_deref(), wb.cluster.as_deref(), 1));
+    let failed = results.failed.iter().map(|wallet| {
+        (
+            &wallet.address,
+            wallet.label.as_deref(),
+            wallet.cluster.as_deref(),
+            0,
+        )
+    });
+    let timed_out = results
+        .timed_out
+        .iter()
+        .map(|address| (address, None, None, 0));
+    for (address, label, cluster, value) in up.chain(failed).chain(timed_out) {
+        let _ = writeln!(
+            page,
+            "solana_wallet_up{{{}}} {}",
+            labels(&[
+                ("address", Some(address)),
+                ("label", label),
+                ("cluster", cluster),
+            ]),
+            value
+        );
+    }
+
+    page.push_str(
+        "# HELP solana_balance_last_refresh_timestamp_seconds Unix time of the last completed refresh\n",
+    );
+    page.push_str("# TYPE solana_balance_last_refresh_timestamp_seconds gauge\n");
+    let _ = writeln!(
+        page,
+        "solana_balance_last_refresh_timestamp_seconds {}",
+        refreshed_at
+    );
+    page
+}
+
+/// Answer HTTP scrapes of `/metrics` on `addr` with the current `page`. Runs until the
+/// listener fails.
+pub async fn serve(addr: SocketAddr, page: MetricsPage) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let page = page.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = answer(stream, &page).await {
+                eprintln!("Metrics scrape from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Read one request head and reply with the metrics, or 404 for any other path
+async fn answer(mut stream: TcpStream, page: &MetricsPage) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_BYTES {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let (status, content_type, body) = match path.split('?').next() {
+        Some("/metrics") => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            page.read().expect("metrics page poisoned").clone(),
+        ),
+        _ => ("404 Not Found", "text/plain", "Not found\n".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -0,0 +1,638 @@
+use crate::cache::{BalanceCache, CacheScope};
+use crate::metadata::MetadataCache;
+use crate::stakes::{self, StakeBalance};
+use crate::tokens::{token_balances, TokenBalance};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use solana_account_decoder::{UiAccount, UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{
+    nonblocking::rpc_client::RpcClient, rpc_config::RpcAccountInfoConfig, rpc_request::RpcRequest,
+    rpc_response::Response,
+};
+use solana_common::{AddressBook, RpcError};
+use solana_sdk::{pubkey::Pubkey, system_program};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+/// Largest number of accounts getMultipleAccounts accepts
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Fetches SOL, token and stake balances of many wallets concurrently. Configured with
+/// `with_*` calls on top of [`BalanceFetcher::new`]:
+///
+/// ```no_run
+/// # async fn run(client: solana_client::nonblocking::rpc_client::RpcClient, wallets: &[solana_sdk::pubkey::Pubkey]) -> anyhow::Result<()> {
+/// use solana_balance_checker::BalanceFetcher;
+///
+/// let fetcher = BalanceFetcher::new(client)
+///     .with_batch_size(Some(100))
+///     .with_token_balances(false);
+/// for wallet in fetcher.fetch_all(wallets).await? {
+///     println!("{}: {} SOL", wallet.address, wallet.balance);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct BalanceFetcher {
+    client: Arc<RpcClient>,
+    book: AddressBook,
+    cluster: Option<String>,
+    max_concurrent: usize,
+    batch_size: Option<usize>,
+    deadline: Option<Duration>,
+    token_balances: bool,
+    token_metadata: bool,
+    metadata: MetadataCache,
+    stake_balances: bool,
+    details: bool,
+    progress: bool,
+    cache: Option<Arc<BalanceCache>>,
+}
+
+/// Outcome of a balance run
+#[derive(Debug, Serialize)]
+pub struct BalanceResults {
+    pub balances: Vec<WalletBalance>,
+    /// Wallets whose requests failed, after retries
+    pub failed: Vec<FailedWallet>,
+    /// Wallets abandoned when the run deadline passed
+    pub timed_out: Vec<String>,
+    /// Stake of all reported wallets, only with `--stakes`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_stake: Option<StakeBalance>,
+    /// Value of all reported balances, only with `--prices`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_usd: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedWallet {
+    pub address: String,
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    pub error: String,
+    /// Code of the RPC error, e.g. `RPC_REQUEST`, when the failure came from the endpoint
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletBalance {
+    pub address: String,
+    pub label: Option<String>,
+    /// Cluster the wallet was queried on, for configs grouping wallets by cluster
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cluster: Option<String>,
+    pub balance: f64,
+    /// Program owning the account when it is not the system program (program-owned or multisig)
+    pub owner_program: Option<String>,
+    /// Off-curve address, i.e. a PDA no private key can sign for
+    pub is_pda: bool,
+    /// SPL Token and Token-2022 holdings per mint
+    pub tokens: Vec<TokenBalance>,
+    /// Stake accounts the wallet is withdraw authority of, only with `--stakes`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stake: Option<StakeBalance>,
+    /// SOL plus priced token holdings in USD, only with `--prices`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usd_value: Option<f64>,
+    /// Full account info, only fetched with `--details`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<AccountDetails>,
+}
+
+/// Account info beyond the balance; missing accounts have none
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountDetails {
+    pub executable: bool,
+    pub owner: String,
+    /// Size of the account data in bytes
+    pub data_len: u64,
+    pub rent_epoch: u64,
+}
+
+/// Lamports and owning program of an account; missing accounts hold nothing and belong to no one
+struct AccountSummary {
+    lamports: u64,
+    owner: Option<Pubkey>,
+    details: Option<AccountDetails>,
+}
+
+/// Error of a failed getMultipleAccounts chunk, shared by all of its wallets
+type ChunkError = (String, Option<&'static str>);
+
+/// Completion counter printing progress to stderr every tenth of a large run
+struct Progress {
+    total: usize,
+    done: AtomicUsize,
+}
+
+impl Progress {
+    fn new(total: usize) -> Self {
+        Self {
+            total,
+            done: AtomicUsize::new(0),
+        }
+    }
+
+    fn finish_one(&self, max_concurrent: usize) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        // Runs that fit in one wave finish too quickly to need it
+        if self.total <= max_concurrent {
+            return;
+        }
+        let step = (self.total / 10).max(1);
+        if done.is_multiple_of(step) || done == self.total {
+            eprintln!("Fetched {}/{} wallets", done, self.total);
+        }
+    }
+}
+
+/// Code of the RPC error behind a failure, if the endpoint caused it
+fn rpc_error_code(error: &anyhow::Error) -> Option<&'static str> {
+    error.downcast_ref::<RpcError>().map(RpcError::code)
+}
+
+/// Message of a failure; an RPC error's own message already ends with its cause, so its
+/// source chain is not appended again
+fn error_message(error: &anyhow::Error) -> String {
+    match error.downcast_ref::<RpcError>() {
+        Some(rpc_error) => rpc_error.to_string(),
+        None => format!("{:#}", error),
+    }
+}
+
+/// Only lamports and owner are needed, so skip the account data
+fn account_config(client: &RpcClient) -> RpcAccountInfoConfig {
+    RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        data_slice: Some(UiDataSliceConfig {
+            offset: 0,
+            length: 0,
+        }),
+        commitment: Some(client.commitment()),
+        min_context_slot: None,
+    }
+}
+
+/// Requested through `send` rather than `get_account_with_config`, which would drop the
+/// `space` field the data length is read from
+async fn get_account(client: &RpcClient, wallet: &str) -> Result<AccountSummary> {
+    let response: Response<Option<UiAccount>> = client
+        .send(
+            RpcRequest::GetAccountInfo,
+            json!([wallet, account_config(client)]),
+        )
+        .await
+        .map_err(|e| RpcError::request("getAccountInfo", &client.url(), e))?;
+    parse_account(response.value)
+}
+
+/// Accounts of `wallets` from a single getMultipleAccounts request, in order
+async fn get_accounts(client: &RpcClient, wallets: &[String]) -> Result<Vec<AccountSummary>> {
+    let response: Response<Vec<Option<UiAccount>>> = client
+        .send(
+            RpcRequest::GetMultipleAccounts,
+            json!([wallets, account_config(client)]),
+        )
+        .await
+        .map_err(|e| RpcError::request("getMultipleAccounts", &client.url(), e))?;
+    if response.value.len() != wallets.len() {
+        return Err(RpcError::InvalidResponse {
+            method: "getMultipleAccounts".to_string(),
+            endpoint: client.url(),
+            reason: format!(
+                "expected {} accounts, got {}",
+                wallets.len(),
+                response.value.len()
+            ),
+        }
+        .into());
+    }
+    response.value.into_iter().map(parse_account).collect()
+}
+
+fn parse_account(account: Option<UiAccount>) -> Result<AccountSummary> {
+    let Some(account) = account else {
+        return Ok(AccountSummary {
+            lamports: 0,
+            owner: None,
+            details: None,
+        });
+    };
+
+    let owner = account
+        .owner
+        .parse::<Pubkey>()
+        .context("Invalid owner in response")?;
+
+    // `space` is the full data length even though the data itself was sliced away
+    let details = AccountDetails {
+        executable: account.executable,
+        owner: owner.to_string(),
+        data_len: account.space.unwrap_or_default(),
+        rent_epoch: account.rent_epoch,
+    };
+
+    Ok(AccountSummary {
+        lamports: account.lamports,
+        owner: Some(owner),
+        details: Some(details),
+    })
+}
+
+/// Accounts of all `wallets` fetched `batch_size` per getMultipleAccounts request, the
+/// chunks concurrently. Wallets of chunks that failed are left out with their error;
+/// those of chunks cut off by the deadline are added to `timed_out`.
+async fn prefetch_accounts(
+    client: &RpcClient,
+    wallets: &[String],
+    batch_size: usize,
+    deadline: Option<tokio::time::Instant>,
+    limiter: &Semaphore,
+    timed_out: &mut Vec<String>,
+) -> HashMap<String, Result<AccountSummary, ChunkError>> {
+    let chunks: Vec<&[String]> = wallets
+        .chunks(batch_size.clamp(1, MAX_MULTIPLE_ACCOUNTS))
+        .collect();
+    let fetches = chunks.iter().map(|chunk| async move {
+        let fetch = async {
+            let _permit = limiter.acquire().await.expect("limiter is never closed");
+            get_accounts(client, chunk).await
+        };
+        match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, fetch).await.ok(),
+            None => Some(fetch.await),
+        }
+    });
+    let results = futures::future::join_all(fetches).await;
+
+    let mut accounts = HashMap::with_capacity(wallets.len());
+    for (chunk, result) in chunks.into_iter().zip(results) {
+        match result {
+            Some(Ok(fetched)) => {
+                accounts.extend(chunk.iter().cloned().zip(fetched.into_iter().map(Ok)))
+            }
+            Some(Err(e)) => {
+                let error = (error_message(&e), rpc_error_code(&e));
+                accounts.extend(
+                    chunk
+                        .iter()
+                        .map(|wallet| (wallet.clone(), Err(error.clone()))),
+                );
+            }
+            None => timed_out.extend(chunk.iter().cloned()),
+        }
+    }
+    accounts
+}
+
+impl BalanceResults {
+    /// Append the results of another run, e.g. of another cluster
+    pub fn merge(&mut self, other: BalanceResults) {
+        self.balances.extend(other.balances);
+        self.failed.extend(other.failed);
+        self.timed_out.extend(other.timed_out);
+        self.total_stake = match (self.total_stake.take(), other.total_stake) {
+            (Some(mut total), Some(other)) => {
+                total.add(&other);
+                Some(total)
+            }
+            (total, other) => total.or(other),
+        };
+    }
+}
+
+impl BalanceFetcher {
+    /// One getAccountInfo request per wallet, 16 wallets at a time, with token balances and
+    /// without stake, account details or a deadline
+    pub fn new(client: RpcClient) -> Self {
+        Self {
+            client: Arc::new(client),
+            book: AddressBook::default(),
+            cluster: None,
+            max_concurrent: 16,
+            batch_size: None,
+            deadline: None,
+            token_balances: true,
+            token_metadata: true,
+            metadata: MetadataCache::default(),
+            stake_balances: false,
+            details: false,
+            progress: false,
+            cache: None,
+        }
+    }
+
+    /// Label wallets with their aliases from `book`
+    pub fn with_address_book(mut self, book: AddressBook) -> Self {
+        self.book = book;
+        self
+    }
+
+    /// Tag every result with the cluster the client queries
+    pub fn with_cluster(mut self, cluster: Option<String>) -> Self {
+        self.cluster = cluster;
+        self
+    }
+
+    /// Wallets (or getMultipleAccounts batches) queried at once; the rest queue
+    pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent = max_concurrent.max(1);
+        self
+    }
+
+    /// Fetch lamports and owners with one getMultipleAccounts request per this many wallets
+    /// (at most 100) instead of one getAccountInfo request per wallet
+    pub fn with_batch_size(mut self, batch_size: Option<usize>) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Wall-clock budget of a fetch; wallets still pending when it passes are reported as
+    /// timed out
+    pub fn with_deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// SPL token holdings; costs two requests per wallet
+    pub fn with_token_balances(mut self, token_balances: bool) -> Self {
+        self.token_balances = token_balances;
+        self
+    }
+
+    /// Names and symbols of held mints from their Metaplex metadata; costs one
+    /// getMultipleAccounts request per 100 mints not looked up yet by this fetcher
+    pub fn with_token_metadata(mut self, token_metadata: bool) -> Self {
+        self.token_metadata = token_metadata;
+        self
+    }
+
+    /// Stake accounts withdrawable by each wallet; costs one getProgramAccounts request per
+    /// wallet
+    pub fn with_stake_balances(mut self, stake_balances: bool) -> Self {
+        self.stake_balances = stake_balances;
+        self
+    }
+
+    /// Keep each wallet's [`AccountDetails`]
+    pub fn with_details(mut self, details: bool) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// Print progress to stderr on runs larger than one wave
+    pub fn with_progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Serve wallets fetched within the cache's TTL from it, and add the ones fetched here;
+    /// saving it is left to the caller
+    pub fn with_cache(mut self, cache: Option<Arc<BalanceCache>>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Balances of the `wallets` that could be fetched, in order; [`BalanceFetcher::fetch`]
+    /// also reports the failed and timed out ones
+    pub async fn fetch_all(&self, wallets: &[Pubkey]) -> Result<Vec<WalletBalance>> {
+        Ok(self.fetch(wallets).await?.balances)
+    }
+
+    /// Current lamports of every wallet from getMultipleAccounts, or the error of its batch
+    pub async fn fetch_lamports(&self, wallets: &[Pubkey]) -> HashMap<Pubkey, Result<u64, String>> {
+        let addresses: Vec<String> = wallets.iter().map(Pubkey::to_string).collect();
+        let limiter = Semaphore::new(self.max_concurrent);
+        let accounts = prefetch_accounts(
+            &self.client,
+            &addresses,
+            MAX_MULTIPLE_ACCOUNTS,
+            None,
+            &limiter,
+            &mut Vec::new(),
+        )
+        .await;
+        wallets
+            .iter()
+            .zip(&addresses)
+            .filter_map(|(wallet, address)| {
+                let lamports = match accounts.get(address)? {
+                    Ok(account) => Ok(account.lamports),
+                    Err((error, _)) => Err(error.clone()),
+                };
+                Some((*wallet, lamports))
+            })
+            .collect()
+    }
+
+    /// Balances of `wallets`, with those that failed or ran past the deadline listed
+    /// separately. Only fails when the current epoch, needed for stake, is unavailable.
+    pub async fn fetch(&self, wallets: &[Pubkey]) -> Result<BalanceResults> {
+        let wallets: Vec<String> = wallets.iter().map(Pubkey::to_string).collect();
+        let client = &self.client;
+        let book = &self.book;
+        let deadline = self
+            .deadline
+            .map(|deadline| tokio::time::Instant::now() + deadline);
+        // Stake is split into active and inactive against the current epoch
+        let epoch = if self.stake_balances {
+            Some(stakes::current_epoch(client).await?)
+        } else {
+            None
+        };
+
+        // Wallets fetched within the TTL, with the same scope, skip the RPC
+        let scope = CacheScope {
+            tokens: self.token_balances,
+            details: self.details,
+            stake_epoch: epoch,
+        };
+        let order: HashMap<String, usize> = wallets
+            .iter()
+            .enumerate()
+            .map(|(index, wallet)| (wallet.clone(), index))
+            .collect();
+        let mut cached = Vec::new();
+        let wallets: Vec<String> = match &self.cache {
+            Some(cache) => wallets
+                .into_iter()
+                .filter(
+                    |wallet| match cache.get(self.cluster.as_deref(), wallet, scope) {
+                        Some(mut balance) => {
+                            balance.label = book.label(wallet).map(str::to_string);
+                            cached.push(balance);
+                            false
+                        }
+                        None => true,
+                    },
+                )
+                .collect(),
+            None => wallets,
+        };
+        if self.progress && !cached.is_empty() {
+            eprintln!("Served {} wallet(s) from the cache", cached.len());
+        }
+
+        let mut wallet_balances = Vec::new();
+        let mut failed = Vec::new();
+        let mut timed_out = Vec::new();
+
+        // Requests beyond the limit wait for a permit instead of all going out at once
+        let max_concurrent = self.max_concurrent;
+        let limiter = Arc::new(Semaphore::new(max_concurrent));
+        // In batched mode lamports and owners come from getMultipleAccounts up front
+        let mut prefetched = match self.batch_size {
+            Some(batch_size) => Some(
+                prefetch_accounts(
+                    client,
+                    &wallets,
+                    batch_size,
+                    deadline,
+                    &limiter,
+                    &mut timed_out,
+                )
+                .await,
+            ),
+            None => None,
+        };
+        let pending = match &prefetched {
+            Some(accounts) => accounts.values().filter(|account| account.is_ok()).count(),
+            None => wallets.len(),
+        };
+        let progress = self.progress.then(|| Arc::new(Progress::new(pending)));
+
+        let mut futures = Vec::new();
+        for wallet in &wallets {
+            let account = match &mut prefetched {
+                Some(accounts) => match accounts.remove(wallet) {
+                    Some(Ok(account)) => Some(account),
+                    Some(Err((error, code))) => {
+                        failed.push(FailedWallet {
+                            address: wallet.clone(),
+                            label: book.label(wallet).map(str::to_string),
+                            cluster: self.cluster.clone(),
+                            error,
+                            code,
+                        });
+                        continue;
+                    }
+                    // Timed out while batching, already accounted for
+                    None => continue,
+                },
+                None => None,
+            };
+            let fetch_tokens = self.token_balances;
+            let details = self.details;
+            let wallet_clone = wallet.clone();
+            let label = book.label(wallet).map(str::to_string);
+            let cluster = self.cluster.clone();
+            let client = client.clone();
+            let limiter = limiter.clone();
+            let progress = progress.clone();
+
+            let fetch = async move {
+                let _permit = limiter
+                    .acquire_owned()
+                    .await
+                    .expect("limiter is never closed");
+                let pubkey: Pubkey = wallet_clone.parse()?;
+                let account = match account {
+                    Some(account) => account,
+                    None => get_account(&client, &wallet_clone).await?,
+                };
+                let tokens = if fetch_tokens {
+                    token_balances(&client, &pubkey).await?
+                } else {
+                    Vec::new()
+                };
+                let stake = match epoch {
+                    Some(epoch) => Some(stakes::stake_balance(&client, &pubkey, epoch).await?),
+                    None => None,
+                };
+                Ok::<WalletBalance, anyhow::Error>(WalletBalance {
+                    address: wallet_clone,
+                    label,
+                    cluster,
+                    // Convert lamports to SOL (1 SOL = 1,000,000,000 lamports)
+                    balance: account.lamports as f64 / 1_000_000_000.0,
+                    owner_program: account
+                        .owner
+                        .filter(|owner| *owner != system_program::id())
+                        .map(|owner| owner.to_string()),
+                    is_pda: !pubkey.is_on_curve(),
+                    tokens,
+                    stake,
+                    usd_value: None,
+                    details: account.details.filter(|_| details),
+                })
+            };
+
+            // Past the deadline the request is dropped, cancelling it, and yields None
+            let wallet = wallet.clone();
+            let future = async move {
+                let result = match deadline {
+                    Some(deadline) => tokio::time::timeout_at(deadline, fetch).await.ok(),
+                    None => Some(fetch.await),
+                };
+                if let Some(progress) = progress {
+                    progress.finish_one(max_concurrent);
+                }
+                (wallet, result)
+            };
+
+            futures.push(future);
+        }
+
+        for (wallet, result) in futures::future::join_all(futures).await {
+            match result {
+                Some(Ok(wallet_balance)) => wallet_balances.push(wallet_balance),
+                Some(Err(e)) => failed.push(FailedWallet {
+                    label: book.label(&wallet).map(str::to_string),
+                    cluster: self.cluster.clone(),
+                    address: wallet,
+                    error: error_message(&e),
+                    code: rpc_error_code(&e),
+                }),
+                None => timed_out.push(wallet),
+            }
+        }
+
+        // Without metadata holdings are still reported, by mint address
+        if self.token_balances && self.token_metadata {
+            if let Err(e) = self.metadata.enrich(client, &mut wallet_balances).await {
+                eprintln!("Token metadata unavailable: {}", error_message(&e));
+            }
+        }
+
+        if let Some(cache) = &self.cache {
+            cache.insert(&wallet_balances, scope);
+            if !cached.is_empty() {
+                wallet_balances.extend(cached);
+                wallet_balances.sort_by_key(|wb| order.get(&wb.address).copied());
+            }
+        }
+
+        let total_stake = epoch.map(|_| {
+            let mut total = StakeBalance::default();
+            for stake in wallet_balances.iter().filter_map(|wb| wb.stake.as_ref()) {
+                total.add(stake);
+            }
+            total
+        });
+
+        Ok(BalanceResults {
+            balances: wallet_balances,
+            failed,
+            timed_out,
+            total_stake,
+            total_usd: None,
+        })
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{BalanceResults, OutputFormat};
+use anyhow::{Context, Result};
+use chrono::{TimeZone, Utc};
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use solana_common::Render;
+use std::path::Path;
+
+/// One row per wallet and run; all rows of a run share `snapshot_ts` (Unix millis)
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS balance_snapshots (
+    snapshot_ts INTEGER NOT NULL,
+    address TEXT NOT NULL,
+    label TEXT,
+    balance_sol REAL NOT NULL,
+    usd_value REAL
+);
+CREATE INDEX IF NOT EXISTS balance_snapshots_address
+    ON balance_snapshots (address, snapshot_ts);
+";
+
+/// A wallet's balance in one past run
+#[derive(Debug, Serialize)]
+pub struct HistoryPoint {
+    pub timestamp: String,
+    pub balance_sol: f64,
+    /// Change since the previous run that reported the wallet
+    pub change_sol: Option<f64>,
+    pub usd_value: Option<f64>,
+}
+
+fn open(path: &Path) -> Result<Connection> {
+    let connection = Connection::open(path)
+        .with_context(|| format!("Failed to open history database {}", path.display()))?;
+    connection
+        .execute_batch(SCHEMA)
+        .context("Failed to create history table")?;
+    Ok(connection)
+}
+
+/// Append the balances of this run; failed and timed out wallets are left out. Returns the
+/// number of rows written.
+pub fn append(path: &Path, results: &BalanceResults) -> Result<usize> {
+    let mut connection = open(path)?;
+    let snapshot_ts = Utc::now().timestamp_millis();
+
+    let transaction = connection.transaction()?;
+    {
+        let mut insert = transaction.prepare(
+            "INSERT INTO balance_snapshots (snapshot_ts, address, label, balance_sol, usd_value)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for wb in &results.balances {
+            insert.execute(params![
+                snapshot_ts,
+                wb.address,
+                wb.label,
+                wb.balance,
+                wb.usd_value
+            ])?;
+        }
+    }
+    transaction
+        .commit()
+        .context("Failed to write history snapshot")?;
+    Ok(results.balances.len())
+}
+
+/// Every recorded balance of `address`, oldest first
+pub fn wallet_history(path: &Path, address: &str) -> Result<Vec<HistoryPoint>> {
+    let connection = open(path)?;
+    let mut query = connection.prepare(
+        "SELECT snapshot_ts, balance_sol, usd_value FROM balance_snapshots
+         WHERE address = ?1 ORDER BY snapshot_ts",
+    )?;
+    let rows = query.query_map(params![address], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, f64>(1)?,
+            row.get::<_, Option<f64>>(2)?,
+        ))
+    })?;
+
+    let mut points: Vec<HistoryPoint> = Vec::new();
+    for row in rows {
+        let (snapshot_ts, balance_sol, usd_value) = row?;
+        let timestamp = Utc
+            .timestamp_millis_opt(snapshot_ts)
+            .single()
+            .map(|time| time.to_rfc3339())
+            .unwrap_or_else(|| snapshot_ts.to_string());
+        points.push(HistoryPoint {
+            timestamp,
+            balance_sol,
+            change_sol: points
+                .last()
+                .map(|previous| balance_sol - previous.balance_sol),
+            usd_value,
+        });
+    }
+    Ok(points)
+}
+
+/// The balance timeline of one wallet
+#[derive(Serialize)]
+#[serde(transparent)]
+struct HistoryReport<'a> {
+    #[serde(skip)]
+    name: &'a str,
+    points: &'a [HistoryPoint],
+}
+
+impl Render for HistoryReport<'_> {
+    fn columns(&self) -> &'static [&'static str] {
+        &["timestamp", "balance_sol", "change_sol", "usd_value"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.points
+            .iter()
+            .map(|point| {
+                vec![
+                    point.timestamp.clone(),
+                    point.balance_sol.to_string(),
+                    point
+                        .change_sol
+                        .map(|change| change.to_string())
+                        .unwrap_or_default(),
+                    point
+                        .usd_value
+                        .map(|usd| format!("{:.2}", usd))
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+
+    fn table(&self) -> String {
+        let mut report = format!("Balance history of {}:\n", self.name);
+        if self.points.is_empty() {
+            report.push_str("No recorded runs\n");
+        }
+        for point in self.points {
+            let change = match point.change_sol {
+                Some(change) => format!(" ({:+} SOL)", change),
+                None => String::new(),
+            };
+            report.push_str(&format!(
+                "{}: {} SOL{}\n",
+                point.timestamp, point.balance_sol, change
+            ));
+        }
+        report
+    }
+}
+
+/// The balance timeline of `name` in the requested format
+pub fn render_history(name: &str, points: &[HistoryPoint], format: OutputFormat) -> Result<String> {
+    Ok(solana_common::render::render(
+        &HistoryReport { name, points },
+        format,
+    )?)
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_common::{AddressBook, RpcError};
+use solana_sdk::pubkey::Pubkey;
+
+/// Offset of the owner in an SPL Token or Token-2022 account, after the mint
+const TOKEN_ACCOUNT_OWNER: std::ops::Range<usize> = 32..64;
+
+/// One of the largest token accounts of a mint
+#[derive(Debug)]
+pub struct Holder {
+    pub token_account: String,
+    /// Wallet owning the token account, when it could be resolved
+    pub owner: Option<String>,
+    pub amount: f64,
+    /// Share of the total supply, in percent
+    pub share: f64,
+}
+
+/// The largest token accounts of `mint` (at most 20, as returned by getTokenLargestAccounts)
+/// with their owners and share of supply
+pub async fn top_holders(client: &RpcClient, mint: &Pubkey) -> Result<Vec<Holder>> {
+    let accounts = client
+        .get_token_largest_accounts(mint)
+        .await
+        .map_err(|e| RpcError::request("getTokenLargestAccounts", &client.url(), e))?;
+
+    let supply = client
+        .get_token_supply(mint)
+        .await
+        .map_err(|e| RpcError::request("getTokenSupply", &client.url(), e))?;
+    let total_supply = supply
+        .ui_amount_string
+        .parse::<f64>()
+        .context("Invalid token supply in response")?;
+
+    let addresses = accounts
+        .iter()
+        .map(|account| account.address.parse::<Pubkey>())
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid token account in response")?;
+
+    // Resolve the owner wallet of every token account in one request
+    let owners = client
+        .get_multiple_accounts(&addresses)
+        .await
+        .map_err(|e| RpcError::request("getMultipleAccounts", &client.url(), e))?;
+
+    let holders = accounts
+        .iter()
+        .zip(owners)
+        .map(|(account, owner)| {
+            let amount = account
+                .amount
+                .ui_amount_string
+                .parse::<f64>()
+                .unwrap_or_default();
+
+            Holder {
+                token_account: account.address.clone(),
+                owner: owner
+                    .and_then(|owner| Pubkey::try_from(owner.data.get(TOKEN_ACCOUNT_OWNER)?).ok())
+                    .map(|owner| owner.to_string()),
+                amount,
+                share: if total_supply > 0.0 {
+                    amount / total_supply * 100.0
+                } else {
+                    0.0
+                },
+            }
+        })
+        .collect();
+
+    Ok(holders)
+}
+
+pub fn print_holders(mint: &str, holders: &[Holder], book: &AddressBook) {
+    println!("Largest holders of {}:", book.display(mint));
+    println!(
+        "{:<5} {:<44} {:<60} {:>24} {:>9}",
+        "Rank", "Token account", "Owner", "Amount", "Supply %"
+    );
+
+    for (rank, holder) in holders.iter().enumerate() {
+        println!(
+            "{:<5} {:<44} {:<60} {:>24} {:>8.2}%",
+            rank + 1,
+            holder.token_account,
+            holder
+                .owner
+                .as_deref()
+                .map(|owner| book.display(owner))
+                .unwrap_or_else(|| "unknown".to_string()),
+            holder.amount,
+            holder.share
+        );
+    }
+
+    let top_share: f64 = holders.iter().map(|holder| holder.share).sum();
+    println!(
+        "\nTop {} accounts hold {:.2}% of supply",
+        holders.len(),
+        top_share
+    );
+}
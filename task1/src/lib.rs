@@ -0,0 +1,24 @@
+//! Balance fetching and reporting behind the task1 CLI, usable from other crates through
+//! [`BalanceFetcher`].
+
+pub mod alerts;
+pub mod assets;
+pub mod cache;
+pub mod clusters;
+pub mod diff;
+pub mod exporter;
+pub mod fetcher;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod holders;
+pub mod metadata;
+#[cfg(feature = "parquet")]
+pub mod parquet_export;
+pub mod prices;
+pub mod report;
+pub mod stakes;
+pub mod tokens;
+pub mod watch;
+
+pub use fetcher::{AccountDetails, BalanceFetcher, BalanceResults, FailedWallet, WalletBalance};
+pub use report::{csv_field, render_balances, OutputFormat};
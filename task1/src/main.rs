@@ -1,106 +1,566 @@
 use anyhow::{Context, Result};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::fs::File;
+use clap::{Parser, Subcommand, ValueEnum};
+use futures::StreamExt;
+use serde::Deserialize;
+use solana_balance_checker::{
+    alerts, assets, cache::{BalanceCache, CacheConfig}, clusters::ClusterConfig, diff, exporter, holders, prices, render_balances, watch, BalanceFetcher, BalanceResults,
+    OutputFormat,
+};
+#[cfg(feature = "history")]
+use solana_balance_checker::history;
+#[cfg(feature = "parquet")]
+use solana_balance_checker::parquet_export;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_common::{config_file, render::render, rpc, AddressBook, CliConfig, ConfigFormat, NotificationConfig, ReportAttachment, RpcLimits};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Without a command, report the balances of the wallets in the config file
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Path to config file
+    #[arg(short, long, default_value = "config.yaml", global = true)]
+    config: String,
+
+    /// Format of the config file: yaml, toml or json [default: from its extension, else yaml]
+    #[arg(long, global = true)]
+    format: Option<ConfigFormat>,
+
+    /// Solana RPC endpoint [default: solana-cli config, else mainnet-beta]
+    #[arg(short, long, global = true)]
+    rpc_url: Option<String>,
+
+    /// Commitment level of the queries [default: solana-cli config, else the node's default]
+    #[arg(long, global = true)]
+    commitment: Option<Commitment>,
+
+    /// Timeout of each RPC request in seconds [default: request_timeout_secs from the config, else 30]
+    #[arg(long, global = true)]
+    timeout: Option<u64>,
+
+    /// Wallets (or getMultipleAccounts batches) queried at once [default: max_concurrent from the config, else 16]
+    #[arg(long)]
+    max_concurrent: Option<usize>,
+
+    /// Fetch full account info per wallet (executable, owner, data length, rent epoch),
+    /// included in JSON output
+    #[arg(long)]
+    details: bool,
+
+    /// Format of the balance report: table, json, csv, ndjson or markdown
+    /// [default: json with --details, else table]
+    #[arg(short, long)]
+    output: Option<OutputFormat>,
+
+    /// Compare with a previous `--output json` report and print only the wallets whose SOL
+    /// balance changed, with the change and its direction
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["watch", "assets", "serve_metrics"])]
+    diff: Option<std::path::PathBuf>,
+
+    /// Write the balance report to this file instead of stdout
+    #[arg(long, value_name = "FILE")]
+    out_file: Option<std::path::PathBuf>,
+
+    /// Value balances in USD (SOL and listed tokens) via the configured price API
+    #[arg(long)]
+    prices: bool,
+    
+    /// Also report stake accounts each wallet can withdraw from, split into active and inactive stake
+    #[arg(long)]
+    stakes: bool,
+
+    /// Keep running and print every balance change pushed over a pubsub WebSocket
+    /// (accountSubscribe) instead of a one-shot report
+    #[arg(long)]
+    watch: bool,
+
+    /// List each wallet's NFTs, regular and compressed, grouped by collection instead of the
+    /// balance report; needs a DAS-compatible RPC (getAssetsByOwner)
+    #[arg(long, conflicts_with_all = ["watch", "serve_metrics"])]
+    assets: bool,
+
+    /// Keep running, refresh the balances every `metrics_interval_secs` and serve them as
+    /// Prometheus gauges on http://ADDR/metrics
+    #[arg(long, value_name = "ADDR", conflicts_with = "watch")]
+    serve_metrics: Option<SocketAddr>,
+
+    /// Pubsub WebSocket endpoint for --watch [default: solana-cli config, else derived from the RPC URL]
+    #[arg(long, requires = "watch")]
+    ws_url: Option<String>,
+
+    /// Also write the balance report as a Parquet snapshot to this file
+    #[cfg(feature = "parquet")]
+    #[arg(long, value_name = "FILE")]
+    parquet: Option<std::path::PathBuf>,
+
+    /// Append the balances of this run to a SQLite database, created if missing
+    #[cfg(feature = "history")]
+    #[arg(long, value_name = "DB")]
+    history: Option<std::path::PathBuf>,
+
+    /// Print the balance timeline of a wallet recorded in --history instead of running a report
+    #[cfg(feature = "history")]
+    #[arg(long, value_name = "WALLET", requires = "history")]
+    show_history: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    fn as_str(self) -> &'static str {
+        match self {
+            Commitment::Processed => "processed",
+            Commitment::Confirmed => "confirmed",
+            Commitment::Finalized => "finalized",
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List the largest token accounts of a mint with their owners and share of supply
+    TopHolders {
+        /// Mint address or address book alias
+        mint: String,
+    },
+}
+
 #[derive(Debug, Deserialize)]
 struct Config {
+    /// Wallets queried on `--rpc-url` (or the solana-cli / built-in endpoint)
+    #[serde(default)]
     wallets: Vec<String>,
+    /// Wallets grouped by cluster name, each group queried on its own endpoint concurrently
+    /// with the others and its results tagged with the cluster
+    #[serde(default)]
+    clusters: BTreeMap<String, ClusterConfig>,
+    #[serde(default)]
+    rpc_limits: RpcLimits,
+    #[serde(default)]
+    notifications: NotificationConfig,
+    /// Timeout of each RPC request
+    #[serde(default = "default_request_timeout_secs")]
+    request_timeout_secs: u64,
+    /// Wall-clock budget for the whole run; wallets still pending when it passes are
+    /// reported as timed out instead of keeping a cron job waiting
+    #[serde(default)]
+    deadline_secs: Option<u64>,
+    /// Fetch lamports and owners with one getMultipleAccounts request per this many
+    /// wallets (at most 100) instead of one getAccountInfo request per wallet
+    #[serde(default)]
+    batch_size: Option<usize>,
+    /// Wallets (or batches) queried at once; the rest queue, so huge wallet lists do not
+    /// flood the endpoint
+    #[serde(default = "default_max_concurrent")]
+    max_concurrent: usize,
+    /// Also report SPL token holdings; costs two requests per wallet
+    #[serde(default = "default_token_balances")]
+    token_balances: bool,
+    /// Show held tokens by the symbol and name in their Metaplex metadata; costs one
+    /// request per 100 distinct mints
+    #[serde(default = "default_token_metadata")]
+    token_metadata: bool,
+    /// Also report stake accounts withdrawable by each wallet (always on with `--stakes`);
+    /// costs one getProgramAccounts request per wallet
+    #[serde(default)]
+    stake_balances: bool,
+    /// Price API used by `--prices`
+    #[serde(default)]
+    prices: prices::PriceConfig,
+    /// Minimum/maximum balance thresholds, alerted on through a webhook
+    #[serde(default)]
+    alerts: alerts::AlertConfig,
+    /// Serve wallets fetched within `ttl_secs` from a cache, kept in `path` between runs
+    #[serde(default)]
+    cache: Option<CacheConfig>,
+    /// Seconds between balance refreshes with `--serve-metrics`
+    #[serde(default = "default_metrics_interval_secs")]
+    metrics_interval_secs: u64,
 }
 
-#[derive(Debug, Serialize)]
-struct WalletBalance {
-    address: String,
-    balance: f64,
+fn default_request_timeout_secs() -> u64 {
+    30
 }
 
-async fn get_balance(client: &Client, rpc_url: &str, wallet: &str) -> Result<f64> {
-    let request_body = json!({
-        "jsonrpc": "2.0",
-        "id": 1,
-        "method": "getBalance",
-        "params": [wallet]
-    });
+fn default_token_balances() -> bool {
+    true
+}
 
-    let response = client
-        .post(rpc_url)
-        .json(&request_body)
-        .send()
-        .await
-        .context("Failed to send request to Solana RPC")?;
+fn default_token_metadata() -> bool {
+    true
+}
 
-    let response_json: Value = response
-        .json()
-        .await
-        .context("Failed to parse response as JSON")?;
+fn default_max_concurrent() -> usize {
+    16
+}
 
-    let balance = response_json["result"]["value"]
-        .as_u64()
-        .context("Failed to extract balance from response")?;
+fn default_metrics_interval_secs() -> u64 {
+    60
+}
+
+/// Async client behind the shared rate-limited transport. Without a configured commitment
+/// it requests `finalized`, the node default.
+fn rpc_client(rpc_url: &str, commitment: Option<&str>, limits: RpcLimits, timeout_secs: u64) -> Result<RpcClient> {
+    let commitment = commitment
+        .map(CommitmentConfig::from_str)
+        .transpose()
+        .context("Invalid commitment")?
+        .unwrap_or_default();
+    Ok(rpc::nonblocking_rpc_client_with_timeout(
+        rpc_url,
+        commitment,
+        limits,
+        Duration::from_secs(timeout_secs),
+    ))
+}
 
-    // Convert lamports to SOL (1 SOL = 1,000,000,000 lamports)
-    Ok(balance as f64 / 1_000_000_000.0)
+/// Configured wallets, given as address book aliases or raw addresses
+fn config_wallets(wallets: &[String], book: &AddressBook) -> Result<Vec<Pubkey>> {
+    wallets
+        .iter()
+        .map(|wallet| book.resolve(wallet))
+        .collect::<Result<Vec<_>, _>>()
+        .context("Invalid wallet in config file")
 }
 
-async fn get_multiple_balances(config_path: &Path) -> Result<Vec<WalletBalance>> {
-    let config_file = File::open(config_path).context("Failed to open config file")?;
-    let config: Config = serde_yaml::from_reader(config_file).context("Failed to parse config file")?;
+/// The config's wallets with their current balances, the baseline of `--watch` deltas
+async fn watched_wallets(
+    config: &Config,
+    rpc_url: &str,
+    commitment: Option<&str>,
+) -> Result<Vec<watch::WatchedWallet>> {
+    let book = AddressBook::load()?;
+    if !config.clusters.is_empty() {
+        eprintln!("--watch follows the top-level wallets only; wallets under clusters are not watched");
+    }
+    let wallets = config_wallets(&config.wallets, &book)?;
+
+    let client = rpc_client(rpc_url, commitment, config.rpc_limits, config.request_timeout_secs)?;
+    let mut accounts = BalanceFetcher::new(client)
+        .with_max_concurrent(config.max_concurrent)
+        .fetch_lamports(&wallets)
+        .await;
+
+    let mut watched = Vec::with_capacity(wallets.len());
+    for wallet in wallets {
+        let lamports = match accounts.remove(&wallet) {
+            Some(Ok(lamports)) => Some(lamports),
+            Some(Err(error)) => {
+                eprintln!("Error getting initial balance of {}: {}", wallet, error);
+                None
+            }
+            None => None,
+        };
+        watched.push(watch::WatchedWallet {
+            address: wallet,
+            label: book.label(&wallet.to_string()).map(str::to_string),
+            lamports,
+        });
+    }
+    Ok(watched)
+}
 
-    let client = Client::builder()
-        .timeout(Duration::from_secs(30))
-        .build()
-        .context("Failed to build HTTP client")?;
+fn load_config(config_path: &Path, format: Option<ConfigFormat>) -> Result<Config> {
+    Ok(config_file::load(config_path, format)?)
+}
 
-    let rpc_url = "https://api.mainnet-beta.solana.com";
+async fn get_multiple_balances(
+    config: &Config,
+    rpc_url: &str,
+    commitment: Option<&str>,
+    details: bool,
+    cache: Option<&Arc<BalanceCache>>,
+) -> Result<BalanceResults> {
+    let book = AddressBook::load()?;
     
-    let mut wallet_balances = Vec::new();
+    // Top-level wallets on the default endpoint, then one group per cluster
+    let mut groups = Vec::new();
+    if !config.wallets.is_empty() {
+        groups.push((None, rpc_url.to_string(), config_wallets(&config.wallets, &book)?));
+    }
+    for (name, cluster) in &config.clusters {
+        let wallets = config_wallets(cluster.wallets(), &book)
+            .with_context(|| format!("Invalid wallet in cluster {}", name))?;
+        groups.push((Some(name.clone()), cluster.rpc_url(name)?, wallets));
+    }
+    if groups.is_empty() {
+        anyhow::bail!("Config file lists no wallets or clusters");
+    }
     
-    // Create a vector to hold all the futures
-    let mut futures = Vec::new();
+    let fetches = groups.into_iter().map(|(cluster, rpc_url, wallets)| {
+        let book = book.clone();
+        let cache = cache.cloned();
+        async move {
+            let client = rpc_client(&rpc_url, commitment, config.rpc_limits, config.request_timeout_secs)?;
+            BalanceFetcher::new(client)
+                .with_address_book(book)
+                .with_cluster(cluster)
+                .with_max_concurrent(config.max_concurrent)
+                .with_batch_size(config.batch_size)
+                .with_deadline(config.deadline_secs.map(Duration::from_secs))
+                .with_token_balances(config.token_balances)
+                .with_token_metadata(config.token_metadata)
+                .with_stake_balances(config.stake_balances)
+                .with_details(details)
+                .with_progress(true)
+                .with_cache(cache)
+                .fetch(&wallets)
+                .await
+        }
+    });
     
-    // Create futures for all wallet balance requests
-    for wallet in &config.wallets {
-        let wallet_clone = wallet.clone();
-        let client_clone = client.clone();
-        let rpc_url_clone = rpc_url.to_string();
-        
-        let future = async move {
-            let balance = get_balance(&client_clone, &rpc_url_clone, &wallet_clone).await?;
-            Ok::<WalletBalance, anyhow::Error>(WalletBalance {
-                address: wallet_clone,
-                balance,
-            })
-        };
-        
-        futures.push(future);
+    let mut results: Option<BalanceResults> = None;
+    for group in futures::future::join_all(fetches).await {
+        let group = group?;
+        match &mut results {
+            Some(results) => results.merge(group),
+            None => results = Some(group),
+        }
     }
     
-    // Execute all futures concurrently
-    let results = futures::future::join_all(futures).await;
+    if let Some(cache) = cache {
+        if let Err(e) = cache.save() {
+            eprintln!("{:#}", e);
+        }
+    }
+    Ok(results.expect("at least one wallet group"))
+}
+
+/// The configured balance cache, if any
+fn open_cache(config: &Config) -> Result<Option<Arc<BalanceCache>>> {
+    Ok(config.cache.as_ref().map(BalanceCache::open).transpose()?.map(Arc::new))
+}
+
+/// NFT holdings of the top-level wallets, for `--assets`; wallets whose lookup fails are
+/// reported on stderr and left out
+async fn report_assets(
+    config: &Config,
+    rpc_url: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    let book = AddressBook::load()?;
+    if !config.clusters.is_empty() {
+        eprintln!("--assets lists the top-level wallets only; wallets under clusters are skipped");
+    }
+    let wallets = config_wallets(&config.wallets, &book)?;
+    let http = reqwest::Client::builder().timeout(Duration::from_secs(config.request_timeout_secs)).build()?;
+    
+    let lookups = wallets.iter().map(|wallet| {
+        let http = &http;
+        async move { (wallet, assets::assets_by_owner(http, rpc_url, wallet).await) }
+    });
+    let results: Vec<_> = futures::stream::iter(lookups)
+        .buffered(config.max_concurrent.max(1))
+        .collect()
+        .await;
     
-    // Process results
-    for result in results {
+    let mut holdings = Vec::new();
+    for (wallet, result) in results {
         match result {
-            Ok(wallet_balance) => wallet_balances.push(wallet_balance),
-            Err(e) => eprintln!("Error getting balance: {}", e),
+            Ok(assets) => holdings.push(assets::WalletAssets {
+                address: wallet.to_string(),
+                label: book.label(&wallet.to_string()).map(str::to_string),
+                assets,
+            }),
+            Err(e) => eprintln!("Failed to list assets of {}: {:#}", wallet, e),
         }
     }
+    
+    print!("{}", render(&assets::AssetReport(holdings), format)?);
+    Ok(())
+}
 
-    Ok(wallet_balances)
+/// Refresh the balances on an interval and serve the latest ones to Prometheus on `addr`;
+/// a failed refresh keeps the previous values up
+async fn serve_metrics(
+    addr: SocketAddr,
+    config: &Config,
+    rpc_url: &str,
+    commitment: Option<&str>,
+) -> Result<()> {
+    let cache = open_cache(config)?;
+    let page = exporter::MetricsPage::default();
+    let mut server = tokio::spawn(exporter::serve(addr, page.clone()));
+    eprintln!("Serving balance metrics on http://{}/metrics", addr);
+    
+    let mut interval = tokio::time::interval(Duration::from_secs(config.metrics_interval_secs.max(1)));
+    loop {
+        tokio::select! {
+            result = &mut server => {
+                result?.with_context(|| format!("Failed to serve metrics on {}", addr))?;
+                anyhow::bail!("Metrics endpoint on {} stopped", addr);
+            }
+            _ = interval.tick() => {
+                match get_multiple_balances(config, rpc_url, commitment, false, cache.as_ref()).await {
+                    Ok(results) => {
+                        let metrics = exporter::render_metrics(&results, chrono::Utc::now().timestamp());
+                        *page.write().expect("metrics page poisoned") = metrics;
+                    }
+                    Err(e) => eprintln!("Balance refresh failed, keeping the previous values: {:#}", e),
+                }
+            }
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let config_path = Path::new("config.yaml");
+    let args = Args::parse();
     
-    let wallet_balances = get_multiple_balances(config_path).await?;
+    // Flags win over the user's solana-cli config, which wins over the built-in defaults
+    let cli_config = CliConfig::load()?;
+    let rpc_url = cli_config.rpc_url(args.rpc_url.as_deref(), "https://api.mainnet-beta.solana.com");
+    let commitment = args
+        .commitment
+        .map(|commitment| commitment.as_str().to_string())
+        .or(cli_config.commitment.clone());
     
-    println!("Wallet Balances:");
-    for wb in wallet_balances {
-        println!("{}: {} SOL", wb.address, wb.balance);
+    let output_format = args.output.unwrap_or(if args.details {
+        OutputFormat::Json
+    } else {
+        OutputFormat::Table
+    });
+    
+    match args.command {
+        Some(Command::TopHolders { mint }) => {
+            let book = AddressBook::load()?;
+            let mint = book.resolve(&mint).context("Invalid mint")?;
+            
+            let timeout = args.timeout.unwrap_or_else(default_request_timeout_secs);
+            let client = rpc_client(&rpc_url, commitment.as_deref(), RpcLimits::default(), timeout)?;
+            
+            let holders = holders::top_holders(&client, &mint).await?;
+            holders::print_holders(&mint.to_string(), &holders, &book);
+        }
+        None => {
+            #[cfg(feature = "history")]
+            if let (Some(wallet), Some(path)) = (&args.show_history, &args.history) {
+                let book = AddressBook::load()?;
+                let address = book.resolve(wallet).context("Invalid wallet")?.to_string();
+                let points = history::wallet_history(path, &address)?;
+                print!("{}", history::render_history(&book.display(&address), &points, output_format)?);
+                return Ok(());
+            }
+            let mut config = load_config(Path::new(&args.config), args.format)?;
+            if let Some(timeout) = args.timeout {
+                config.request_timeout_secs = timeout;
+            }
+            if let Some(max_concurrent) = args.max_concurrent {
+                config.max_concurrent = max_concurrent;
+            }
+            if args.stakes {
+                config.stake_balances = true;
+            }
+            let alerting = config
+                .alerts
+                .resolve(&AddressBook::load()?, config.notifications.webhook.as_ref())?;
+            if args.watch {
+                let ws_url = cli_config.websocket_url(args.ws_url.as_deref(), &rpc_url);
+                let mut wallets = watched_wallets(&config, &rpc_url, commitment.as_deref()).await?;
+                return watch::watch_balances(&ws_url, &mut wallets, commitment.as_deref(), output_format, alerting.as_ref()).await;
+            }
+            if args.assets {
+                return report_assets(&config, &rpc_url, output_format).await;
+            }
+            if let Some(addr) = args.serve_metrics {
+                return serve_metrics(addr, &config, &rpc_url, commitment.as_deref()).await;
+            }
+            let cache = open_cache(&config)?;
+            let mut results = get_multiple_balances(&config, &rpc_url, commitment.as_deref(), args.details, cache.as_ref()).await?;
+            // Valuation is best effort: the balances are reported either way
+            if args.prices {
+                let mut mints: Vec<String> = results
+                    .balances
+                    .iter()
+                    .flat_map(|wb| wb.tokens.iter().map(|token| token.mint.clone()))
+                    .collect();
+                mints.sort();
+                mints.dedup();
+                match config.prices.fetch(&mints).await {
+                    Ok(prices) => prices.apply(&mut results),
+                    Err(e) => eprintln!("USD valuation skipped: {:#}", e),
+                }
+            }
+            #[cfg(feature = "parquet")]
+            if let Some(path) = &args.parquet {
+                parquet_export::write_snapshot(path, &results.balances)?;
+                eprintln!("Wrote Parquet snapshot to {}", path.display());
+            }
+            #[cfg(feature = "history")]
+            if let Some(path) = &args.history {
+                let rows = history::append(path, &results)?;
+                eprintln!("Recorded {} balances in {}", rows, path.display());
+            }
+            let report = render_balances(&results);
+            let output = match &args.diff {
+                Some(previous) => {
+                    let diff = diff::diff_against(previous, &results)?;
+                    render(&diff, output_format)?
+                }
+                None => render(&results, output_format)?,
+            };
+            match &args.out_file {
+                Some(path) => {
+                    std::fs::write(path, output)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    eprintln!("Wrote balance report to {}", path.display());
+                }
+                None => print!("{}", output),
+            }
+
+            if let Some(alerting) = &alerting {
+                let alerts = alerting.check_results(&results);
+                if !alerts.is_empty() {
+                    alerting.send(&alerts).await?;
+                    eprintln!("Sent {} balance alert(s)", alerts.len());
+                }
+            }
+
+            if let Some(email) = config.notifications.email {
+                let attachment = ReportAttachment {
+                    filename: "balances.csv".to_string(),
+                    content_type: "text/csv",
+                    body: render(&results, OutputFormat::Csv)?.into_bytes(),
+                };
+                tokio::task::spawn_blocking(move || {
+                    email.send("Wallet balance report", &report, &[attachment])
+                })
+                .await?
+                .context("Failed to email balance report")?;
+                eprintln!("Emailed balance report");
+            }
+        }
+    }
+    
+    // Keep machine-readable output on stdout parseable
+    for endpoint in rpc::metrics() {
+        let line = format!(
+            "\nRPC {}: {} requests, {} rate limited, {} errors, avg latency {:.1} ms",
+            endpoint.endpoint,
+            endpoint.requests,
+            endpoint.rate_limited,
+            endpoint.errors,
+            endpoint.avg_latency_ms
+        );
+        if output_format == OutputFormat::Table {
+            println!("{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
     }
     
     Ok(())
@@ -0,0 +1,128 @@
+use crate::fetcher::WalletBalance;
+use anyhow::{Context, Result};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_common::RpcError;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Metaplex Token Metadata program, which holds the name and symbol of most mints
+const METADATA_PROGRAM: Pubkey = solana_sdk::pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// `Key::MetadataV1`, the first byte of a metadata account
+const METADATA_V1: u8 = 4;
+
+/// Name and symbol of a mint from its Metaplex metadata account
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: String,
+}
+
+/// Metadata lookups of one run, per mint; mints without metadata are remembered too
+#[derive(Debug, Default)]
+pub struct MetadataCache {
+    mints: Mutex<HashMap<Pubkey, Option<TokenMetadata>>>,
+}
+
+/// Address of the metadata account of `mint`
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", METADATA_PROGRAM.as_ref(), mint.as_ref()],
+        &METADATA_PROGRAM,
+    )
+    .0
+}
+
+/// Read the name and symbol from a metadata account: key, update authority and mint, then
+/// the Borsh strings, which Metaplex pads with NUL bytes
+fn parse_metadata(data: &[u8]) -> Option<TokenMetadata> {
+    if data.first() != Some(&METADATA_V1) {
+        return None;
+    }
+    let mut rest = data.get(1 + 32 + 32..)?;
+    let mut read_string = || {
+        let len = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+        let value = rest.get(4..4 + len)?;
+        rest = &rest[4 + len..];
+        Some(
+            String::from_utf8_lossy(value)
+                .trim_end_matches('\0')
+                .trim()
+                .to_string(),
+        )
+    };
+    let name = read_string()?;
+    let symbol = read_string()?;
+    Some(TokenMetadata { name, symbol })
+}
+
+impl MetadataCache {
+    /// Metadata of `mints`, fetching those not looked up yet with getMultipleAccounts
+    pub async fn resolve(
+        &self,
+        client: &RpcClient,
+        mints: &[Pubkey],
+    ) -> Result<HashMap<Pubkey, TokenMetadata>> {
+        let missing: Vec<Pubkey> = {
+            let cache = self.mints.lock().expect("metadata cache poisoned");
+            let mut missing: Vec<Pubkey> = mints
+                .iter()
+                .filter(|mint| !cache.contains_key(mint))
+                .copied()
+                .collect();
+            missing.sort();
+            missing.dedup();
+            missing
+        };
+
+        for chunk in missing.chunks(100) {
+            let addresses: Vec<Pubkey> = chunk.iter().map(metadata_address).collect();
+            let accounts = client
+                .get_multiple_accounts(&addresses)
+                .await
+                .map_err(|e| RpcError::request("getMultipleAccounts", &client.url(), e))?;
+            let mut cache = self.mints.lock().expect("metadata cache poisoned");
+            for (mint, account) in chunk.iter().zip(accounts) {
+                let metadata = account
+                    .filter(|account| account.owner == METADATA_PROGRAM)
+                    .and_then(|account| parse_metadata(&account.data));
+                cache.insert(*mint, metadata);
+            }
+        }
+
+        let cache = self.mints.lock().expect("metadata cache poisoned");
+        Ok(mints
+            .iter()
+            .filter_map(|mint| Some((*mint, cache.get(mint)?.clone()?)))
+            .collect())
+    }
+
+    /// Fill in the name and symbol of every token holding of `balances`
+    pub async fn enrich(&self, client: &RpcClient, balances: &mut [WalletBalance]) -> Result<()> {
+        let mints = balances
+            .iter()
+            .flat_map(|wb| &wb.tokens)
+            .map(|token| {
+                token
+                    .mint
+                    .parse::<Pubkey>()
+                    .with_context(|| format!("Invalid mint {}", token.mint))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let metadata = self.resolve(client, &mints).await?;
+
+        for token in balances.iter_mut().flat_map(|wb| &mut wb.tokens) {
+            if let Some(found) = token
+                .mint
+                .parse::<Pubkey>()
+                .ok()
+                .and_then(|mint| metadata.get(&mint))
+            {
+                token.name = Some(found.name.clone()).filter(|name| !name.is_empty());
+                token.symbol = Some(found.symbol.clone()).filter(|symbol| !symbol.is_empty());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::WalletBalance;
+use anyhow::{Context, Result};
+use parquet::{
+    data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{
+        properties::WriterProperties,
+        writer::{SerializedColumnWriter, SerializedFileWriter},
+    },
+    schema::parser::parse_message_type,
+};
+use std::{
+    fs::File,
+    path::Path,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// One row per wallet; every row of a snapshot shares `snapshot_ts`, so appending
+/// snapshots of recurring runs to a directory gives a time series readable with
+/// `SELECT * FROM 'snapshots/*.parquet'` in DuckDB.
+const SCHEMA: &str = "
+message balance_snapshot {
+    required int64 snapshot_ts (TIMESTAMP(MILLIS, true));
+    required binary address (STRING);
+    optional binary label (STRING);
+    required double balance_sol;
+    optional binary owner_program (STRING);
+    required boolean is_pda;
+    optional binary cluster (STRING);
+}
+";
+
+/// Write `balances` as a single row group Parquet file at `path`
+pub fn write_snapshot(path: &Path, balances: &[WalletBalance]) -> Result<()> {
+    let schema = Arc::new(parse_message_type(SCHEMA).context("Invalid snapshot schema")?);
+    let file = File::create(path)
+        .with_context(|| format!("Failed to create snapshot file {}", path.display()))?;
+    let mut writer =
+        SerializedFileWriter::new(file, schema, Arc::new(WriterProperties::builder().build()))?;
+
+    let snapshot_ts = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System clock is before the Unix epoch")?
+        .as_millis() as i64;
+
+    let mut row_group = writer.next_row_group()?;
+    let mut column = 0;
+    while let Some(mut column_writer) = row_group.next_column()? {
+        match column {
+            0 => write_values::<Int64Type>(&mut column_writer, vec![snapshot_ts; balances.len()])?,
+            1 => write_values::<ByteArrayType>(
+                &mut column_writer,
+                balances
+                    .iter()
+                    .map(|wb| ByteArray::from(wb.address.as_str()))
+                    .collect(),
+            )?,
+            2 => write_optional(
+                &mut column_writer,
+                balances.iter().map(|wb| wb.label.as_deref()),
+            )?,
+            3 => write_values::<DoubleType>(
+                &mut column_writer,
+                balances.iter().map(|wb| wb.balance).collect(),
+            )?,
+            4 => write_optional(
+                &mut column_writer,
+                balances.iter().map(|wb| wb.owner_program.as_deref()),
+            )?,
+            5 => write_values::<BoolType>(
+                &mut column_writer,
+                balances.iter().map(|wb| wb.is_pda).collect(),
+            )?,
+            _ => write_optional(
+                &mut column_writer,
+                balances.iter().map(|wb| wb.cluster.as_deref()),
+            )?,
+        }
+        column_writer.close()?;
+        column += 1;
+    }
+    row_group.close()?;
+    writer.close().context("Failed to finish snapshot file")?;
+
+    Ok(())
+}
+
+fn write_values<T: parquet::data_type::DataType>(
+    writer: &mut SerializedColumnWriter<'_>,
+    values: Vec<T::T>,
+) -> Result<()> {
+    writer.typed::<T>().write_batch(&values, None, None)?;
+    Ok(())
+}
+
+/// Nullable string column: definition level 1 marks a present value, 0 a null
+fn write_optional<'a>(
+    writer: &mut SerializedColumnWriter<'_>,
+    values: impl Iterator<Item = Option<&'a str>>,
+) -> Result<()> {
+    let mut present = Vec::new();
+    let mut levels = Vec::new();
+    for value in values {
+        levels.push(value.is_some() as i16);
+        present.extend(value.map(ByteArray::from));
+    }
+    writer
+        .typed::<ByteArrayType>()
+        .write_batch(&present, Some(&levels), None)?;
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::BalanceResults;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::HashMap, time::Duration};
+
+/// Mints priced per token_price request
+const MINTS_PER_REQUEST: usize = 50;
+
+/// Where `--prices` looks up USD prices (CoinGecko or an API-compatible proxy)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PriceConfig {
+    pub api_url: String,
+    /// Environment variable holding an API key, sent in `api_key_header`
+    pub api_key_env: Option<String>,
+    pub api_key_header: String,
+    pub timeout_secs: u64,
+}
+
+impl Default for PriceConfig {
+    fn default() -> Self {
+        Self {
+            api_url: "https://api.coingecko.com/api/v3".to_string(),
+            api_key_env: None,
+            api_key_header: "x-cg-demo-api-key".to_string(),
+            timeout_secs: 10,
+        }
+    }
+}
+
+/// USD prices of SOL and of the mints CoinGecko knows
+pub struct Prices {
+    sol_usd: f64,
+    /// Keyed by lowercased mint, since the API may lowercase contract addresses
+    tokens: HashMap<String, f64>,
+}
+
+impl PriceConfig {
+    /// Price SOL and every mint in `mints`; mints without a listing are left out
+    pub async fn fetch(&self, mints: &[String]) -> Result<Prices> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(self.timeout_secs))
+            .build()?;
+
+        let sol = self
+            .get(&client, "simple/price", &[("ids", "solana".to_string())])
+            .await?;
+        let sol_usd = sol["solana"]["usd"]
+            .as_f64()
+            .context("No SOL price in response")?;
+
+        let mut tokens = HashMap::new();
+        for chunk in mints.chunks(MINTS_PER_REQUEST) {
+            let response = self
+                .get(
+                    &client,
+                    "simple/token_price/solana",
+                    &[("contract_addresses", chunk.join(","))],
+                )
+                .await?;
+            if let Some(prices) = response.as_object() {
+                tokens.extend(prices.iter().filter_map(|(mint, price)| {
+                    Some((mint.to_lowercase(), price["usd"].as_f64()?))
+                }));
+            }
+        }
+
+        Ok(Prices { sol_usd, tokens })
+    }
+
+    async fn get(
+        &self,
+        client: &reqwest::Client,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<Value> {
+        let url = format!("{}/{}", self.api_url.trim_end_matches('/'), path);
+        let mut request = client
+            .get(&url)
+            .query(query)
+            .query(&[("vs_currencies", "usd")]);
+        if let Some(var) = &self.api_key_env {
+            let key = std::env::var(var)
+                .with_context(|| format!("Price API key variable {} is not set", var))?;
+            request = request.header(self.api_key_header.as_str(), key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Price lookup at {} failed", url))?;
+        response
+            .json()
+            .await
+            .with_context(|| format!("Invalid price response from {}", url))
+    }
+}
+
+impl Prices {
+    /// Value every wallet's SOL and priced token holdings and the whole portfolio
+    pub fn apply(&self, results: &mut BalanceResults) {
+        let mut total = 0.0;
+        for wallet in &mut results.balances {
+            let mut value = wallet.balance * self.sol_usd;
+            for token in &mut wallet.tokens {
+                let Some(price) = self.tokens.get(&token.mint.to_lowercase()) else {
+                    continue;
+                };
+                let amount = token.raw_amount as f64 / 10f64.powi(token.decimals as i32);
+                let token_value = amount * price;
+                token.usd_value = Some(token_value);
+                value += token_value;
+            }
+            wallet.usd_value = Some(value);
+            total += value;
+        }
+        results.total_usd = Some(total);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::fetcher::{BalanceResults, WalletBalance};
+use crate::stakes::StakeBalance;
+use crate::tokens::{format_amount, TokenAccountState};
+use solana_common::Render;
+
+pub use solana_common::OutputFormat;
+
+/// The report as printed for `--output table`, one line per wallet followed by its tokens
+/// and stake
+pub fn render_balances(results: &BalanceResults) -> String {
+    let mut report = String::from("Wallet Balances:\n");
+    for wb in &results.balances {
+        // Balances of PDAs and program-owned accounts are not freely spendable SOL
+        let mut notes = Vec::new();
+        if wb.is_pda {
+            notes.push("PDA".to_string());
+        }
+        if let Some(owner) = &wb.owner_program {
+            notes.push(format!("owned by program {}", owner));
+        }
+        let mut notes = if notes.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", notes.join(", "))
+        };
+        if let Some(usd) = wb.usd_value {
+            notes.push_str(&format!(" (total ${:.2})", usd));
+        }
+
+        report.push_str(&cluster_prefix(wb.cluster.as_deref()));
+        match &wb.label {
+            Some(label) => report.push_str(&format!(
+                "{} ({}): {} SOL{}\n",
+                label, wb.address, wb.balance, notes
+            )),
+            None => report.push_str(&format!("{}: {} SOL{}\n", wb.address, wb.balance, notes)),
+        }
+        for token in &wb.tokens {
+            let holding = match &token.symbol {
+                Some(symbol) => format!("{}: {}", symbol, token.amount),
+                None => format!("{} {}", token.amount, token.mint),
+            };
+            match token.usd_value {
+                Some(usd) => report.push_str(&format!("    {} (${:.2})\n", holding, usd)),
+                None => report.push_str(&format!("    {}\n", holding)),
+            }
+            for account in token.accounts.iter().filter(|account| account.is_notable()) {
+                report.push_str(&format!(
+                    "        {}\n",
+                    format_account_state(account, token.decimals)
+                ));
+            }
+        }
+        if let Some(stake) = &wb.stake {
+            report.push_str(&format!("    stake: {}\n", format_stake(stake)));
+        }
+    }
+    for wallet in &results.failed {
+        report.push_str(&cluster_prefix(wallet.cluster.as_deref()));
+        match &wallet.label {
+            Some(label) => report.push_str(&format!(
+                "{} ({}): error: {}\n",
+                label, wallet.address, wallet.error
+            )),
+            None => report.push_str(&format!("{}: error: {}\n", wallet.address, wallet.error)),
+        }
+    }
+    for wallet in &results.timed_out {
+        report.push_str(&format!("{}: timed out (run deadline passed)\n", wallet));
+    }
+    if let Some(stake) = &results.total_stake {
+        report.push_str(&format!("Total stake: {}\n", format_stake(stake)));
+    }
+    if let Some(total) = results.total_usd {
+        report.push_str(&format!("Total portfolio value: ${:.2}\n", total));
+    }
+    report
+}
+
+/// `[cluster] ` in front of a wallet line of a multi-cluster report
+pub(crate) fn cluster_prefix(cluster: Option<&str>) -> String {
+    cluster
+        .map(|cluster| format!("[{}] ", cluster))
+        .unwrap_or_default()
+}
+
+fn format_stake(stake: &StakeBalance) -> String {
+    format!(
+        "{} SOL active, {} SOL inactive ({} accounts)",
+        stake.active_lamports as f64 / 1_000_000_000.0,
+        stake.inactive_lamports as f64 / 1_000_000_000.0,
+        stake.accounts.len()
+    )
+}
+
+/// One row per wallet; wallets without a balance have a `status` other than `ok` and empty
+/// balance columns
+impl Render for BalanceResults {
+    fn columns(&self) -> &'static [&'static str] {
+        &[
+            "address",
+            "label",
+            "balance_sol",
+            "owner_program",
+            "is_pda",
+            "usd_value",
+            "active_stake_sol",
+            "inactive_stake_sol",
+            "cluster",
+            "status",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        let mut rows = Vec::new();
+        for wb in &self.balances {
+            rows.push(vec![
+                wb.address.clone(),
+                wb.label.clone().unwrap_or_default(),
+                wb.balance.to_string(),
+                wb.owner_program.clone().unwrap_or_default(),
+                wb.is_pda.to_string(),
+                wb.usd_value
+                    .map(|usd| format!("{:.2}", usd))
+                    .unwrap_or_default(),
+                stake_sol(wb, |stake| stake.active_lamports),
+                stake_sol(wb, |stake| stake.inactive_lamports),
+                wb.cluster.clone().unwrap_or_default(),
+                "ok".to_string(),
+            ]);
+        }
+        for wallet in &self.failed {
+            let mut row = vec![String::new(); 10];
+            row[0] = wallet.address.clone();
+            row[1] = wallet.label.clone().unwrap_or_default();
+            row[8] = wallet.cluster.clone().unwrap_or_default();
+            row[9] = format!("error: {}", wallet.error);
+            rows.push(row);
+        }
+        for wallet in &self.timed_out {
+            let mut row = vec![String::new(); 10];
+            row[0] = wallet.clone();
+            row[9] = "timed_out".to_string();
+            rows.push(row);
+        }
+        rows
+    }
+
+    fn summary(&self) -> Vec<(String, String)> {
+        let mut summary = Vec::new();
+        if let Some(stake) = &self.total_stake {
+            summary.push((
+                "total_active_stake_sol".to_string(),
+                (stake.active_lamports as f64 / 1_000_000_000.0).to_string(),
+            ));
+            summary.push((
+                "total_inactive_stake_sol".to_string(),
+                (stake.inactive_lamports as f64 / 1_000_000_000.0).to_string(),
+            ));
+        }
+        if let Some(total) = self.total_usd {
+            summary.push(("total_usd".to_string(), format!("{:.2}", total)));
+        }
+        summary
+    }
+
+    fn table(&self) -> String {
+        render_balances(self)
+    }
+}
+
+/// Delegate and freeze state of one token account, e.g.
+/// `account 7xKX...: delegate 9WzD... may move 12.5, frozen`
+fn format_account_state(account: &TokenAccountState, decimals: u8) -> String {
+    let mut details = Vec::new();
+    if let Some(delegate) = &account.delegate {
+        details.push(format!(
+            "delegate {} may move {}",
+            delegate,
+            format_amount(account.delegated_amount, decimals)
+        ));
+    }
+    if account.is_frozen() {
+        details.push("frozen".to_string());
+    }
+    format!("account {}: {}", account.address, details.join(", "))
+}
+
+/// One stake total of a wallet in SOL, empty without `--stakes`
+fn stake_sol(wb: &WalletBalance, lamports: impl Fn(&StakeBalance) -> u64) -> String {
+    wb.stake
+        .as_ref()
+        .map(|stake| (lamports(stake) as f64 / 1_000_000_000.0).to_string())
+        .unwrap_or_default()
+}
+
+/// Quote a free-text field when it contains CSV metacharacters
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_common::RpcError;
+use solana_sdk::{
+    pubkey::Pubkey,
+    stake::{self, state::StakeStateV2},
+};
+
+/// Size of a stake account
+const STAKE_ACCOUNT_LEN: u64 = 200;
+
+/// Offset of the withdraw authority in a stake account: enum tag (4 bytes), rent-exempt
+/// reserve (8) and staker (32) come first
+const WITHDRAWER_OFFSET: usize = 44;
+
+/// Activation epoch of stake delegated at genesis, active from the start
+const GENESIS_ACTIVATION: u64 = u64::MAX;
+
+/// Stake accounts a wallet can withdraw from, with their totals
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StakeBalance {
+    pub active_lamports: u64,
+    pub inactive_lamports: u64,
+    /// Empty in the run total
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accounts: Vec<StakeAccount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StakeAccount {
+    pub address: String,
+    pub lamports: u64,
+    pub active_lamports: u64,
+    /// Vote account the stake is delegated to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vote_account: Option<String>,
+}
+
+impl StakeBalance {
+    /// Add another wallet's stake to this total, without its accounts
+    pub fn add(&mut self, other: &StakeBalance) {
+        self.active_lamports += other.active_lamports;
+        self.inactive_lamports += other.inactive_lamports;
+    }
+}
+
+/// Current epoch, needed to tell active from activating or deactivated stake
+pub async fn current_epoch(client: &RpcClient) -> Result<u64, RpcError> {
+    client
+        .get_epoch_info()
+        .await
+        .map(|info| info.epoch)
+        .map_err(|e| RpcError::request("getEpochInfo", &client.url(), e))
+}
+
+/// Stake accounts whose withdraw authority is `owner`. Delegated stake counts as active from
+/// the epoch after activation through its deactivation epoch; warmup and cooldown rate
+/// limits are not modelled, and everything else (rent reserve, undelegated or cooled-down
+/// lamports) is inactive.
+pub async fn stake_balance(client: &RpcClient, owner: &Pubkey, epoch: u64) -> Result<StakeBalance> {
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![
+            RpcFilterType::DataSize(STAKE_ACCOUNT_LEN),
+            RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                WITHDRAWER_OFFSET,
+                owner.as_ref(),
+            )),
+        ]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(client.commitment()),
+            ..RpcAccountInfoConfig::default()
+        },
+        ..RpcProgramAccountsConfig::default()
+    };
+    let accounts = client
+        .get_program_accounts_with_config(&stake::program::id(), config)
+        .await
+        .map_err(|e| RpcError::request("getProgramAccounts", &client.url(), e))?;
+
+    let mut balance = StakeBalance::default();
+    for (address, account) in accounts {
+        let state: StakeStateV2 = account
+            .deserialize_data()
+            .with_context(|| format!("Invalid stake account {}", address))?;
+        let delegation = state.delegation();
+        let active_lamports = delegation
+            .filter(|delegation| {
+                (delegation.activation_epoch < epoch
+                    || delegation.activation_epoch == GENESIS_ACTIVATION)
+                    && epoch <= delegation.deactivation_epoch
+            })
+            .map_or(0, |delegation| delegation.stake)
+            .min(account.lamports);
+
+        balance.active_lamports += active_lamports;
+        balance.inactive_lamports += account.lamports - active_lamports;
+        balance.accounts.push(StakeAccount {
+            address: address.to_string(),
+            lamports: account.lamports,
+            active_lamports,
+            vote_account: delegation.map(|delegation| delegation.voter_pubkey.to_string()),
+        });
+    }
+    Ok(balance)
+}
@@ -0,0 +1,152 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use solana_account_decoder::{parse_token::UiTokenAmount, UiAccountData};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::TokenAccountsFilter};
+use solana_common::RpcError;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeMap;
+
+/// Programs whose token accounts are reported: SPL Token and Token-2022
+const TOKEN_PROGRAMS: [Pubkey; 2] = [
+    solana_sdk::pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+    solana_sdk::pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"),
+];
+
+/// Holdings of one mint summed over all of a wallet's token accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenBalance {
+    pub mint: String,
+    /// Symbol and name from the mint's Metaplex metadata, when it has any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Amount in base units
+    pub raw_amount: u128,
+    pub decimals: u8,
+    /// `raw_amount` scaled by the mint's decimals
+    pub amount: String,
+    /// Only set with `--prices`, for mints with a known price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usd_value: Option<f64>,
+    /// The wallet's non-empty token accounts of the mint, with their approvals and state
+    #[serde(default)]
+    pub accounts: Vec<TokenAccountState>,
+}
+
+/// One token account behind a [`TokenBalance`], for spotting risky approvals and freezes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenAccountState {
+    pub address: String,
+    pub raw_amount: u128,
+    /// Wallet allowed to transfer up to `delegated_amount` out of the account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delegate: Option<String>,
+    /// Base units the delegate may still transfer
+    pub delegated_amount: u128,
+    /// `initialized`, or `frozen` when the mint's freeze authority froze the account
+    pub state: String,
+}
+
+impl TokenAccountState {
+    pub fn is_frozen(&self) -> bool {
+        self.state == "frozen"
+    }
+
+    /// Worth a line in the report: the account has a delegate or is frozen
+    pub fn is_notable(&self) -> bool {
+        self.delegate.is_some() || self.is_frozen()
+    }
+}
+
+/// The parts of a jsonParsed token account read here; the full `UiTokenAccount` would also
+/// reject Token-2022 extensions newer than this client
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ParsedTokenAccount {
+    mint: String,
+    token_amount: UiTokenAmount,
+    #[serde(default)]
+    delegate: Option<String>,
+    #[serde(default)]
+    delegated_amount: Option<UiTokenAmount>,
+    state: String,
+}
+
+/// Token balances of `owner` per mint, skipping empty token accounts. Decimals come from
+/// the jsonParsed token amount, which the node resolves from the mint account.
+pub async fn token_balances(client: &RpcClient, owner: &Pubkey) -> Result<Vec<TokenBalance>> {
+    let mut by_mint: BTreeMap<String, (u128, u8, Vec<TokenAccountState>)> = BTreeMap::new();
+    for program in TOKEN_PROGRAMS {
+        let accounts = client
+            .get_token_accounts_by_owner(owner, TokenAccountsFilter::ProgramId(program))
+            .await
+            .map_err(|e| RpcError::request("getTokenAccountsByOwner", &client.url(), e))?;
+
+        for account in accounts {
+            let UiAccountData::Json(data) = account.account.data else {
+                anyhow::bail!("Token account {} is not jsonParsed", account.pubkey);
+            };
+            let token: ParsedTokenAccount = serde_json::from_value(data.parsed["info"].clone())
+                .with_context(|| format!("Invalid token account {}", account.pubkey))?;
+            let raw_amount =
+                token.token_amount.amount.parse::<u128>().with_context(|| {
+                    format!("Invalid amount in token account {}", account.pubkey)
+                })?;
+
+            if raw_amount == 0 {
+                continue;
+            }
+            let delegated_amount = match &token.delegated_amount {
+                Some(delegated) => delegated.amount.parse::<u128>().with_context(|| {
+                    format!(
+                        "Invalid delegated amount in token account {}",
+                        account.pubkey
+                    )
+                })?,
+                None => 0,
+            };
+            let entry =
+                by_mint
+                    .entry(token.mint)
+                    .or_insert((0, token.token_amount.decimals, Vec::new()));
+            entry.0 += raw_amount;
+            entry.2.push(TokenAccountState {
+                address: account.pubkey,
+                raw_amount,
+                delegate: token.delegate,
+                delegated_amount,
+                state: token.state,
+            });
+        }
+    }
+
+    Ok(by_mint
+        .into_iter()
+        .map(|(mint, (raw_amount, decimals, accounts))| TokenBalance {
+            mint,
+            symbol: None,
+            name: None,
+            raw_amount,
+            decimals,
+            amount: format_amount(raw_amount, decimals),
+            usd_value: None,
+            accounts,
+        })
+        .collect())
+}
+
+/// Exact decimal rendering of `raw` base units, without trailing zeros
+pub fn format_amount(raw: u128, decimals: u8) -> String {
+    if decimals == 0 {
+        return raw.to_string();
+    }
+    let digits = format!("{:0>width$}", raw, width = decimals as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals as usize);
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{alerts::Alerting, OutputFormat};
+use anyhow::{Context, Result};
+use futures::stream::{select_all, StreamExt};
+use serde::Serialize;
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::{nonblocking::pubsub_client::PubsubClient, rpc_config::RpcAccountInfoConfig};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use std::{str::FromStr, time::Duration};
+
+/// Pause before reconnecting after the WebSocket connection drops
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// A wallet being watched and its last known balance, if the initial fetch succeeded
+pub struct WatchedWallet {
+    pub address: Pubkey,
+    pub label: Option<String>,
+    pub lamports: Option<u64>,
+}
+
+/// One balance change pushed by the node
+#[derive(Debug, Serialize)]
+struct BalanceChange<'a> {
+    timestamp: String,
+    slot: u64,
+    address: String,
+    label: Option<&'a str>,
+    lamports: u64,
+    delta_lamports: Option<i128>,
+}
+
+/// Subscribe to every wallet over `ws_url` and print each balance change until Ctrl-C,
+/// reconnecting whenever the connection drops. Changes missed while disconnected show up
+/// as one combined delta with the next notification. With `alerting`, a change that moves a
+/// wallet across one of its thresholds is alerted on.
+pub async fn watch_balances(
+    ws_url: &str,
+    wallets: &mut [WatchedWallet],
+    commitment: Option<&str>,
+    format: OutputFormat,
+    alerting: Option<&Alerting>,
+) -> Result<()> {
+    let commitment = commitment
+        .map(CommitmentConfig::from_str)
+        .transpose()
+        .context("Invalid commitment")?;
+    let config = RpcAccountInfoConfig {
+        // Only lamports matter, so skip the account data
+        encoding: Some(UiAccountEncoding::Base64),
+        data_slice: Some(UiDataSliceConfig {
+            offset: 0,
+            length: 0,
+        }),
+        commitment,
+        min_context_slot: None,
+    };
+
+    match format {
+        OutputFormat::Csv => println!("timestamp,slot,address,label,lamports,delta_lamports"),
+        OutputFormat::Markdown => {
+            println!("| timestamp | slot | address | label | lamports | delta_lamports |");
+            println!("| --- | --- | --- | --- | --- | --- |");
+        }
+        _ => {}
+    }
+
+    loop {
+        tokio::select! {
+            result = watch_connection(ws_url, wallets, &config, format, alerting) => {
+                if let Err(e) = result {
+                    eprintln!("Watch connection to {} lost: {:#}", ws_url, e);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+/// Run one connection until its subscriptions end
+async fn watch_connection(
+    ws_url: &str,
+    wallets: &mut [WatchedWallet],
+    config: &RpcAccountInfoConfig,
+    format: OutputFormat,
+    alerting: Option<&Alerting>,
+) -> Result<()> {
+    let client = PubsubClient::new(ws_url)
+        .await
+        .with_context(|| format!("Failed to connect to {}", ws_url))?;
+
+    let mut streams = Vec::with_capacity(wallets.len());
+    for (index, wallet) in wallets.iter().enumerate() {
+        let (stream, _unsubscribe) = client
+            .account_subscribe(&wallet.address, Some(config.clone()))
+            .await
+            .with_context(|| format!("Failed to subscribe to {}", wallet.address))?;
+        streams.push(stream.map(move |update| (index, update)));
+    }
+    eprintln!("Watching {} wallets via {}", wallets.len(), ws_url);
+
+    let mut updates = select_all(streams);
+    while let Some((index, update)) = updates.next().await {
+        let wallet = &mut wallets[index];
+        let lamports = update.value.lamports;
+        if wallet.lamports == Some(lamports) {
+            continue;
+        }
+
+        let change = BalanceChange {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            slot: update.context.slot,
+            address: wallet.address.to_string(),
+            label: wallet.label.as_deref(),
+            lamports,
+            delta_lamports: wallet
+                .lamports
+                .map(|previous| lamports as i128 - previous as i128),
+        };
+        print_change(&change, format)?;
+
+        if let Some(alerting) = alerting {
+            let alert = alerting
+                .crossed(&change.address, wallet.lamports, lamports)
+                .then(|| alerting.check(&change.address, change.label, lamports))
+                .flatten();
+            // Delivery retries must not hold up the stream
+            if let Some(alert) = alert {
+                let alerting = alerting.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = alerting.send(&[alert]).await {
+                        eprintln!("{:#}", e);
+                    }
+                });
+            }
+        }
+        wallet.lamports = Some(lamports);
+    }
+
+    anyhow::bail!("subscriptions closed by the node")
+}
+
+fn print_change(change: &BalanceChange, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Table => {
+            let name = match change.label {
+                Some(label) => format!("{} ({})", label, change.address),
+                None => change.address.clone(),
+            };
+            let delta = match change.delta_lamports {
+                Some(delta) => format!("{:+} SOL", delta as f64 / 1_000_000_000.0),
+                None => "previous balance unknown".to_string(),
+            };
+            println!(
+                "{} slot {} {}: {} SOL ({})",
+                change.timestamp,
+                change.slot,
+                name,
+                change.lamports as f64 / 1_000_000_000.0,
+                delta
+            );
+        }
+        // One object per line, so the stream can be piped into jq and friends
+        OutputFormat::Json | OutputFormat::Ndjson => {
+            println!("{}", serde_json::to_string(change)?)
+        }
+        OutputFormat::Csv => println!(
+            "{},{},{},{},{},{}",
+            change.timestamp,
+            change.slot,
+            change.address,
+            crate::csv_field(change.label.unwrap_or_default()),
+            change.lamports,
+            change
+                .delta_lamports
+                .map(|delta| delta.to_string())
+                .unwrap_or_default()
+        ),
+        OutputFormat::Markdown => println!(
+            "| {} | {} | {} | {} | {} | {} |",
+            change.timestamp,
+            change.slot,
+            change.address,
+            change.label.unwrap_or_default().replace('|', "\\|"),
+            change.lamports,
+            change
+                .delta_lamports
+                .map(|delta| delta.to_string())
+                .unwrap_or_default()
+        ),
+    }
+    Ok(())
+}
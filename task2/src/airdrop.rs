@@ -0,0 +1,472 @@
+use crate::{
+    plan::{parse_units, PlanError, RowError},
+    token::{TokenError, TokenMint},
+};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_common::{ComputeBudget, ConfigError, RpcError, Submitter};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use thiserror::Error;
+
+/// Pause between confirmation checks (and rebroadcasts) of an airdrop batch
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Error)]
+pub enum AirdropError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Claims(#[from] PlanError),
+
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error(transparent)]
+    Token(#[from] TokenError),
+
+    #[error("Claims ledger {} tracks mint {ledger}, not {requested}", path.display())]
+    MintMismatch {
+        path: PathBuf,
+        ledger: String,
+        requested: String,
+    },
+}
+
+impl AirdropError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AirdropError::Config(e) => e.code(),
+            AirdropError::Claims(e) => e.code(),
+            AirdropError::Rpc(e) => e.code(),
+            AirdropError::Token(e) => e.code(),
+            AirdropError::MintMismatch { .. } => "AIRDROP_MINT_MISMATCH",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClaimStatus {
+    /// Not sent yet, or a previous attempt expired or failed
+    Pending,
+    /// Broadcast; the outcome is unknown until the blockhash expires
+    Sent,
+    Confirmed,
+    Failed,
+}
+
+/// Progress of one recipient's claim
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimRecord {
+    /// Amount in base units of the mint
+    pub amount: u64,
+    pub status: ClaimStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Block height after which the sent transaction can no longer land
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_valid_block_height: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Claims of an airdrop, persisted after every change so an interrupted run resumes
+/// where it stopped without paying anyone twice
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ClaimLedger {
+    pub mint: String,
+    pub claims: BTreeMap<String, ClaimRecord>,
+    /// File the ledger is saved to
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaimRow {
+    address: String,
+    amount: String,
+}
+
+/// Claim counts after a run
+#[derive(Debug, Default)]
+pub struct AirdropSummary {
+    pub confirmed: usize,
+    pub pending: usize,
+    pub failed: usize,
+}
+
+impl ClaimLedger {
+    /// Load the ledger at `path`, or start an empty one for `mint` when it does not exist
+    pub fn open(path: &Path, mint: &Pubkey) -> Result<Self, AirdropError> {
+        if !path.exists() {
+            return Ok(Self {
+                mint: mint.to_string(),
+                claims: BTreeMap::new(),
+                path: path.to_path_buf(),
+            });
+        }
+
+        let file = File::open(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut ledger: Self =
+            serde_yaml::from_reader(file).map_err(|source| ConfigError::Parse {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        if ledger.mint != mint.to_string() {
+            return Err(AirdropError::MintMismatch {
+                path: path.to_path_buf(),
+                ledger: ledger.mint,
+                requested: mint.to_string(),
+            });
+        }
+        ledger.path = path.to_path_buf();
+        Ok(ledger)
+    }
+
+    /// Write the ledger atomically, so a crash mid-write never loses claim state
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = &self.path;
+        let temp = path.with_extension("tmp");
+        let file = File::create(&temp).map_err(|source| ConfigError::Write {
+            path: temp.clone(),
+            source,
+        })?;
+        serde_yaml::to_writer(file, self).map_err(|source| ConfigError::Serialize {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        std::fs::rename(&temp, path).map_err(|source| ConfigError::Write {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Add the `address,amount` rows of a claims CSV (amounts in whole tokens) as pending
+    /// claims. Recipients already in the ledger keep their recorded state.
+    pub fn import_csv(&mut self, path: &Path, decimals: u8) -> Result<usize, PlanError> {
+        let csv_error = |source| PlanError::Csv {
+            path: path.to_path_buf(),
+            source,
+        };
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)
+            .map_err(csv_error)?;
+
+        let headers = reader.headers().map_err(csv_error)?.clone();
+
+        let mut rows = Vec::new();
+        let mut errors = Vec::new();
+        for record in reader.records() {
+            let record = record.map_err(csv_error)?;
+            let line = record.position().map_or(0, |p| p.line());
+
+            let result = record
+                .deserialize::<ClaimRow>(Some(&headers))
+                .map_err(|e| e.to_string())
+                .and_then(|row| {
+                    let address = Pubkey::from_str(&row.address)
+                        .map_err(|_| format!("invalid address '{}'", row.address))?;
+                    let amount = parse_units(&row.amount, decimals)
+                        .filter(|&amount| amount > 0)
+                        .ok_or_else(|| format!("invalid amount '{}'", row.amount))?;
+                    Ok((address, amount))
+                });
+            match result {
+                Ok(row) => rows.push(row),
+                Err(reason) => errors.push(RowError { line, reason }),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(PlanError::InvalidRows(errors));
+        }
+        if rows.is_empty() {
+            return Err(PlanError::Empty);
+        }
+
+        let mut added = 0;
+        for (address, amount) in rows {
+            self.claims.entry(address.to_string()).or_insert_with(|| {
+                added += 1;
+                ClaimRecord {
+                    amount,
+                    status: ClaimStatus::Pending,
+                    signature: None,
+                    last_valid_block_height: None,
+                    error: None,
+                }
+            });
+        }
+        Ok(added)
+    }
+
+    fn summary(&self) -> AirdropSummary {
+        let mut summary = AirdropSummary::default();
+        for claim in self.claims.values() {
+            match claim.status {
+                ClaimStatus::Confirmed => summary.confirmed += 1,
+                ClaimStatus::Failed => summary.failed += 1,
+                ClaimStatus::Pending | ClaimStatus::Sent => summary.pending += 1,
+            }
+        }
+        summary
+    }
+}
+
+/// Decimals and owning token program of `mint`
+pub fn mint_info(client: &RpcClient, mint: &Pubkey) -> Result<(u8, Pubkey), AirdropError> {
+    let token = TokenMint::fetch(client, mint)?;
+    Ok((token.decimals, token.token_program))
+}
+
+/// Pay every pending claim in batches of `batch_size` recipients per transaction,
+/// creating missing associated token accounts on the way. Claims sent by an earlier
+/// run are only retried once their blockhash has expired without them landing.
+pub async fn run_airdrop(
+    client: &RpcClient,
+    payer: &Keypair,
+    mint: &Pubkey,
+    ledger: &mut ClaimLedger,
+    batch_size: usize,
+    budget: &ComputeBudget,
+    submitter: &Submitter,
+) -> Result<AirdropSummary, AirdropError> {
+    let (decimals, token_program) = mint_info(client, mint)?;
+    let source_ata =
+        get_associated_token_address_with_program_id(&payer.pubkey(), mint, &token_program);
+
+    settle_sent_claims(client, ledger)?;
+    ledger.save()?;
+
+    let pending: Vec<(String, u64)> = ledger
+        .claims
+        .iter()
+        .filter(|(_, claim)| matches!(claim.status, ClaimStatus::Pending | ClaimStatus::Failed))
+        .map(|(address, claim)| (address.clone(), claim.amount))
+        .collect();
+
+    for batch in pending.chunks(batch_size.max(1)) {
+        let mut instructions = Vec::with_capacity(batch.len() * 2);
+        for (address, amount) in batch {
+            let recipient = Pubkey::from_str(address).expect("ledger addresses are validated");
+            instructions.extend(claim_instructions(
+                &payer.pubkey(),
+                &source_ata,
+                &recipient,
+                mint,
+                &token_program,
+                *amount,
+                decimals,
+            ));
+        }
+        let instructions = budget.apply(client, &payer.pubkey(), instructions)?;
+
+        let (blockhash, last_valid_block_height) = client
+            .get_latest_blockhash_with_commitment(CommitmentConfig::confirmed())
+            .map_err(|e| RpcError::request("getLatestBlockhash", &client.url(), e))?;
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[payer],
+            blockhash,
+        );
+        let signature = transaction.signatures[0];
+
+        // Record the attempt before broadcasting so a crash can never hide a sent batch
+        for (address, _) in batch {
+            let claim = ledger
+                .claims
+                .get_mut(address)
+                .expect("pending claim is in the ledger");
+            claim.status = ClaimStatus::Sent;
+            claim.signature = Some(signature.to_string());
+            claim.last_valid_block_height = Some(last_valid_block_height);
+            claim.error = None;
+        }
+        ledger.save()?;
+
+        if let Err(e) = submitter.send(client, &transaction).await {
+            eprintln!("Airdrop batch {} was not accepted: {}", signature, e);
+        }
+        let outcome =
+            await_outcome(client, submitter, &transaction, last_valid_block_height).await?;
+
+        for (address, _) in batch {
+            let claim = ledger
+                .claims
+                .get_mut(address)
+                .expect("pending claim is in the ledger");
+            match &outcome {
+                BatchOutcome::Confirmed => claim.status = ClaimStatus::Confirmed,
+                BatchOutcome::Failed(reason) => {
+                    claim.status = ClaimStatus::Failed;
+                    claim.error = Some(reason.clone());
+                }
+                BatchOutcome::Expired => claim.status = ClaimStatus::Pending,
+            }
+        }
+        ledger.save()?;
+
+        println!(
+            "Airdrop batch {} ({} recipients): {}",
+            signature,
+            batch.len(),
+            match &outcome {
+                BatchOutcome::Confirmed => "confirmed".to_string(),
+                BatchOutcome::Failed(reason) => format!("failed: {}", reason),
+                BatchOutcome::Expired => "expired, will be retried".to_string(),
+            }
+        );
+    }
+
+    Ok(ledger.summary())
+}
+
+/// Create the recipient's associated token account if needed (paid by `payer`) and
+/// transfer `amount` base units to it
+fn claim_instructions(
+    payer: &Pubkey,
+    source_ata: &Pubkey,
+    recipient: &Pubkey,
+    mint: &Pubkey,
+    token_program: &Pubkey,
+    amount: u64,
+    decimals: u8,
+) -> Vec<Instruction> {
+    let destination_ata =
+        get_associated_token_address_with_program_id(recipient, mint, token_program);
+    vec![
+        create_associated_token_account_idempotent(payer, recipient, mint, token_program),
+        spl_token::instruction::transfer_checked(
+            token_program,
+            source_ata,
+            mint,
+            &destination_ata,
+            payer,
+            &[],
+            amount,
+            decimals,
+        )
+        .expect("token program id is valid for transfer_checked"),
+    ]
+}
+
+enum BatchOutcome {
+    Confirmed,
+    Failed(String),
+    /// The blockhash expired without the transaction landing; safe to resend
+    Expired,
+}
+
+/// Poll the batch until it is confirmed, fails, or can no longer land, rebroadcasting
+/// the same signed transaction meanwhile
+async fn await_outcome(
+    client: &RpcClient,
+    submitter: &Submitter,
+    transaction: &Transaction,
+    last_valid_block_height: u64,
+) -> Result<BatchOutcome, RpcError> {
+    let signature = transaction.signatures[0];
+    loop {
+        if let Some(outcome) = signature_outcome(client, &signature, last_valid_block_height)? {
+            return Ok(outcome);
+        }
+        tokio::time::sleep(CONFIRM_POLL_INTERVAL).await;
+        if let Err(e) = submitter.send(client, transaction).await {
+            eprintln!("Rebroadcast of {} failed: {}", signature, e);
+        }
+    }
+}
+
+/// Final outcome of `signature`, or None while it may still land
+fn signature_outcome(
+    client: &RpcClient,
+    signature: &Signature,
+    last_valid_block_height: u64,
+) -> Result<Option<BatchOutcome>, RpcError> {
+    let status = client
+        .get_signature_statuses_with_history(&[*signature])
+        .map_err(|e| RpcError::request("getSignatureStatuses", &client.url(), e))?
+        .value
+        .into_iter()
+        .next()
+        .flatten();
+
+    if let Some(status) = status {
+        if let Some(err) = status.err {
+            return Ok(Some(BatchOutcome::Failed(err.to_string())));
+        }
+        if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+            return Ok(Some(BatchOutcome::Confirmed));
+        }
+        return Ok(None);
+    }
+
+    let block_height = client
+        .get_block_height_with_commitment(CommitmentConfig::confirmed())
+        .map_err(|e| RpcError::request("getBlockHeight", &client.url(), e))?;
+    Ok((block_height > last_valid_block_height).then_some(BatchOutcome::Expired))
+}
+
+/// Resolve claims left in `Sent` by an earlier run: landed ones become confirmed or
+/// failed, expired ones pending again; those that may still land are left alone
+fn settle_sent_claims(client: &RpcClient, ledger: &mut ClaimLedger) -> Result<(), RpcError> {
+    let mut outcomes: BTreeMap<String, Option<BatchOutcome>> = BTreeMap::new();
+    for claim in ledger.claims.values() {
+        if claim.status != ClaimStatus::Sent {
+            continue;
+        }
+        let (Some(signature), Some(last_valid)) = (&claim.signature, claim.last_valid_block_height)
+        else {
+            continue;
+        };
+        if outcomes.contains_key(signature) {
+            continue;
+        }
+        let outcome = match Signature::from_str(signature) {
+            Ok(parsed) => signature_outcome(client, &parsed, last_valid)?,
+            Err(_) => Some(BatchOutcome::Expired),
+        };
+        outcomes.insert(signature.clone(), outcome);
+    }
+
+    for claim in ledger.claims.values_mut() {
+        let Some(Some(outcome)) = claim.signature.as_ref().and_then(|s| outcomes.get(s)) else {
+            continue;
+        };
+        if claim.status != ClaimStatus::Sent {
+            continue;
+        }
+        match outcome {
+            BatchOutcome::Confirmed => claim.status = ClaimStatus::Confirmed,
+            BatchOutcome::Failed(reason) => {
+                claim.status = ClaimStatus::Failed;
+                claim.error = Some(reason.clone());
+            }
+            BatchOutcome::Expired => claim.status = ClaimStatus::Pending,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,144 @@
+use crate::plan::{PlannedTransfer, TransferKind};
+use serde::Deserialize;
+use solana_client::{rpc_client::RpcClient, rpc_request::MAX_MULTIPLE_ACCOUNTS};
+use solana_common::{AddressBook, ConfigError, RpcError};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Current lamports of `accounts`, with missing accounts at zero
+pub fn fetch_balances(
+    client: &RpcClient,
+    accounts: &[Pubkey],
+) -> Result<HashMap<Pubkey, u64>, RpcError> {
+    let mut balances = HashMap::with_capacity(accounts.len());
+    for chunk in accounts.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let fetched = client
+            .get_multiple_accounts(chunk)
+            .map_err(|e| RpcError::request("getMultipleAccounts", &client.url(), e))?;
+        for (pubkey, account) in chunk.iter().zip(fetched) {
+            balances.insert(*pubkey, account.map_or(0, |account| account.lamports));
+        }
+    }
+    Ok(balances)
+}
+
+/// A transfer left out because its destination is already funded
+#[derive(Debug, Clone)]
+pub struct SkippedTransfer {
+    pub transfer: PlannedTransfer,
+    pub destination_lamports: u64,
+}
+
+/// Drop transfers whose destination already holds more than `threshold` lamports.
+/// Destinations that cannot be resolved are kept so the send reports the error.
+pub fn skip_funded_destinations(
+    client: &RpcClient,
+    book: &AddressBook,
+    transfers: Vec<PlannedTransfer>,
+    threshold: u64,
+) -> Result<(Vec<PlannedTransfer>, Vec<SkippedTransfer>), RpcError> {
+    let mut destinations: Vec<Pubkey> = transfers
+        .iter()
+        .filter_map(|transfer| book.resolve(&transfer.destination).ok())
+        .collect();
+    destinations.sort_unstable();
+    destinations.dedup();
+    let balances = fetch_balances(client, &destinations)?;
+
+    let mut kept = Vec::with_capacity(transfers.len());
+    let mut skipped = Vec::new();
+    for transfer in transfers {
+        let balance = book
+            .resolve(&transfer.destination)
+            .ok()
+            .and_then(|destination| balances.get(&destination).copied());
+        match balance {
+            Some(balance) if balance > threshold => skipped.push(SkippedTransfer {
+                transfer,
+                destination_lamports: balance,
+            }),
+            _ => kept.push(transfer),
+        }
+    }
+    Ok((kept, skipped))
+}
+
+/// Bring every destination up to a target balance, sending only the missing lamports
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopUp {
+    /// Funding wallet (alias or address); may be omitted when the config has a single source
+    #[serde(default)]
+    pub source: Option<String>,
+    pub targets: Vec<TopUpTarget>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopUpTarget {
+    /// Destination wallet (alias or address)
+    pub destination: String,
+    pub target_lamports: u64,
+}
+
+#[derive(Debug, Error)]
+pub enum TopUpError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error("Top-up has no source and the config has more than one source wallet")]
+    AmbiguousSource,
+}
+
+impl TopUpError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TopUpError::Config(e) => e.code(),
+            TopUpError::Rpc(e) => e.code(),
+            TopUpError::AmbiguousSource => "TOP_UP_AMBIGUOUS_SOURCE",
+        }
+    }
+}
+
+impl TopUp {
+    /// One transfer of `target - current` per destination below its target, paid from
+    /// `source` or else the only entry of `sources`
+    pub fn plan(
+        &self,
+        client: &RpcClient,
+        book: &AddressBook,
+        sources: &[String],
+    ) -> Result<Vec<PlannedTransfer>, TopUpError> {
+        let source = match (&self.source, sources) {
+            (Some(source), _) => source.clone(),
+            (None, [source]) => source.clone(),
+            (None, _) => return Err(TopUpError::AmbiguousSource),
+        };
+
+        let destinations = self
+            .targets
+            .iter()
+            .map(|target| book.resolve(&target.destination))
+            .collect::<Result<Vec<_>, _>>()?;
+        let balances = fetch_balances(client, &destinations)?;
+
+        Ok(self
+            .targets
+            .iter()
+            .zip(&destinations)
+            .filter_map(|(target, destination)| {
+                let current = balances.get(destination).copied().unwrap_or_default();
+                let missing = target.target_lamports.saturating_sub(current);
+                (missing > 0).then(|| PlannedTransfer {
+                    source: source.clone(),
+                    destination: destination.to_string(),
+                    amount_lamports: missing,
+                    memo: None,
+                    kind: TransferKind::System,
+                })
+            })
+            .collect())
+    }
+}
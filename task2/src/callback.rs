@@ -0,0 +1,176 @@
+use crate::{failure::FailureClass, transfer::TransferError};
+use serde::Serialize;
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_common::RpcError;
+use solana_sdk::{signature::Signature, transaction::TransactionError};
+use solana_transaction_status::UiTransactionEncoding;
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// How long to wait for a sent transfer to confirm before reporting it unconfirmed; a
+/// blockhash stays valid for roughly this long, so later confirmation is unlikely
+pub const RECEIPT_WAIT: Duration = Duration::from_secs(90);
+
+const RECEIPT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Final status of a transfer, as posted to the `notifications.webhook` endpoint
+#[derive(Debug, Serialize)]
+pub struct TransferCallback {
+    pub source: String,
+    pub destination: String,
+    pub lamports: u64,
+    pub signature: Option<String>,
+    pub status: CallbackStatus,
+    /// Slot the transaction landed in
+    pub slot: Option<u64>,
+    pub fee_lamports: Option<u64>,
+    pub error: Option<String>,
+    pub failure: Option<FailureClass>,
+    /// Hash of the signed run manifest that approved the transfer
+    pub manifest: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallbackStatus {
+    Confirmed,
+    /// Rejected before sending, or landed with an error (then `slot` and `fee_lamports`
+    /// are set: failed transactions still pay their fee)
+    Failed,
+    /// Not confirmed before the transfer deadline; it may still land
+    Abandoned,
+    /// Sent, but not confirmed within [`RECEIPT_WAIT`]
+    Unconfirmed,
+}
+
+/// Where and at what cost a transaction landed
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    pub slot: u64,
+    pub fee_lamports: u64,
+    pub error: Option<TransactionError>,
+}
+
+/// Wait up to `wait` for `signature` to reach the client's commitment and read its slot,
+/// fee and error; `None` if it has not landed by then
+pub async fn transfer_receipt(
+    client: &RpcClient,
+    signature: &Signature,
+    wait: Duration,
+) -> Result<Option<Receipt>, RpcError> {
+    let start = Instant::now();
+    loop {
+        let status = client
+            .get_signature_statuses(&[*signature])
+            .map_err(|e| RpcError::request("getSignatureStatuses", &client.url(), e))?
+            .value
+            .into_iter()
+            .next()
+            .flatten();
+
+        if status.is_some_and(|status| status.satisfies_commitment(client.commitment())) {
+            break;
+        }
+        if start.elapsed() >= wait {
+            return Ok(None);
+        }
+        tokio::time::sleep(RECEIPT_POLL_INTERVAL).await;
+    }
+
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        commitment: Some(client.commitment()),
+        max_supported_transaction_version: Some(0),
+    };
+    let transaction = client
+        .get_transaction_with_config(signature, config)
+        .map_err(|e| RpcError::request("getTransaction", &client.url(), e))?;
+    let meta = transaction.transaction.meta;
+
+    Ok(Some(Receipt {
+        slot: transaction.slot,
+        fee_lamports: meta.as_ref().map_or(0, |meta| meta.fee),
+        error: meta.and_then(|meta| meta.err),
+    }))
+}
+
+impl TransferCallback {
+    /// Final status of a transfer from its send result, waiting for sent transactions to
+    /// land so the slot and fee can be reported
+    pub async fn resolve(
+        client: &RpcClient,
+        result: &Result<(String, u128), TransferError>,
+        source: String,
+        destination: String,
+        lamports: u64,
+        manifest: Option<String>,
+    ) -> Self {
+        let mut callback = TransferCallback {
+            source,
+            destination,
+            lamports,
+            signature: None,
+            status: CallbackStatus::Failed,
+            slot: None,
+            fee_lamports: None,
+            error: None,
+            failure: None,
+            manifest,
+        };
+
+        let signature = match result {
+            Ok((signature, _)) | Err(TransferError::Failed { signature, .. }) => signature,
+            Err(e) => {
+                match e {
+                    TransferError::Abandoned { signature, .. }
+                    | TransferError::Unsettled { signature, .. } => {
+                        callback.signature = Some(signature.clone());
+                        callback.status = CallbackStatus::Abandoned;
+                    }
+                    // Sent, but it can no longer land
+                    TransferError::Expired { signature } => {
+                        callback.signature = Some(signature.clone());
+                    }
+                    _ => {}
+                }
+                callback.error = Some(e.to_string());
+                callback.failure = Some(e.failure_class());
+                return callback;
+            }
+        };
+        callback.signature = Some(signature.clone());
+
+        let receipt = match Signature::from_str(signature) {
+            Ok(signature) => transfer_receipt(client, &signature, RECEIPT_WAIT).await,
+            Err(e) => {
+                callback.error = Some(format!("Invalid signature: {}", e));
+                return callback;
+            }
+        };
+        match receipt {
+            Ok(Some(receipt)) => {
+                callback.slot = Some(receipt.slot);
+                callback.fee_lamports = Some(receipt.fee_lamports);
+                match receipt.error {
+                    Some(error) => {
+                        callback.failure = Some(FailureClass::from_transaction_error(&error));
+                        callback.error = Some(error.to_string());
+                    }
+                    None => callback.status = CallbackStatus::Confirmed,
+                }
+            }
+            Ok(None) => {
+                callback.status = CallbackStatus::Unconfirmed;
+                callback.failure = Some(FailureClass::Unconfirmed);
+            }
+            // The transaction was sent; only its receipt is missing
+            Err(e) => {
+                callback.status = CallbackStatus::Unconfirmed;
+                callback.error = Some(e.to_string());
+            }
+        }
+        callback
+    }
+}
@@ -0,0 +1,303 @@
+use serde::Deserialize;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::sync::{Notify, Semaphore, SemaphorePermit};
+
+/// Bounds and latency target of the adaptive concurrency controller
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveConcurrencyConfig {
+    /// Sends in flight at the start of a run
+    pub initial: usize,
+    pub min: usize,
+    pub max: usize,
+    /// Sends completing faster than this let the limit grow; slower ones hold it
+    pub target_latency_ms: u64,
+}
+
+impl Default for AdaptiveConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            initial: 4,
+            min: 1,
+            max: 64,
+            target_latency_ms: 2_000,
+        }
+    }
+}
+
+/// How a send ended, as far as the controller is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendOutcome {
+    /// Completed (successfully or with an error unrelated to load)
+    Completed,
+    /// Rate limited (429) or the blockhash expired before landing: the cluster or the
+    /// endpoint is overloaded
+    Overloaded,
+}
+
+struct State {
+    limit: f64,
+    in_flight: usize,
+}
+
+/// Additive-increase / multiplicative-decrease limit on sends in flight: the limit
+/// grows by about one per round of fast sends and halves on every overload signal
+pub struct AdaptiveConcurrency {
+    config: AdaptiveConcurrencyConfig,
+    state: Mutex<State>,
+    released: Notify,
+}
+
+/// A slot for one send; report how it ended with [`Permit::finish`]
+pub struct Permit<'a> {
+    controller: &'a AdaptiveConcurrency,
+    started: Instant,
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(config: AdaptiveConcurrencyConfig) -> Self {
+        let min = config.min.max(1);
+        let max = config.max.max(min);
+        Self {
+            config: AdaptiveConcurrencyConfig { min, max, ..config },
+            state: Mutex::new(State {
+                limit: config.initial.clamp(min, max) as f64,
+                in_flight: 0,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Current limit on sends in flight
+    pub fn limit(&self) -> usize {
+        self.lock().limit as usize
+    }
+
+    /// Wait until a send may start
+    pub async fn acquire(&self) -> Permit<'_> {
+        loop {
+            let released = self.released.notified();
+            {
+                let mut state = self.lock();
+                if state.in_flight < state.limit as usize {
+                    state.in_flight += 1;
+                    return Permit {
+                        controller: self,
+                        started: Instant::now(),
+                    };
+                }
+            }
+            released.await;
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, outcome: SendOutcome, latency: Duration) {
+        let mut state = self.lock();
+        let (min, max) = (self.config.min as f64, self.config.max as f64);
+        state.limit = match outcome {
+            SendOutcome::Overloaded => (state.limit / 2.0).max(min),
+            SendOutcome::Completed
+                if latency <= Duration::from_millis(self.config.target_latency_ms) =>
+            {
+                (state.limit + 1.0 / state.limit).min(max)
+            }
+            SendOutcome::Completed => state.limit,
+        };
+    }
+}
+
+impl Permit<'_> {
+    /// Release the slot and adjust the limit by the send's outcome and latency
+    pub fn finish(self, outcome: SendOutcome) {
+        self.controller.record(outcome, self.started.elapsed());
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.controller.lock().in_flight -= 1;
+        self.controller.released.notify_waiters();
+    }
+}
+
+/// Fixed caps on sends, on top of the adaptive controller and the per-endpoint `rpc_limits`
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct SendLimitsConfig {
+    /// Transfers (or batches) sending or awaiting confirmation at once
+    pub max_concurrent_sends: Option<usize>,
+    /// Sustained rate of broadcasts and simulations, retries included
+    pub requests_per_second: Option<f64>,
+}
+
+/// Token bucket holding up to a second's worth of sends
+struct SendBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl SendBucket {
+    fn new(rate: f64) -> Self {
+        let rate = rate.max(0.001);
+        let capacity = rate.ceil();
+        Self {
+            rate,
+            capacity,
+            tokens: capacity,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token, returning how long the caller has to wait for it
+    fn reserve(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last_refill = now;
+
+        // Tokens may go negative: each queued send waits for its own refill slot
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Enforces [`SendLimitsConfig`]: a semaphore for sends in flight and a token bucket for
+/// their rate. Both are no-ops when unset.
+pub struct SendLimiter {
+    slots: Option<Semaphore>,
+    bucket: Option<Mutex<SendBucket>>,
+}
+
+impl SendLimiter {
+    pub fn new(config: SendLimitsConfig) -> Self {
+        Self {
+            slots: config
+                .max_concurrent_sends
+                .map(|max| Semaphore::new(max.max(1))),
+            bucket: config
+                .requests_per_second
+                .map(|rate| Mutex::new(SendBucket::new(rate))),
+        }
+    }
+
+    /// Wait for a slot among the sends in flight; it is released when the permit is dropped
+    pub async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        let slots = self.slots.as_ref()?;
+        Some(slots.acquire().await.expect("send slots are never closed"))
+    }
+
+    /// Wait until one more request may go out under the rate limit
+    pub async fn pace(&self) {
+        let Some(bucket) = &self.bucket else {
+            return;
+        };
+        let wait = bucket.lock().unwrap_or_else(|e| e.into_inner()).reserve();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Most sends seen in flight at once when `sends` blocking sends, run the way task2 runs
+    /// them (one blocking thread each), first wait for `limiter` and `controller`
+    async fn peak_in_flight(
+        sends: usize,
+        limiter: Arc<SendLimiter>,
+        controller: Option<Arc<AdaptiveConcurrency>>,
+    ) -> usize {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..sends)
+            .map(|_| {
+                let (limiter, controller) = (limiter.clone(), controller.clone());
+                let (in_flight, peak) = (in_flight.clone(), peak.clone());
+                let runtime = tokio::runtime::Handle::current();
+                tokio::task::spawn_blocking(move || {
+                    runtime.block_on(async move {
+                        let _slot = limiter.acquire().await;
+                        let permit = match &controller {
+                            Some(controller) => Some(controller.acquire().await),
+                            None => None,
+                        };
+                        let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        // A blocking RPC call
+                        std::thread::sleep(Duration::from_millis(20));
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        if let Some(permit) = permit {
+                            permit.finish(SendOutcome::Completed);
+                        }
+                    })
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        peak.load(Ordering::SeqCst)
+    }
+
+    fn controller(initial: usize, max: usize) -> AdaptiveConcurrency {
+        AdaptiveConcurrency::new(AdaptiveConcurrencyConfig {
+            initial,
+            min: 1,
+            max,
+            target_latency_ms: 60_000,
+        })
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_controller_bounds_concurrent_sends() {
+        let controller = Arc::new(controller(4, 4));
+        let limiter = Arc::new(SendLimiter::new(SendLimitsConfig::default()));
+        assert_eq!(peak_in_flight(16, limiter, Some(controller)).await, 4);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_fixed_cap_bounds_concurrent_sends() {
+        let limiter = Arc::new(SendLimiter::new(SendLimitsConfig {
+            max_concurrent_sends: Some(3),
+            requests_per_second: None,
+        }));
+        assert_eq!(peak_in_flight(12, limiter, None).await, 3);
+    }
+
+    #[tokio::test]
+    async fn the_limit_grows_on_fast_sends_and_halves_on_overload() {
+        let controller = controller(4, 64);
+        for _ in 0..4 {
+            controller.acquire().await.finish(SendOutcome::Completed);
+        }
+        assert_eq!(controller.limit(), 4);
+        controller.acquire().await.finish(SendOutcome::Completed);
+        assert_eq!(controller.limit(), 5);
+
+        controller.acquire().await.finish(SendOutcome::Overloaded);
+        assert_eq!(controller.limit(), 2);
+        for _ in 0..4 {
+            controller.acquire().await.finish(SendOutcome::Overloaded);
+        }
+        assert_eq!(controller.limit(), 1);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{
+    balances::TopUp,
+    concurrency::{AdaptiveConcurrencyConfig, SendLimitsConfig},
+    confirmation::ConfirmationConfig,
+    distribution::{Distribution, DistributionError},
+    manifest::ManifestConfig,
+    plan::{PaymentTemplate, Plan, PlanError, PlannedTransfer, TransferKind, TransferMemos},
+};
+use serde::Deserialize;
+use solana_common::{
+    AddressBook, AuditConfig, ComputeBudgetConfig, ConfigError, FeeOracleConfig,
+    NotificationConfig, RpcLimits, SafetyConfig, SigningError, SubmissionConfig,
+};
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct SourceWallet {
+    pub address: String,
+    /// bs58-encoded secret key; give this or `secret_key_path`
+    #[serde(default)]
+    pub secret_key: Option<String>,
+    /// Keypair file in the JSON byte-array format of `solana-keygen`, e.g. its `id.json`
+    #[serde(default)]
+    pub secret_key_path: Option<PathBuf>,
+    /// Durable nonce account (address or alias) whose authority is this wallet; transfers from
+    /// it then use the nonce instead of a recent blockhash and do not expire
+    #[serde(default)]
+    pub nonce_account: Option<String>,
+}
+
+impl SourceWallet {
+    /// Source entry for a keypair that does not come from the config (e.g. the solana-cli default)
+    pub fn from_keypair(keypair: &Keypair) -> Self {
+        Self {
+            address: keypair.pubkey().to_string(),
+            secret_key: Some(bs58::encode(keypair.to_bytes()).into_string()),
+            secret_key_path: None,
+            nonce_account: None,
+        }
+    }
+    
+    /// Signing key of the wallet, from whichever of `secret_key` and `secret_key_path` is set
+    pub fn keypair(&self) -> Result<Keypair, SigningError> {
+        match (&self.secret_key, &self.secret_key_path) {
+            (Some(secret_key), None) => load_keypair_from_secret(secret_key),
+            (None, Some(path)) => load_keypair_from_file(path),
+            _ => Err(SigningError::SecretSource { address: self.address.clone() }),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub source_wallets: Vec<SourceWallet>,
+    #[serde(default)]
+    pub destination_wallets: Vec<String>,
+    #[serde(default)]
+    pub amount_lamports: u64,
+    /// Explicit source, destination and amount of every transfer, replacing the sources x
+    /// destinations matrix (and a distribution) so each recipient can get its own amount
+    #[serde(default)]
+    pub transfers: Vec<PlannedTransfer>,
+    /// Send this SPL token (mint address or alias) instead of SOL. Transfers then move tokens
+    /// between the wallets' associated token accounts, and every amount, `amount_lamports`
+    /// included, is in base units of the mint.
+    #[serde(default)]
+    pub token_mint: Option<String>,
+    /// What the config matrix sends each destination with `token_mint`, in whole tokens
+    /// (e.g. `2.5`); replaces `amount_lamports`
+    #[serde(default)]
+    pub amount_tokens: Option<String>,
+    /// Create missing destination token accounts with `token_mint`, paid by the source;
+    /// without it transfers to wallets lacking one fail
+    #[serde(default = "default_create_destination_ata")]
+    pub create_destination_ata: bool,
+    /// Memo attached to every transfer without one of its own, so payouts can be reconciled
+    /// on-chain; stake transfers carry no memo
+    #[serde(default)]
+    pub memo: Option<String>,
+    /// Memos by destination (alias or address), overriding `memo`
+    #[serde(default)]
+    pub destination_memos: BTreeMap<String, String>,
+    /// Kind of the config matrix (and distribution) transfers
+    #[serde(default)]
+    pub transfer_kind: TransferKind,
+    /// Vote account stake transfers delegate to
+    #[serde(default)]
+    pub stake_vote_account: Option<String>,
+    /// RPC endpoints requests are spread over round-robin, failing over to the next when one
+    /// errors or times out; replaces the solana-cli RPC URL, but not `--rpc-url`
+    #[serde(default)]
+    pub rpc_urls: Vec<String>,
+    /// Limits of each RPC endpoint; with `rpc_urls`, `retry.max_attempts` is the number of
+    /// rounds over all endpoints
+    #[serde(default)]
+    pub rpc_limits: RpcLimits,
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    /// Sign a manifest of the planned transfers with an operator key before sending
+    #[serde(default)]
+    pub manifest: ManifestConfig,
+    /// `webhook` receives the final status of every transfer
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    /// Attach a compute unit price suggested by the fee oracle to every transfer
+    #[serde(default)]
+    pub priority_fee: Option<FeeOracleConfig>,
+    /// Request a compute unit limit sized from simulation for every transfer
+    #[serde(default)]
+    pub compute_budget: Option<ComputeBudgetConfig>,
+    #[serde(default)]
+    pub submission: SubmissionConfig,
+    /// Split a total over weighted destinations instead of sending `amount_lamports`
+    /// to every `destination_wallets` entry
+    #[serde(default)]
+    pub distribution: Option<Distribution>,
+    /// Send each listed destination only what it lacks to reach its target balance
+    #[serde(default)]
+    pub top_up: Option<TopUp>,
+    /// Skip transfers to destinations already holding more than this many lamports
+    #[serde(default)]
+    pub skip_destinations_above_lamports: Option<u64>,
+    /// Wait at most this long for each transfer to confirm, rebroadcasting meanwhile;
+    /// unconfirmed transfers are then abandoned so the rest of the batch continues
+    #[serde(default)]
+    pub transfer_deadline_secs: Option<u64>,
+    /// Times a transfer that failed transiently (expired blockhash, rate limit, lagging node)
+    /// without landing is rebuilt with a fresh blockhash and sent again, with backoff
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Commitment every sent transfer is awaited at, and for how long, before it is reported
+    #[serde(default)]
+    pub confirmation: ConfirmationConfig,
+    /// Stop sending once the run's fees (base plus priority) would exceed this
+    #[serde(default)]
+    pub max_total_fees_lamports: Option<u64>,
+    /// Adapt the number of sends in flight to confirmation latency and overload signals;
+    /// without it every transfer is sent at once
+    #[serde(default)]
+    pub adaptive_concurrency: Option<AdaptiveConcurrencyConfig>,
+    /// Fixed cap on sends in flight and on their rate
+    #[serde(default)]
+    pub send_limits: SendLimitsConfig,
+    /// Pack each source's transfers into as few transactions as fit instead of sending one per
+    /// destination; stake transfers are still sent one by one
+    #[serde(default)]
+    pub batch_transfers: bool,
+    /// Recurring payee sets, e.g. `payroll`, run with `--template <name>`
+    #[serde(default)]
+    pub templates: BTreeMap<String, PaymentTemplate>,
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_create_destination_ata() -> bool {
+    true
+}
+
+impl Config {
+    pub fn load(config_path: &Path) -> Result<Self, ConfigError> {
+        let config_file = File::open(config_path).map_err(|source| ConfigError::Read {
+            path: config_path.to_path_buf(),
+            source,
+        })?;
+        let config: Config = serde_yaml::from_reader(config_file).map_err(|source| ConfigError::Parse {
+            path: config_path.to_path_buf(),
+            source,
+        })?;
+        
+        Ok(config)
+    }
+    
+    /// The explicit `transfers` list when there is one. Otherwise every source sends
+    /// `amount_lamports` to every destination, or with a distribution configured, its
+    /// weighted part of the total. Transfers of zero, and shares that round to zero, are skipped.
+    pub fn planned_transfers(&self) -> Result<Vec<PlannedTransfer>, DistributionError> {
+        if !self.transfers.is_empty() {
+            return Ok(self
+                .transfers
+                .iter()
+                .filter(|transfer| transfer.amount_lamports > 0)
+                .cloned()
+                .collect());
+        }
+
+        let amounts = match &self.distribution {
+            Some(distribution) => distribution.amounts()?,
+            None => self
+                .destination_wallets
+                .iter()
+                .map(|destination| (destination.clone(), self.amount_lamports))
+                .collect(),
+        };
+
+        Ok(self
+            .source_wallets
+            .iter()
+            .flat_map(|source| {
+                amounts
+                    .iter()
+                    .filter(|(_, amount)| *amount > 0)
+                    .map(|(destination, amount)| PlannedTransfer {
+                        source: source.address.clone(),
+                        destination: destination.clone(),
+                        amount_lamports: *amount,
+                        memo: None,
+                        kind: self.transfer_kind,
+                    })
+            })
+            .collect())
+    }
+
+    /// Addresses of the configured source wallets
+    pub fn source_addresses(&self) -> Vec<String> {
+        self.source_wallets
+            .iter()
+            .map(|source| source.address.clone())
+            .collect()
+    }
+
+    /// The config's memos, for transfers that carry none of their own
+    pub fn memos(&self, book: &AddressBook) -> Result<TransferMemos, PlanError> {
+        TransferMemos::resolve(self.memo.as_deref(), &self.destination_memos, book)
+    }
+
+    /// Transfers of the named template
+    pub fn template_plan(&self, name: &str) -> Result<Plan, PlanError> {
+        let template = self
+            .templates
+            .get(name)
+            .ok_or_else(|| PlanError::UnknownTemplate {
+                name: name.to_string(),
+            })?;
+        template.transfers(name, &self.source_addresses())
+    }
+}
+
+pub fn load_keypair_from_secret(secret_key: &str) -> Result<Keypair, SigningError> {
+    let secret_bytes = bs58::decode(secret_key)
+        .into_vec()
+        .map_err(|e| SigningError::InvalidSecretKey { reason: e.to_string() })?;
+    
+    let keypair = Keypair::from_bytes(&secret_bytes)
+        .map_err(|e| SigningError::InvalidKeypair { reason: e.to_string() })?;
+    
+    Ok(keypair)
+}
+
+/// Read a keypair file written by `solana-keygen`
+pub fn load_keypair_from_file(path: &Path) -> Result<Keypair, SigningError> {
+    read_keypair_file(path).map_err(|e| SigningError::InvalidKeypair {
+        reason: format!("{}: {}", path.display(), e),
+    })
+}
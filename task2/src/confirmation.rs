@@ -0,0 +1,141 @@
+use crate::transfer::TransferError;
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::TransactionConfirmationStatus;
+use std::{
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How far a sent transaction has got, in increasing order
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Commitment {
+    Processed,
+    #[default]
+    Confirmed,
+    Finalized,
+}
+
+impl Commitment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Commitment::Processed => "processed",
+            Commitment::Confirmed => "confirmed",
+            Commitment::Finalized => "finalized",
+        }
+    }
+}
+
+impl From<TransactionConfirmationStatus> for Commitment {
+    fn from(status: TransactionConfirmationStatus) -> Self {
+        match status {
+            TransactionConfirmationStatus::Processed => Commitment::Processed,
+            TransactionConfirmationStatus::Confirmed => Commitment::Confirmed,
+            TransactionConfirmationStatus::Finalized => Commitment::Finalized,
+        }
+    }
+}
+
+/// Wait for every sent transfer to reach a commitment level before reporting it
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ConfirmationConfig {
+    pub commitment: Commitment,
+    /// Seconds to wait for each transfer; 0 reports transfers as soon as they are sent
+    pub timeout_secs: u64,
+}
+
+impl Default for ConfirmationConfig {
+    fn default() -> Self {
+        Self {
+            commitment: Commitment::default(),
+            timeout_secs: 60,
+        }
+    }
+}
+
+impl ConfirmationConfig {
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_secs > 0).then(|| Duration::from_secs(self.timeout_secs))
+    }
+}
+
+/// Whether the chain has moved past `last_valid_block_height`, so a transaction signed with
+/// that expiry can no longer land. Without an expiry (durable nonces) or a block height to
+/// compare it with, nothing counts as expired.
+pub(crate) fn is_expired(client: &RpcClient, last_valid_block_height: Option<u64>) -> bool {
+    let Some(last_valid) = last_valid_block_height else {
+        return false;
+    };
+    match client.get_block_height() {
+        Ok(height) => height > last_valid,
+        Err(e) => {
+            eprintln!("Block height check on {} failed: {}", client.url(), e);
+            false
+        }
+    }
+}
+
+/// Poll the sent transaction `signature` until it reaches `commitment` and return the level
+/// it reached. It is abandoned when `timeout` runs out first, and expired once the chain has
+/// moved past `last_valid_block_height`, the last block its blockhash was valid for, without
+/// it landing. Durable nonce transactions have none and never expire that way.
+///
+/// `client` should talk to the node that accepted the transaction: another node may lag
+/// behind it and not know the transaction yet. The transaction may have landed whatever the
+/// node answers, so failed polls are retried until the timeout rather than returned.
+pub async fn await_commitment(
+    client: &RpcClient,
+    signature: &str,
+    commitment: Commitment,
+    timeout: Duration,
+    last_valid_block_height: Option<u64>,
+) -> Result<Commitment, TransferError> {
+    let start = Instant::now();
+    let parsed = Signature::from_str(signature).expect("sent transaction signatures parse");
+
+    loop {
+        // None when the poll failed, Some(None) when the node does not know the transaction
+        let status = match client.get_signature_statuses(&[parsed]) {
+            Ok(statuses) => Some(statuses.value.into_iter().next().flatten()),
+            Err(e) => {
+                eprintln!("Status check of {} failed: {}", signature, e);
+                None
+            }
+        };
+
+        match status {
+            Some(Some(status)) => {
+                if let Some(error) = status.err.clone() {
+                    return Err(TransferError::Failed {
+                        signature: signature.to_string(),
+                        error,
+                    });
+                }
+                let reached = Commitment::from(status.confirmation_status());
+                if reached >= commitment {
+                    return Ok(reached);
+                }
+            }
+            Some(None) if is_expired(client, last_valid_block_height) => {
+                return Err(TransferError::Expired {
+                    signature: signature.to_string(),
+                });
+            }
+            Some(None) | None => {}
+        }
+
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Err(TransferError::Abandoned {
+                signature: signature.to_string(),
+                after: timeout,
+            });
+        }
+        tokio::time::sleep(remaining.min(POLL_INTERVAL)).await;
+    }
+}
@@ -0,0 +1,277 @@
+use serde::Deserialize;
+use thiserror::Error;
+
+/// Split a total amount over destinations by weight instead of sending a fixed amount
+#[derive(Debug, Clone, Deserialize)]
+pub struct Distribution {
+    /// Lamports each source wallet distributes in total
+    pub total_lamports: u64,
+    #[serde(default)]
+    pub rounding: Rounding,
+    pub shares: Vec<Share>,
+}
+
+/// One destination's part of the total, as a relative weight or a percentage
+#[derive(Debug, Clone, Deserialize)]
+pub struct Share {
+    /// Destination wallet (alias or address)
+    pub destination: String,
+    #[serde(default)]
+    pub weight: Option<u64>,
+    #[serde(default)]
+    pub percent: Option<f64>,
+}
+
+/// Where the lamports left over by flooring every share go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// One lamport each to the shares with the largest fractional parts, so the whole
+    /// total is sent and no share is off by more than one lamport
+    #[default]
+    LargestRemainder,
+    /// All leftover lamports to the first destination
+    First,
+    /// Leftover lamports stay with the source
+    Keep,
+}
+
+#[derive(Debug, Error)]
+pub enum DistributionError {
+    #[error("Distribution has no shares")]
+    NoShares,
+
+    #[error("Share for {destination} needs exactly one of weight or percent")]
+    AmbiguousShare { destination: String },
+
+    #[error("Distribution mixes weights and percentages")]
+    MixedShares,
+
+    #[error("Distribution percentages add up to {total}, not 100")]
+    PercentTotal { total: f64 },
+
+    #[error("Distribution weights add up to zero")]
+    ZeroWeight,
+}
+
+impl DistributionError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DistributionError::NoShares => "DISTRIBUTION_NO_SHARES",
+            DistributionError::AmbiguousShare { .. } => "DISTRIBUTION_AMBIGUOUS_SHARE",
+            DistributionError::MixedShares => "DISTRIBUTION_MIXED_SHARES",
+            DistributionError::PercentTotal { .. } => "DISTRIBUTION_PERCENT_TOTAL",
+            DistributionError::ZeroWeight => "DISTRIBUTION_ZERO_WEIGHT",
+        }
+    }
+}
+
+/// Percentages are converted to weights at this resolution (1/10000 of a percent)
+const PERCENT_SCALE: f64 = 10_000.0;
+
+impl Distribution {
+    /// Lamports for every share, in share order. Zero amounts are kept so callers can
+    /// report destinations whose share rounded away.
+    pub fn amounts(&self) -> Result<Vec<(String, u64)>, DistributionError> {
+        let weights = self.weights()?;
+        let weight_sum: u128 = weights.iter().map(|&w| w as u128).sum();
+        if weight_sum == 0 {
+            return Err(DistributionError::ZeroWeight);
+        }
+
+        let total = self.total_lamports as u128;
+        let mut amounts: Vec<u64> = Vec::with_capacity(weights.len());
+        let mut remainders = Vec::with_capacity(weights.len());
+        for (index, &weight) in weights.iter().enumerate() {
+            let exact = total * weight as u128;
+            // Each floored share is at most the total, so it fits in u64
+            amounts.push((exact / weight_sum) as u64);
+            remainders.push((exact % weight_sum, index));
+        }
+
+        let leftover = self.total_lamports - amounts.iter().sum::<u64>();
+        match self.rounding {
+            Rounding::LargestRemainder => {
+                // Ties go to the earlier share
+                remainders.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+                for &(_, index) in remainders.iter().take(leftover as usize) {
+                    amounts[index] += 1;
+                }
+            }
+            Rounding::First => amounts[0] += leftover,
+            Rounding::Keep => {}
+        }
+
+        Ok(self
+            .shares
+            .iter()
+            .map(|share| share.destination.clone())
+            .zip(amounts)
+            .collect())
+    }
+
+    fn weights(&self) -> Result<Vec<u64>, DistributionError> {
+        if self.shares.is_empty() {
+            return Err(DistributionError::NoShares);
+        }
+
+        let mut weights = Vec::with_capacity(self.shares.len());
+        let mut percent_total = 0.0;
+        let mut uses_percent = None;
+        for share in &self.shares {
+            let (weight, is_percent) = match (share.weight, share.percent) {
+                (Some(weight), None) => (weight, false),
+                (None, Some(percent)) if percent >= 0.0 => {
+                    percent_total += percent;
+                    ((percent * PERCENT_SCALE).round() as u64, true)
+                }
+                _ => {
+                    return Err(DistributionError::AmbiguousShare {
+                        destination: share.destination.clone(),
+                    })
+                }
+            };
+            if *uses_percent.get_or_insert(is_percent) != is_percent {
+                return Err(DistributionError::MixedShares);
+            }
+            weights.push(weight);
+        }
+
+        if uses_percent == Some(true) && (percent_total - 100.0).abs() > 1e-6 {
+            return Err(DistributionError::PercentTotal {
+                total: percent_total,
+            });
+        }
+        Ok(weights)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weighted(total_lamports: u64, rounding: Rounding, weights: &[u64]) -> Distribution {
+        Distribution {
+            total_lamports,
+            rounding,
+            shares: weights
+                .iter()
+                .enumerate()
+                .map(|(index, &weight)| Share {
+                    destination: format!("d{}", index),
+                    weight: Some(weight),
+                    percent: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn lamports(distribution: &Distribution) -> Vec<u64> {
+        distribution
+            .amounts()
+            .unwrap()
+            .into_iter()
+            .map(|(_, amount)| amount)
+            .collect()
+    }
+
+    #[test]
+    fn largest_remainder_sends_exactly_the_total() {
+        // 100 / 3 floors to 33 each; the leftover lamport goes to the first tie
+        assert_eq!(
+            lamports(&weighted(100, Rounding::LargestRemainder, &[1, 1, 1])),
+            [34, 33, 33]
+        );
+        // 10 * 1/6 = 1.67, 10 * 2/6 = 3.33, 10 * 3/6 = 5: the largest fraction gets it
+        assert_eq!(
+            lamports(&weighted(10, Rounding::LargestRemainder, &[1, 2, 3])),
+            [2, 3, 5]
+        );
+
+        for total in [0, 1, 7, 999, 1_000_000_007, u64::MAX] {
+            for weights in [
+                &[1, 1, 1][..],
+                &[3, 7],
+                &[1, 2, 3, 4, 5, 6, 7],
+                &[u64::MAX, 1],
+            ] {
+                let amounts = lamports(&weighted(total, Rounding::LargestRemainder, weights));
+                let sum: u128 = amounts.iter().map(|&amount| amount as u128).sum();
+                assert_eq!(sum, total as u128, "{} over {:?}", total, weights);
+
+                // No share is off its exact value by a whole lamport
+                let weight_sum: u128 = weights.iter().map(|&w| w as u128).sum();
+                for (&amount, &weight) in amounts.iter().zip(weights) {
+                    let exact = total as u128 * weight as u128;
+                    let floor = exact / weight_sum;
+                    assert!(amount as u128 == floor || amount as u128 == floor + 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn first_rounding_gives_the_leftover_to_the_first_share() {
+        assert_eq!(
+            lamports(&weighted(100, Rounding::First, &[1, 1, 1])),
+            [34, 33, 33]
+        );
+        assert_eq!(
+            lamports(&weighted(11, Rounding::First, &[1, 1, 1, 1])),
+            [5, 2, 2, 2]
+        );
+    }
+
+    #[test]
+    fn keep_rounding_leaves_the_leftover_with_the_source() {
+        assert_eq!(
+            lamports(&weighted(11, Rounding::Keep, &[1, 1, 1, 1])),
+            [2, 2, 2, 2]
+        );
+        // Shares that round to nothing are kept in the output
+        assert_eq!(lamports(&weighted(1, Rounding::Keep, &[1, 1])), [0, 0]);
+    }
+
+    #[test]
+    fn percentages_split_like_weights_and_must_total_100() {
+        let mut distribution = weighted(1_000, Rounding::LargestRemainder, &[]);
+        distribution.shares = [("a", 50.0), ("b", 33.3333), ("c", 16.6667)]
+            .into_iter()
+            .map(|(destination, percent)| Share {
+                destination: destination.to_string(),
+                weight: None,
+                percent: Some(percent),
+            })
+            .collect();
+        assert_eq!(lamports(&distribution), [500, 333, 167]);
+
+        distribution.shares[0].percent = Some(40.0);
+        assert!(matches!(
+            distribution.amounts(),
+            Err(DistributionError::PercentTotal { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_shares_are_rejected() {
+        let empty = weighted(10, Rounding::LargestRemainder, &[]);
+        assert!(matches!(empty.amounts(), Err(DistributionError::NoShares)));
+
+        let zero = weighted(10, Rounding::LargestRemainder, &[0, 0]);
+        assert!(matches!(zero.amounts(), Err(DistributionError::ZeroWeight)));
+
+        let mut mixed = weighted(10, Rounding::LargestRemainder, &[1, 1]);
+        mixed.shares[1].weight = None;
+        mixed.shares[1].percent = Some(50.0);
+        assert!(matches!(
+            mixed.amounts(),
+            Err(DistributionError::MixedShares)
+        ));
+
+        mixed.shares[1].weight = Some(1);
+        assert!(matches!(
+            mixed.amounts(),
+            Err(DistributionError::AmbiguousShare { .. })
+        ));
+    }
+}
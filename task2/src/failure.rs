@@ -0,0 +1,198 @@
+use serde::Serialize;
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+use std::fmt;
+
+/// Broad cause of a failed transfer, for deciding what to do about it without parsing
+/// error messages: top up the source, retry with a fresh blockhash, switch endpoints...
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureClass {
+    /// The source cannot pay the amount, the fee or the rent
+    InsufficientFunds,
+    /// The blockhash expired (or was unknown to the node) before the transaction landed
+    BlockhashExpired,
+    /// The node is unhealthy or lagging behind the cluster
+    NodeBehind,
+    /// The endpoint rate limited the request
+    RateLimited,
+    /// A program rejected an instruction with a custom error code
+    ProgramError,
+    /// Refused locally, before broadcasting (e.g. above the safe mode cap)
+    Refused,
+    /// Broadcast but not confirmed before the transfer deadline; it may still land
+    Unconfirmed,
+    Other,
+}
+
+impl FailureClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureClass::InsufficientFunds => "insufficient_funds",
+            FailureClass::BlockhashExpired => "blockhash_expired",
+            FailureClass::NodeBehind => "node_behind",
+            FailureClass::RateLimited => "rate_limited",
+            FailureClass::ProgramError => "program_error",
+            FailureClass::Refused => "refused",
+            FailureClass::Unconfirmed => "unconfirmed",
+            FailureClass::Other => "other",
+        }
+    }
+
+    /// Classify a failure from its transaction error, if the node returned one, and its
+    /// full message including any simulation logs
+    pub fn classify(error: Option<&TransactionError>, message: &str) -> Self {
+        match Self::from_message(message) {
+            FailureClass::Other => error.map_or(FailureClass::Other, Self::from_transaction_error),
+            class => class,
+        }
+    }
+
+    pub fn from_transaction_error(error: &TransactionError) -> Self {
+        match error {
+            TransactionError::InsufficientFundsForFee
+            | TransactionError::InsufficientFundsForRent { .. }
+            | TransactionError::InstructionError(_, InstructionError::InsufficientFunds) => {
+                FailureClass::InsufficientFunds
+            }
+            TransactionError::BlockhashNotFound => FailureClass::BlockhashExpired,
+            TransactionError::InstructionError(_, InstructionError::Custom(_)) => {
+                FailureClass::ProgramError
+            }
+            _ => FailureClass::Other,
+        }
+    }
+
+    /// Classify by the wording of RPC errors and program logs. Checked before the
+    /// transaction error because the system program reports a short balance only as
+    /// custom error 1, with the explanation in its logs.
+    pub fn from_message(message: &str) -> Self {
+        let message = message.to_ascii_lowercase();
+        let contains_any = |needles: &[&str]| needles.iter().any(|n| message.contains(n));
+
+        if contains_any(&["insufficient lamports", "insufficient funds"]) {
+            FailureClass::InsufficientFunds
+        } else if contains_any(&[
+            "blockhash not found",
+            "blockhashnotfound",
+            "block height exceeded",
+        ]) {
+            FailureClass::BlockhashExpired
+        } else if contains_any(&[
+            "node is behind",
+            "node is unhealthy",
+            "min context slot",
+            "minimum context slot",
+        ]) {
+            FailureClass::NodeBehind
+        } else if contains_any(&["429", "too many requests"]) {
+            FailureClass::RateLimited
+        } else if message.contains("custom program error") {
+            FailureClass::ProgramError
+        } else {
+            FailureClass::Other
+        }
+    }
+}
+
+impl fmt::Display for FailureClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_rpc_and_log_messages() {
+        let cases = [
+            (
+                "Transfer: insufficient lamports 10, need 5000",
+                FailureClass::InsufficientFunds,
+            ),
+            (
+                "Transaction simulation failed: Blockhash not found",
+                FailureClass::BlockhashExpired,
+            ),
+            (
+                "transaction has expired: block height exceeded",
+                FailureClass::BlockhashExpired,
+            ),
+            (
+                "RPC response error -32005: Node is behind by 150 slots",
+                FailureClass::NodeBehind,
+            ),
+            (
+                "Minimum context slot has not been reached",
+                FailureClass::NodeBehind,
+            ),
+            ("min context slot not reached", FailureClass::NodeBehind),
+            (
+                "HTTP status client error (429 Too Many Requests)",
+                FailureClass::RateLimited,
+            ),
+            (
+                "Error processing Instruction 0: custom program error: 0x1771",
+                FailureClass::ProgramError,
+            ),
+            ("connection reset by peer", FailureClass::Other),
+        ];
+        for (message, class) in cases {
+            assert_eq!(FailureClass::from_message(message), class, "{}", message);
+        }
+    }
+
+    #[test]
+    fn classifies_transaction_errors() {
+        let cases = [
+            (
+                TransactionError::InsufficientFundsForFee,
+                FailureClass::InsufficientFunds,
+            ),
+            (
+                TransactionError::InsufficientFundsForRent { account_index: 1 },
+                FailureClass::InsufficientFunds,
+            ),
+            (
+                TransactionError::InstructionError(0, InstructionError::InsufficientFunds),
+                FailureClass::InsufficientFunds,
+            ),
+            (
+                TransactionError::BlockhashNotFound,
+                FailureClass::BlockhashExpired,
+            ),
+            (
+                TransactionError::InstructionError(1, InstructionError::Custom(6001)),
+                FailureClass::ProgramError,
+            ),
+            (TransactionError::AccountInUse, FailureClass::Other),
+        ];
+        for (error, class) in cases {
+            assert_eq!(
+                FailureClass::from_transaction_error(&error),
+                class,
+                "{:?}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn message_takes_precedence_over_the_transaction_error() {
+        // The system program reports a short balance as custom error 1 and explains it in its logs
+        let error = TransactionError::InstructionError(0, InstructionError::Custom(1));
+        assert_eq!(
+            FailureClass::classify(Some(&error), "Transfer: insufficient lamports 1, need 2"),
+            FailureClass::InsufficientFunds
+        );
+        assert_eq!(
+            FailureClass::classify(Some(&error), "failed to send transaction"),
+            FailureClass::ProgramError
+        );
+        assert_eq!(
+            FailureClass::classify(None, "failed to send transaction"),
+            FailureClass::Other
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::Mutex;
+
+/// Cumulative fee cap for one run. Every transfer reserves its fee (base plus priority)
+/// before it is broadcast; the first transfer that would cross the cap trips the
+/// ceiling and every later one is refused, so a fee spike cannot drain the sources.
+#[derive(Debug)]
+pub struct FeeCeiling {
+    max_lamports: u64,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    spent: u64,
+    tripped: bool,
+}
+
+impl FeeCeiling {
+    pub fn new(max_lamports: u64) -> Self {
+        Self {
+            max_lamports,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn max_lamports(&self) -> u64 {
+        self.max_lamports
+    }
+
+    /// Fees reserved by transfers that were (or are being) broadcast
+    pub fn spent(&self) -> u64 {
+        self.lock().spent
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.lock().tripped
+    }
+
+    /// Reserve `fee` lamports; `Err` with the amount already spent once the ceiling
+    /// is (or would be) crossed
+    pub fn reserve(&self, fee: u64) -> Result<(), u64> {
+        let mut state = self.lock();
+        if state.tripped || state.spent.saturating_add(fee) > self.max_lamports {
+            state.tripped = true;
+            return Err(state.spent);
+        }
+        state.spent += fee;
+        Ok(())
+    }
+
+    /// Return the reservation of a transaction that was never broadcast
+    pub fn release(&self, fee: u64) {
+        let mut state = self.lock();
+        state.spent = state.spent.saturating_sub(fee);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_common::{ConfigError, ConfigFormat, RpcError};
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalStatus {
+    /// Not sent yet, or a previous attempt expired without landing
+    Pending,
+    /// Broadcast; the outcome is unknown until it lands or its blockhash expires
+    Sent,
+    Confirmed,
+    Failed,
+}
+
+/// Progress of one planned transfer, identified by its source, destination and amount
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub source: String,
+    pub destination: String,
+    pub lamports: u64,
+    pub status: JournalStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Block height after which the sent transaction can no longer land
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_valid_block_height: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl JournalEntry {
+    /// Whether a run must leave this transfer alone: it landed, or it may still land
+    pub fn is_settled(&self) -> bool {
+        matches!(self.status, JournalStatus::Confirmed | JournalStatus::Sent)
+    }
+}
+
+/// Transfers of a run, persisted after every change so rerunning the same plan after a
+/// crash sends only what has not landed
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunJournal {
+    pub entries: Vec<JournalEntry>,
+    /// File the journal is saved to
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl RunJournal {
+    /// Load the journal at `path`, or start an empty one when it does not exist
+    pub fn open(path: &Path) -> Result<Self, ConfigError> {
+        if !path.exists() {
+            return Ok(Self {
+                entries: Vec::new(),
+                path: path.to_path_buf(),
+            });
+        }
+
+        let file = File::open(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut journal: Self =
+            serde_json::from_reader(BufReader::new(file)).map_err(|e| ConfigError::Decode {
+                path: path.to_path_buf(),
+                format: ConfigFormat::Json,
+                source: e.into(),
+            })?;
+        journal.path = path.to_path_buf();
+        Ok(journal)
+    }
+
+    /// Write the journal atomically, so a crash mid-write never loses transfer state
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = &self.path;
+        let temp = path.with_extension("tmp");
+        let file = File::create(&temp).map_err(|source| ConfigError::Write {
+            path: temp.clone(),
+            source,
+        })?;
+        serde_json::to_writer_pretty(file, self).map_err(|e| ConfigError::Write {
+            path: temp.clone(),
+            source: e.into(),
+        })?;
+        std::fs::rename(&temp, path).map_err(|source| ConfigError::Write {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Index of the entry of every planned `(source, destination, lamports)` transfer, in
+    /// order, adding pending entries for new ones. Identical transfers planned more than
+    /// once map to distinct entries in the order they were first journaled.
+    pub fn track<'a>(
+        &mut self,
+        transfers: impl IntoIterator<Item = (&'a str, &'a str, u64)>,
+    ) -> Vec<usize> {
+        let mut seen: HashMap<(String, String, u64), usize> = HashMap::new();
+        let mut indexes = Vec::new();
+        for (source, destination, lamports) in transfers {
+            let occurrence = seen
+                .entry((source.to_string(), destination.to_string(), lamports))
+                .or_default();
+            let existing = self
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| {
+                    entry.source == source
+                        && entry.destination == destination
+                        && entry.lamports == lamports
+                })
+                .nth(*occurrence)
+                .map(|(index, _)| index);
+            *occurrence += 1;
+
+            let index = existing.unwrap_or_else(|| {
+                self.entries.push(JournalEntry {
+                    source: source.to_string(),
+                    destination: destination.to_string(),
+                    lamports,
+                    status: JournalStatus::Pending,
+                    signature: None,
+                    last_valid_block_height: None,
+                    error: None,
+                });
+                self.entries.len() - 1
+            });
+            indexes.push(index);
+        }
+        indexes
+    }
+
+    /// Resolve entries left in `Sent` by an earlier run: landed ones become confirmed or
+    /// failed, expired ones pending again; those that may still land are left alone
+    pub fn settle_sent(&mut self, client: &RpcClient) -> Result<(), RpcError> {
+        let mut block_height = None;
+        for entry in &mut self.entries {
+            if entry.status != JournalStatus::Sent {
+                continue;
+            }
+            let Some(signature) = entry
+                .signature
+                .as_deref()
+                .and_then(|signature| Signature::from_str(signature).ok())
+            else {
+                entry.status = JournalStatus::Pending;
+                continue;
+            };
+
+            let status = client
+                .get_signature_statuses_with_history(&[signature])
+                .map_err(|e| RpcError::request("getSignatureStatuses", &client.url(), e))?
+                .value
+                .into_iter()
+                .next()
+                .flatten();
+            match status {
+                Some(status) => {
+                    if let Some(error) = status.err {
+                        entry.status = JournalStatus::Failed;
+                        entry.error = Some(error.to_string());
+                    } else if status.satisfies_commitment(CommitmentConfig::confirmed()) {
+                        entry.status = JournalStatus::Confirmed;
+                    }
+                }
+                // Without a known expiry the transfer may still land, so it stays sent
+                None => {
+                    let Some(last_valid) = entry.last_valid_block_height else {
+                        continue;
+                    };
+                    if block_height.is_none() {
+                        let height = client
+                            .get_block_height_with_commitment(CommitmentConfig::confirmed())
+                            .map_err(|e| RpcError::request("getBlockHeight", &client.url(), e))?;
+                        block_height = Some(height);
+                    }
+                    if block_height.is_some_and(|height| height > last_valid) {
+                        entry.status = JournalStatus::Pending;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn entry(&self, index: usize) -> &JournalEntry {
+        &self.entries[index]
+    }
+}
+
+/// One transfer's entry in a journal shared by the concurrent transfers of a run; every
+/// change is saved right away
+#[derive(Debug, Clone)]
+pub struct JournalHandle {
+    journal: Arc<Mutex<RunJournal>>,
+    index: usize,
+}
+
+impl JournalHandle {
+    pub fn new(journal: Arc<Mutex<RunJournal>>, index: usize) -> Self {
+        Self { journal, index }
+    }
+
+    fn update(&self, change: impl FnOnce(&mut JournalEntry)) -> Result<(), ConfigError> {
+        let mut journal = self.journal.lock().unwrap_or_else(|e| e.into_inner());
+        change(&mut journal.entries[self.index]);
+        journal.save()
+    }
+
+    /// Record that the transfer was broadcast as `signature`, whose blockhash is valid up to
+    /// `last_valid_block_height` (None for durable nonce transactions)
+    pub fn mark_sent(
+        &self,
+        signature: &str,
+        last_valid_block_height: Option<u64>,
+    ) -> Result<(), ConfigError> {
+        self.update(|entry| {
+            entry.status = JournalStatus::Sent;
+            entry.signature = Some(signature.to_string());
+            entry.last_valid_block_height = last_valid_block_height;
+            entry.error = None;
+        })
+    }
+
+    /// Record the final status of the transfer
+    pub fn mark(&self, status: JournalStatus, error: Option<String>) -> Result<(), ConfigError> {
+        self.update(|entry| {
+            entry.status = status;
+            entry.error = error;
+        })
+    }
+}
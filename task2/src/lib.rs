@@ -0,0 +1,24 @@
+pub mod airdrop;
+pub mod balances;
+pub mod callback;
+pub mod concurrency;
+pub mod config;
+pub mod confirmation;
+pub mod distribution;
+pub mod failure;
+pub mod fee_ceiling;
+pub mod journal;
+pub mod manifest;
+pub mod pipeline;
+pub mod plan;
+pub mod preflight;
+pub mod report;
+pub mod token;
+pub mod transfer;
+
+pub use config::{load_keypair_from_file, load_keypair_from_secret, Config, SourceWallet};
+pub use distribution::{Distribution, DistributionError, Rounding, Share};
+pub use failure::FailureClass;
+pub use plan::{
+    Payee, PaymentTemplate, Plan, PlanError, PlannedTransfer, TransferKind, TransferMemos,
+};
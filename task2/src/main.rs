@@ -1,37 +1,96 @@
 use anyhow::{Context, Result};
 use chrono::Utc;
 use clap::Parser;
-use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
+use serde::Serialize;
+use solana_common::{
+    rpc, AddressBook, AuditRecord, CliConfig, ComputeBudget, EndpointPool, FeeOracle, OutputFormat, RetryPolicy,
+    SubmissionProfile, Submitter,
+};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    instruction::Instruction,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
-    system_instruction,
-    transaction::Transaction,
+    transaction::TransactionError,
+};
+use solana_token_transfer::{
+    airdrop::{mint_info, run_airdrop, ClaimLedger},
+    callback::TransferCallback,
+    confirmation::Commitment,
+    manifest::{ManifestTransfer, RunManifest},
+    report::{render_report, report_format, write_report, CsvRecord, RunSummary},
+    balances::skip_funded_destinations,
+    concurrency::{AdaptiveConcurrency, SendLimiter, SendOutcome},
+    fee_ceiling::FeeCeiling,
+    journal::{JournalHandle, JournalStatus, RunJournal},
+    pipeline::{deliver, journal_outcome, DeliveryPolicy, Payload},
+    preflight::{check_sources, PlannedOutflow},
+    token::{TokenMint, TokenTransfers},
+    transfer::{
+        pack_transfers, sign_batch, simulate_batch, simulate_stake_transaction,
+        transfer_instructions, SendPolicy, Simulation, SourceKey, TransferError,
+    },
+    Config, FailureClass, Plan, SourceWallet, TransferKind,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
-use std::{fs::File, path::Path, str::FromStr, time::Instant, sync::Arc};
-
-#[derive(Debug, Deserialize)]
-struct SourceWallet {
-    address: String,
-    secret_key: String,
-}
-
-#[derive(Debug, Deserialize)]
-struct Config {
-    source_wallets: Vec<SourceWallet>,
-    destination_wallets: Vec<String>,
-    amount_lamports: u64,
-}
 
 #[derive(Debug, Serialize)]
 struct TransactionResult {
     source: String,
     destination: String,
+    /// Memo attached to the transfer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memo: Option<String>,
     signature: String,
     status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<FailureClass>,
     time_ms: u128,
+    /// Time spent waiting for a send slot and the send rate limit
+    queue_ms: u128,
+    /// Sends it took, retries included
+    attempts: u32,
+    /// Commitment the transfer reached, unless confirmation is disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    commitment: Option<Commitment>,
+    /// Result, logs and fee of the simulated transfer, only with `--dry-run`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    simulation: Option<Simulation>,
+}
+
+impl CsvRecord for TransactionResult {
+    fn header() -> &'static [&'static str] {
+        &[
+            "source", "destination", "memo", "signature", "status", "failure", "time_ms", "queue_ms", "attempts", "commitment",
+            "fee_lamports", "units_consumed", "logs",
+        ]
+    }
+    
+    fn record(&self) -> Vec<String> {
+        let simulation = self.simulation.as_ref();
+        vec![
+            self.source.clone(),
+            self.destination.clone(),
+            self.memo.clone().unwrap_or_default(),
+            self.signature.clone(),
+            self.status.clone(),
+            self.failure.map_or("", |class| class.as_str()).to_string(),
+            self.time_ms.to_string(),
+            self.queue_ms.to_string(),
+            self.attempts.to_string(),
+            self.commitment.map_or("", |commitment| commitment.as_str()).to_string(),
+            simulation.map_or(String::new(), |simulation| simulation.fee_lamports.to_string()),
+            simulation
+                .and_then(|simulation| simulation.units_consumed)
+                .map_or(String::new(), |units| units.to_string()),
+            simulation.map_or(String::new(), |simulation| simulation.logs.join("\n")),
+        ]
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -40,51 +99,78 @@ struct Args {
     /// Path to config file
     #[arg(short, long, default_value = "config.yaml")]
     config: String,
-}
-
-async fn send_transaction(
-    client: &RpcClient,
-    source_keypair: &Keypair,
-    destination: &Pubkey,
-    lamports: u64,
-) -> Result<(String, u128)> {
-    let start = Instant::now();
-    
-    let instruction = system_instruction::transfer(
-        &source_keypair.pubkey(),
-        destination,
-        lamports,
-    );
     
-    let recent_blockhash = client
-        .get_latest_blockhash()
-        .context("Failed to get recent blockhash")?;
+    /// Solana RPC endpoint [default: solana-cli config, else devnet]
+    #[arg(short, long)]
+    rpc_url: Option<String>,
     
-    let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
-        Some(&source_keypair.pubkey()),
-        &[source_keypair],
-        recent_blockhash,
-    );
+    /// Simulate every transfer first and refuse to send failing or over-cap ones
+    #[arg(long)]
+    safe_mode: bool,
     
-    let signature = client
-        .send_transaction(&transaction)
-        .context("Failed to send transaction")?;
+    /// Broadcast through public RPC or only through the configured private relays
+    #[arg(long)]
+    submission: Option<SubmissionProfile>,
     
-    let elapsed = start.elapsed().as_millis();
+    /// Transfer plan (YAML, or a source,destination,amount,memo CSV) used instead of the config matrix
+    #[arg(long)]
+    plan: Option<String>,
     
-    Ok((signature.to_string(), elapsed))
-}
-
-fn load_keypair_from_secret(secret_key: &str) -> Result<Keypair> {
-    let secret_bytes = bs58::decode(secret_key)
-        .into_vec()
-        .context("Failed to decode secret key")?;
+    /// Run a named payee template from the config (e.g. payroll) instead of the config matrix
+    #[arg(long, conflicts_with = "plan")]
+    template: Option<String>,
+    
+    /// Airdrop --mint tokens to the address,amount rows of this claims CSV (amounts in whole tokens)
+    #[arg(long, requires = "mint", conflicts_with_all = ["plan", "template"])]
+    airdrop: Option<String>,
+    
+    /// Mint of the airdropped token
+    #[arg(long)]
+    mint: Option<String>,
+    
+    /// Claims ledger recording every recipient's status, used to resume an interrupted airdrop
+    #[arg(long, default_value = "airdrop-claims.yaml")]
+    claims: String,
+    
+    /// Recipients per airdrop transaction
+    #[arg(long, default_value_t = 5)]
+    batch_size: usize,
+    
+    /// Build and sign every transfer as usual but only simulate it, reporting each one's result, logs and fee; nothing is sent
+    #[arg(long, conflicts_with = "airdrop")]
+    dry_run: bool,
+    
+    /// Also write every result and the summary to this file, in the format its extension names
+    /// (.csv, .json, .ndjson, .md or .txt for a table)
+    #[arg(long, conflicts_with = "airdrop")]
+    report: Option<String>,
+    
+    /// Format of the results and summary printed at the end: table, json, csv, ndjson or markdown
+    #[arg(short, long, default_value = "table", conflicts_with = "airdrop")]
+    output: OutputFormat,
     
-    let keypair = Keypair::from_bytes(&secret_bytes)
-        .context("Failed to create keypair from secret bytes")?;
+    /// Journal of every transfer's progress; rerunning with the same journal skips transfers that already landed
+    #[arg(long, conflicts_with_all = ["airdrop", "dry_run"])]
+    journal: Option<String>,
     
-    Ok(keypair)
+    /// Send even when the pre-flight check finds a source that cannot cover its transfers and fees
+    #[arg(long, conflicts_with = "airdrop")]
+    force: bool,
+}
+
+/// Instructions of a transfer that is not a stake one: the token's with a token mint, else a
+/// system transfer
+fn payment_instructions(
+    token: Option<&TokenTransfers>,
+    source: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+    memo: Option<&str>,
+) -> Vec<Instruction> {
+    match token {
+        Some(token) => token.instructions(source, destination, amount, memo),
+        None => transfer_instructions(source, destination, amount, memo),
+    }
 }
 
 #[tokio::main]
@@ -92,110 +178,906 @@ async fn main() -> Result<()> {
     let args = Args::parse();
     let config_path = Path::new(&args.config);
     
-    let config_file = File::open(config_path).context("Failed to open config file")?;
-    let config: Config = serde_yaml::from_reader(config_file).context("Failed to parse config file")?;
+    // Checked up front so a typo does not surface only after every transfer was sent
+    let report = args
+        .report
+        .as_deref()
+        .map(|path| report_format(Path::new(path)).map(|format| (Path::new(path), format)))
+        .transpose()?;
     
-    let client = Arc::new(RpcClient::new_with_commitment(
-        "https://api.devnet.solana.com".to_string(),
-        CommitmentConfig::confirmed(),
+    let mut config = Config::load(config_path)?;
+    
+    // Unset values fall back to the user's solana-cli config
+    let cli_config = CliConfig::load()?;
+    if config.source_wallets.is_empty() {
+        if let Some(keypair) = cli_config.keypair()? {
+            println!("No source wallets configured, sending from solana-cli keypair {}", keypair.pubkey());
+            config.source_wallets.push(SourceWallet::from_keypair(&keypair));
+        }
+    }
+    let safety = config.safety.with_flag(args.safe_mode);
+    let submitter = Submitter::new(&config.submission.clone().with_profile(args.submission))?;
+    
+    // Every submitted transfer is appended to the audit log when one is configured
+    let audit = config.audit.open()?.map(Arc::new);
+    
+    // Destinations may be given as address book aliases or raw addresses
+    let book = AddressBook::load()?;
+    
+    // `--rpc-url` pins one endpoint; otherwise the configured ones share the load
+    let rpc_urls = if args.rpc_url.is_none() && !config.rpc_urls.is_empty() {
+        config.rpc_urls.clone()
+    } else {
+        vec![cli_config.rpc_url(args.rpc_url.as_deref(), "https://api.devnet.solana.com")]
+    };
+    if rpc_urls.len() > 1 {
+        println!("RPC failover: spreading requests over {} endpoints", rpc_urls.len());
+    }
+    let client = Arc::new(rpc::failover_rpc_client(
+        &rpc_urls,
+        cli_config.commitment(CommitmentConfig::confirmed()),
+        config.rpc_limits,
     ));
+    // Each transfer attempt is signed, sent and confirmed on one endpoint of the pool
+    let endpoints = Arc::new(EndpointPool::new(
+        &rpc_urls,
+        cli_config.commitment(CommitmentConfig::confirmed()),
+        config.rpc_limits,
+    ));
+    
+    // Priority fees are only attached when the config enables the oracle
+    let fee_oracle = config
+        .priority_fee
+        .clone()
+        .map(|oracle_config| Arc::new(FeeOracle::new(&rpc_urls[0], oracle_config)));
+    
+    if let Some(claims_csv) = &args.airdrop {
+        let mint = book.resolve(args.mint.as_deref().unwrap_or_default())?;
+        let Some(payer) = config.source_wallets.first() else {
+            anyhow::bail!("An airdrop needs a source wallet holding the tokens");
+        };
+        let payer = payer.keypair()?;
+        
+        let mut ledger = ClaimLedger::open(Path::new(&args.claims), &mint)?;
+        let (decimals, _) = mint_info(&client, &mint)?;
+        let added = ledger.import_csv(Path::new(claims_csv), decimals)?;
+        ledger.save()?;
+        println!(
+            "Airdropping {} from {}: {} claims ({} new) tracked in {}",
+            book.display(&mint.to_string()),
+            payer.pubkey(),
+            ledger.claims.len(),
+            added,
+            args.claims
+        );
+        
+        let budget = ComputeBudget {
+            unit_price: None,
+            limit: config.compute_budget,
+        };
+        let summary = run_airdrop(&client, &payer, &mint, &mut ledger, args.batch_size, &budget, &submitter).await?;
+        println!(
+            "\nAirdrop summary: {} confirmed, {} pending, {} failed",
+            summary.confirmed, summary.pending, summary.failed
+        );
+        return Ok(());
+    }
     
-    println!("Starting SOL transfers at {}", Utc::now());
-    println!("Amount per transfer: {} lamports", config.amount_lamports);
+    // With a token mint every transfer sends that token, and every amount is in its base units
+    let token = match &config.token_mint {
+        Some(mint) => {
+            if config.top_up.is_some() || config.skip_destinations_above_lamports.is_some() {
+                anyhow::bail!("top_up and skip_destinations_above_lamports compare SOL balances and cannot be used with token_mint");
+            }
+            let mint = TokenMint::fetch(&client, &book.resolve(mint)?)?;
+            if let Some(amount) = &config.amount_tokens {
+                config.amount_lamports = mint.parse_amount(amount)?;
+            }
+            Some(TokenTransfers {
+                mint,
+                create_destination_account: config.create_destination_ata,
+            })
+        }
+        None => None,
+    };
+    let (asset, unit) = match &token {
+        Some(token) => (format!("{} token", book.display(&token.mint.mint.to_string())), "base units"),
+        None => ("SOL".to_string(), "lamports"),
+    };
+    
+    // An explicit plan, template or top-up replaces the config's sources x destinations matrix
+    let transfers = match (&args.plan, &args.template) {
+        (Some(plan_path), _) => Plan::load_any(Path::new(plan_path), &book)?.transfers,
+        (None, Some(template)) => config.template_plan(template)?.transfers,
+        (None, None) => match &config.top_up {
+            Some(top_up) => top_up.plan(&client, &book, &config.source_addresses())?,
+            None => config.planned_transfers()?,
+        },
+    };
     
-    let mut futures = Vec::new();
+    // Top-up workflows leave wallets that are already funded alone
+    let transfers = match config.skip_destinations_above_lamports {
+        Some(threshold) => {
+            let (kept, skipped) = skip_funded_destinations(&client, &book, transfers, threshold)?;
+            for skip in &skipped {
+                println!(
+                    "Skipping {} -> {}: destination holds {} lamports (threshold {})",
+                    book.display(&skip.transfer.source),
+                    book.display(&skip.transfer.destination),
+                    skip.destination_lamports,
+                    threshold
+                );
+            }
+            kept
+        }
+        None => transfers,
+    };
+    
+    let started_at = Utc::now();
+    if args.dry_run {
+        println!("Dry run: simulating {} transfers at {}, nothing will be sent", asset, started_at);
+    } else {
+        println!("Starting {} transfers at {}", asset, started_at);
+    }
+    println!(
+        "Planned transfers: {} ({} {} in total)",
+        transfers.len(),
+        transfers.iter().map(|t| t.amount_lamports).sum::<u64>(),
+        unit
+    );
+    if safety.safe_mode {
+        println!("Safe mode: simulating every transfer before sending");
+    }
+    if submitter.profile() == SubmissionProfile::Private {
+        println!("Private submission: sending only through {} relay(s)", config.submission.relays.len());
+    }
     
-    // Create a vector of futures for all transactions
+    // Signing keys of the configured source wallets, and their durable nonce accounts, by address
+    let mut source_keys = HashMap::new();
+    let mut nonce_accounts = HashMap::new();
     for source in &config.source_wallets {
-        let source_keypair = match load_keypair_from_secret(&source.secret_key) {
-            Ok(keypair) => keypair,
+        match source.keypair() {
+            Ok(keypair) => {
+                source_keys.insert(keypair.pubkey(), keypair.to_bytes());
+                if let Some(nonce_account) = &source.nonce_account {
+                    nonce_accounts.insert(keypair.pubkey(), book.resolve(nonce_account)?);
+                }
+            }
+            Err(e) => eprintln!("Error loading keypair for {}: {}", source.address, e),
+        }
+    }
+    
+    let transfer_deadline = config.transfer_deadline_secs.map(Duration::from_secs);
+    let confirmation = config.confirmation;
+    let max_retries = config.max_retries;
+    let retry = RetryPolicy::default();
+    if let Some(timeout) = confirmation.timeout().filter(|_| !args.dry_run) {
+        println!("Waiting up to {}s for every transfer to be {}", timeout.as_secs(), confirmation.commitment.as_str());
+    }
+    let fee_ceiling = config.max_total_fees_lamports.map(|max| Arc::new(FeeCeiling::new(max)));
+    let controller = config.adaptive_concurrency.map(|limits| Arc::new(AdaptiveConcurrency::new(limits)));
+    let limiter = Arc::new(SendLimiter::new(config.send_limits));
+    let stake_vote_account = config
+        .stake_vote_account
+        .as_deref()
+        .map(|vote_account| book.resolve(vote_account))
+        .transpose()?;
+    
+    // Resolve every transfer up front so the manifest lists exactly what gets sent
+    let memos = config.memos(&book)?;
+    let mut ready = Vec::new();
+    for mut transfer in transfers {
+        let source = match book.resolve(&transfer.source) {
+            Ok(pubkey) => pubkey,
             Err(e) => {
-                eprintln!("Error loading keypair for {}: {}", source.address, e);
+                eprintln!("Error parsing source address {}: {}", transfer.source, e);
                 continue;
             }
         };
+        // We need to copy the keypair data since it doesn't implement Clone
+        let Some(keypair_bytes) = source_keys.get(&source).copied() else {
+            eprintln!("No secret key configured for source wallet {}", transfer.source);
+            continue;
+        };
         
-        for dest_addr in &config.destination_wallets {
-            let destination = match Pubkey::from_str(dest_addr) {
-                Ok(pubkey) => pubkey,
-                Err(e) => {
-                    eprintln!("Error parsing destination address {}: {}", dest_addr, e);
-                    continue;
+        let destination = match book.resolve(&transfer.destination) {
+            Ok(pubkey) => pubkey,
+            Err(e) => {
+                eprintln!("Error parsing destination address {}: {}", transfer.destination, e);
+                continue;
+            }
+        };
+        
+        // Stake transfers need a validator to delegate to
+        let vote_account = match (transfer.kind, stake_vote_account) {
+            (TransferKind::System, _) => None,
+            (TransferKind::Stake, _) if token.is_some() => {
+                eprintln!(
+                    "Stake transfer {} -> {} cannot be made with token_mint set",
+                    transfer.source, transfer.destination
+                );
+                continue;
+            }
+            (TransferKind::Stake, Some(vote_account)) => Some(vote_account),
+            (TransferKind::Stake, None) => {
+                eprintln!(
+                    "Stake transfer {} -> {} needs stake_vote_account in the config",
+                    transfer.source, transfer.destination
+                );
+                continue;
+            }
+        };
+        
+        // Transfers without a memo of their own take the config's, for on-chain reconciliation
+        if transfer.memo.is_none() && vote_account.is_none() {
+            transfer.memo = memos.memo_for(&destination).map(str::to_string);
+        }
+        
+        ready.push((transfer, source, keypair_bytes, destination, vote_account));
+    }
+    
+    // With a journal, transfers that landed (or may still land) in an earlier run are not sent again
+    let mut journal_handles = vec![None; ready.len()];
+    if let Some(journal_path) = &args.journal {
+        let mut journal = RunJournal::open(Path::new(journal_path))?;
+        journal.settle_sent(&client)?;
+        let keys: Vec<(String, String, u64)> = ready
+            .iter()
+            .map(|(transfer, source, _, destination, _)| (source.to_string(), destination.to_string(), transfer.amount_lamports))
+            .collect();
+        let indexes = journal.track(keys.iter().map(|(source, destination, lamports)| (source.as_str(), destination.as_str(), *lamports)));
+        journal.save()?;
+        
+        let mut kept = Vec::new();
+        for (item, index) in ready.into_iter().zip(indexes) {
+            let entry = journal.entry(index);
+            if entry.is_settled() {
+                println!(
+                    "Skipping {} -> {}: {} in journal ({})",
+                    book.display(&entry.source),
+                    book.display(&entry.destination),
+                    if entry.status == JournalStatus::Confirmed { "confirmed" } else { "sent and may still land" },
+                    entry.signature.as_deref().unwrap_or_default()
+                );
+                continue;
+            }
+            kept.push((item, index));
+        }
+        let journal = Arc::new(Mutex::new(journal));
+        (ready, journal_handles) = kept
+            .into_iter()
+            .map(|(item, index)| (item, Some(JournalHandle::new(journal.clone(), index))))
+            .unzip();
+    }
+    
+    // Refuse up front a run whose sources cannot pay for it, rather than failing transfer by transfer
+    let mut outflows: Vec<(Pubkey, PlannedOutflow)> = Vec::new();
+    for (transfer, source, _, destination, vote_account) in &ready {
+        let index = match outflows.iter().position(|(planned, _)| planned == source) {
+            Some(index) => index,
+            None => {
+                outflows.push((*source, PlannedOutflow::default()));
+                outflows.len() - 1
+            }
+        };
+        let outflow = &mut outflows[index].1;
+        match (&token, vote_account) {
+            (Some(token), None) => {
+                outflow.tokens += transfer.amount_lamports;
+                if token.create_destination_account {
+                    outflow.token_accounts.push(token.mint.associated_account(destination));
                 }
+            }
+            _ => outflow.lamports += transfer.amount_lamports,
+        }
+        // A stake transfer is also signed by its new stake account
+        if vote_account.is_some() {
+            outflow.signatures += 2;
+        } else if !config.batch_transfers {
+            outflow.signatures += 1;
+        }
+    }
+    // Batched transfers pay one signature per transaction they are packed into
+    if config.batch_transfers {
+        for (source, outflow) in &mut outflows {
+            let instructions: Vec<Vec<Instruction>> = ready
+                .iter()
+                .filter(|(_, transfer_source, _, _, vote_account)| transfer_source == source && vote_account.is_none())
+                .map(|(transfer, _, _, destination, _)| {
+                    payment_instructions(token.as_ref(), source, destination, transfer.amount_lamports, transfer.memo.as_deref())
+                })
+                .collect();
+            outflow.signatures += pack_transfers(source, nonce_accounts.get(source), &instructions).len() as u64;
+        }
+    }
+    let checks = check_sources(&client, &outflows, token.as_ref().map(|token| &token.mint))?;
+    let short: Vec<_> = checks.iter().filter(|check| check.shortfall.is_some()).collect();
+    for check in &short {
+        eprintln!(
+            "Pre-flight: {} holds {} lamports and its transfers need {} (fees {}): {}",
+            book.display(&check.source.to_string()),
+            check.balance,
+            check.outflow,
+            check.fees,
+            check.shortfall.as_ref().expect("only short sources are listed")
+        );
+    }
+    if !short.is_empty() {
+        if args.dry_run {
+            eprintln!("{} source wallet(s) cannot cover their transfers; a real run would be refused without --force", short.len());
+        } else if args.force {
+            eprintln!("Sending anyway (--force): {} source wallet(s) cannot cover their transfers", short.len());
+        } else {
+            anyhow::bail!("{} source wallet(s) cannot cover their transfers; nothing was sent (rerun with --force to send anyway)", short.len());
+        }
+    }
+    
+    let manifest = RunManifest::new(
+        ready
+            .iter()
+            .map(|(transfer, source, _, destination, _)| ManifestTransfer {
+                source: source.to_string(),
+                destination: destination.to_string(),
+                lamports: transfer.amount_lamports,
+                memo: transfer.memo.clone(),
+                kind: transfer.kind,
+            })
+            .collect(),
+    );
+    // A dry run sends nothing, so there is nothing to vouch for
+    let written = if args.dry_run { None } else { config.manifest.write(manifest)? };
+    let manifest_hash = match written {
+        Some((signed, path)) => {
+            println!("Run manifest {} signed by {} ({})", signed.hash, signed.operator, path.display());
+            Some(signed.hash)
+        }
+        None => None,
+    };
+    
+    let webhook = config.notifications.webhook.clone().map(Arc::new);
+    
+    // A nonce value signs a single transaction, so transfers sharing a nonce account go one at a time
+    let nonce_locks: HashMap<Pubkey, Arc<tokio::sync::Mutex<()>>> = nonce_accounts
+        .values()
+        .map(|nonce| (*nonce, Arc::new(tokio::sync::Mutex::new(()))))
+        .collect();
+    
+    // Transfers sent together in one transaction: with batching, each source's transfers (stake
+    // ones aside) packed as tightly as the transaction size allows, otherwise one each
+    let mut batches = Vec::new();
+    let mut by_source: Vec<(Pubkey, Vec<_>)> = Vec::new();
+    for (item, journal_entry) in ready.into_iter().zip(journal_handles) {
+        let (_, source, _, _, vote_account) = &item;
+        if !config.batch_transfers || vote_account.is_some() {
+            batches.push(vec![(item, journal_entry)]);
+            continue;
+        }
+        match by_source.iter_mut().find(|(batch_source, _)| batch_source == source) {
+            Some((_, items)) => items.push((item, journal_entry)),
+            None => by_source.push((*source, vec![(item, journal_entry)])),
+        }
+    }
+    for (source, items) in by_source {
+        let instructions: Vec<Vec<Instruction>> = items
+            .iter()
+            .map(|((transfer, source, _, destination, _), _)| {
+                payment_instructions(token.as_ref(), source, destination, transfer.amount_lamports, transfer.memo.as_deref())
+            })
+            .collect();
+        let mut items = items.into_iter();
+        for run in pack_transfers(&source, nonce_accounts.get(&source), &instructions) {
+            batches.push(items.by_ref().take(run.len()).collect());
+        }
+    }
+    if config.batch_transfers {
+        println!("Batching: {} transfers packed into {} transactions", batches.iter().map(Vec::len).sum::<usize>(), batches.len());
+    }
+    
+    let delivery_policy = DeliveryPolicy {
+        send: SendPolicy {
+            safety,
+            submitter: submitter.clone(),
+            deadline: transfer_deadline,
+            fee_ceiling: fee_ceiling.clone(),
+        },
+        confirmation,
+        max_retries,
+        retry,
+    };
+    let mut tasks = Vec::new();
+    
+    // Start a task for every transaction
+    for batch in batches {
+        let client_ref = client.clone();
+        let endpoints_ref = endpoints.clone();
+        let controller_ref = controller.clone();
+        let limiter_ref = limiter.clone();
+        let audit_ref = audit.clone();
+        let manifest_ref = manifest_hash.clone();
+        let webhook_ref = webhook.clone();
+        let oracle_ref = fee_oracle.clone();
+        let policy = delivery_policy.clone();
+        let compute_limit = config.compute_budget;
+        let (_, source, keypair_bytes, _, vote_account) = batch[0].0;
+        let source_addr = source.to_string();
+        let nonce = nonce_accounts.get(&source).copied();
+        let nonce_lock = nonce.and_then(|nonce| nonce_locks.get(&nonce).cloned());
+        let dry_run = args.dry_run;
+        
+        // Stake transfers are never batched, so a batch either is a single stake transfer or only pays
+        let mut transfers = Vec::with_capacity(batch.len());
+        let mut destinations = Vec::with_capacity(batch.len());
+        let mut journal_entries = Vec::with_capacity(batch.len());
+        for ((transfer, _, _, destination, _), journal_entry) in batch {
+            transfers.push(transfer);
+            destinations.push(destination);
+            journal_entries.push(journal_entry);
+        }
+        let instructions: Vec<Vec<Instruction>> = transfers
+            .iter()
+            .zip(&destinations)
+            .map(|(transfer, destination)| {
+                payment_instructions(token.as_ref(), &source, destination, transfer.amount_lamports, transfer.memo.as_deref())
+            })
+            .collect();
+        // Token transfers move no lamports, so safe mode's lamport cap does not apply to them
+        let lamports: u64 = match &token {
+            Some(_) if vote_account.is_none() => 0,
+            _ => transfers.iter().map(|transfer| transfer.amount_lamports).sum(),
+        };
+        let mut dest_addrs: Vec<String> = destinations.iter().map(Pubkey::to_string).collect();
+        // Retries and reports name the batch by its only destination, or its size
+        let label = match dest_addrs.as_slice() {
+            [destination] => format!("{} -> {}", source_addr, destination),
+            _ => format!("{} -> {} destinations", source_addr, dest_addrs.len()),
+        };
+        
+        let future = async move {
+            // Wait for a slot under the fixed cap, then the adaptive controller, when they limit sends in flight
+            let queued = Instant::now();
+            let slot = limiter_ref.acquire().await;
+            let permit = match &controller_ref {
+                Some(controller) => Some(controller.acquire().await),
+                None => None,
+            };
+            let mut queue_time = queued.elapsed();
+            
+            // Recreate the keypair from bytes
+            let keypair_copy = Keypair::from_bytes(&keypair_bytes).unwrap();
+            let source_key = SourceKey {
+                keypair: &keypair_copy,
+                nonce: nonce.as_ref(),
             };
             
-            let client_ref = client.clone();
-            // We need to copy the keypair data since it doesn't implement Clone
-            let keypair_bytes = source_keypair.to_bytes();
-            let source_addr = source.address.clone();
-            let dest_addr_clone = dest_addr.clone();
-            let amount = config.amount_lamports;
+            let accounts: Vec<Pubkey> = std::iter::once(source).chain(destinations.iter().copied()).collect();
+            let unit_price = match &oracle_ref {
+                Some(oracle) => match oracle.suggest_fee(&accounts, oracle.urgency()).await {
+                    Ok(fee) => Some(fee),
+                    Err(e) => {
+                        eprintln!("No priority fee for {}: {}", label, e);
+                        None
+                    }
+                },
+                None => None,
+            };
             
-            let future = async move {
-                // Recreate the keypair from bytes
-                let keypair_copy = Keypair::from_bytes(&keypair_bytes).unwrap();
+            let budget = ComputeBudget {
+                unit_price,
+                limit: compute_limit,
+            };
+            
+            // Simulated transfers are not audited or called back, as nothing was sent
+            if dry_run {
+                let paced = Instant::now();
+                limiter_ref.pace().await;
+                queue_time += paced.elapsed();
+                let start = Instant::now();
+                let mut signed = None;
+                let simulated = match vote_account {
+                    Some(vote_account) => simulate_stake_transaction(&client_ref, source_key, &destinations[0], &vote_account, transfers[0].amount_lamports, &budget),
+                    None => sign_batch(&client_ref, source_key, instructions, &budget).and_then(|batch| {
+                        let simulated = simulate_batch(&client_ref, &batch);
+                        signed = Some(batch);
+                        simulated
+                    }),
+                };
+                if let Some(permit) = permit {
+                    let overloaded = simulated.as_ref().is_err_and(TransferError::is_overload);
+                    permit.finish(if overloaded { SendOutcome::Overloaded } else { SendOutcome::Completed });
+                }
+                drop(slot);
                 
-                let result = send_transaction(&client_ref, &keypair_copy, &destination, amount).await;
+                let failed_index = match &simulated {
+                    Ok(simulation) => match &simulation.error {
+                        Some(TransactionError::InstructionError(index, _)) => Some(*index),
+                        _ => None,
+                    },
+                    Err(e) => e.instruction_index(),
+                };
+                let failed = failed_index.and_then(|index| signed.as_ref()?.transfer_at(index));
+                let time_ms = start.elapsed().as_millis();
+                
+                // The batch's fee, compute units and logs are reported on its first transfer
+                let mut simulation = simulated.as_ref().ok().cloned();
+                let mut results = Vec::with_capacity(dest_addrs.len());
+                for (index, dest_addr) in dest_addrs.into_iter().enumerate() {
+                    let rolled_back = failed
+                        .filter(|&failed| failed != index)
+                        .map(|failed| TransferError::RolledBack { destination: destinations[failed].to_string() });
+                    let (signature, status, failure) = match (&simulated, rolled_back) {
+                        (_, Some(rolled_back)) => ("Simulated", format!("Error: simulation failed: {}", rolled_back), Some(rolled_back.failure_class())),
+                        (Ok(simulation), None) => match &simulation.error {
+                            None => ("Simulated", "Success".to_string(), None),
+                            Some(error) => ("Simulated", format!("Error: simulation failed: {}", error), Some(FailureClass::from_transaction_error(error))),
+                        },
+                        (Err(e), None) => ("Failed", format!("Error: {}", e), Some(e.failure_class())),
+                    };
+                    results.push(TransactionResult {
+                        source: source_addr.clone(),
+                        destination: dest_addr,
+                        memo: transfers[index].memo.clone(),
+                        signature: signature.to_string(),
+                        status,
+                        failure,
+                        time_ms: if simulated.is_ok() { time_ms } else { 0 },
+                        queue_ms: queue_time.as_millis(),
+                        attempts: 1,
+                        commitment: None,
+                        simulation: simulation.take(),
+                    });
+                }
+                return results;
+            }
+            
+            // Held until the transfer is confirmed, so the next one signs with the advanced nonce
+            let _nonce_guard = match &nonce_lock {
+                Some(lock) => Some(lock.lock().await),
+                None => None,
+            };
+            
+            let payload = match vote_account {
+                Some(vote_account) => Payload::Stake {
+                    authority: destinations[0],
+                    vote_account,
+                    lamports,
+                },
+                None => Payload::Transfers(instructions),
+            };
+            let journaled: Vec<JournalHandle> = journal_entries.iter().flatten().cloned().collect();
+            let delivery = deliver(&endpoints_ref, source_key, &payload, lamports, &budget, &policy, &limiter_ref, &journaled, &label).await;
+            queue_time += delivery.paced;
+            let attempts = delivery.attempts;
+            let commitment = delivery.commitment;
+            
+            // The lamports land in the new stake account, so it is what gets audited and reported
+            if let (Some(stake_account), Some(vote_account)) = (delivery.stake_account, vote_account) {
+                println!("Stake account {} for {} delegated to {}", stake_account, dest_addrs[0], vote_account);
+                dest_addrs[0] = stake_account.to_string();
+            }
+            
+            if let Some(permit) = permit {
+                let overloaded = delivery.result.as_ref().is_err_and(TransferError::is_overload);
+                permit.finish(if overloaded { SendOutcome::Overloaded } else { SendOutcome::Completed });
+            }
+            drop(slot);
+            
+            // A transaction fails as a whole: the transfer whose instruction failed gets the error,
+            // the others of its batch are reported as rolled back
+            let failed = delivery.failed_transfer();
+            let last_valid_block_height = delivery.last_valid_block_height();
+            let result = delivery.result;
+            
+            let mut results = Vec::with_capacity(dest_addrs.len());
+            for (index, ((transfer, dest_addr), journal_entry)) in transfers.into_iter().zip(dest_addrs).zip(journal_entries).enumerate() {
+                let amount = transfer.amount_lamports;
+                let memo = transfer.memo;
+                let rolled_back: Result<(String, u128), TransferError>;
+                let result = match failed {
+                    Some(failed) if failed != index => {
+                        rolled_back = Err(TransferError::RolledBack { destination: destinations[failed].to_string() });
+                        &rolled_back
+                    }
+                    _ => &result,
+                };
+                
+                // Expired transfers can be sent again on the next run, abandoned ones may still land
+                if let Some(journal_entry) = &journal_entry {
+                    let journaled = journal_outcome(journal_entry, result, policy.awaits_confirmation(), last_valid_block_height);
+                    if let Err(e) = journaled {
+                        eprintln!("Failed to journal transfer {} -> {}: {}", source_addr, dest_addr, e);
+                    }
+                }
+                
+                // Abandoned, unsettled and expired transfers were (or may have been) broadcast, so they are audited too
+                let submitted = match result {
+                    Ok((signature, _))
+                    | Err(TransferError::Abandoned { signature, .. })
+                    | Err(TransferError::Unsettled { signature, .. })
+                    | Err(TransferError::Expired { signature }) => Some(signature),
+                    Err(_) => None,
+                };
+                if let (Some(signature), Some(audit)) = (submitted, &audit_ref) {
+                    let record = AuditRecord {
+                        origin: "task2".to_string(),
+                        source: source_addr.clone(),
+                        destination: dest_addr.clone(),
+                        lamports: amount,
+                        signature: signature.clone(),
+                        manifest: manifest_ref.clone(),
+                    };
+                    if let Err(e) = audit.append(record) {
+                        eprintln!("Failed to write audit entry for {}: {}", signature, e);
+                    }
+                }
                 
-                match result {
+                // Post the final status, waiting for sent transfers to land for their slot and fee
+                if let Some(webhook) = &webhook_ref {
+                    let callback = TransferCallback::resolve(
+                        &client_ref,
+                        result,
+                        source_addr.clone(),
+                        dest_addr.clone(),
+                        amount,
+                        manifest_ref.clone(),
+                    )
+                    .await;
+                    if let Err(e) = webhook.post(&callback).await {
+                        eprintln!("Failed to post transfer callback for {}: {}", source_addr, e);
+                    }
+                }
+                
+                results.push(match result {
                     Ok((signature, time_ms)) => TransactionResult {
-                        source: source_addr,
-                        destination: dest_addr_clone,
-                        signature,
+                        source: source_addr.clone(),
+                        destination: dest_addr,
+                        memo: memo.clone(),
+                        signature: signature.clone(),
                         status: "Success".to_string(),
-                        time_ms,
+                        failure: None,
+                        time_ms: *time_ms,
+                        queue_ms: queue_time.as_millis(),
+                        attempts,
+                        commitment,
+                        simulation: None,
+                    },
+                    Err(TransferError::Abandoned { signature, .. } | TransferError::Unsettled { signature, .. }) => TransactionResult {
+                        source: source_addr.clone(),
+                        destination: dest_addr,
+                        memo: memo.clone(),
+                        signature: signature.clone(),
+                        status: "Abandoned".to_string(),
+                        failure: Some(FailureClass::Unconfirmed),
+                        time_ms: 0,
+                        queue_ms: queue_time.as_millis(),
+                        attempts,
+                        commitment: None,
+                        simulation: None,
+                    },
+                    Err(TransferError::Expired { signature }) => TransactionResult {
+                        source: source_addr.clone(),
+                        destination: dest_addr,
+                        memo: memo.clone(),
+                        signature: signature.clone(),
+                        status: "Expired".to_string(),
+                        failure: Some(FailureClass::BlockhashExpired),
+                        time_ms: 0,
+                        queue_ms: queue_time.as_millis(),
+                        attempts,
+                        commitment: None,
+                        simulation: None,
                     },
                     Err(e) => TransactionResult {
-                        source: source_addr,
-                        destination: dest_addr_clone,
+                        source: source_addr.clone(),
+                        destination: dest_addr,
+                        memo: memo.clone(),
                         signature: "Failed".to_string(),
                         status: format!("Error: {}", e),
+                        failure: Some(e.failure_class()),
                         time_ms: 0,
+                        queue_ms: queue_time.as_millis(),
+                        attempts,
+                        commitment: None,
+                        simulation: None,
                     },
-                }
-            };
-            
-            futures.push(future);
-        }
+                });
+            }
+            results
+        };
+        
+        // The RPC client blocks, so each transaction gets its own blocking thread: on one task
+        // the sends would take turns, and max_concurrent_sends, requests_per_second and the
+        // adaptive controller would never see more than one of them in flight
+        let runtime = tokio::runtime::Handle::current();
+        tasks.push(tokio::task::spawn_blocking(move || runtime.block_on(future)));
     }
+
+    // Wait for every transaction; they run concurrently within the send limits
+    let results: Vec<TransactionResult> = futures::future::join_all(tasks)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()
+        .context("A transfer task panicked")?
+        .into_iter()
+        .flatten()
+        .collect();
     
-    // Execute all futures concurrently
-    let results = futures::future::join_all(futures).await;
-    
-    // Process and display results
-    println!("\nTransaction Results:");
-    println!("{:<10} {:<44} {:<44} {:<64} {:<20}", "Status", "Source", "Destination", "Signature", "Time (ms)");
+    // Process and display results; other formats print the rendered report at the end instead
+    let table = args.output == OutputFormat::Table;
+    if table && args.dry_run {
+        println!("\nSimulated Transactions (dry run, nothing was sent):");
+        println!("{:<10} {:<44} {:<44} {:<16} {:<16} Failure", "Status", "Source", "Destination", "Fee (lamports)", "Compute units");
+    } else if table {
+        println!("\nTransaction Results:");
+        println!("{:<10} {:<44} {:<44} {:<64} {:<20} Failure", "Status", "Source", "Destination", "Signature", "Time (ms)");
+    }
     
-    let mut success_count = 0;
+    let mut summary = RunSummary {
+        started_at: started_at.to_rfc3339(),
+        finished_at: Utc::now().to_rfc3339(),
+        dry_run: args.dry_run,
+        total: results.len(),
+        manifest: manifest_hash.clone(),
+        ..RunSummary::default()
+    };
     let mut total_time = 0;
+    let mut total_queue_time = 0;
     
     for result in &results {
-        println!(
-            "{:<10} {:<44} {:<44} {:<64} {:<20}",
-            if result.signature != "Failed" { "Success" } else { "Failed" },
-            result.source,
-            result.destination,
-            result.signature,
-            result.time_ms
-        );
+        let status = match result.status.as_str() {
+            "Success" | "Abandoned" | "Expired" => result.status.as_str(),
+            _ => "Failed",
+        };
+        // Confirmed transfers show the commitment they reached
+        let label = match result.commitment {
+            Some(Commitment::Processed) => "Processed",
+            Some(Commitment::Confirmed) => "Confirmed",
+            Some(Commitment::Finalized) => "Finalized",
+            None => status,
+        };
+        if table && args.dry_run {
+            let simulation = result.simulation.as_ref();
+            println!(
+                "{:<10} {:<44} {:<44} {:<16} {:<16} {}",
+                status,
+                book.display(&result.source),
+                book.display(&result.destination),
+                simulation.map_or("-".to_string(), |simulation| simulation.fee_lamports.to_string()),
+                simulation
+                    .and_then(|simulation| simulation.units_consumed)
+                    .map_or("-".to_string(), |units| units.to_string()),
+                result.failure.map_or("", |class| class.as_str())
+            );
+        } else if table {
+            println!(
+                "{:<10} {:<44} {:<44} {:<64} {:<20} {}",
+                label,
+                book.display(&result.source),
+                book.display(&result.destination),
+                result.signature,
+                result.time_ms,
+                result.failure.map_or("", |class| class.as_str())
+            );
+        }
+        if table && status == "Failed" {
+            println!("           {}", result.status);
+        }
+        if let Some(simulation) = result.simulation.as_ref().filter(|_| table) {
+            for line in &simulation.logs {
+                println!("           {}", line);
+            }
+        }
+        if let Some(class) = result.failure {
+            *summary.failures_by_class.entry(class).or_default() += 1;
+        }
+        if result.attempts > 1 {
+            summary.retried += 1;
+        }
+        total_queue_time += result.queue_ms;
         
-        if result.signature != "Failed" {
-            success_count += 1;
-            total_time += result.time_ms;
+        match status {
+            "Success" => {
+                summary.successful += 1;
+                total_time += result.time_ms;
+            }
+            "Abandoned" => summary.abandoned += 1,
+            "Expired" => summary.expired += 1,
+            _ => summary.failed += 1,
         }
     }
     
-    let avg_time = if success_count > 0 {
-        total_time as f64 / success_count as f64
+    if summary.successful > 0 {
+        summary.avg_time_ms = total_time as f64 / summary.successful as f64;
+    }
+    if summary.total > 0 {
+        summary.avg_queue_ms = total_queue_time as f64 / summary.total as f64;
+    }
+    summary.fees_lamports = if args.dry_run {
+        Some(
+            results
+                .iter()
+                .filter_map(|result| result.simulation.as_ref())
+                .map(|simulation| simulation.fee_lamports)
+                .sum(),
+        )
     } else {
-        0.0
+        fee_ceiling.as_ref().map(|ceiling| ceiling.spent())
     };
     
-    println!("\nSummary:");
-    println!("Total transactions: {}", results.len());
-    println!("Successful transactions: {}", success_count);
-    println!("Failed transactions: {}", results.len() - success_count);
-    println!("Average processing time: {:.2} ms", avg_time);
+    if table {
+        println!("\nSummary:");
+        println!("Total transactions: {}", summary.total);
+        if args.dry_run {
+            println!("Would succeed: {}", summary.successful);
+            println!("Would fail: {}", summary.total - summary.successful);
+            println!("Estimated fees: {} lamports", summary.fees_lamports.unwrap_or_default());
+        } else {
+            println!("Successful transactions: {}", summary.successful);
+            println!("Failed transactions: {}", summary.failed);
+        }
+        if summary.abandoned > 0 {
+            println!("Abandoned transactions: {}", summary.abandoned);
+        }
+        if summary.expired > 0 {
+            println!("Expired transactions: {}", summary.expired);
+        }
+        for (class, count) in &summary.failures_by_class {
+            println!("  {}: {}", class, count);
+        }
+        if summary.retried > 0 {
+            println!("Retried transactions: {}", summary.retried);
+        }
+        println!("Average processing time: {:.2} ms", summary.avg_time_ms);
+        if config.send_limits.max_concurrent_sends.is_some() || config.send_limits.requests_per_second.is_some() {
+            println!("Average queue wait: {:.2} ms", summary.avg_queue_ms);
+        }
+        if let Some(ceiling) = fee_ceiling.as_ref().filter(|_| !args.dry_run) {
+            println!("Fees: {} of at most {} lamports", ceiling.spent(), ceiling.max_lamports());
+            if ceiling.is_tripped() {
+                println!("Fee ceiling reached: remaining transfers were not sent");
+            }
+        }
+        if let Some(manifest_hash) = &manifest_hash {
+            println!("Run manifest: {}", manifest_hash);
+        }
+        if let Some(controller) = &controller {
+            println!("Adaptive concurrency limit at end of run: {}", controller.limit());
+        }
+    } else {
+        print!("{}", render_report(args.output, &results, &summary)?);
+    }
+    
+    // Keep a rendered report on stdout parseable
+    for endpoint in rpc::metrics() {
+        let mut line = format!(
+            "RPC {}: {} requests, {} rate limited, {} errors, avg latency {:.1} ms",
+            endpoint.endpoint,
+            endpoint.requests,
+            endpoint.rate_limited,
+            endpoint.errors,
+            endpoint.avg_latency_ms
+        );
+        if rpc_urls.len() > 1 {
+            line.push_str(&format!(
+                ", {} failed over, {}",
+                endpoint.failovers,
+                if endpoint.healthy { "healthy" } else { "circuit open" }
+            ));
+        }
+        if table {
+            println!("{}", line);
+        } else {
+            eprintln!("{}", line);
+        }
+    }
+    
+    if let Some((path, format)) = report {
+        write_report(path, format, &results, &summary)?;
+        if table {
+            println!("Report written to {}", path.display());
+        } else {
+            eprintln!("Report written to {}", path.display());
+        }
+    }
     
     Ok(())
 }
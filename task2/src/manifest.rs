@@ -0,0 +1,225 @@
+use crate::plan::TransferKind;
+use serde::{Deserialize, Serialize};
+use solana_common::audit::AuditEntry;
+use solana_sdk::{
+    hash::{hash, Hash},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signature, Signer},
+};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// Where signed run manifests are written and which operator key approves them; off
+/// unless `dir` is set
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ManifestConfig {
+    /// Directory receiving one `manifest-<hash>.json` per run
+    pub dir: Option<PathBuf>,
+    /// Keypair file of the operator key signing every manifest
+    pub key: Option<PathBuf>,
+}
+
+#[derive(Debug, Error)]
+pub enum ManifestError {
+    #[error("Manifest {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Failed to load operator key {}: {reason}", path.display())]
+    Key { path: PathBuf, reason: String },
+
+    #[error("Manifest {} is not valid: {reason}", path.display())]
+    Corrupt { path: PathBuf, reason: String },
+
+    #[error("Manifest was modified (hash mismatch)")]
+    HashMismatch,
+
+    #[error("Manifest has an invalid operator signature")]
+    BadSignature,
+
+    #[error("Manifest was signed by unexpected key {operator}")]
+    UnexpectedOperator { operator: String },
+}
+
+impl ManifestError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ManifestError::Io { .. } => "MANIFEST_IO",
+            ManifestError::Key { .. } => "MANIFEST_KEY",
+            ManifestError::Corrupt { .. } => "MANIFEST_CORRUPT",
+            ManifestError::HashMismatch => "MANIFEST_HASH_MISMATCH",
+            ManifestError::BadSignature => "MANIFEST_BAD_SIGNATURE",
+            ManifestError::UnexpectedOperator { .. } => "MANIFEST_UNEXPECTED_OPERATOR",
+        }
+    }
+}
+
+/// One approved transfer, with aliases resolved to addresses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestTransfer {
+    pub source: String,
+    /// Recipient; for stake transfers the staker and withdrawer of the new stake account
+    pub destination: String,
+    pub lamports: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    #[serde(default, skip_serializing_if = "TransferKind::is_system")]
+    pub kind: TransferKind,
+}
+
+/// Every transfer of a run, fixed before the first one is sent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunManifest {
+    pub created_at: String,
+    pub transfers: Vec<ManifestTransfer>,
+}
+
+impl RunManifest {
+    pub fn new(transfers: Vec<ManifestTransfer>) -> Self {
+        Self {
+            created_at: chrono::Utc::now().to_rfc3339(),
+            transfers,
+        }
+    }
+
+    /// Compact JSON with fields in declaration order, the form that is hashed
+    pub fn canonical_json(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("run manifest serializes")
+    }
+
+    pub fn hash(&self) -> Hash {
+        hash(&self.canonical_json())
+    }
+}
+
+/// A manifest with its hash and the operator key's signature over that hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedManifest {
+    pub manifest: RunManifest,
+    pub hash: String,
+    pub operator: String,
+    pub signature: String,
+}
+
+/// Audit entries of a run compared with its manifest
+#[derive(Debug, Default)]
+pub struct ManifestCheck<'a> {
+    /// Entries tagged with the manifest that match no approved transfer
+    pub unapproved: Vec<&'a AuditEntry>,
+    /// Approved transfers without an audit entry (failed or never sent)
+    pub unsent: Vec<&'a ManifestTransfer>,
+}
+
+impl ManifestConfig {
+    /// Sign `manifest` with the operator key and write it to `dir`; `None` when manifests
+    /// are not configured
+    pub fn write(
+        &self,
+        manifest: RunManifest,
+    ) -> Result<Option<(SignedManifest, PathBuf)>, ManifestError> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+
+        let key_path = self.key.as_ref().ok_or_else(|| ManifestError::Key {
+            path: PathBuf::new(),
+            reason: "manifest.key must be set when manifest.dir is".to_string(),
+        })?;
+        let key = read_keypair_file(key_path).map_err(|e| ManifestError::Key {
+            path: key_path.clone(),
+            reason: e.to_string(),
+        })?;
+
+        let signed = SignedManifest::sign(manifest, &key);
+        let path = dir.join(format!("manifest-{}.json", signed.hash));
+        let io_error = |source| ManifestError::Io {
+            path: path.clone(),
+            source,
+        };
+        fs::create_dir_all(dir).map_err(io_error)?;
+        let json = serde_json::to_string_pretty(&signed).expect("signed manifest serializes");
+        fs::write(&path, json).map_err(io_error)?;
+
+        Ok(Some((signed, path)))
+    }
+}
+
+impl SignedManifest {
+    pub fn sign(manifest: RunManifest, key: &Keypair) -> Self {
+        let manifest_hash = manifest.hash();
+        Self {
+            manifest,
+            hash: manifest_hash.to_string(),
+            operator: key.pubkey().to_string(),
+            signature: key.sign_message(manifest_hash.as_ref()).to_string(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let json = fs::read_to_string(path).map_err(|source| ManifestError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        serde_json::from_str(&json).map_err(|e| ManifestError::Corrupt {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Recompute the hash and check the signature. When `operator` is given, the
+    /// manifest must also be signed by that key.
+    pub fn verify(&self, operator: Option<&Pubkey>) -> Result<(), ManifestError> {
+        let manifest_hash = self.manifest.hash();
+        if manifest_hash.to_string() != self.hash {
+            return Err(ManifestError::HashMismatch);
+        }
+
+        let signer = Pubkey::from_str(&self.operator).map_err(|_| ManifestError::BadSignature)?;
+        if operator.is_some_and(|operator| *operator != signer) {
+            return Err(ManifestError::UnexpectedOperator {
+                operator: self.operator.clone(),
+            });
+        }
+
+        let signature =
+            Signature::from_str(&self.signature).map_err(|_| ManifestError::BadSignature)?;
+        if !signature.verify(signer.as_ref(), manifest_hash.as_ref()) {
+            return Err(ManifestError::BadSignature);
+        }
+        Ok(())
+    }
+
+    /// Match the audit entries tagged with this manifest's hash against its transfers,
+    /// each approved transfer covering at most one entry. Stake transfers are audited
+    /// with the new stake account as destination, so only their source and amount count.
+    pub fn check<'a>(&'a self, entries: &'a [AuditEntry]) -> ManifestCheck<'a> {
+        let mut open: Vec<&ManifestTransfer> = self.manifest.transfers.iter().collect();
+        let mut check = ManifestCheck::default();
+
+        for entry in entries {
+            if entry.record.manifest.as_deref() != Some(self.hash.as_str()) {
+                continue;
+            }
+            let record = &entry.record;
+            let matched = open.iter().position(|transfer| {
+                transfer.source == record.source
+                    && transfer.lamports == record.lamports
+                    && (transfer.kind == TransferKind::Stake
+                        || transfer.destination == record.destination)
+            });
+            match matched {
+                Some(index) => {
+                    open.remove(index);
+                }
+                None => check.unapproved.push(entry),
+            }
+        }
+
+        check.unsent = open;
+        check
+    }
+}
@@ -0,0 +1,766 @@
+use crate::{
+    concurrency::SendLimiter,
+    confirmation::{await_commitment, Commitment, ConfirmationConfig},
+    failure::FailureClass,
+    journal::{JournalHandle, JournalStatus},
+    transfer::{
+        send_batch, sign_batch, sign_stake, SendPolicy, SignedBatch, SourceKey, TransferError,
+    },
+};
+use solana_client::rpc_client::RpcClient;
+use solana_common::{ComputeBudget, ConfigError, EndpointPool, RetryPolicy};
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+use std::time::{Duration, Instant};
+
+/// How the transfers of a run are sent, confirmed and retried; the same for all of them
+#[derive(Clone)]
+pub struct DeliveryPolicy {
+    pub send: SendPolicy,
+    pub confirmation: ConfirmationConfig,
+    /// Times a transfer that failed without landing is signed and sent again
+    pub max_retries: u32,
+    pub retry: RetryPolicy,
+}
+
+impl DeliveryPolicy {
+    /// Whether a sent transfer is only reported once it was confirmed (or given up on)
+    pub fn awaits_confirmation(&self) -> bool {
+        self.confirmation.timeout().is_some() || self.send.deadline.is_some()
+    }
+}
+
+/// What a batch signs on every attempt
+#[derive(Debug, Clone)]
+pub enum Payload {
+    /// The instructions of each transfer paid by the source, signed into one transaction
+    Transfers(Vec<Vec<Instruction>>),
+    /// A new stake account funded with `lamports`, controlled by `authority` and delegated to
+    /// `vote_account`
+    Stake {
+        authority: Pubkey,
+        vote_account: Pubkey,
+        lamports: u64,
+    },
+}
+
+impl Payload {
+    fn sign(
+        &self,
+        client: &RpcClient,
+        source: SourceKey<'_>,
+        budget: &ComputeBudget,
+    ) -> Result<(SignedBatch, Option<Pubkey>), TransferError> {
+        match self {
+            Payload::Transfers(transfers) => {
+                sign_batch(client, source, transfers.clone(), budget).map(|batch| (batch, None))
+            }
+            Payload::Stake {
+                authority,
+                vote_account,
+                lamports,
+            } => sign_stake(client, source, authority, vote_account, *lamports, budget)
+                .map(|(batch, stake_account)| (batch, Some(stake_account))),
+        }
+    }
+}
+
+/// How sending one batch ended
+pub struct Delivery {
+    pub result: Result<(String, u128), TransferError>,
+    /// Sends it took, retries included
+    pub attempts: u32,
+    /// Commitment the transaction reached, unless confirmation is disabled
+    pub commitment: Option<Commitment>,
+    /// Time spent waiting for the send rate limit
+    pub paced: Duration,
+    /// The last signed attempt, to tell which transfer a failed instruction belongs to
+    pub batch: Option<SignedBatch>,
+    /// Stake account funded by a stake payload, once it was sent
+    pub stake_account: Option<Pubkey>,
+}
+
+impl Delivery {
+    /// Transfer of the batch whose instruction made the transaction fail, when the node said
+    pub fn failed_transfer(&self) -> Option<usize> {
+        match (&self.result, &self.batch) {
+            (Err(e), Some(batch)) => e
+                .instruction_index()
+                .and_then(|index| batch.transfer_at(index)),
+            _ => None,
+        }
+    }
+
+    /// Last block height the last attempt could land at, None for durable nonce transactions
+    /// and attempts that were never signed
+    pub fn last_valid_block_height(&self) -> Option<u64> {
+        self.batch
+            .as_ref()
+            .and_then(SignedBatch::last_valid_block_height)
+    }
+}
+
+/// Sign `payload` from `source`, send it like a transfer of `lamports` and wait for it to be
+/// confirmed, journaling its signature in every `journal` entry of the batch before each
+/// send. Rejected attempts are sent again as signed while their blockhash is valid, expired
+/// ones are signed again with a fresh blockhash, up to the policy's retries; `label` names the
+/// batch in retry messages. An attempt that may have been broadcast is only ever confirmed: it
+/// is signed again once it expired, never before.
+///
+/// Every attempt is signed, sent and confirmed through one endpoint of `endpoints`, so its
+/// expiry and status come from the node that handed out its blockhash and accepted it.
+#[allow(clippy::too_many_arguments)]
+pub async fn deliver(
+    endpoints: &EndpointPool,
+    source: SourceKey<'_>,
+    payload: &Payload,
+    lamports: u64,
+    budget: &ComputeBudget,
+    policy: &DeliveryPolicy,
+    limiter: &SendLimiter,
+    journal: &[JournalHandle],
+    label: &str,
+) -> Delivery {
+    let mut attempts = 0;
+    let mut paced = Duration::ZERO;
+    let mut batch: Option<SignedBatch> = None;
+    let mut signed_stake = None;
+    let mut stake_account = None;
+    let mut resend = false;
+    loop {
+        attempts += 1;
+        let pace_start = Instant::now();
+        limiter.pace().await;
+        paced += pace_start.elapsed();
+
+        let client = endpoints.pick();
+        // A rejected attempt that can still land is sent again as signed, so that all of its
+        // copies share one signature; signing a new one could land both
+        let reuse = resend
+            && batch
+                .as_ref()
+                .is_some_and(|previous| previous.is_live(client).unwrap_or(false));
+        let signed = if reuse {
+            Ok(())
+        } else {
+            payload.sign(client, source, budget).map(|(signed, stake)| {
+                batch = Some(signed);
+                signed_stake = stake;
+            })
+        };
+        // Journal the signature before broadcasting, so a crash at any point leaves the
+        // transfer sent for the next run to look up rather than pending and paid twice
+        let journaled = signed.and_then(|()| {
+            let signed = batch.as_ref().expect("signed or reused above");
+            let signature = signed.signature().to_string();
+            for entry in journal {
+                entry.mark_sent(&signature, signed.last_valid_block_height())?;
+            }
+            Ok(signed)
+        });
+        let result = match journaled {
+            Ok(signed) => {
+                let result = send_batch(client, signed, lamports, &policy.send).await;
+                if result.is_ok() {
+                    stake_account = signed_stake;
+                }
+                result
+            }
+            Err(e) => Err(e),
+        };
+
+        // A send that may have gone through is confirmed like a sent one and never signed again
+        let result = match result {
+            Err(TransferError::Unsettled { signature, source })
+                if policy.confirmation.timeout().is_some() =>
+            {
+                eprintln!(
+                    "Transfer {} may have been sent ({}), waiting for it to land or expire",
+                    label, source
+                );
+                Ok((signature, batch.as_ref().map_or(0, SignedBatch::elapsed_ms)))
+            }
+            result => result,
+        };
+
+        let last_valid_block_height = batch
+            .as_ref()
+            .and_then(SignedBatch::last_valid_block_height);
+
+        // A sent transfer is only reported once it reaches the configured commitment, expires or times out
+        let mut commitment = None;
+        let result = match (result, policy.confirmation.timeout()) {
+            (Ok((signature, time_ms)), Some(timeout)) => {
+                match await_commitment(
+                    client,
+                    &signature,
+                    policy.confirmation.commitment,
+                    timeout,
+                    last_valid_block_height,
+                )
+                .await
+                {
+                    Ok(reached) => {
+                        commitment = Some(reached);
+                        Ok((signature, time_ms))
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            (result, _) => result,
+        };
+
+        match &result {
+            Err(e) if e.is_retryable() && attempts <= policy.max_retries => {
+                // An expired blockhash, or one the node does not know, needs a new signature
+                resend = e.failure_class() != FailureClass::BlockhashExpired;
+                let delay = policy.retry.delay(attempts);
+                eprintln!(
+                    "Transfer {} failed ({}), retrying in {} ms ({} of {} retries)",
+                    label,
+                    e,
+                    delay.as_millis(),
+                    attempts,
+                    policy.max_retries
+                );
+                tokio::time::sleep(delay).await;
+            }
+            _ => {
+                return Delivery {
+                    result,
+                    attempts,
+                    commitment,
+                    paced,
+                    batch,
+                    stake_account,
+                }
+            }
+        }
+    }
+}
+
+/// Journal how one transfer of a delivered batch ended: confirmed, failed, pending again once
+/// expired, or still sent when abandoned or unsettled since it may land until
+/// `last_valid_block_height`.
+/// `confirmed` says whether a successful result was confirmed; unconfirmed ones stay sent for
+/// the next run to check.
+pub fn journal_outcome(
+    entry: &JournalHandle,
+    result: &Result<(String, u128), TransferError>,
+    confirmed: bool,
+    last_valid_block_height: Option<u64>,
+) -> Result<(), ConfigError> {
+    match result {
+        Ok(_) if !confirmed => Ok(()),
+        Ok(_) => entry.mark(JournalStatus::Confirmed, None),
+        Err(
+            TransferError::Abandoned { signature, .. } | TransferError::Unsettled { signature, .. },
+        ) => entry.mark_sent(signature, last_valid_block_height),
+        Err(TransferError::Expired { .. }) => entry.mark(JournalStatus::Pending, None),
+        Err(e) => entry.mark(JournalStatus::Failed, Some(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{concurrency::SendLimitsConfig, confirmation::Commitment, journal::RunJournal};
+    use async_trait::async_trait;
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+    use serde_json::{json, Value};
+    use solana_client::{
+        client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+        rpc_client::RpcClientConfig,
+        rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
+        rpc_sender::{RpcSender, RpcTransportStats},
+    };
+    use solana_common::{EndpointPool, SafetyConfig, SubmissionConfig, Submitter};
+    use solana_sdk::{
+        hash::Hash,
+        signature::{Keypair, Signer},
+        system_instruction,
+        transaction::Transaction,
+    };
+    use std::{
+        collections::{HashMap, VecDeque},
+        io,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    /// What the node answers a request with
+    enum Answer {
+        Result(Value),
+        /// A JSON-RPC error code and message
+        Error(i64, &'static str),
+        /// The connection dropped before an answer arrived
+        Dropped,
+    }
+
+    type Script = Box<dyn Fn(&Value) -> Answer + Send + Sync>;
+
+    /// RPC node answering from scripts: each method replies with its queued replies in order,
+    /// then with a default for a healthy cluster where every transaction lands confirmed
+    #[derive(Default)]
+    struct MockNode {
+        scripts: Mutex<HashMap<String, VecDeque<Script>>>,
+        calls: Mutex<Vec<(String, Value)>>,
+        /// Blockhashes handed out, in order
+        blockhashes: Mutex<Vec<Hash>>,
+    }
+
+    impl MockNode {
+        fn script(&self, method: &str, reply: impl Fn(&Value) -> Answer + Send + Sync + 'static) {
+            self.scripts
+                .lock()
+                .unwrap()
+                .entry(method.to_string())
+                .or_default()
+                .push_back(Box::new(reply));
+        }
+
+        fn calls(&self, method: &str) -> Vec<Value> {
+            let calls = self.calls.lock().unwrap();
+            calls
+                .iter()
+                .filter(|(called, _)| called == method)
+                .map(|(_, params)| params.clone())
+                .collect()
+        }
+
+        /// Signatures of the transactions broadcast, in order
+        fn sent(&self) -> Vec<String> {
+            self.calls("sendTransaction")
+                .iter()
+                .map(signature_of)
+                .collect()
+        }
+
+        fn default_result(&self, method: &str, params: &Value) -> Value {
+            match method {
+                "getVersion" => json!({ "solana-core": "1.18.26", "feature-set": 0 }),
+                "getLatestBlockhash" => {
+                    let blockhash = Hash::new_unique();
+                    self.blockhashes.lock().unwrap().push(blockhash);
+                    context(json!({
+                        "blockhash": blockhash.to_string(),
+                        "lastValidBlockHeight": 400,
+                    }))
+                }
+                "getBlockHeight" => json!(100),
+                "sendTransaction" => json!(signature_of(params)),
+                "getSignatureStatuses" => context(json!([status("confirmed", None)])),
+                other => panic!("unexpected {} request", other),
+            }
+        }
+    }
+
+    struct MockSender(Arc<MockNode>);
+
+    #[async_trait]
+    impl RpcSender for MockSender {
+        async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+            let method = request.to_string();
+            self.0
+                .calls
+                .lock()
+                .unwrap()
+                .push((method.clone(), params.clone()));
+            let scripted = self
+                .0
+                .scripts
+                .lock()
+                .unwrap()
+                .get_mut(&method)
+                .and_then(VecDeque::pop_front);
+            match scripted.map(|reply| reply(&params)) {
+                Some(Answer::Result(result)) => Ok(result),
+                Some(Answer::Error(code, message)) => Err(rpc_error(code, message)),
+                Some(Answer::Dropped) => {
+                    Err(ClientErrorKind::Io(io::ErrorKind::ConnectionReset.into()).into())
+                }
+                None => Ok(self.0.default_result(&method, &params)),
+            }
+        }
+
+        fn get_transport_stats(&self) -> RpcTransportStats {
+            RpcTransportStats::default()
+        }
+
+        fn url(&self) -> String {
+            "mock".to_string()
+        }
+    }
+
+    fn context(value: Value) -> Value {
+        json!({ "context": { "slot": 1 }, "value": value })
+    }
+
+    fn status(confirmation: &str, err: Option<Value>) -> Value {
+        json!({
+            "slot": 1,
+            "confirmations": null,
+            "err": err,
+            "status": match &err {
+                Some(err) => json!({ "Err": err }),
+                None => json!({ "Ok": null }),
+            },
+            "confirmationStatus": confirmation,
+        })
+    }
+
+    fn transaction_of(params: &Value) -> Transaction {
+        let wire = BASE64.decode(params[0].as_str().unwrap()).unwrap();
+        bincode::deserialize(&wire).unwrap()
+    }
+
+    fn signature_of(params: &Value) -> String {
+        transaction_of(params).signatures[0].to_string()
+    }
+
+    fn rpc_error(code: i64, message: &str) -> ClientError {
+        ClientErrorKind::RpcError(RpcError::RpcResponseError {
+            code,
+            message: message.to_string(),
+            data: RpcResponseErrorData::Empty,
+        })
+        .into()
+    }
+
+    fn client(node: &Arc<MockNode>) -> RpcClient {
+        RpcClient::new_sender(
+            MockSender(node.clone()),
+            RpcClientConfig::with_commitment(Default::default()),
+        )
+    }
+
+    fn policy(max_retries: u32) -> DeliveryPolicy {
+        DeliveryPolicy {
+            send: SendPolicy {
+                safety: SafetyConfig::default(),
+                submitter: Submitter::new(&SubmissionConfig::default()).unwrap(),
+                deadline: None,
+                fee_ceiling: None,
+            },
+            confirmation: Default::default(),
+            max_retries,
+            retry: RetryPolicy {
+                max_attempts: 1,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 1,
+                jitter: false,
+            },
+        }
+    }
+
+    fn payload(source: &Keypair) -> Payload {
+        let transfer = system_instruction::transfer(&source.pubkey(), &Pubkey::new_unique(), 1_000);
+        Payload::Transfers(vec![vec![transfer]])
+    }
+
+    /// A journal with one pending transfer, in its own file removed on drop
+    struct TempJournal {
+        path: PathBuf,
+        handle: JournalHandle,
+    }
+
+    impl TempJournal {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("pipeline-{}-{}.json", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let mut journal = RunJournal::open(&path).unwrap();
+            journal.track([("source", "destination", 1_000)]);
+            let handle = JournalHandle::new(Arc::new(Mutex::new(journal)), 0);
+            Self { path, handle }
+        }
+
+        fn saved(&self) -> RunJournal {
+            RunJournal::open(&self.path).unwrap()
+        }
+    }
+
+    impl Drop for TempJournal {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    async fn deliver_once(
+        node: &Arc<MockNode>,
+        policy: &DeliveryPolicy,
+        journal: &[JournalHandle],
+    ) -> Delivery {
+        deliver_through(std::slice::from_ref(node), policy, journal).await
+    }
+
+    /// Deliver a transfer through a pool of `nodes`, tried in order
+    async fn deliver_through(
+        nodes: &[Arc<MockNode>],
+        policy: &DeliveryPolicy,
+        journal: &[JournalHandle],
+    ) -> Delivery {
+        let source = Keypair::new();
+        let limiter = SendLimiter::new(SendLimitsConfig::default());
+        deliver(
+            &EndpointPool::from_clients(nodes.iter().map(client).collect()),
+            SourceKey::from(&source),
+            &payload(&source),
+            1_000,
+            &ComputeBudget::default(),
+            policy,
+            &limiter,
+            journal,
+            "test",
+        )
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sends_confirms_and_journals_a_transfer() {
+        let node = Arc::new(MockNode::default());
+        let journal = TempJournal::new("confirmed");
+
+        let delivery = deliver_once(&node, &policy(3), std::slice::from_ref(&journal.handle)).await;
+        let (signature, _) = delivery.result.as_ref().unwrap();
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.commitment, Some(Commitment::Confirmed));
+        assert_eq!(node.sent(), std::slice::from_ref(signature));
+
+        let entry = journal.saved().entries.remove(0);
+        assert_eq!(entry.status, JournalStatus::Sent);
+        assert_eq!(entry.signature.as_ref(), Some(signature));
+
+        journal_outcome(
+            &journal.handle,
+            &delivery.result,
+            true,
+            delivery.last_valid_block_height(),
+        )
+        .unwrap();
+        assert_eq!(journal.saved().entries[0].status, JournalStatus::Confirmed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rejected_sends_are_resent_as_signed() {
+        let node = Arc::new(MockNode::default());
+        node.script("sendTransaction", |_| {
+            Answer::Error(-32005, "Node is behind by 42 slots")
+        });
+
+        let delivery = deliver_once(&node, &policy(3), &[]).await;
+        assert!(delivery.result.is_ok());
+        assert_eq!(delivery.attempts, 2);
+        let sent = node.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0], sent[1], "a live transaction keeps its signature");
+        assert_eq!(node.calls("getLatestBlockhash").len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unknown_blockhashes_are_signed_again() {
+        let node = Arc::new(MockNode::default());
+        node.script("sendTransaction", |_| {
+            Answer::Error(-32002, "Transaction simulation failed: Blockhash not found")
+        });
+
+        let delivery = deliver_once(&node, &policy(3), &[]).await;
+        assert!(delivery.result.is_ok());
+        let sent = node.sent();
+        assert_eq!(sent.len(), 2);
+        assert_ne!(sent[0], sent[1], "a retry signs with a fresh blockhash");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rejected_sends_past_their_expiry_are_signed_again() {
+        let node = Arc::new(MockNode::default());
+        node.script("sendTransaction", |_| {
+            Answer::Error(-32005, "Node is behind by 42 slots")
+        });
+        node.script("getBlockHeight", |_| Answer::Result(json!(401)));
+
+        let delivery = deliver_once(&node, &policy(3), &[]).await;
+        assert!(delivery.result.is_ok());
+        let sent = node.sent();
+        assert_eq!(sent.len(), 2);
+        assert_ne!(sent[0], sent[1]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retries_stop_at_the_limit() {
+        let node = Arc::new(MockNode::default());
+        for _ in 0..3 {
+            node.script("sendTransaction", |_| {
+                Answer::Error(-32005, "Node is behind by 42 slots")
+            });
+        }
+
+        let delivery = deliver_once(&node, &policy(2), &[]).await;
+        assert!(matches!(delivery.result, Err(TransferError::Rpc(_))));
+        assert_eq!(delivery.attempts, 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failed_transactions_are_not_retried() {
+        let node = Arc::new(MockNode::default());
+        let error = json!({ "InstructionError": [0, { "Custom": 1 }] });
+        node.script("getSignatureStatuses", move |_| {
+            Answer::Result(context(json!([status("confirmed", Some(error.clone()))])))
+        });
+        let journal = TempJournal::new("failed");
+
+        let delivery = deliver_once(&node, &policy(3), std::slice::from_ref(&journal.handle)).await;
+        assert!(matches!(delivery.result, Err(TransferError::Failed { .. })));
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.failed_transfer(), Some(0));
+
+        journal_outcome(
+            &journal.handle,
+            &delivery.result,
+            true,
+            delivery.last_valid_block_height(),
+        )
+        .unwrap();
+        assert_eq!(journal.saved().entries[0].status, JournalStatus::Failed);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn expiry_comes_from_the_blockhash_the_transfer_was_signed_with() {
+        let node = Arc::new(MockNode::default());
+        node.script("getSignatureStatuses", |_| {
+            Answer::Result(context(json!([null])))
+        });
+        node.script("getBlockHeight", |_| Answer::Result(json!(401)));
+        let journal = TempJournal::new("expired");
+
+        let delivery = deliver_once(&node, &policy(3), std::slice::from_ref(&journal.handle)).await;
+        assert!(delivery.result.is_ok());
+        assert_eq!(delivery.attempts, 2, "the expired attempt is signed again");
+        // Heights are only asked for to check a missing transaction against its expiry
+        assert_eq!(node.calls("getBlockHeight").len(), 1);
+        assert_eq!(delivery.last_valid_block_height(), Some(400));
+        assert_eq!(
+            journal.saved().entries[0].last_valid_block_height,
+            Some(400)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn each_attempt_stays_on_one_endpoint() {
+        let rejecting = Arc::new(MockNode::default());
+        rejecting.script("sendTransaction", |_| {
+            Answer::Error(-32002, "Transaction simulation failed: Blockhash not found")
+        });
+        let accepting = Arc::new(MockNode::default());
+
+        let delivery =
+            deliver_through(&[rejecting.clone(), accepting.clone()], &policy(3), &[]).await;
+        assert!(delivery.result.is_ok());
+        assert_eq!(delivery.attempts, 2);
+
+        // The retry was signed, sent and confirmed by the node that accepted it
+        assert!(rejecting.calls("getSignatureStatuses").is_empty());
+        let sent = accepting.calls("sendTransaction");
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            transaction_of(&sent[0]).message.recent_blockhash,
+            accepting.blockhashes.lock().unwrap()[0]
+        );
+        assert_eq!(accepting.calls("getSignatureStatuses").len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sends_that_may_have_gone_through_are_confirmed_not_signed_again() {
+        let node = Arc::new(MockNode::default());
+        node.script("sendTransaction", |_| Answer::Dropped);
+        let journal = TempJournal::new("dropped");
+
+        let delivery = deliver_once(&node, &policy(3), std::slice::from_ref(&journal.handle)).await;
+        assert!(delivery.result.is_ok());
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(node.sent().len(), 1);
+        let signature = delivery.batch.as_ref().unwrap().signature().to_string();
+        assert_eq!(journal.saved().entries[0].signature, Some(signature));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unsettled_sends_are_reported_without_confirmation() {
+        let node = Arc::new(MockNode::default());
+        node.script("sendTransaction", |_| Answer::Dropped);
+        let mut policy = policy(3);
+        policy.confirmation.timeout_secs = 0;
+        let journal = TempJournal::new("unsettled");
+
+        let delivery = deliver_once(&node, &policy, std::slice::from_ref(&journal.handle)).await;
+        assert!(matches!(
+            delivery.result,
+            Err(TransferError::Unsettled { .. })
+        ));
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(node.sent().len(), 1);
+
+        journal_outcome(
+            &journal.handle,
+            &delivery.result,
+            false,
+            delivery.last_valid_block_height(),
+        )
+        .unwrap();
+        assert_eq!(journal.saved().entries[0].status, JournalStatus::Sent);
+        assert_eq!(
+            journal.saved().entries[0].last_valid_block_height,
+            Some(400)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failed_status_checks_keep_polling_instead_of_resending() {
+        let node = Arc::new(MockNode::default());
+        node.script("getSignatureStatuses", |_| {
+            Answer::Error(429, "Too many requests for a specific RPC call")
+        });
+
+        let delivery = deliver_once(&node, &policy(3), &[]).await;
+        assert!(delivery.result.is_ok());
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(node.sent().len(), 1);
+        assert_eq!(node.calls("getSignatureStatuses").len(), 2);
+        // A failed poll says nothing about the transaction, so expiry is not checked
+        assert!(node.calls("getBlockHeight").is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transfers_are_journaled_sent_before_they_are_broadcast() {
+        let node = Arc::new(MockNode::default());
+        let journal = TempJournal::new("before-send");
+        let path = journal.path.clone();
+        let seen = Arc::new(Mutex::new(None));
+        let recorded = seen.clone();
+        node.script("sendTransaction", move |params| {
+            let entry = RunJournal::open(&path).unwrap().entries[0].clone();
+            *recorded.lock().unwrap() = Some((entry.status, entry.signature));
+            Answer::Result(json!(signature_of(params)))
+        });
+
+        let delivery = deliver_once(&node, &policy(3), std::slice::from_ref(&journal.handle)).await;
+        let (signature, _) = delivery.result.unwrap();
+        assert_eq!(
+            seen.lock().unwrap().take(),
+            Some((JournalStatus::Sent, Some(signature)))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn transfers_the_journal_cannot_record_are_not_sent() {
+        let node = Arc::new(MockNode::default());
+        let journal = TempJournal::new("unwritable");
+        // A directory where the journal file should be makes every save fail
+        std::fs::create_dir(&journal.path).unwrap();
+
+        let delivery = deliver_once(&node, &policy(3), std::slice::from_ref(&journal.handle)).await;
+        std::fs::remove_dir(&journal.path).unwrap();
+        let _ = std::fs::remove_file(journal.path.with_extension("tmp"));
+        assert!(matches!(delivery.result, Err(TransferError::Journal(_))));
+        assert_eq!(delivery.attempts, 1);
+        assert!(node.sent().is_empty());
+    }
+}
@@ -0,0 +1,558 @@
+use serde::{Deserialize, Serialize};
+use solana_common::{AddressBook, ConfigError};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::File,
+    path::Path,
+};
+use thiserror::Error;
+
+/// Largest memo that still fits in a transaction next to a transfer
+pub const MAX_MEMO_LEN: usize = 566;
+
+/// One explicit transfer of a plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedTransfer {
+    /// Source wallet (alias or address); its secret key must be in the task2 config
+    pub source: String,
+    /// Destination wallet (alias or address)
+    pub destination: String,
+    pub amount_lamports: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    #[serde(default, skip_serializing_if = "TransferKind::is_system")]
+    pub kind: TransferKind,
+}
+
+/// What a transfer does with the lamports it moves
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferKind {
+    /// Plain system transfer to the destination
+    #[default]
+    System,
+    /// Fund a new stake account with the destination as staker and withdrawer, delegated
+    /// to the configured `stake_vote_account`
+    Stake,
+}
+
+impl TransferKind {
+    pub fn is_system(&self) -> bool {
+        *self == TransferKind::System
+    }
+}
+
+impl std::str::FromStr for TransferKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "system" => Ok(TransferKind::System),
+            "stake" => Ok(TransferKind::Stake),
+            other => Err(format!("unknown transfer kind '{}' (system, stake)", other)),
+        }
+    }
+}
+
+/// A list of explicit transfers, used instead of the config's sources x destinations matrix
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Plan {
+    pub transfers: Vec<PlannedTransfer>,
+}
+
+/// A named, recurring set of payees selected with `--template <name>`
+#[derive(Debug, Clone, Deserialize)]
+pub struct PaymentTemplate {
+    /// Paying wallet (alias or address); may be omitted when the config has a single source
+    #[serde(default)]
+    pub source: Option<String>,
+    pub payees: Vec<Payee>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Payee {
+    /// Destination wallet (alias or address)
+    pub destination: String,
+    pub amount_lamports: u64,
+    #[serde(default)]
+    pub memo: Option<String>,
+    #[serde(default)]
+    pub kind: TransferKind,
+}
+
+impl PaymentTemplate {
+    /// The template's transfers, paid from `source` or else the only entry of `sources`
+    pub fn transfers(&self, name: &str, sources: &[String]) -> Result<Plan, PlanError> {
+        let source = match (&self.source, sources) {
+            (Some(source), _) => source.clone(),
+            (None, [source]) => source.clone(),
+            (None, _) => {
+                return Err(PlanError::AmbiguousTemplateSource {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        for payee in &self.payees {
+            let memo_len = payee.memo.as_ref().map_or(0, String::len);
+            if memo_len > MAX_MEMO_LEN {
+                return Err(PlanError::MemoTooLong {
+                    destination: payee.destination.clone(),
+                    len: memo_len,
+                });
+            }
+        }
+
+        let transfers: Vec<PlannedTransfer> = self
+            .payees
+            .iter()
+            .filter(|payee| payee.amount_lamports > 0)
+            .map(|payee| PlannedTransfer {
+                source: source.clone(),
+                destination: payee.destination.clone(),
+                amount_lamports: payee.amount_lamports,
+                memo: payee.memo.clone(),
+                kind: payee.kind,
+            })
+            .collect();
+
+        if transfers.is_empty() {
+            return Err(PlanError::Empty);
+        }
+        Ok(Plan { transfers })
+    }
+}
+
+/// Memos from the config for transfers that carry none of their own
+#[derive(Debug, Clone, Default)]
+pub struct TransferMemos {
+    default: Option<String>,
+    by_destination: HashMap<Pubkey, String>,
+}
+
+impl TransferMemos {
+    /// Resolve the destinations (aliases or addresses) of the per-destination memos, which
+    /// override `default`, and check every memo fits next to a transfer
+    pub fn resolve(
+        default: Option<&str>,
+        by_destination: &BTreeMap<String, String>,
+        book: &AddressBook,
+    ) -> Result<Self, PlanError> {
+        let check = |destination: &str, memo: &str| {
+            if memo.len() > MAX_MEMO_LEN {
+                return Err(PlanError::MemoTooLong {
+                    destination: destination.to_string(),
+                    len: memo.len(),
+                });
+            }
+            Ok(())
+        };
+        if let Some(memo) = default {
+            check("every destination", memo)?;
+        }
+        let mut resolved = HashMap::with_capacity(by_destination.len());
+        for (destination, memo) in by_destination {
+            check(destination, memo)?;
+            resolved.insert(book.resolve(destination)?, memo.clone());
+        }
+        Ok(Self {
+            default: default.map(str::to_string),
+            by_destination: resolved,
+        })
+    }
+
+    /// Memo for a transfer to `destination`
+    pub fn memo_for(&self, destination: &Pubkey) -> Option<&str> {
+        self.by_destination
+            .get(destination)
+            .or(self.default.as_ref())
+            .map(String::as_str)
+    }
+}
+
+/// A CSV row that failed validation, with its 1-based line number
+#[derive(Debug, Clone)]
+pub struct RowError {
+    pub line: u64,
+    pub reason: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PlanError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error("Failed to read CSV {}: {source}", path.display())]
+    Csv {
+        path: std::path::PathBuf,
+        source: csv::Error,
+    },
+
+    #[error("{} invalid row(s) in CSV plan:\n  {}", .0.len(), join_rows(.0))]
+    InvalidRows(Vec<RowError>),
+
+    #[error("Plan contains no transfers")]
+    Empty,
+
+    #[error("Memo for {destination} is {len} bytes, limit is {MAX_MEMO_LEN}")]
+    MemoTooLong { destination: String, len: usize },
+
+    #[error("No transfer template named '{name}' in the config")]
+    UnknownTemplate { name: String },
+
+    #[error("Template '{name}' has no source and the config has more than one source wallet")]
+    AmbiguousTemplateSource { name: String },
+}
+
+impl PlanError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            PlanError::Config(e) => e.code(),
+            PlanError::Csv { .. } => "PLAN_CSV",
+            PlanError::InvalidRows(_) => "PLAN_INVALID_ROWS",
+            PlanError::Empty => "PLAN_EMPTY",
+            PlanError::MemoTooLong { .. } => "PLAN_MEMO_TOO_LONG",
+            PlanError::UnknownTemplate { .. } => "PLAN_UNKNOWN_TEMPLATE",
+            PlanError::AmbiguousTemplateSource { .. } => "PLAN_AMBIGUOUS_TEMPLATE_SOURCE",
+        }
+    }
+}
+
+fn join_rows(rows: &[RowError]) -> String {
+    rows.iter()
+        .map(RowError::to_string)
+        .collect::<Vec<_>>()
+        .join("\n  ")
+}
+
+/// Columns of a finance CSV export. `amount` is in SOL, `amount_lamports` in lamports.
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    source: String,
+    destination: String,
+    #[serde(default)]
+    amount: Option<String>,
+    #[serde(default)]
+    amount_lamports: Option<String>,
+    #[serde(default)]
+    memo: Option<String>,
+    #[serde(default)]
+    kind: Option<String>,
+}
+
+impl Plan {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let file = File::open(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        serde_yaml::from_reader(file).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ConfigError> {
+        let file = File::create(path).map_err(|source| ConfigError::Write {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        serde_yaml::to_writer(file, self).map_err(|source| ConfigError::Serialize {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Load a YAML plan, or import it on the fly when `path` ends in `.csv`
+    pub fn load_any(path: &Path, book: &AddressBook) -> Result<Self, PlanError> {
+        let is_csv = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+
+        if is_csv {
+            Self::from_csv(path, book)
+        } else {
+            Ok(Self::load(path)?)
+        }
+    }
+
+    /// Import a `source,destination,amount,memo` CSV. Every row is validated and all
+    /// problems are reported together; wallets are stored as resolved base58 addresses.
+    pub fn from_csv(path: &Path, book: &AddressBook) -> Result<Self, PlanError> {
+        let csv_error = |source| PlanError::Csv {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_path(path)
+            .map_err(csv_error)?;
+        let headers = reader.headers().map_err(csv_error)?.clone();
+
+        let mut transfers = Vec::new();
+        let mut errors = Vec::new();
+
+        for record in reader.records() {
+            let record = record.map_err(csv_error)?;
+            let line = record.position().map_or(0, |p| p.line());
+
+            let result = record
+                .deserialize::<CsvRow>(Some(&headers))
+                .map_err(|e| e.to_string())
+                .and_then(|row| validate_row(row, book));
+
+            match result {
+                Ok(transfer) => transfers.push(transfer),
+                Err(reason) => errors.push(RowError { line, reason }),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(PlanError::InvalidRows(errors));
+        }
+        if transfers.is_empty() {
+            return Err(PlanError::Empty);
+        }
+
+        Ok(Plan { transfers })
+    }
+
+    pub fn total_lamports(&self) -> u64 {
+        self.transfers.iter().map(|t| t.amount_lamports).sum()
+    }
+}
+
+fn validate_row(row: CsvRow, book: &AddressBook) -> Result<PlannedTransfer, String> {
+    let source = book
+        .resolve(&row.source)
+        .map_err(|e| format!("source: {}", e))?;
+    let destination = book
+        .resolve(&row.destination)
+        .map_err(|e| format!("destination: {}", e))?;
+
+    if source == destination {
+        return Err("source and destination are the same wallet".to_string());
+    }
+
+    let amount_lamports = match (non_empty(row.amount), non_empty(row.amount_lamports)) {
+        (Some(_), Some(_)) => {
+            return Err("give either amount or amount_lamports, not both".to_string())
+        }
+        (Some(sol), None) => {
+            parse_sol(&sol).ok_or_else(|| format!("invalid SOL amount '{}'", sol))?
+        }
+        (None, Some(lamports)) => lamports
+            .replace('_', "")
+            .parse::<u64>()
+            .map_err(|_| format!("invalid lamport amount '{}'", lamports))?,
+        (None, None) => return Err("missing amount".to_string()),
+    };
+    if amount_lamports == 0 {
+        return Err("amount must be greater than zero".to_string());
+    }
+
+    let memo = non_empty(row.memo);
+    if let Some(memo) = &memo {
+        if memo.len() > MAX_MEMO_LEN {
+            return Err(format!(
+                "memo is {} bytes, limit is {}",
+                memo.len(),
+                MAX_MEMO_LEN
+            ));
+        }
+    }
+
+    let kind = match non_empty(row.kind) {
+        Some(kind) => kind.parse()?,
+        None => TransferKind::System,
+    };
+
+    Ok(PlannedTransfer {
+        source: source.to_string(),
+        destination: destination.to_string(),
+        amount_lamports,
+        memo,
+        kind,
+    })
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty())
+}
+
+/// Parse a decimal SOL amount exactly (spreadsheets export e.g. `1.5` or `0.000000001`)
+pub fn parse_sol(value: &str) -> Option<u64> {
+    parse_units(value, 9)
+}
+
+/// Parse a decimal amount exactly into base units of a currency with `decimals` places
+pub fn parse_units(value: &str, decimals: u8) -> Option<u64> {
+    let value = value.replace('_', "");
+    let (whole, fraction) = value.split_once('.').unwrap_or((&value, ""));
+
+    if whole.is_empty() && fraction.is_empty() {
+        return None;
+    }
+    if fraction.len() > decimals as usize
+        || !whole.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let whole: u64 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let fraction: u64 = if decimals == 0 {
+        0
+    } else {
+        format!("{:0<width$}", fraction, width = decimals as usize)
+            .parse()
+            .ok()?
+    };
+
+    whole
+        .checked_mul(10u64.checked_pow(decimals as u32)?)?
+        .checked_add(fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf};
+
+    const TREASURY: &str = "Vote111111111111111111111111111111111111111";
+    const PAYEE: &str = "Stake11111111111111111111111111111111111111";
+
+    /// A CSV plan under the system temp dir, removed when dropped
+    struct TempCsv(PathBuf);
+
+    impl TempCsv {
+        fn new(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "task2-plan-{}-{}.csv",
+                name,
+                std::process::id()
+            ));
+            fs::write(&path, contents).unwrap();
+            TempCsv(path)
+        }
+    }
+
+    impl Drop for TempCsv {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn book() -> AddressBook {
+        let mut book = AddressBook::default();
+        book.add("treasury", TREASURY).unwrap();
+        book
+    }
+
+    fn invalid_rows(result: Result<Plan, PlanError>) -> Vec<(u64, String)> {
+        match result {
+            Err(PlanError::InvalidRows(rows)) => {
+                rows.into_iter().map(|row| (row.line, row.reason)).collect()
+            }
+            other => panic!(
+                "expected invalid rows, got {:?}",
+                other.map(|plan| plan.transfers)
+            ),
+        }
+    }
+
+    #[test]
+    fn from_csv_resolves_aliases_and_parses_amounts_memos_and_kinds() {
+        let csv = TempCsv::new(
+            "valid",
+            &format!(
+                "source,destination,amount,amount_lamports,memo,kind\n\
+                 treasury,{PAYEE},1.5,,invoice 7,\n\
+                 {TREASURY}, {PAYEE} ,,1_000,,stake\n"
+            ),
+        );
+
+        let plan = Plan::from_csv(&csv.0, &book()).unwrap();
+        assert_eq!(plan.transfers.len(), 2);
+        assert_eq!(plan.transfers[0].source, TREASURY);
+        assert_eq!(plan.transfers[0].destination, PAYEE);
+        assert_eq!(plan.transfers[0].amount_lamports, 1_500_000_000);
+        assert_eq!(plan.transfers[0].memo.as_deref(), Some("invoice 7"));
+        assert_eq!(plan.transfers[0].kind, TransferKind::System);
+        assert_eq!(plan.transfers[1].amount_lamports, 1_000);
+        assert_eq!(plan.transfers[1].memo, None);
+        assert_eq!(plan.transfers[1].kind, TransferKind::Stake);
+        assert_eq!(plan.total_lamports(), 1_500_001_000);
+    }
+
+    #[test]
+    fn from_csv_reports_every_invalid_row_with_its_line() {
+        let long_memo = "m".repeat(MAX_MEMO_LEN + 1);
+        let csv = TempCsv::new(
+            "invalid",
+            &format!(
+                "source,destination,amount,amount_lamports,memo,kind\n\
+                 treasury,{PAYEE},1,,,\n\
+                 nobody,{PAYEE},1,,,\n\
+                 treasury,{TREASURY},1,,,\n\
+                 treasury,{PAYEE},1,5,,\n\
+                 treasury,{PAYEE},,,,\n\
+                 treasury,{PAYEE},0,,,\n\
+                 treasury,{PAYEE},1.0000000001,,,\n\
+                 treasury,{PAYEE},1,,{long_memo},\n\
+                 treasury,{PAYEE},1,,,vote\n"
+            ),
+        );
+
+        let rows = invalid_rows(Plan::from_csv(&csv.0, &book()));
+        let lines: Vec<u64> = rows.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [3, 4, 5, 6, 7, 8, 9, 10]);
+        assert!(rows[0].1.starts_with("source:"));
+        assert_eq!(rows[1].1, "source and destination are the same wallet");
+        assert_eq!(rows[2].1, "give either amount or amount_lamports, not both");
+        assert_eq!(rows[3].1, "missing amount");
+        assert_eq!(rows[4].1, "amount must be greater than zero");
+        assert_eq!(rows[5].1, "invalid SOL amount '1.0000000001'");
+        assert!(rows[6].1.starts_with("memo is 567 bytes"));
+    }
+
+    #[test]
+    fn from_csv_rejects_a_plan_without_rows() {
+        let csv = TempCsv::new("empty", "source,destination,amount\n");
+        assert!(matches!(
+            Plan::from_csv(&csv.0, &book()),
+            Err(PlanError::Empty)
+        ));
+    }
+
+    #[test]
+    fn parse_units_is_exact() {
+        assert_eq!(parse_sol("1.5"), Some(1_500_000_000));
+        assert_eq!(parse_sol("0.000000001"), Some(1));
+        assert_eq!(parse_sol(".25"), Some(250_000_000));
+        assert_eq!(parse_sol("1_000"), Some(1_000_000_000_000));
+        assert_eq!(parse_units("2.5", 6), Some(2_500_000));
+        assert_eq!(parse_units("7", 0), Some(7));
+
+        for invalid in ["", ".", "-1", "1e9", "1.2.3", "0.0000000001"] {
+            assert_eq!(parse_sol(invalid), None, "{:?}", invalid);
+        }
+        assert_eq!(parse_units("1.5", 0), None);
+        assert_eq!(parse_sol("18446744074"), None);
+    }
+}
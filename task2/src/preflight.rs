@@ -0,0 +1,164 @@
+use crate::{balances::fetch_balances, token::TokenMint};
+use solana_client::rpc_client::RpcClient;
+use solana_common::RpcError;
+use solana_sdk::{message::Message, program_pack::Pack, pubkey::Pubkey, system_instruction};
+use std::fmt;
+
+/// What the transfers of one source wallet are about to spend
+#[derive(Debug, Clone, Default)]
+pub struct PlannedOutflow {
+    /// Lamports the transfers move (SOL and stake transfers)
+    pub lamports: u64,
+    /// Token base units the transfers move, with a token mint
+    pub tokens: u64,
+    /// Signatures over all of its transactions, each paying the cluster's fee per signature
+    pub signatures: u64,
+    /// Destination token accounts the transfers create, and pay the rent of, when missing
+    pub token_accounts: Vec<Pubkey>,
+}
+
+/// Why a source wallet cannot cover its transfers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Shortfall {
+    /// Needs more lamports than it holds
+    Overdraw { missing: u64 },
+    /// Would keep a non-zero balance below the rent-exempt minimum, which the runtime refuses
+    BelowRentExemption { remaining: u64, minimum: u64 },
+    /// Its token account holds fewer tokens than the transfers move
+    TokenOverdraw { balance: u64, needed: u64 },
+}
+
+impl fmt::Display for Shortfall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shortfall::Overdraw { missing } => write!(f, "{} lamports short", missing),
+            Shortfall::BelowRentExemption { remaining, minimum } => write!(
+                f,
+                "would keep {} lamports, below the rent-exempt minimum of {}",
+                remaining, minimum
+            ),
+            Shortfall::TokenOverdraw { balance, needed } => {
+                write!(f, "holds {} token base units, {} needed", balance, needed)
+            }
+        }
+    }
+}
+
+/// A source wallet's balance against what its transfers spend
+#[derive(Debug, Clone)]
+pub struct SourceCheck {
+    pub source: Pubkey,
+    pub balance: u64,
+    /// Lamports spent in total: transfers, fees and new token account rent
+    pub outflow: u64,
+    /// Estimated base fees, included in `outflow`; priority fees come on top
+    pub fees: u64,
+    pub shortfall: Option<Shortfall>,
+}
+
+/// Check every source against its planned outflow: fees are estimated with `getFeeForMessage`
+/// and a source may neither overdraw nor keep less than the rent-exempt minimum (emptying it
+/// is fine). With a token mint, its associated token account must hold the tokens sent.
+pub fn check_sources(
+    client: &RpcClient,
+    outflows: &[(Pubkey, PlannedOutflow)],
+    token: Option<&TokenMint>,
+) -> Result<Vec<SourceCheck>, RpcError> {
+    let Some((payer, _)) = outflows.first() else {
+        return Ok(Vec::new());
+    };
+
+    // Every transaction pays the same fee per signature, so one single-signer message prices all
+    let blockhash = client
+        .get_latest_blockhash()
+        .map_err(|e| RpcError::request("getLatestBlockhash", &client.url(), e))?;
+    let message = Message::new_with_blockhash(
+        &[system_instruction::transfer(payer, payer, 0)],
+        Some(payer),
+        &blockhash,
+    );
+    let fee_per_signature = client
+        .get_fee_for_message(&message)
+        .map_err(|e| RpcError::request("getFeeForMessage", &client.url(), e))?;
+    let rent_exempt_minimum = client
+        .get_minimum_balance_for_rent_exemption(0)
+        .map_err(|e| RpcError::request("getMinimumBalanceForRentExemption", &client.url(), e))?;
+
+    let sources: Vec<Pubkey> = outflows.iter().map(|(source, _)| *source).collect();
+    let balances = fetch_balances(client, &sources)?;
+
+    // Destination token accounts that do not exist yet are created at the source's expense
+    let token_accounts: Vec<Pubkey> = outflows
+        .iter()
+        .flat_map(|(_, outflow)| outflow.token_accounts.iter().copied())
+        .collect();
+    let existing = fetch_balances(client, &token_accounts)?;
+    let token_account_rent = if token_accounts.is_empty() {
+        0
+    } else {
+        client
+            .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            .map_err(|e| RpcError::request("getMinimumBalanceForRentExemption", &client.url(), e))?
+    };
+
+    let mut checks = Vec::with_capacity(outflows.len());
+    for (source, outflow) in outflows {
+        let balance = balances.get(source).copied().unwrap_or_default();
+        let fees = outflow.signatures.saturating_mul(fee_per_signature);
+        let new_accounts = outflow
+            .token_accounts
+            .iter()
+            .filter(|account| existing.get(account).copied().unwrap_or_default() == 0)
+            .count() as u64;
+        let outflow_lamports = outflow
+            .lamports
+            .saturating_add(fees)
+            .saturating_add(new_accounts.saturating_mul(token_account_rent));
+
+        let mut shortfall = match balance.checked_sub(outflow_lamports) {
+            None => Some(Shortfall::Overdraw {
+                missing: outflow_lamports - balance,
+            }),
+            Some(remaining) if remaining > 0 && remaining < rent_exempt_minimum => {
+                Some(Shortfall::BelowRentExemption {
+                    remaining,
+                    minimum: rent_exempt_minimum,
+                })
+            }
+            Some(_) => None,
+        };
+        if let (None, Some(token)) = (&shortfall, token.filter(|_| outflow.tokens > 0)) {
+            let token_balance = token_balance(client, &token.associated_account(source))?;
+            if token_balance < outflow.tokens {
+                shortfall = Some(Shortfall::TokenOverdraw {
+                    balance: token_balance,
+                    needed: outflow.tokens,
+                });
+            }
+        }
+
+        checks.push(SourceCheck {
+            source: *source,
+            balance,
+            outflow: outflow_lamports,
+            fees,
+            shortfall,
+        });
+    }
+    Ok(checks)
+}
+
+/// Base units held by a token account, zero when it does not exist
+fn token_balance(client: &RpcClient, account: &Pubkey) -> Result<u64, RpcError> {
+    let data = client
+        .get_account_with_commitment(account, client.commitment())
+        .map_err(|e| RpcError::request("getAccountInfo", &client.url(), e))?
+        .value
+        .map(|account| account.data)
+        .unwrap_or_default();
+    // Token-2022 accounts carry extensions after the base account layout
+    Ok(data
+        .get(..spl_token::state::Account::LEN)
+        .and_then(|base| spl_token::state::Account::unpack_from_slice(base).ok())
+        .map_or(0, |account| account.amount))
+}
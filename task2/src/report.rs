@@ -0,0 +1,154 @@
+use crate::failure::FailureClass;
+use serde::Serialize;
+use solana_common::{render::render, OutputFormat, Render, RenderError};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ReportError {
+    #[error(
+        "Report {} must end in .csv, .json, .ndjson, .md or .txt",
+        path.display()
+    )]
+    UnknownFormat { path: PathBuf },
+
+    #[error("Failed to write report {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("Failed to render report {}: {source}", path.display())]
+    Render { path: PathBuf, source: RenderError },
+}
+
+impl ReportError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ReportError::UnknownFormat { .. } => "REPORT_UNKNOWN_FORMAT",
+            ReportError::Io { .. } => "REPORT_IO",
+            ReportError::Render { source, .. } => source.code(),
+        }
+    }
+}
+
+/// Format of the report at `path`, implied by its extension
+pub fn report_format(path: &Path) -> Result<OutputFormat, ReportError> {
+    OutputFormat::from_extension(path).ok_or_else(|| ReportError::UnknownFormat {
+        path: path.to_path_buf(),
+    })
+}
+
+/// A per-transfer result that can be written as one row of a CSV report
+pub trait CsvRecord {
+    fn header() -> &'static [&'static str];
+    fn record(&self) -> Vec<String>;
+}
+
+/// Totals of a run, as printed under "Summary"
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunSummary {
+    pub started_at: String,
+    pub finished_at: String,
+    pub dry_run: bool,
+    pub total: usize,
+    /// Transfers that succeeded, or would have with `--dry-run`
+    pub successful: usize,
+    pub failed: usize,
+    pub abandoned: usize,
+    pub expired: usize,
+    pub retried: usize,
+    pub failures_by_class: BTreeMap<FailureClass, usize>,
+    /// Average time of the successful transfers
+    pub avg_time_ms: f64,
+    /// Average time transfers waited for a send slot and the send rate limit
+    pub avg_queue_ms: f64,
+    /// Fees paid, or estimated with `--dry-run`; unknown without a fee ceiling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fees_lamports: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest: Option<String>,
+}
+
+impl RunSummary {
+    /// The summary as metric,value pairs, one per failure class
+    fn metrics(&self) -> Vec<(String, String)> {
+        let mut metrics = vec![
+            ("started_at".to_string(), self.started_at.clone()),
+            ("finished_at".to_string(), self.finished_at.clone()),
+            ("dry_run".to_string(), self.dry_run.to_string()),
+            ("total".to_string(), self.total.to_string()),
+            ("successful".to_string(), self.successful.to_string()),
+            ("failed".to_string(), self.failed.to_string()),
+            ("abandoned".to_string(), self.abandoned.to_string()),
+            ("expired".to_string(), self.expired.to_string()),
+            ("retried".to_string(), self.retried.to_string()),
+        ];
+        for (class, count) in &self.failures_by_class {
+            metrics.push((format!("failures.{}", class.as_str()), count.to_string()));
+        }
+        metrics.push((
+            "avg_time_ms".to_string(),
+            format!("{:.2}", self.avg_time_ms),
+        ));
+        metrics.push((
+            "avg_queue_ms".to_string(),
+            format!("{:.2}", self.avg_queue_ms),
+        ));
+        if let Some(fees) = self.fees_lamports {
+            metrics.push(("fees_lamports".to_string(), fees.to_string()));
+        }
+        if let Some(manifest) = &self.manifest {
+            metrics.push(("manifest".to_string(), manifest.clone()));
+        }
+        metrics
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a, T> {
+    summary: &'a RunSummary,
+    results: &'a [T],
+}
+
+/// One row per result, then the summary; JSON holds a `summary` object and a `results` array
+impl<T: Serialize + CsvRecord> Render for Report<'_, T> {
+    fn columns(&self) -> &'static [&'static str] {
+        T::header()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.results.iter().map(CsvRecord::record).collect()
+    }
+
+    fn summary(&self) -> Vec<(String, String)> {
+        self.summary.metrics()
+    }
+}
+
+/// Every result and the run's summary in `format`
+pub fn render_report<T: Serialize + CsvRecord>(
+    format: OutputFormat,
+    results: &[T],
+    summary: &RunSummary,
+) -> Result<String, RenderError> {
+    render(&Report { summary, results }, format)
+}
+
+/// Write every result and the run's summary to `path` in `format`
+pub fn write_report<T: Serialize + CsvRecord>(
+    path: &Path,
+    format: OutputFormat,
+    results: &[T],
+    summary: &RunSummary,
+) -> Result<(), ReportError> {
+    let report = render_report(format, results, summary).map_err(|source| ReportError::Render {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    fs::write(path, report).map_err(|source| ReportError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
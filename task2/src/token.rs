@@ -0,0 +1,130 @@
+use crate::plan::parse_units;
+use solana_client::rpc_client::RpcClient;
+use solana_common::RpcError;
+use solana_sdk::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TokenError {
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error("{mint} is not a token mint")]
+    NotAMint { mint: Pubkey },
+
+    #[error("Invalid token amount '{value}': {mint} has {decimals} decimals")]
+    InvalidAmount {
+        value: String,
+        mint: Pubkey,
+        decimals: u8,
+    },
+}
+
+impl TokenError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TokenError::Rpc(e) => e.code(),
+            TokenError::NotAMint { .. } => "TOKEN_NOT_A_MINT",
+            TokenError::InvalidAmount { .. } => "TOKEN_INVALID_AMOUNT",
+        }
+    }
+}
+
+/// An SPL token mint as read from the cluster
+#[derive(Debug, Clone, Copy)]
+pub struct TokenMint {
+    pub mint: Pubkey,
+    pub decimals: u8,
+    /// Token program owning the mint, SPL Token or Token-2022
+    pub token_program: Pubkey,
+}
+
+impl TokenMint {
+    /// Read the decimals and owning token program of `mint`
+    pub fn fetch(client: &RpcClient, mint: &Pubkey) -> Result<Self, TokenError> {
+        let account = client
+            .get_account(mint)
+            .map_err(|e| RpcError::request("getAccountInfo", &client.url(), e))?;
+
+        // Token-2022 mints carry extensions after the base mint layout
+        let base = account
+            .data
+            .get(..spl_token::state::Mint::LEN)
+            .ok_or(TokenError::NotAMint { mint: *mint })?;
+        let state = spl_token::state::Mint::unpack_from_slice(base)
+            .map_err(|_| TokenError::NotAMint { mint: *mint })?;
+
+        Ok(Self {
+            mint: *mint,
+            decimals: state.decimals,
+            token_program: account.owner,
+        })
+    }
+
+    /// Base units of a decimal amount of whole tokens, e.g. `2.5`
+    pub fn parse_amount(&self, value: &str) -> Result<u64, TokenError> {
+        parse_units(value, self.decimals).ok_or_else(|| TokenError::InvalidAmount {
+            value: value.to_string(),
+            mint: self.mint,
+            decimals: self.decimals,
+        })
+    }
+
+    /// Associated token account of `wallet` for this mint
+    pub fn associated_account(&self, wallet: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(wallet, &self.mint, &self.token_program)
+    }
+}
+
+/// Token sent by every transfer of a run instead of SOL
+#[derive(Debug, Clone, Copy)]
+pub struct TokenTransfers {
+    pub mint: TokenMint,
+    /// Create the destination's associated token account, paid by the source, when missing
+    pub create_destination_account: bool,
+}
+
+impl TokenTransfers {
+    /// Instructions moving `amount` base units from the associated token account of `source`
+    /// to that of `destination`, with an optional memo
+    pub fn instructions(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        amount: u64,
+        memo: Option<&str>,
+    ) -> Vec<Instruction> {
+        let mint = &self.mint;
+        let mut instructions = Vec::with_capacity(3);
+        if self.create_destination_account {
+            instructions.push(create_associated_token_account_idempotent(
+                source,
+                destination,
+                &mint.mint,
+                &mint.token_program,
+            ));
+        }
+        let mut transfer = spl_token::instruction::transfer_checked(
+            &spl_token::id(),
+            &mint.associated_account(source),
+            &mint.mint,
+            &mint.associated_account(destination),
+            source,
+            &[],
+            amount,
+            mint.decimals,
+        )
+        .expect("SPL Token program id is valid for transfer_checked");
+        // Token-2022 shares the instruction layout, but the builder only accepts SPL Token
+        transfer.program_id = mint.token_program;
+        instructions.push(transfer);
+        if let Some(memo) = memo {
+            instructions.push(spl_memo::build_memo(memo.as_bytes(), &[source]));
+        }
+        instructions
+    }
+}
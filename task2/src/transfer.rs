@@ -0,0 +1,737 @@
+use crate::{confirmation::is_expired, failure::FailureClass, fee_ceiling::FeeCeiling};
+use serde::Serialize;
+use solana_client::{nonce_utils, rpc_client::RpcClient};
+use solana_common::{ComputeBudget, ConfigError, RpcError, SafetyConfig, SafetyError, Submission, Submitter};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    message::Message,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    stake::{
+        instruction as stake_instruction,
+        state::{Authorized, Lockup},
+    },
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+use std::{
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TransferError {
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+
+    #[error("Refused by safe mode: {0}")]
+    Safety(#[from] SafetyError),
+
+    #[error("Transaction {signature} failed: {error}")]
+    Failed {
+        signature: String,
+        error: TransactionError,
+    },
+
+    #[error("Transaction {signature} abandoned: not confirmed within {}s", .after.as_secs())]
+    Abandoned { signature: String, after: Duration },
+
+    #[error("Transaction {signature} expired: its blockhash is no longer valid and it never landed")]
+    Expired { signature: String },
+
+    #[error("Transaction {signature} may have been broadcast, its outcome is unknown: {source}")]
+    Unsettled { signature: String, source: RpcError },
+
+    #[error("Fee ceiling of {max} lamports reached ({spent} spent, this transfer costs {fee})")]
+    FeeCeiling { spent: u64, fee: u64, max: u64 },
+
+    #[error("Cannot use nonce account {account}: {reason}")]
+    Nonce { account: Pubkey, reason: String },
+
+    #[error("Not executed: the transfer to {destination} in the same transaction failed")]
+    RolledBack { destination: String },
+
+    #[error("Not sent, the run journal could not record it: {0}")]
+    Journal(#[from] ConfigError),
+}
+
+impl TransferError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TransferError::Rpc(e) => e.code(),
+            TransferError::Safety(e) => e.code(),
+            TransferError::Failed { .. } => "TRANSFER_FAILED",
+            TransferError::Abandoned { .. } => "TRANSFER_ABANDONED",
+            TransferError::Expired { .. } => "TRANSFER_EXPIRED",
+            TransferError::Unsettled { .. } => "TRANSFER_UNSETTLED",
+            TransferError::FeeCeiling { .. } => "TRANSFER_FEE_CEILING",
+            TransferError::Nonce { .. } => "TRANSFER_NONCE",
+            TransferError::RolledBack { .. } => "TRANSFER_ROLLED_BACK",
+            TransferError::Journal(_) => "TRANSFER_JOURNAL",
+        }
+    }
+
+    pub fn failure_class(&self) -> FailureClass {
+        match self {
+            TransferError::Rpc(e) => classify_rpc_error(e),
+            TransferError::Safety(SafetyError::SimulationFailed { reason, logs }) => {
+                FailureClass::from_message(&format!("{}\n{}", reason, logs.join("\n")))
+            }
+            TransferError::Safety(SafetyError::Simulation(e)) => classify_rpc_error(e),
+            TransferError::Safety(SafetyError::ValueCapExceeded { .. })
+            | TransferError::FeeCeiling { .. } => FailureClass::Refused,
+            TransferError::Failed { error, .. } => FailureClass::from_transaction_error(error),
+            TransferError::Abandoned { .. } | TransferError::Unsettled { .. } => FailureClass::Unconfirmed,
+            TransferError::Expired { .. } => FailureClass::BlockhashExpired,
+            TransferError::Nonce { .. } | TransferError::RolledBack { .. } | TransferError::Journal(_) => {
+                FailureClass::Other
+            }
+        }
+    }
+
+    /// Whether sending the transfer again with a fresh blockhash is safe and may succeed: it
+    /// failed transiently (expired blockhash, rate limit, lagging node) and was either rejected
+    /// before it could land or has expired and can no longer land. RPC errors only come from
+    /// requests made before the broadcast; a send that may have gone through is `Unsettled`.
+    pub fn is_retryable(&self) -> bool {
+        let transient = matches!(
+            self.failure_class(),
+            FailureClass::BlockhashExpired | FailureClass::RateLimited | FailureClass::NodeBehind
+        );
+        let never_landed = match self {
+            TransferError::Rpc(_)
+            | TransferError::Safety(_)
+            | TransferError::Expired { .. }
+            | TransferError::Nonce { .. }
+            | TransferError::RolledBack { .. }
+            | TransferError::Journal(_) => true,
+            TransferError::Failed { .. }
+            | TransferError::Abandoned { .. }
+            | TransferError::Unsettled { .. }
+            | TransferError::FeeCeiling { .. } => false,
+        };
+        transient && never_landed
+    }
+
+    /// Index of the instruction that made the transaction fail, when the node reported one
+    pub fn instruction_index(&self) -> Option<u8> {
+        let error = match self {
+            TransferError::Failed { error, .. } => Some(error.clone()),
+            TransferError::Rpc(RpcError::Request { source, .. })
+            | TransferError::Safety(SafetyError::Simulation(RpcError::Request { source, .. })) => {
+                source.get_transaction_error()
+            }
+            _ => None,
+        };
+        match error {
+            Some(TransactionError::InstructionError(index, _)) => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Whether the failure signals overload: rate limiting, or a transaction that could
+    /// not land before its blockhash expired
+    pub fn is_overload(&self) -> bool {
+        matches!(
+            self.failure_class(),
+            FailureClass::RateLimited | FailureClass::BlockhashExpired | FailureClass::Unconfirmed
+        )
+    }
+}
+
+fn classify_rpc_error(error: &RpcError) -> FailureClass {
+    let transaction_error = match error {
+        RpcError::Request { source, .. } => source.get_transaction_error(),
+        _ => None,
+    };
+    FailureClass::classify(transaction_error.as_ref(), &error.to_string())
+}
+
+/// What simulating a signed transfer showed, for `--dry-run`
+#[derive(Debug, Clone, Serialize)]
+pub struct Simulation {
+    /// Why the transfer would fail, `None` when it would succeed
+    pub error: Option<TransactionError>,
+    pub logs: Vec<String>,
+    pub units_consumed: Option<u64>,
+    /// Fee of the signed message, priority fee included
+    pub fee_lamports: u64,
+}
+
+/// How signed transfers are checked and broadcast; the same for every transfer of a run
+#[derive(Clone)]
+pub struct SendPolicy {
+    pub safety: SafetyConfig,
+    pub submitter: Submitter,
+    /// Wait for confirmation, rebroadcasting, for at most this long before abandoning
+    pub deadline: Option<Duration>,
+    /// Cap on the fees of the whole run, shared by all of its transfers
+    pub fee_ceiling: Option<Arc<FeeCeiling>>,
+}
+
+/// Key a transfer is sent from, and the durable nonce account it advances instead of using a
+/// recent blockhash, if any
+#[derive(Clone, Copy)]
+pub struct SourceKey<'a> {
+    pub keypair: &'a Keypair,
+    pub nonce: Option<&'a Pubkey>,
+}
+
+impl<'a> From<&'a Keypair> for SourceKey<'a> {
+    fn from(keypair: &'a Keypair) -> Self {
+        Self { keypair, nonce: None }
+    }
+}
+
+/// Pause between confirmation checks (and rebroadcasts) while waiting on a deadline
+const CONFIRM_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Instructions making up a single transfer from `source` to `destination`, with an optional memo
+pub fn transfer_instructions(
+    source: &Pubkey,
+    destination: &Pubkey,
+    lamports: u64,
+    memo: Option<&str>,
+) -> Vec<Instruction> {
+    let mut instructions = vec![system_instruction::transfer(source, destination, lamports)];
+    if let Some(memo) = memo {
+        instructions.push(spl_memo::build_memo(memo.as_bytes(), &[source]));
+    }
+    instructions
+}
+
+pub async fn send_transaction(
+    client: &RpcClient,
+    source: SourceKey<'_>,
+    destination: &Pubkey,
+    lamports: u64,
+    memo: Option<&str>,
+    budget: &ComputeBudget,
+    policy: &SendPolicy,
+) -> Result<(String, u128), TransferError> {
+    let instructions = transfer_instructions(&source.keypair.pubkey(), destination, lamports, memo);
+    send_instructions(client, source, instructions, lamports, budget, policy).await
+}
+
+/// Build and sign the same transfer as [`send_transaction`], but only simulate it
+pub fn simulate_transaction(
+    client: &RpcClient,
+    source: SourceKey<'_>,
+    destination: &Pubkey,
+    lamports: u64,
+    memo: Option<&str>,
+    budget: &ComputeBudget,
+) -> Result<Simulation, TransferError> {
+    let instructions = transfer_instructions(&source.keypair.pubkey(), destination, lamports, memo);
+    simulate_instructions(client, source, instructions, budget)
+}
+
+/// Sign `instructions` as a transaction paid by `source` and send it like a transfer of
+/// `lamports`, which is what safe mode's per-transaction cap is checked against
+pub async fn send_instructions(
+    client: &RpcClient,
+    source: SourceKey<'_>,
+    instructions: Vec<Instruction>,
+    lamports: u64,
+    budget: &ComputeBudget,
+    policy: &SendPolicy,
+) -> Result<(String, u128), TransferError> {
+    let start = Instant::now();
+    
+    let submission = sign(client, &[source.keypair], instructions, budget, source.nonce)?;
+    let signature = send_signed(client, &submission, lamports, policy, start).await?;
+    
+    let elapsed = start.elapsed().as_millis();
+    
+    Ok((signature.to_string(), elapsed))
+}
+
+/// Build and sign the same transaction as [`send_instructions`], but only simulate it
+pub fn simulate_instructions(
+    client: &RpcClient,
+    source: SourceKey<'_>,
+    instructions: Vec<Instruction>,
+    budget: &ComputeBudget,
+) -> Result<Simulation, TransferError> {
+    let submission = sign(client, &[source.keypair], instructions, budget, source.nonce)?;
+    simulate(client, submission.transaction())
+}
+
+/// Instructions creating `stake_account` funded with `lamports` from `source`, with
+/// `authority` as staker and withdrawer, and delegating it to `vote_account`
+pub fn stake_instructions(
+    source: &Pubkey,
+    stake_account: &Pubkey,
+    authority: &Pubkey,
+    vote_account: &Pubkey,
+    lamports: u64,
+) -> Vec<Instruction> {
+    stake_instruction::create_account_and_delegate_stake(
+        source,
+        stake_account,
+        vote_account,
+        &Authorized::auto(authority),
+        &Lockup::default(),
+        lamports,
+    )
+}
+
+/// Fund a fresh stake account controlled by `authority` and delegate it to `vote_account`.
+/// Returns the signature, elapsed time and the new stake account's address.
+pub async fn send_stake_transaction(
+    client: &RpcClient,
+    source: SourceKey<'_>,
+    authority: &Pubkey,
+    vote_account: &Pubkey,
+    lamports: u64,
+    budget: &ComputeBudget,
+    policy: &SendPolicy,
+) -> Result<(String, u128, Pubkey), TransferError> {
+    let (batch, stake_account) = sign_stake(client, source, authority, vote_account, lamports, budget)?;
+    let (signature, elapsed) = send_batch(client, &batch, lamports, policy).await?;
+    
+    Ok((signature, elapsed, stake_account))
+}
+
+/// Sign the stake transfer of [`send_stake_transaction`] as a batch of one, without sending
+/// it. Returns the batch and the new stake account's address.
+pub fn sign_stake(
+    client: &RpcClient,
+    source: SourceKey<'_>,
+    authority: &Pubkey,
+    vote_account: &Pubkey,
+    lamports: u64,
+    budget: &ComputeBudget,
+) -> Result<(SignedBatch, Pubkey), TransferError> {
+    let started = Instant::now();
+    
+    // The stake account key only signs its creation; the authority controls it afterwards
+    let stake_account = Keypair::new();
+    let instructions = stake_instructions(
+        &source.keypair.pubkey(),
+        &stake_account.pubkey(),
+        authority,
+        vote_account,
+        lamports,
+    );
+    let payload_len = instructions.len();
+    let submission = sign(client, &[source.keypair, &stake_account], instructions, budget, source.nonce)?;
+    let starts = vec![submission.transaction().message.instructions.len() - payload_len];
+    
+    Ok((
+        SignedBatch {
+            submission,
+            starts,
+            started,
+        },
+        stake_account.pubkey(),
+    ))
+}
+
+/// Build and sign the same stake transfer as [`send_stake_transaction`], with a throwaway
+/// stake account, but only simulate it
+pub fn simulate_stake_transaction(
+    client: &RpcClient,
+    source: SourceKey<'_>,
+    authority: &Pubkey,
+    vote_account: &Pubkey,
+    lamports: u64,
+    budget: &ComputeBudget,
+) -> Result<Simulation, TransferError> {
+    let stake_account = Keypair::new();
+    let instructions = stake_instructions(
+        &source.keypair.pubkey(),
+        &stake_account.pubkey(),
+        authority,
+        vote_account,
+        lamports,
+    );
+    let submission = sign(client, &[source.keypair, &stake_account], instructions, budget, source.nonce)?;
+    simulate(client, submission.transaction())
+}
+
+/// Transfers of one source signed into a single transaction
+pub struct SignedBatch {
+    submission: Submission,
+    /// Index of the first instruction of every transfer, after the nonce and compute budget ones
+    starts: Vec<usize>,
+    started: Instant,
+}
+
+impl SignedBatch {
+    /// Transfer of the batch that instruction `index` of the transaction belongs to; None
+    /// for the nonce and compute budget instructions
+    pub fn transfer_at(&self, index: u8) -> Option<usize> {
+        let index = index as usize;
+        if index < *self.starts.first()? {
+            return None;
+        }
+        Some(self.starts.partition_point(|&start| start <= index) - 1)
+    }
+    
+    /// Last block height the transaction can land at, as reported with its blockhash by the
+    /// node that signed it; None for durable nonce transactions, which do not expire
+    pub fn last_valid_block_height(&self) -> Option<u64> {
+        self.submission.last_valid_block_height()
+    }
+    
+    pub fn signature(&self) -> Signature {
+        self.submission.signature()
+    }
+    
+    /// Whether the batch can still land, so that a retry has to send it again as signed
+    pub fn is_live(&self, client: &RpcClient) -> Result<bool, TransferError> {
+        Ok(self.submission.is_live(client)?)
+    }
+    
+    /// Milliseconds since the batch was signed
+    pub fn elapsed_ms(&self) -> u128 {
+        self.started.elapsed().as_millis()
+    }
+}
+
+/// Split the instructions of each transfer paid by `payer` into consecutive runs that each fit
+/// in one transaction, next to the compute budget instructions and, with a durable `nonce`,
+/// its advance instruction. A transfer too large to share a transaction goes alone.
+pub fn pack_transfers(payer: &Pubkey, nonce: Option<&Pubkey>, transfers: &[Vec<Instruction>]) -> Vec<Range<usize>> {
+    // Worst case overhead: a unit price, a unit limit and the nonce advance
+    let mut overhead = vec![
+        ComputeBudgetInstruction::set_compute_unit_price(0),
+        ComputeBudgetInstruction::set_compute_unit_limit(0),
+    ];
+    if let Some(nonce) = nonce {
+        overhead.push(system_instruction::advance_nonce_account(nonce, payer));
+    }
+    let fits = |instructions: &[Instruction]| {
+        let message = Message::new(instructions, Some(payer));
+        let signatures = message.header.num_required_signatures as usize;
+        // A compact-u16 signature count, the signatures, then the message
+        1 + signatures * 64 + message.serialize().len() <= PACKET_DATA_SIZE
+    };
+    
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut instructions = overhead.clone();
+    for (index, transfer) in transfers.iter().enumerate() {
+        instructions.extend(transfer.iter().cloned());
+        if index > start && !fits(&instructions) {
+            runs.push(start..index);
+            start = index;
+            instructions = overhead.clone();
+            instructions.extend(transfer.iter().cloned());
+        }
+    }
+    if start < transfers.len() {
+        runs.push(start..transfers.len());
+    }
+    runs
+}
+
+/// Budget and sign the instructions of several transfers from `source` as one transaction
+pub fn sign_batch(
+    client: &RpcClient,
+    source: SourceKey<'_>,
+    transfers: Vec<Vec<Instruction>>,
+    budget: &ComputeBudget,
+) -> Result<SignedBatch, TransferError> {
+    let started = Instant::now();
+    let lens: Vec<usize> = transfers.iter().map(Vec::len).collect();
+    let instructions: Vec<Instruction> = transfers.into_iter().flatten().collect();
+    let payload_len = instructions.len();
+    let submission = sign(client, &[source.keypair], instructions, budget, source.nonce)?;
+    
+    // The nonce and compute budget instructions come first, the transfers after them in order
+    let mut start = submission.transaction().message.instructions.len() - payload_len;
+    let starts = lens
+        .iter()
+        .map(|len| {
+            let first = start;
+            start += len;
+            first
+        })
+        .collect();
+    Ok(SignedBatch {
+        submission,
+        starts,
+        started,
+    })
+}
+
+/// Send a signed batch moving `lamports` in total, like a single transfer
+pub async fn send_batch(
+    client: &RpcClient,
+    batch: &SignedBatch,
+    lamports: u64,
+    policy: &SendPolicy,
+) -> Result<(String, u128), TransferError> {
+    let signature = send_signed(client, &batch.submission, lamports, policy, batch.started).await?;
+    Ok((signature.to_string(), batch.elapsed_ms()))
+}
+
+/// Simulate a signed batch without broadcasting it
+pub fn simulate_batch(client: &RpcClient, batch: &SignedBatch) -> Result<Simulation, TransferError> {
+    simulate(client, batch.submission.transaction())
+}
+
+/// Budget `instructions` and sign them (the first signer pays) with the latest blockhash, or
+/// with the blockhash of the durable `nonce` account so the transaction does not expire.
+/// The submission carries the last block height its blockhash is valid at, None with a nonce.
+fn sign(
+    client: &RpcClient,
+    signers: &[&Keypair],
+    instructions: Vec<Instruction>,
+    budget: &ComputeBudget,
+    nonce: Option<&Pubkey>,
+) -> Result<Submission, TransferError> {
+    let payer = signers[0].pubkey();
+    
+    let Some(nonce) = nonce else {
+        let instructions = budget.apply(client, &payer, instructions)?;
+        let (recent_blockhash, last_valid_block_height) = client
+            .get_latest_blockhash_with_commitment(client.commitment())
+            .map_err(|e| RpcError::request("getLatestBlockhash", &client.url(), e))?;
+        let transaction = Transaction::new_signed_with_payer(&instructions, Some(&payer), signers, recent_blockhash);
+        return Ok(Submission::new(transaction, Some(last_valid_block_height)));
+    };
+    
+    // The nonce must be advanced by the very first instruction, ahead of the compute budget
+    // ones, but is budgeted together with the transfer
+    let blockhash = nonce_blockhash(client, nonce, &payer)?;
+    let transfer_len = instructions.len();
+    let advance = system_instruction::advance_nonce_account(nonce, &payer);
+    let mut instructions = budget.apply(client, &payer, [advance].into_iter().chain(instructions).collect())?;
+    let budget_len = instructions.len() - transfer_len - 1;
+    instructions[..=budget_len].rotate_right(1);
+    
+    Ok(Submission::new(Transaction::new_signed_with_payer(&instructions, Some(&payer), signers, blockhash), None))
+}
+
+/// Blockhash stored in the durable `nonce` account, which `authority` must be able to advance
+fn nonce_blockhash(client: &RpcClient, nonce: &Pubkey, authority: &Pubkey) -> Result<Hash, TransferError> {
+    let nonce_error = |reason: String| TransferError::Nonce {
+        account: *nonce,
+        reason,
+    };
+    let account = nonce_utils::get_account_with_commitment(client, nonce, client.commitment())
+        .map_err(|e| nonce_error(e.to_string()))?;
+    let data = nonce_utils::data_from_account(&account).map_err(|e| nonce_error(e.to_string()))?;
+    if data.authority != *authority {
+        return Err(nonce_error(format!("its authority is {}, not the source wallet", data.authority)));
+    }
+    Ok(data.blockhash())
+}
+
+/// Simulate a signed `transaction` and price it, without broadcasting it
+fn simulate(client: &RpcClient, transaction: &Transaction) -> Result<Simulation, TransferError> {
+    let simulation = client
+        .simulate_transaction(transaction)
+        .map_err(|e| RpcError::request("simulateTransaction", &client.url(), e))?
+        .value;
+    let fee_lamports = client
+        .get_fee_for_message(transaction.message())
+        .map_err(|e| RpcError::request("getFeeForMessage", &client.url(), e))?;
+    
+    Ok(Simulation {
+        error: simulation.err,
+        logs: simulation.logs.unwrap_or_default(),
+        units_consumed: simulation.units_consumed,
+        fee_lamports,
+    })
+}
+
+/// Safety-check and broadcast a signed `submission`, then wait for confirmation when the
+/// policy has a deadline. A send that fails without proof that nothing was broadcast is
+/// `Unsettled`, or confirmed like a sent one under a deadline.
+async fn send_signed(
+    client: &RpcClient,
+    submission: &Submission,
+    lamports: u64,
+    policy: &SendPolicy,
+    start: Instant,
+) -> Result<Signature, TransferError> {
+    let transaction = submission.transaction();
+    policy.safety.check(client, transaction, lamports)?;
+    
+    // The fee, priority fee included, is charged to the run's ceiling before broadcasting
+    let reserved_fee = match &policy.fee_ceiling {
+        Some(ceiling) => {
+            let fee = client
+                .get_fee_for_message(transaction.message())
+                .map_err(|e| RpcError::request("getFeeForMessage", &client.url(), e))?;
+            ceiling.reserve(fee).map_err(|spent| TransferError::FeeCeiling {
+                spent,
+                fee,
+                max: ceiling.max_lamports(),
+            })?;
+            Some((ceiling, fee))
+        }
+        None => None,
+    };
+    
+    let signature = match policy.submitter.send(client, transaction).await {
+        Ok(signature) => signature,
+        Err(e) if policy.submitter.rejected(&e) => {
+            // Rejected transactions never land, so they cost nothing
+            if let Some((ceiling, fee)) = reserved_fee {
+                ceiling.release(fee);
+            }
+            return Err(e.into());
+        }
+        Err(e) if policy.deadline.is_some() => {
+            eprintln!("Send of {} may have gone through ({}), confirming it", transaction.signatures[0], e);
+            transaction.signatures[0]
+        }
+        Err(e) => {
+            return Err(TransferError::Unsettled {
+                signature: transaction.signatures[0].to_string(),
+                source: e,
+            })
+        }
+    };
+    
+    if let Some(deadline) = policy.deadline {
+        confirm_before(client, &policy.submitter, transaction, &signature, start, deadline, submission.last_valid_block_height()).await?;
+    }
+    
+    Ok(signature)
+}
+
+/// Poll `signature` until it is confirmed, rebroadcasting the same signed transaction
+/// between polls (which can never land twice), and give up once `deadline` has passed
+/// since `start`. It is expired once the chain is past `last_valid_block_height` without it.
+/// The transaction may have been broadcast, so failed polls are retried rather than returned.
+async fn confirm_before(
+    client: &RpcClient,
+    submitter: &Submitter,
+    transaction: &Transaction,
+    signature: &Signature,
+    start: Instant,
+    deadline: Duration,
+    last_valid_block_height: Option<u64>,
+) -> Result<(), TransferError> {
+    loop {
+        // None when the poll failed, Some(None) when the node does not know the transaction
+        let status = match client.get_signature_statuses(&[*signature]) {
+            Ok(statuses) => Some(statuses.value.into_iter().next().flatten()),
+            Err(e) => {
+                eprintln!("Status check of {} failed: {}", signature, e);
+                None
+            }
+        };
+
+        if let Some(Some(status)) = &status {
+            if let Some(err) = &status.err {
+                return Err(TransferError::Failed {
+                    signature: signature.to_string(),
+                    error: err.clone(),
+                });
+            }
+            if status.satisfies_commitment(client.commitment()) {
+                return Ok(());
+            }
+        } else if status.is_some() && is_expired(client, last_valid_block_height) {
+            return Err(TransferError::Expired {
+                signature: signature.to_string(),
+            });
+        }
+
+        let remaining = deadline.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Err(TransferError::Abandoned {
+                signature: signature.to_string(),
+                after: deadline,
+            });
+        }
+        tokio::time::sleep(remaining.min(CONFIRM_POLL_INTERVAL)).await;
+
+        if !matches!(status, Some(Some(_))) {
+            if let Err(e) = submitter.send(client, transaction).await {
+                eprintln!("Rebroadcast of {} failed: {}", signature, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfers(payer: &Pubkey, count: usize) -> Vec<Vec<Instruction>> {
+        (0..count)
+            .map(|_| vec![system_instruction::transfer(payer, &Pubkey::new_unique(), 1)])
+            .collect()
+    }
+
+    fn packet_len(payer: &Pubkey, nonce: Option<&Pubkey>, transfers: &[Vec<Instruction>]) -> usize {
+        let mut instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_price(u64::MAX),
+            ComputeBudgetInstruction::set_compute_unit_limit(u32::MAX),
+        ];
+        if let Some(nonce) = nonce {
+            instructions.push(system_instruction::advance_nonce_account(nonce, payer));
+        }
+        instructions.extend(transfers.iter().flatten().cloned());
+        let message = Message::new(&instructions, Some(payer));
+        1 + message.header.num_required_signatures as usize * 64 + message.serialize().len()
+    }
+
+    #[test]
+    fn packs_transfers_into_full_transactions() {
+        let payer = Pubkey::new_unique();
+        let nonce = Pubkey::new_unique();
+        for nonce in [None, Some(&nonce)] {
+            let transfers = transfers(&payer, 100);
+            let runs = pack_transfers(&payer, nonce, &transfers);
+            assert!(runs.len() > 1, "100 transfers do not fit one packet");
+
+            // Consecutive runs covering every transfer, each one as full as it can be
+            let mut next = 0;
+            for run in &runs {
+                assert_eq!(run.start, next);
+                assert!(!run.is_empty());
+                assert!(packet_len(&payer, nonce, &transfers[run.clone()]) <= PACKET_DATA_SIZE);
+                if run.end < transfers.len() {
+                    let grown = &transfers[run.start..run.end + 1];
+                    assert!(packet_len(&payer, nonce, grown) > PACKET_DATA_SIZE);
+                }
+                next = run.end;
+            }
+            assert_eq!(next, transfers.len());
+        }
+    }
+
+    #[test]
+    fn oversized_transfer_goes_alone() {
+        let payer = Pubkey::new_unique();
+        let mut transfers = transfers(&payer, 3);
+        let memo = spl_memo::build_memo(&[b'x'; PACKET_DATA_SIZE], &[&payer]);
+        transfers.insert(1, vec![system_instruction::transfer(&payer, &Pubkey::new_unique(), 1), memo]);
+
+        assert_eq!(pack_transfers(&payer, None, &transfers), vec![0..1, 1..2, 2..4]);
+        assert!(pack_transfers(&payer, None, &[]).is_empty());
+    }
+
+    #[test]
+    fn maps_instructions_back_to_transfers() {
+        // A nonce advance and two compute budget instructions, then transfers of 1, 2 and 2 instructions
+        let batch = SignedBatch {
+            submission: Submission::new(Transaction::default(), None),
+            starts: vec![3, 4, 6],
+            started: Instant::now(),
+        };
+        let owners: Vec<Option<usize>> = (0..8).map(|index| batch.transfer_at(index)).collect();
+        assert_eq!(owners, [None, None, None, Some(0), Some(1), Some(1), Some(2), Some(2)]);
+
+        let empty = SignedBatch {
+            submission: Submission::new(Transaction::default(), None),
+            starts: Vec::new(),
+            started: Instant::now(),
+        };
+        assert_eq!(empty.transfer_at(0), None);
+    }
+}
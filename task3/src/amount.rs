@@ -0,0 +1,102 @@
+use rand::Rng;
+use serde::Deserialize;
+
+/// How a pipeline sizes each triggered transfer
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum AmountStrategy {
+    /// The same amount every time
+    Fixed { lamports: u64 },
+    /// Uniformly random within the range, both ends included
+    Random { min_lamports: u64, max_lamports: u64 },
+    /// Proportional to the number of transactions in the triggering block
+    PerTransaction {
+        lamports_per_transaction: u64,
+        #[serde(default)]
+        max_lamports: Option<u64>,
+    },
+    /// A field of the latest update of a watched account, divided by `divisor`
+    AccountField {
+        /// Address or address book alias; resolved to an address at startup
+        account: String,
+        field: AccountField,
+        /// Byte offset of a `data_u64` field
+        #[serde(default)]
+        offset: usize,
+        #[serde(default = "default_divisor")]
+        divisor: u64,
+        #[serde(default)]
+        max_lamports: Option<u64>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountField {
+    Lamports,
+    /// Little-endian u64 in the account data
+    DataU64,
+}
+
+fn default_divisor() -> u64 {
+    1
+}
+
+/// Latest state of the account an `account_field` strategy reads
+#[derive(Debug, Clone)]
+pub struct AccountSnapshot {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+}
+
+impl AmountStrategy {
+    /// Account whose updates must be streamed for this strategy
+    pub fn watched_account(&self) -> Option<&str> {
+        match self {
+            AmountStrategy::AccountField { account, .. } => Some(account),
+            _ => None,
+        }
+    }
+
+    /// Amount for a block with `transactions` transactions. `None` when it cannot be computed
+    /// yet: no update of the watched account has arrived or its data is too short.
+    pub fn amount(&self, transactions: usize, account: Option<&AccountSnapshot>) -> Option<u64> {
+        match self {
+            AmountStrategy::Fixed { lamports } => Some(*lamports),
+            AmountStrategy::Random {
+                min_lamports,
+                max_lamports,
+            } => {
+                if min_lamports >= max_lamports {
+                    return Some(*min_lamports);
+                }
+                Some(rand::thread_rng().gen_range(*min_lamports..=*max_lamports))
+            }
+            AmountStrategy::PerTransaction {
+                lamports_per_transaction,
+                max_lamports,
+            } => {
+                let amount = lamports_per_transaction.saturating_mul(transactions as u64);
+                Some(max_lamports.map_or(amount, |max| amount.min(max)))
+            }
+            AmountStrategy::AccountField {
+                field,
+                offset,
+                divisor,
+                max_lamports,
+                ..
+            } => {
+                let account = account?;
+                let value = match field {
+                    AccountField::Lamports => account.lamports,
+                    AccountField::DataU64 => {
+                        let bytes = account.data.get(*offset..offset.checked_add(8)?)?;
+                        u64::from_le_bytes(bytes.try_into().ok()?)
+                    }
+                };
+                let amount = value / (*divisor).max(1);
+                Some(max_lamports.map_or(amount, |max| amount.min(max)))
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use serde::Deserialize;
+use std::sync::Mutex;
+
+/// Keep a pipeline from sending transfers its source wallet cannot pay for
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct BudgetConfig {
+    /// Lamports the source wallet always keeps; a trigger whose amount and fee would dip
+    /// below is skipped
+    pub reserve_lamports: u64,
+    /// Fee debited per send on top of the amount, until the wallet's next update
+    #[serde(default = "default_fee_lamports")]
+    pub fee_lamports: u64,
+}
+
+fn default_fee_lamports() -> u64 {
+    5_000
+}
+
+/// Outcome of asking the budget for a trigger's lamports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spend {
+    /// Debited from the local balance; `resumed` when the previous trigger was refused
+    Approved { resumed: bool },
+    /// Would leave less than the reserve; `alert` on the first refusal since the last
+    /// approved trigger
+    Refused { lamports: u64, alert: bool },
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Unknown until the first RPC snapshot or streamed update of the wallet
+    lamports: Option<u64>,
+    exhausted: bool,
+}
+
+/// Local view of a source wallet's balance: debited on every send, replaced by every update
+/// of the wallet, so triggers stop before transfers start failing on-chain
+#[derive(Debug)]
+pub struct Budget {
+    config: BudgetConfig,
+    state: Mutex<State>,
+}
+
+impl Budget {
+    pub fn new(config: BudgetConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn config(&self) -> &BudgetConfig {
+        &self.config
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Take the balance the RPC node or the stream reports for the wallet. It may still
+    /// include sends that have not landed; their update corrects it once they do.
+    pub fn observe(&self, lamports: u64) {
+        self.state().lamports = Some(lamports);
+    }
+
+    /// Debit `amount` plus the fee, unless that leaves less than the reserve. Triggers are let
+    /// through while the balance is unknown.
+    pub fn spend(&self, amount: u64) -> Spend {
+        let mut state = self.state();
+        let cost = amount.saturating_add(self.config.fee_lamports);
+        match state.lamports {
+            Some(lamports)
+                if !lamports
+                    .checked_sub(cost)
+                    .is_some_and(|left| left >= self.config.reserve_lamports) =>
+            {
+                let alert = !state.exhausted;
+                state.exhausted = true;
+                Spend::Refused { lamports, alert }
+            }
+            lamports => {
+                state.lamports = lamports.map(|lamports| lamports - cost);
+                let resumed = std::mem::take(&mut state.exhausted);
+                Spend::Approved { resumed }
+            }
+        }
+    }
+
+    /// Credit back an approved `amount` whose transaction was never sent
+    pub fn refund(&self, amount: u64) {
+        let cost = amount.saturating_add(self.config.fee_lamports);
+        if let Some(lamports) = &mut self.state().lamports {
+            *lamports = lamports.saturating_add(cost);
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Minimum distance between two fired triggers of a pipeline
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CooldownConfig {
+    Slots(u64),
+    Seconds(u64),
+}
+
+/// Whether a trigger may fire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Fire; `coalesced` triggers were swallowed since the previous one fired
+    Fire { coalesced: u64 },
+    /// Still cooling down; the trigger is counted and dropped
+    Coalesced,
+}
+
+/// The last fired trigger of a pipeline and the triggers coalesced since, shared by the
+/// tasks that fire them
+#[derive(Debug)]
+pub struct Cooldown {
+    config: CooldownConfig,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    last: Option<(u64, Instant)>,
+    coalesced: u64,
+}
+
+impl Cooldown {
+    pub fn new(config: CooldownConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Admit a trigger at `slot`, starting a new cooldown when it fires
+    pub fn admit(&self, slot: u64) -> Admission {
+        let mut state = self.state.lock().expect("cooldown lock poisoned");
+        let cooling = state
+            .last
+            .is_some_and(|(last_slot, last_at)| match self.config {
+                CooldownConfig::Slots(slots) => slot.saturating_sub(last_slot) < slots,
+                CooldownConfig::Seconds(seconds) => {
+                    last_at.elapsed() < Duration::from_secs(seconds)
+                }
+            });
+        if cooling {
+            state.coalesced += 1;
+            return Admission::Coalesced;
+        }
+
+        state.last = Some((slot, Instant::now()));
+        Admission::Fire {
+            coalesced: std::mem::take(&mut state.coalesced),
+        }
+    }
+}
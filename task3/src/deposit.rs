@@ -0,0 +1,57 @@
+use serde::Deserialize;
+use solana_deposit_withdraw::instruction;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey};
+
+/// Call the task4 deposit program instead of sending a system transfer
+#[derive(Debug, Clone, Deserialize)]
+pub struct DepositActionConfig {
+    /// Deployed task4 program, address or address book alias
+    pub program_id: String,
+    /// Program-owned deposit account, address or address book alias
+    pub deposit_account: String,
+    pub instruction: DepositOperation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DepositOperation {
+    /// Move the amount from the pipeline's keypair into the deposit account
+    Deposit,
+    /// Move the amount from the deposit account, which the pipeline's keypair owns, to the
+    /// pipeline's destination
+    Withdraw,
+}
+
+/// A [`DepositActionConfig`] with its addresses resolved
+#[derive(Debug, Clone, Copy)]
+pub struct DepositAction {
+    pub program_id: Pubkey,
+    pub deposit_account: Pubkey,
+    pub operation: DepositOperation,
+}
+
+impl DepositAction {
+    /// The task4 instruction for one trigger, signed by `signer`
+    pub fn instruction(&self, signer: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction {
+        match self.operation {
+            DepositOperation::Deposit => {
+                instruction::deposit(&self.program_id, signer, &self.deposit_account, amount)
+            }
+            DepositOperation::Withdraw => instruction::withdraw(
+                &self.program_id,
+                signer,
+                &self.deposit_account,
+                destination,
+                amount,
+            ),
+        }
+    }
+
+    /// Accounts the instruction writes, for priority fee estimates
+    pub fn writable_accounts(&self, signer: Pubkey, destination: Pubkey) -> Vec<Pubkey> {
+        match self.operation {
+            DepositOperation::Deposit => vec![signer, self.deposit_account],
+            DepositOperation::Withdraw => vec![self.deposit_account, destination],
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use crate::geyser::{
+    filter::Filter as FilterEnum, subscribe_update::Update, AccountsFilter, BlocksFilter, Filter,
+    SubscribeRequest, SubscribeUpdate, TransactionsFilter,
+};
+
+/// What a pipeline reacts to, taken from a streamed update
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// A new block, which may trigger a transfer
+    Block { slot: u64, transactions: usize },
+    /// A new state of a watched account, kept for amount strategies reading it
+    Account {
+        pubkey: String,
+        lamports: u64,
+        data: Vec<u8>,
+    },
+    /// A successful non-vote transaction mentioning the subscribed program
+    Transaction { signature: String, slot: u64 },
+}
+
+/// What one pipeline streams
+#[derive(Debug, Clone, Default)]
+pub struct Subscription {
+    pub blocks: bool,
+    /// Accounts whose updates are streamed
+    pub accounts: Vec<String>,
+    /// Program whose successful non-vote transactions are streamed
+    pub program: Option<String>,
+}
+
+impl Subscription {
+    pub fn request(&self) -> SubscribeRequest {
+        let mut filters = Vec::new();
+        if self.blocks {
+            filters.push(Filter {
+                filter: Some(FilterEnum::Blocks(BlocksFilter {
+                    account_include: false,
+                })),
+            });
+        }
+        if !self.accounts.is_empty() {
+            filters.push(Filter {
+                filter: Some(FilterEnum::Accounts(AccountsFilter {
+                    account: self.accounts.clone(),
+                    owner: String::new(),
+                    filters: Vec::new(),
+                })),
+            });
+        }
+        if let Some(program) = &self.program {
+            filters.push(Filter {
+                filter: Some(FilterEnum::Transactions(TransactionsFilter {
+                    vote: false,
+                    failed: false,
+                    signature: String::new(),
+                    account_include: vec![program.clone()],
+                    account_exclude: Vec::new(),
+                })),
+            });
+        }
+
+        SubscribeRequest { filters }
+    }
+}
+
+/// Subscription request for new blocks, without per-account filtering
+pub fn blocks_request() -> SubscribeRequest {
+    Subscription {
+        blocks: true,
+        ..Subscription::default()
+    }
+    .request()
+}
+
+/// Block, account or transaction event of an update; every other update kind is ignored
+pub fn stream_event(update: SubscribeUpdate) -> Option<StreamEvent> {
+    match update.update {
+        Some(Update::Block(block)) => Some(StreamEvent::Block {
+            slot: block.slot,
+            transactions: block.transactions.len(),
+        }),
+        Some(Update::Account(account)) => Some(StreamEvent::Account {
+            pubkey: account.pubkey,
+            lamports: account.lamports,
+            data: account.data,
+        }),
+        Some(Update::Transaction(transaction)) => Some(StreamEvent::Transaction {
+            signature: transaction.signature,
+            slot: transaction.slot,
+        }),
+        _ => None,
+    }
+}
+
+/// Slot of a block update; every other update kind (pings, slots, accounts...) is ignored.
+/// Runs once per streamed update.
+pub fn block_slot(update: &SubscribeUpdate) -> Option<u64> {
+    match &update.update {
+        Some(Update::Block(block)) => Some(block.slot),
+        _ => None,
+    }
+}
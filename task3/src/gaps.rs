@@ -0,0 +1,25 @@
+/// Follows the slots of streamed blocks and spots the ones that never arrived, whether the
+/// provider dropped them, the stream was down or the leader skipped its slot
+#[derive(Debug, Default)]
+pub struct SlotGaps {
+    last: Option<u64>,
+}
+
+impl SlotGaps {
+    /// Record a block at `slot`; the number of slots missed since the previous block, if any.
+    /// Repeated or out-of-order blocks, e.g. replayed after a reconnect, are ignored.
+    pub fn observe(&mut self, slot: u64) -> Option<u64> {
+        let missed = match self.last {
+            Some(last) if slot <= last => return None,
+            Some(last) => slot - last - 1,
+            None => 0,
+        };
+        self.last = Some(slot);
+        (missed > 0).then_some(missed)
+    }
+
+    /// Slot of the latest block seen
+    pub fn last(&self) -> Option<u64> {
+        self.last
+    }
+}
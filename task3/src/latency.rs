@@ -0,0 +1,208 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+/// How many slots behind the newest one a slot waits for slower endpoints before it is
+/// settled, counting it as missed by every endpoint that has not delivered it
+const PENDING_SLOTS: u64 = 150;
+
+/// Which endpoint delivered a slot first and how far behind it each other one was
+#[derive(Debug)]
+pub struct SlotResult {
+    pub slot: u64,
+    pub first: usize,
+    /// Lag of every endpoint behind the first, `None` for one that never delivered the slot
+    pub lags: Vec<Option<Duration>>,
+}
+
+#[derive(Debug)]
+struct PendingSlot {
+    first: usize,
+    at: Instant,
+    lags: Vec<Option<Duration>>,
+    settled: bool,
+}
+
+#[derive(Debug, Default)]
+struct Standing {
+    wins: u64,
+    missed: u64,
+    /// Lag behind the first endpoint of every delivered slot, zero for the ones it won
+    lags: Vec<Duration>,
+    /// Lead over the runner-up of every slot it won
+    margins: Vec<Duration>,
+}
+
+/// Races several streams of the same blocks against each other, slot by slot
+#[derive(Debug)]
+pub struct LatencyRace {
+    endpoints: Vec<String>,
+    pending: BTreeMap<u64, PendingSlot>,
+    standings: Vec<Standing>,
+    newest: u64,
+}
+
+impl LatencyRace {
+    pub fn new(endpoints: Vec<String>) -> Self {
+        let standings = endpoints.iter().map(|_| Standing::default()).collect();
+        Self {
+            endpoints,
+            pending: BTreeMap::new(),
+            standings,
+            newest: 0,
+        }
+    }
+
+    /// Record `endpoint` receiving the block at `slot` at `at`. Returns the slots this settles:
+    /// the one every endpoint has now delivered and those too old to wait for any longer.
+    pub fn observe(&mut self, endpoint: usize, slot: u64, at: Instant) -> Vec<SlotResult> {
+        let mut settled = Vec::new();
+        match self.pending.get_mut(&slot) {
+            // Repeated after a reconnect
+            Some(pending) if pending.first == endpoint || pending.lags[endpoint].is_some() => {}
+            Some(pending) => {
+                pending.lags[endpoint] = Some(at.saturating_duration_since(pending.at));
+                let complete =
+                    pending.lags.iter().filter(|lag| lag.is_some()).count() == self.endpoints.len();
+                if complete && !pending.settled {
+                    pending.settled = true;
+                    let result = SlotResult {
+                        slot,
+                        first: pending.first,
+                        lags: pending.lags.clone(),
+                    };
+                    self.settle(&result);
+                    settled.push(result);
+                }
+            }
+            // Already settled and dropped from the window
+            None if slot + PENDING_SLOTS < self.newest => {}
+            None => {
+                let mut lags = vec![None; self.endpoints.len()];
+                lags[endpoint] = Some(Duration::ZERO);
+                self.pending.insert(
+                    slot,
+                    PendingSlot {
+                        first: endpoint,
+                        at,
+                        lags,
+                        settled: false,
+                    },
+                );
+                self.newest = self.newest.max(slot);
+            }
+        }
+
+        let keep = self
+            .pending
+            .split_off(&self.newest.saturating_sub(PENDING_SLOTS));
+        let expired = std::mem::replace(&mut self.pending, keep);
+        settled.extend(self.settle_all(expired));
+        settled
+    }
+
+    /// Settle every slot still waiting for an endpoint, at the end of the race
+    pub fn finish(&mut self) -> Vec<SlotResult> {
+        let pending = std::mem::take(&mut self.pending);
+        self.settle_all(pending)
+    }
+
+    fn settle_all(&mut self, slots: BTreeMap<u64, PendingSlot>) -> Vec<SlotResult> {
+        slots
+            .into_iter()
+            .filter(|(_, pending)| !pending.settled)
+            .map(|(slot, pending)| {
+                let result = SlotResult {
+                    slot,
+                    first: pending.first,
+                    lags: pending.lags,
+                };
+                self.settle(&result);
+                result
+            })
+            .collect()
+    }
+
+    fn settle(&mut self, result: &SlotResult) {
+        for (standing, lag) in self.standings.iter_mut().zip(&result.lags) {
+            match lag {
+                Some(lag) => standing.lags.push(*lag),
+                None => standing.missed += 1,
+            }
+        }
+        let winner = &mut self.standings[result.first];
+        winner.wins += 1;
+        let runner_up = result
+            .lags
+            .iter()
+            .enumerate()
+            .filter(|(endpoint, _)| *endpoint != result.first)
+            .filter_map(|(_, lag)| *lag)
+            .min();
+        if let Some(margin) = runner_up {
+            winner.margins.push(margin);
+        }
+    }
+
+    /// One line per settled slot: the first endpoint, then every other one's lag
+    pub fn describe(&self, result: &SlotResult) -> String {
+        let mut line = format!(
+            "Slot {}: {} first",
+            result.slot, self.endpoints[result.first]
+        );
+        for (endpoint, lag) in result.lags.iter().enumerate() {
+            if endpoint == result.first {
+                continue;
+            }
+            match lag {
+                Some(lag) => write!(line, ", {} +{}", self.endpoints[endpoint], millis(*lag)),
+                None => write!(line, ", {} missed", self.endpoints[endpoint]),
+            }
+            .expect("writing to a String");
+        }
+        line
+    }
+
+    /// Per-endpoint standings over every settled slot, fastest first
+    pub fn report(&self) -> String {
+        let slots: u64 = self.standings.iter().map(|standing| standing.wins).sum();
+        let mut order: Vec<usize> = (0..self.endpoints.len()).collect();
+        order.sort_by_key(|&endpoint| {
+            (
+                std::cmp::Reverse(self.standings[endpoint].wins),
+                percentile(&self.standings[endpoint].lags, 50).unwrap_or(Duration::MAX),
+            )
+        });
+
+        let mut report = format!("Endpoint latency over {} slot(s):\n", slots);
+        for endpoint in order {
+            let standing = &self.standings[endpoint];
+            writeln!(
+                report,
+                "  {}: first {} ({:.1}%), median lag {}, p90 lag {}, median lead when first {}, missed {}",
+                self.endpoints[endpoint],
+                standing.wins,
+                standing.wins as f64 * 100.0 / slots.max(1) as f64,
+                percentile(&standing.lags, 50).map_or("-".to_string(), millis),
+                percentile(&standing.lags, 90).map_or("-".to_string(), millis),
+                percentile(&standing.margins, 50).map_or("-".to_string(), millis),
+                standing.missed
+            )
+            .expect("writing to a String");
+        }
+        report
+    }
+}
+
+fn percentile(values: &[Duration], percent: usize) -> Option<Duration> {
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    let last = sorted.len().checked_sub(1)?;
+    Some(sorted[(last * percent + 50) / 100])
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
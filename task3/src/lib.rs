@@ -0,0 +1,21 @@
+//! Generated Geyser gRPC types, the per-update filter, and the amount strategies, source
+//! wallet budgets, log rules, cooldowns, transaction templates, task4 deposit actions, slot
+//! gap tracking, the stale-stream watchdog, the endpoint latency race and service manager
+//! integration used by the block-triggered sender.
+
+pub mod amount;
+pub mod budget;
+pub mod cooldown;
+pub mod deposit;
+pub mod filter;
+pub mod gaps;
+pub mod latency;
+pub mod logs;
+pub mod service;
+pub mod template;
+pub mod watchdog;
+
+// Include the generated gRPC code
+pub mod geyser {
+    tonic::include_proto!("geyser");
+}
@@ -0,0 +1,51 @@
+use regex::Regex;
+use serde::Deserialize;
+
+/// Fire only for transactions of `program` with a log line matching one of the patterns
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogRule {
+    /// Program (address or address book alias) whose transactions are streamed
+    pub program: String,
+    /// Substrings, any of which a log line may contain
+    #[serde(default)]
+    pub contains: Vec<String>,
+    /// Regular expressions, any of which a log line may match
+    #[serde(default)]
+    pub regex: Vec<String>,
+}
+
+/// A [`LogRule`] with its regexes compiled
+#[derive(Debug, Clone)]
+pub struct LogMatcher {
+    contains: Vec<String>,
+    regex: Vec<Regex>,
+}
+
+impl LogRule {
+    /// A rule without patterns matches every transaction of the program
+    pub fn matcher(&self) -> Result<LogMatcher, regex::Error> {
+        Ok(LogMatcher {
+            contains: self.contains.clone(),
+            regex: self
+                .regex
+                .iter()
+                .map(|pattern| Regex::new(pattern))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl LogMatcher {
+    /// First log line matching a pattern
+    pub fn find<'a>(&self, logs: &'a [String]) -> Option<&'a str> {
+        if self.contains.is_empty() && self.regex.is_empty() {
+            return logs.first().map(String::as_str);
+        }
+        logs.iter().map(String::as_str).find(|line| {
+            self.contains
+                .iter()
+                .any(|needle| line.contains(needle.as_str()))
+                || self.regex.iter().any(|regex| regex.is_match(line))
+        })
+    }
+}
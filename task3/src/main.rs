@@ -1,42 +1,223 @@
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use serde::{Deserialize, Serialize};
-use solana_client::rpc_client::RpcClient;
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_common::{
+    events::{self, Event, EventBus, SkipReason, TransferState},
+    rpc, ActionReceipt, AddressBook, AuditConfig, AuditLog, AuditRecord, BreakerConfig, CallError,
+    CliConfig, ComputeBudget, ComputeBudgetConfig, FeeOracle, FeeOracleConfig, OutputFormat,
+    ReceiptConfig, ReceiptLog, Render, Resilient, RetryPolicy, RpcLimits, SafetyConfig, StreamError,
+    SubmissionConfig, SubmissionProfile, Submitter, TpuConfig,
+};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    hash::Hash,
+    instruction::Instruction,
     pubkey::Pubkey,
-    signature::{Keypair, Signer},
+    signature::{Keypair, Signature, Signer},
     system_instruction,
     transaction::Transaction,
 };
-use std::{fs::File, path::Path, str::FromStr, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    net::SocketAddr,
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use solana_transaction_status::UiTransactionEncoding;
 use tokio::sync::mpsc;
-use tonic::transport::Channel;
+use tonic::{codec::Streaming, transport::Channel, Code, Status};
 
-// Include the generated gRPC code
-pub mod geyser {
-    tonic::include_proto!("geyser");
-}
-
-use geyser::{
-    geyser_client::GeyserClient,
-    Filter, SubscribeRequest, SubscribeUpdate,
-    filter::Filter as FilterEnum,
-    subscribe_update::Update,
-    BlocksFilter,
+use solana_geyser_subscription::{
+    amount::{AccountSnapshot, AmountStrategy},
+    budget::{Budget, BudgetConfig, Spend},
+    cooldown::{Admission, Cooldown, CooldownConfig},
+    deposit::{DepositAction, DepositActionConfig, DepositOperation},
+    filter::{self, StreamEvent, Subscription},
+    gaps::SlotGaps,
+    latency::LatencyRace,
+    logs::{LogMatcher, LogRule},
+    service,
+    template::{CompiledTemplate, TemplateValues, TransactionTemplate},
+    watchdog::WatchdogConfig,
+    geyser::{geyser_client::GeyserClient, SubscribeUpdate},
 };
 
-#[derive(Debug, Deserialize)]
+/// Set while `--emit ndjson` writes to stdout, which then carries nothing else
+static STDOUT_IS_TAP: AtomicBool = AtomicBool::new(false);
+
+/// Human-readable progress on stdout, or on stderr while stdout carries the NDJSON tap
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if STDOUT_IS_TAP.load(Ordering::Relaxed) {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+#[derive(Debug, Clone, Deserialize)]
 struct SourceWallet {
     address: String,
     secret_key: String,
 }
 
+/// One strategy: its own block stream, trigger rule, signer and destination
+#[derive(Debug, Clone, Deserialize)]
+struct PipelineConfig {
+    /// Geyser endpoint, defaults to `--grpc-endpoint`
+    #[serde(default)]
+    grpc_endpoint: Option<String>,
+    /// Overrides the top-level `grpc_fallback_endpoints`
+    #[serde(default)]
+    grpc_fallback_endpoints: Option<Vec<String>>,
+    /// Trigger on a block only when at least this many slots passed since the last trigger
+    #[serde(default = "default_slot_interval")]
+    slot_interval: u64,
+    /// Trigger on transactions of a program whose logs match, instead of on every block
+    #[serde(default)]
+    logs: Option<LogRule>,
+    /// After a trigger fires, drop further triggers for this many slots or seconds
+    #[serde(default)]
+    cooldown: Option<CooldownConfig>,
+    /// Falls back to the solana-cli default keypair when absent
+    #[serde(default)]
+    source_wallet: Option<SourceWallet>,
+    /// Required unless a `deposit_program` deposit action sends to its deposit account
+    #[serde(default)]
+    destination_wallet: Option<String>,
+    /// Shorthand for a `fixed` amount strategy
+    #[serde(default)]
+    amount_lamports: Option<u64>,
+    /// How each triggered transfer is sized; takes precedence over `amount_lamports`
+    #[serde(default)]
+    amount: Option<AmountStrategy>,
+    /// Instructions to send instead of a system transfer to `destination_wallet`
+    #[serde(default)]
+    transaction: Option<TransactionTemplate>,
+    /// Deposit into or withdraw from the task4 program instead of a system transfer
+    #[serde(default)]
+    deposit_program: Option<DepositActionConfig>,
+    /// Overrides the top-level `priority_fee`
+    #[serde(default)]
+    priority_fee: Option<FeeOracleConfig>,
+    /// Overrides the top-level `compute_budget`
+    #[serde(default)]
+    compute_budget: Option<ComputeBudgetConfig>,
+    /// Overrides the top-level `budget`
+    #[serde(default)]
+    budget: Option<BudgetConfig>,
+}
+
+fn default_slot_interval() -> u64 {
+    1
+}
+
 #[derive(Debug, Deserialize)]
 struct Config {
-    source_wallet: SourceWallet,
-    destination_wallet: String,
-    amount_lamports: u64,
+    /// Falls back to the solana-cli default keypair when absent
+    #[serde(default)]
+    source_wallet: Option<SourceWallet>,
+    /// Required unless `pipelines` or a `deposit_program` deposit action is set
+    #[serde(default)]
+    destination_wallet: Option<String>,
+    #[serde(default)]
+    amount_lamports: Option<u64>,
+    #[serde(default)]
+    amount: Option<AmountStrategy>,
+    #[serde(default)]
+    transaction: Option<TransactionTemplate>,
+    #[serde(default)]
+    deposit_program: Option<DepositActionConfig>,
+    /// Named pipelines run side by side; without them the top-level wallet settings form a single `default` pipeline
+    #[serde(default)]
+    pipelines: BTreeMap<String, PipelineConfig>,
+    #[serde(default)]
+    rpc_limits: RpcLimits,
+    #[serde(default)]
+    grpc_retry: RetryPolicy,
+    #[serde(default)]
+    grpc_circuit_breaker: BreakerConfig,
+    /// Geyser endpoints a stalled stream moves on to, in turn, after the pipeline's own
+    #[serde(default)]
+    grpc_fallback_endpoints: Vec<String>,
+    #[serde(default)]
+    stream_watchdog: WatchdogConfig,
+    #[serde(default)]
+    safety: SafetyConfig,
+    #[serde(default)]
+    audit: AuditConfig,
+    /// Sign a receipt of every triggered transaction with the operator key
+    #[serde(default)]
+    receipts: ReceiptConfig,
+    /// Attach a compute unit price suggested by the fee oracle to every triggered transfer
+    #[serde(default)]
+    priority_fee: Option<FeeOracleConfig>,
+    /// Request a compute unit limit sized from simulation for every triggered transfer
+    #[serde(default)]
+    compute_budget: Option<ComputeBudgetConfig>,
+    /// Track every source wallet's balance and skip triggers that would leave less than a reserve
+    #[serde(default)]
+    budget: Option<BudgetConfig>,
+    #[serde(default)]
+    submission: SubmissionConfig,
+}
+
+impl Config {
+    /// Configured pipelines by name, with top-level fee and fallback settings filled in where a pipeline sets none
+    fn pipelines(&self) -> Result<BTreeMap<String, PipelineConfig>> {
+        let mut pipelines = if self.pipelines.is_empty() {
+            if self.destination_wallet.is_none() && self.deposit_program.is_none() {
+                anyhow::bail!("Config needs destination_wallet, deposit_program or pipelines");
+            }
+            let pipeline = PipelineConfig {
+                grpc_endpoint: None,
+                grpc_fallback_endpoints: None,
+                slot_interval: default_slot_interval(),
+                logs: None,
+                cooldown: None,
+                source_wallet: self.source_wallet.clone(),
+                destination_wallet: self.destination_wallet.clone(),
+                amount_lamports: self.amount_lamports,
+                amount: self.amount.clone(),
+                transaction: self.transaction.clone(),
+                deposit_program: self.deposit_program.clone(),
+                priority_fee: None,
+                compute_budget: None,
+                budget: None,
+            };
+            BTreeMap::from([("default".to_string(), pipeline)])
+        } else {
+            self.pipelines.clone()
+        };
+        
+        for pipeline in pipelines.values_mut() {
+            if pipeline.priority_fee.is_none() {
+                pipeline.priority_fee = self.priority_fee.clone();
+            }
+            pipeline.compute_budget = pipeline.compute_budget.or(self.compute_budget);
+            pipeline.budget = pipeline.budget.or(self.budget);
+            if pipeline.grpc_fallback_endpoints.is_none() {
+                pipeline.grpc_fallback_endpoints = Some(self.grpc_fallback_endpoints.clone());
+            }
+        }
+        Ok(pipelines)
+    }
+}
+
+impl PipelineConfig {
+    fn amount_strategy(&self) -> Option<AmountStrategy> {
+        self.amount
+            .clone()
+            .or(self.amount_lamports.map(|lamports| AmountStrategy::Fixed { lamports }))
+    }
 }
 
 #[derive(Parser, Debug)]
@@ -49,33 +230,315 @@ struct Args {
     /// GRPC endpoint
     #[arg(short, long, default_value = "https://grpc.ny.shyft.to")]
     grpc_endpoint: String,
+    
+    /// Solana RPC endpoint [default: solana-cli config, else devnet]
+    #[arg(short, long)]
+    rpc_url: Option<String>,
+    
+    /// Simulate every transfer first and refuse to send failing or over-cap ones
+    #[arg(long)]
+    safe_mode: bool,
+    
+    /// Broadcast through public RPC or only through the configured private relays
+    #[arg(long)]
+    submission: Option<SubmissionProfile>,
+    
+    /// Send transfers straight to the upcoming leaders' TPU ports, falling back to RPC
+    #[arg(long)]
+    tpu: bool,
+    
+    /// Serve a WebSocket on this address pushing block triggers, transfer statuses and balance changes as JSON
+    #[arg(long)]
+    events_addr: Option<SocketAddr>,
+    
+    /// Also write every received update and trigger decision, one JSON object per line, to stdout (progress moves to stderr) or --emit-file
+    #[arg(long)]
+    emit: Option<Emit>,
+    
+    /// Append the --emit output to this file instead of stdout
+    #[arg(long, value_name = "FILE", requires = "emit")]
+    emit_file: Option<std::path::PathBuf>,
+    
+    /// Subscribe to blocks on every configured gRPC endpoint at once for SECS seconds (or until Ctrl-C), report which delivers each slot first and by what margin, then exit without sending anything
+    #[arg(long, value_name = "SECS")]
+    compare_endpoints: Option<u64>,
+    
+    /// Run under a service manager: report readiness to systemd (sd_notify) or the Windows service control manager, feed the systemd watchdog, and stop cleanly on SIGTERM or a service stop request
+    #[arg(long, conflicts_with = "compare_endpoints")]
+    service: bool,
+    
+    /// Format of the per-pipeline trigger summaries printed every minute: table, json, csv, ndjson or markdown
+    #[arg(short, long, default_value = "table")]
+    output: OutputFormat,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Emit {
+    Ndjson,
+}
+
+/// How often watched wallet balances are polled for the event stream
+const BALANCE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Lookups of a streamed transaction's logs before it is skipped, and the pause between them
+const LOG_FETCH_ATTEMPTS: u32 = 10;
+const LOG_FETCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often per-pipeline metrics are printed
+const METRICS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Counters of one pipeline, shared with its send tasks
+#[derive(Debug, Default)]
+struct PipelineMetrics {
+    blocks: AtomicU64,
+    /// Breaks in the streamed block slots, and the slots missing in them
+    slot_gaps: AtomicU64,
+    missed_slots: AtomicU64,
+    /// Streamed program transactions, for log-triggered pipelines
+    transactions: AtomicU64,
+    triggers: AtomicU64,
+    /// Triggers dropped during a cooldown
+    coalesced: AtomicU64,
+    /// Triggers skipped to keep the source wallet's reserve
+    below_reserve: AtomicU64,
+    sent: AtomicU64,
+    failed: AtomicU64,
+    lamports_sent: AtomicU64,
+}
+
+/// What a trigger sends
+#[derive(Clone)]
+enum Payload {
+    /// System transfer of the amount to the destination
+    Transfer,
+    /// Transaction template filled with the trigger's values
+    Template(Arc<CompiledTemplate>),
+    /// task4 deposit or withdrawal of the amount
+    Deposit(DepositAction),
+}
+
+impl Payload {
+    /// Instructions of one trigger
+    fn instructions(&self, values: &TemplateValues) -> Vec<Instruction> {
+        match self {
+            Payload::Transfer => vec![system_instruction::transfer(&values.source, &values.destination, values.amount)],
+            Payload::Template(template) => template.instructions(values),
+            Payload::Deposit(deposit) => vec![deposit.instruction(&values.source, &values.destination, values.amount)],
+        }
+    }
+    
+    /// Accounts the trigger's transaction writes, for priority fee estimates
+    fn writable_accounts(&self, source: Pubkey, destination: Pubkey) -> Vec<Pubkey> {
+        match self {
+            Payload::Transfer => vec![source, destination],
+            Payload::Template(template) => template.writable_accounts(source, destination),
+            Payload::Deposit(deposit) => deposit.writable_accounts(source, destination),
+        }
+    }
+}
+
+/// A pipeline ready to run, with everything it does not share with the others
+struct Pipeline {
+    name: String,
+    slot_interval: u64,
+    source_keypair: Keypair,
+    destination: Pubkey,
+    amount: AmountStrategy,
+    payload: Payload,
+    /// Program and log matcher of a log-triggered pipeline
+    logs: Option<(String, LogMatcher)>,
+    cooldown: Option<Arc<Cooldown>>,
+    fee_oracle: Option<Arc<FeeOracle>>,
+    compute_limit: Option<ComputeBudgetConfig>,
+    budget: Option<Arc<Budget>>,
+    /// The Geyser endpoint followed by its fallbacks, each with its own retries and breaker
+    streams: Vec<(String, Resilient)>,
+    watchdog: WatchdogConfig,
+    metrics: Arc<PipelineMetrics>,
+}
+
+/// What every pipeline sends through
+struct Shared {
+    rpc_client: Arc<RpcClient>,
+    safety: SafetyConfig,
+    submitter: Submitter,
+    audit: Option<Arc<AuditLog>>,
+    receipts: Option<Arc<ReceiptLog>>,
+    bus: EventBus,
+    /// Publish every stream update too, for `--emit`
+    tap: bool,
+}
+
+/// One pipeline's counters at the time of a report
+#[derive(Debug, Serialize)]
+struct TriggerSummary {
+    pipeline: String,
+    blocks: u64,
+    slot_gaps: u64,
+    missed_slots: u64,
+    transactions: u64,
+    triggers: u64,
+    coalesced: u64,
+    below_reserve: u64,
+    sent: u64,
+    failed: u64,
+    lamports_sent: u64,
+}
+
+impl TriggerSummary {
+    fn new(pipeline: &str, metrics: &PipelineMetrics) -> Self {
+        TriggerSummary {
+            pipeline: pipeline.to_string(),
+            blocks: metrics.blocks.load(Ordering::Relaxed),
+            slot_gaps: metrics.slot_gaps.load(Ordering::Relaxed),
+            missed_slots: metrics.missed_slots.load(Ordering::Relaxed),
+            transactions: metrics.transactions.load(Ordering::Relaxed),
+            triggers: metrics.triggers.load(Ordering::Relaxed),
+            coalesced: metrics.coalesced.load(Ordering::Relaxed),
+            below_reserve: metrics.below_reserve.load(Ordering::Relaxed),
+            sent: metrics.sent.load(Ordering::Relaxed),
+            failed: metrics.failed.load(Ordering::Relaxed),
+            lamports_sent: metrics.lamports_sent.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Every pipeline's summary, one row each
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct TriggerReport(Vec<TriggerSummary>);
+
+impl Render for TriggerReport {
+    fn columns(&self) -> &'static [&'static str] {
+        &[
+            "pipeline", "blocks", "slot_gaps", "missed_slots", "transactions", "triggers",
+            "coalesced", "below_reserve", "sent", "failed", "lamports_sent",
+        ]
+    }
+    
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .map(|summary| {
+                vec![
+                    summary.pipeline.clone(),
+                    summary.blocks.to_string(),
+                    summary.slot_gaps.to_string(),
+                    summary.missed_slots.to_string(),
+                    summary.transactions.to_string(),
+                    summary.triggers.to_string(),
+                    summary.coalesced.to_string(),
+                    summary.below_reserve.to_string(),
+                    summary.sent.to_string(),
+                    summary.failed.to_string(),
+                    summary.lamports_sent.to_string(),
+                ]
+            })
+            .collect()
+    }
+    
+    fn table(&self) -> String {
+        self.0
+            .iter()
+            .map(|summary| {
+                format!(
+                    "[{}] blocks: {}, slot gaps: {} ({} slots), transactions: {}, triggers: {}, coalesced: {}, below reserve: {}, sent: {}, failed: {}, lamports sent: {}\n",
+                    summary.pipeline,
+                    summary.blocks,
+                    summary.slot_gaps,
+                    summary.missed_slots,
+                    summary.transactions,
+                    summary.triggers,
+                    summary.coalesced,
+                    summary.below_reserve,
+                    summary.sent,
+                    summary.failed,
+                    summary.lamports_sent,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Print every pipeline's counters in `format` once per [`METRICS_INTERVAL`]
+async fn report_metrics(metrics: Vec<(String, Arc<PipelineMetrics>)>, format: OutputFormat) {
+    loop {
+        tokio::time::sleep(METRICS_INTERVAL).await;
+        let report = TriggerReport(
+            metrics
+                .iter()
+                .map(|(name, metrics)| TriggerSummary::new(name, metrics))
+                .collect(),
+        );
+        match solana_common::render::render(&report, format) {
+            Ok(rendered) => status!("{}", rendered.trim_end()),
+            Err(e) => eprintln!("Failed to render pipeline metrics: {}", e),
+        }
+    }
+}
+
+/// Publish a `balance_changed` event whenever one of `wallets` changes balance
+async fn watch_balances(
+    client: solana_client::nonblocking::rpc_client::RpcClient,
+    wallets: Vec<Pubkey>,
+    bus: EventBus,
+) {
+    let mut last: HashMap<Pubkey, u64> = HashMap::new();
+    
+    loop {
+        for wallet in &wallets {
+            match client.get_balance(wallet).await {
+                Ok(lamports) => {
+                    let previous = last.insert(*wallet, lamports);
+                    if previous != Some(lamports) {
+                        bus.publish(Event::BalanceChanged {
+                            address: wallet.to_string(),
+                            previous_lamports: previous,
+                            lamports,
+                        });
+                    }
+                }
+                Err(e) => eprintln!("Failed to poll balance of {}: {}", wallet, e),
+            }
+        }
+        
+        tokio::time::sleep(BALANCE_POLL_INTERVAL).await;
+    }
 }
 
+/// Send the instructions `build` returns for the latest blockhash; `lamports` is the amount
+/// checked against the safe mode cap
 async fn send_transaction(
     client: &RpcClient,
     source_keypair: &Keypair,
-    destination: &Pubkey,
+    build: impl FnOnce(Hash) -> Vec<Instruction>,
     lamports: u64,
+    budget: &ComputeBudget,
+    safety: &SafetyConfig,
+    submitter: &Submitter,
 ) -> Result<String> {
-    let instruction = system_instruction::transfer(
-        &source_keypair.pubkey(),
-        destination,
-        lamports,
-    );
-    
     let recent_blockhash = client
         .get_latest_blockhash()
         .context("Failed to get recent blockhash")?;
     
+    let instructions = budget
+        .apply(client, &source_keypair.pubkey(), build(recent_blockhash))
+        .context("Failed to size compute budget")?;
+    
     let transaction = Transaction::new_signed_with_payer(
-        &[instruction],
+        &instructions,
         Some(&source_keypair.pubkey()),
         &[source_keypair],
         recent_blockhash,
     );
     
-    let signature = client
-        .send_transaction(&transaction)
+    safety
+        .check(client, &transaction, lamports)
+        .context("Refused by safe mode")?;
+    
+    let signature = submitter
+        .send(client, &transaction)
+        .await
         .context("Failed to send transaction")?;
     
     Ok(signature.to_string())
@@ -92,130 +555,970 @@ fn load_keypair_from_secret(secret_key: &str) -> Result<Keypair> {
     Ok(keypair)
 }
 
-async fn subscribe_to_blocks(
-    grpc_endpoint: &str,
-    tx: mpsc::Sender<u64>,
-) -> Result<()> {
+async fn open_block_stream(grpc_endpoint: &str, subscription: &Subscription) -> Result<Streaming<SubscribeUpdate>, StreamError> {
+    let connect_error = |e: tonic::transport::Error| StreamError::Connect {
+        endpoint: grpc_endpoint.to_string(),
+        source: e.into(),
+    };
+    
     // Connect to the gRPC server
     let channel = Channel::from_shared(grpc_endpoint.to_string())
-        .context("Failed to create channel")?
+        .map_err(|e| StreamError::Connect {
+            endpoint: grpc_endpoint.to_string(),
+            source: e.into(),
+        })?
         .connect()
         .await
-        .context("Failed to connect to gRPC endpoint")?;
+        .map_err(connect_error)?;
     
     let mut client = GeyserClient::new(channel);
     
-    // Create a subscription request for new blocks
-    let blocks_filter = BlocksFilter {
-        account_include: false,
-    };
+    // Subscribe to new blocks or program transactions, plus the accounts amount strategies read
+    let stream = client
+        .subscribe(subscription.request())
+        .await
+        .map_err(|status| StreamError::Subscribe {
+            endpoint: grpc_endpoint.to_string(),
+            source: status.into(),
+        })?
+        .into_inner();
+    
+    Ok(stream)
+}
+
+/// Connection failures and overloaded/unavailable servers are retried, anything else is fatal
+fn is_transient_grpc_error(error: &StreamError) -> bool {
+    match error {
+        StreamError::Connect { source, .. } => source.is::<tonic::transport::Error>(),
+        StreamError::Subscribe { source, .. } => source.downcast_ref::<Status>().is_some_and(|status| {
+            matches!(
+                status.code(),
+                Code::Unavailable | Code::DeadlineExceeded | Code::ResourceExhausted | Code::Aborted | Code::Internal | Code::Unknown
+            )
+        }),
+        StreamError::CircuitOpen { .. } | StreamError::Closed { .. } => false,
+    }
+}
+
+/// Current state of the watched `accounts` from RPC, so amount strategies start from a real
+/// baseline instead of waiting for (or misreading) the first streamed update
+fn account_snapshots(name: &str, client: &RpcClient, accounts: &[String]) -> Vec<StreamEvent> {
+    let pubkeys: Vec<Pubkey> = accounts.iter().filter_map(|account| account.parse().ok()).collect();
+    if pubkeys.is_empty() {
+        return Vec::new();
+    }
     
-    let filter = Filter {
-        filter: Some(FilterEnum::Blocks(blocks_filter)),
+    match client.get_multiple_accounts(&pubkeys) {
+        Ok(found) => pubkeys
+            .iter()
+            .zip(found)
+            .filter_map(|(pubkey, account)| {
+                let Some(account) = account else {
+                    eprintln!("[{}] Watched account {} does not exist yet", name, pubkey);
+                    return None;
+                };
+                Some(StreamEvent::Account {
+                    pubkey: pubkey.to_string(),
+                    lamports: account.lamports,
+                    data: account.data,
+                })
+            })
+            .collect(),
+        Err(e) => {
+            eprintln!("[{}] Failed to snapshot watched accounts, waiting for stream updates: {}", name, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Forward the pipeline's updates to `tx` until it goes away, resubscribing whenever the stream
+/// breaks. A stream the watchdog finds stalled is restarted on the next of `streams`.
+async fn subscribe_to_blocks(
+    name: &str,
+    streams: &[(String, Resilient)],
+    subscription: &Subscription,
+    rpc_client: &RpcClient,
+    tx: mpsc::Sender<StreamEvent>,
+    watchdog: &WatchdogConfig,
+) -> Result<(), StreamError> {
+    let mut current = 0;
+    loop {
+        let (grpc_endpoint, resilience) = &streams[current];
+        let mut stream = match resilience
+            .call(|| open_block_stream(grpc_endpoint, subscription), is_transient_grpc_error)
+            .await
+        {
+            Ok(stream) => stream,
+            Err(CallError::CircuitOpen { endpoint }) => {
+                let wait = resilience.breaker().retry_in();
+                eprintln!("[{}] gRPC endpoint {} is failing, retrying in {:?}", name, endpoint, wait);
+                tokio::time::sleep(wait).await;
+                continue;
+            }
+            Err(CallError::Failed(e)) => return Err(e),
+        };
+        
+        status!("[{}] Successfully subscribed to updates on {}", name, grpc_endpoint);
+        
+        // Snapshot after subscribing, and again on every reconnect, so no update is lost between
+        // the baseline and the stream, including those missed while disconnected
+        for event in account_snapshots(name, rpc_client, &subscription.accounts) {
+            if tx.send(event).await.is_err() {
+                return Ok(());
+            }
+        }
+        
+        // Process incoming updates until the stream breaks, then resubscribe
+        loop {
+            // Dropping a stalled stream cancels its subscription
+            let Some(message) = watchdog.watch(stream.message()).await else {
+                current = (current + 1) % streams.len();
+                eprintln!("[{}] No update from {} for {}s, restarting the stream on {}", name, grpc_endpoint, watchdog.stale_after_secs, streams[current].0);
+                break;
+            };
+            match message {
+                Ok(Some(update)) => {
+                    if let Some(event) = filter::stream_event(update) {
+                        if let StreamEvent::Block { slot, .. } = &event {
+                            status!("[{}] New block detected: Slot {}", name, slot);
+                        }
+                        if tx.send(event).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                }
+                Ok(None) => {
+                    eprintln!("[{}] gRPC stream closed by server, resubscribing", name);
+                    break;
+                }
+                Err(status) => {
+                    eprintln!("[{}] gRPC stream error, resubscribing: {}", name, status);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Every distinct gRPC endpoint of the pipelines, own endpoints and fallbacks alike
+fn configured_endpoints(pipelines: &BTreeMap<String, PipelineConfig>, default_endpoint: &str) -> Vec<String> {
+    let mut endpoints: Vec<String> = Vec::new();
+    for pipeline in pipelines.values() {
+        let own = pipeline.grpc_endpoint.as_deref().unwrap_or(default_endpoint);
+        for endpoint in std::iter::once(own).chain(pipeline.grpc_fallback_endpoints.iter().flatten().map(String::as_str)) {
+            if !endpoints.iter().any(|known| known == endpoint) {
+                endpoints.push(endpoint.to_string());
+            }
+        }
+    }
+    endpoints
+}
+
+/// Stream blocks from `endpoint` into the race as `(index, slot, arrival)`, resubscribing
+/// whenever the stream breaks
+async fn race_endpoint(index: usize, endpoint: String, resilience: Resilient, tx: mpsc::Sender<(usize, u64, Instant)>) {
+    let subscription = Subscription {
+        blocks: true,
+        ..Subscription::default()
     };
+    loop {
+        let mut stream = match resilience
+            .call(|| open_block_stream(&endpoint, &subscription), is_transient_grpc_error)
+            .await
+        {
+            Ok(stream) => stream,
+            Err(CallError::CircuitOpen { .. }) => {
+                tokio::time::sleep(resilience.breaker().retry_in()).await;
+                continue;
+            }
+            Err(CallError::Failed(e)) => {
+                eprintln!("Dropping {} from the comparison: {}", endpoint, e);
+                return;
+            }
+        };
+        status!("Subscribed to blocks on {}", endpoint);
+        
+        loop {
+            match stream.message().await {
+                Ok(Some(update)) => {
+                    // Timestamped before anything else so the queue adds no lag
+                    let arrival = Instant::now();
+                    if let Some(slot) = filter::block_slot(&update) {
+                        if tx.send((index, slot, arrival)).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+                Ok(None) => {
+                    eprintln!("gRPC stream of {} closed by server, resubscribing", endpoint);
+                    break;
+                }
+                Err(status) => {
+                    eprintln!("gRPC stream error on {}, resubscribing: {}", endpoint, status);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// `--compare-endpoints`: race the block streams of every endpoint for `duration`, printing
+/// each slot's winner and the others' lag as slots settle, then the standings
+async fn compare_endpoints(endpoints: Vec<String>, config: &Config, duration: Duration) -> Result<()> {
+    if endpoints.len() < 2 {
+        anyhow::bail!("--compare-endpoints needs at least two gRPC endpoints; add grpc_fallback_endpoints or pipelines with their own grpc_endpoint");
+    }
+    status!("Comparing {} gRPC endpoints for {}s: {}", endpoints.len(), duration.as_secs(), endpoints.join(", "));
+    
+    let (tx, mut rx) = mpsc::channel(1024);
+    for (index, endpoint) in endpoints.iter().enumerate() {
+        let resilience = Resilient::new(endpoint, config.grpc_retry, config.grpc_circuit_breaker);
+        tokio::spawn(race_endpoint(index, endpoint.clone(), resilience, tx.clone()));
+    }
+    drop(tx);
     
-    let request = SubscribeRequest {
-        filters: vec![filter],
+    let mut race = LatencyRace::new(endpoints);
+    let deadline = tokio::time::sleep(duration);
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            arrival = rx.recv() => {
+                let Some((index, slot, at)) = arrival else {
+                    eprintln!("Every stream failed, ending the comparison early");
+                    break;
+                };
+                for result in race.observe(index, slot, at) {
+                    status!("{}", race.describe(&result));
+                }
+            }
+            _ = &mut deadline => break,
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    
+    for result in race.finish() {
+        status!("{}", race.describe(&result));
+    }
+    status!("{}", race.report().trim_end());
+    Ok(())
+}
+
+fn describe_amount(amount: &AmountStrategy) -> String {
+    match amount {
+        AmountStrategy::Fixed { lamports } => format!("{} lamports", lamports),
+        AmountStrategy::Random { min_lamports, max_lamports } => {
+            format!("{}-{} lamports", min_lamports, max_lamports)
+        }
+        AmountStrategy::PerTransaction { lamports_per_transaction, .. } => {
+            format!("{} lamports per block transaction", lamports_per_transaction)
+        }
+        AmountStrategy::AccountField { account, .. } => format!("an amount read from account {}", account),
+    }
+}
+
+/// The transfer a pipeline sends when triggered, with what it needs to send it from a spawned task
+#[derive(Clone)]
+struct Action {
+    name: String,
+    shared: Arc<Shared>,
+    keypair_bytes: [u8; 64],
+    source: Pubkey,
+    destination: Pubkey,
+    payload: Payload,
+    cooldown: Option<Arc<Cooldown>>,
+    fee_oracle: Option<Arc<FeeOracle>>,
+    compute_limit: Option<ComputeBudgetConfig>,
+    budget: Option<Arc<Budget>>,
+    metrics: Arc<PipelineMetrics>,
+}
+
+impl Action {
+    /// Report a trigger at `slot` that did not fire
+    fn skipped(&self, slot: u64, reason: SkipReason) {
+        self.shared.bus.publish(Event::TriggerSkipped {
+            slot,
+            reason,
+            pipeline: Some(self.name.clone()),
+        });
+    }
+    
+    /// Put lamports debited from the budget for a transfer that was never sent back
+    fn refund(&self, amount: u64) {
+        if let Some(budget) = &self.budget {
+            budget.refund(amount);
+        }
+    }
+    
+    /// Send `amount` lamports (or the pipeline's template or task4 call with it) for the trigger
+    /// at `slot`, reporting the outcome, unless the pipeline is cooling down from its previous trigger
+    /// or the transfer would leave the source wallet below its reserve
+    async fn fire(&self, slot: u64, amount: u64) {
+        let name = &self.name;
+        let shared = &self.shared;
+        // Checked before the cooldown so a refused trigger does not start one
+        if let Some(budget) = &self.budget {
+            match budget.spend(amount) {
+                Spend::Approved { resumed: true } => {
+                    status!("[{}] Source wallet {} is funded again, resuming triggers", name, self.source);
+                }
+                Spend::Approved { .. } => {}
+                Spend::Refused { lamports, alert } => {
+                    self.metrics.below_reserve.fetch_add(1, Ordering::Relaxed);
+                    self.skipped(slot, SkipReason::BelowReserve);
+                    if alert {
+                        let reserve_lamports = budget.config().reserve_lamports;
+                        eprintln!("[{}] Source wallet {} holds {} lamports, too little to send {} and keep the {} lamport reserve; skipping triggers until it is funded", name, self.source, lamports, amount, reserve_lamports);
+                        shared.bus.publish(Event::BudgetExhausted {
+                            source: self.source.to_string(),
+                            lamports,
+                            reserve_lamports,
+                            pipeline: Some(name.clone()),
+                        });
+                    }
+                    return;
+                }
+            }
+        }
+        if let Some(cooldown) = &self.cooldown {
+            match cooldown.admit(slot) {
+                Admission::Coalesced => {
+                    self.refund(amount);
+                    self.metrics.coalesced.fetch_add(1, Ordering::Relaxed);
+                    self.skipped(slot, SkipReason::Cooldown);
+                    return;
+                }
+                Admission::Fire { coalesced } if coalesced > 0 => {
+                    status!("[{}] {} trigger(s) coalesced during the cooldown", name, coalesced);
+                }
+                Admission::Fire { .. } => {}
+            }
+        }
+        self.metrics.triggers.fetch_add(1, Ordering::Relaxed);
+        let triggered_at = chrono::Utc::now();
+        status!("[{}] Processing block at slot: {} ({} lamports)", name, slot, amount);
+        shared.bus.publish(Event::GeyserTrigger {
+            slot,
+            pipeline: Some(name.clone()),
+        });
+        
+        // Recreate keypair from bytes
+        let keypair_copy = match Keypair::from_bytes(&self.keypair_bytes) {
+            Ok(kp) => kp,
+            Err(e) => {
+                eprintln!("[{}] Error recreating keypair: {}", name, e);
+                self.refund(amount);
+                return;
+            }
+        };
+        
+        let source = keypair_copy.pubkey();
+        let destination = self.destination;
+        let unit_price = match &self.fee_oracle {
+            Some(oracle) => {
+                let accounts = self.payload.writable_accounts(source, destination);
+                match oracle.suggest_fee(&accounts, oracle.urgency()).await {
+                    Ok(fee) => Some(fee),
+                    Err(e) => {
+                        eprintln!("[{}] No priority fee for block {}: {}", name, slot, e);
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+        let budget = ComputeBudget {
+            unit_price,
+            limit: self.compute_limit,
+        };
+        
+        let build = |blockhash| {
+            self.payload.instructions(&TemplateValues {
+                slot,
+                amount,
+                blockhash,
+                source,
+                destination,
+            })
+        };
+        
+        match send_transaction(&shared.rpc_client, &keypair_copy, build, amount, &budget, &shared.safety, &shared.submitter).await {
+            Ok(signature) => {
+                self.metrics.sent.fetch_add(1, Ordering::Relaxed);
+                self.metrics.lamports_sent.fetch_add(amount, Ordering::Relaxed);
+                status!("[{}] Transaction sent successfully for block {}", name, slot);
+                status!("[{}] Signature: {}", name, signature);
+                shared.bus.publish(Event::TransferStatus {
+                    source: keypair_copy.pubkey().to_string(),
+                    destination: self.destination.to_string(),
+                    lamports: amount,
+                    signature: Some(signature.clone()),
+                    status: TransferState::Sent,
+                    error: None,
+                    pipeline: Some(name.clone()),
+                });
+                
+                if let Some(audit) = &shared.audit {
+                    let record = AuditRecord {
+                        origin: "task3".to_string(),
+                        source: keypair_copy.pubkey().to_string(),
+                        destination: self.destination.to_string(),
+                        lamports: amount,
+                        signature: signature.clone(),
+                        manifest: None,
+                    };
+                    if let Err(e) = audit.append(record) {
+                        eprintln!("[{}] Failed to write audit entry for {}: {}", name, signature, e);
+                    }
+                }
+                
+                if let Some(receipts) = &shared.receipts {
+                    let receipt = ActionReceipt {
+                        rule: name.clone(),
+                        trigger_slot: slot,
+                        signature: signature.clone(),
+                        source: keypair_copy.pubkey().to_string(),
+                        destination: self.destination.to_string(),
+                        lamports: amount,
+                        triggered_at: triggered_at.to_rfc3339(),
+                        sent_at: chrono::Utc::now().to_rfc3339(),
+                    };
+                    if let Err(e) = receipts.append(receipt) {
+                        eprintln!("[{}] Failed to write receipt for {}: {}", name, signature, e);
+                    }
+                }
+            }
+            Err(e) => {
+                self.refund(amount);
+                self.metrics.failed.fetch_add(1, Ordering::Relaxed);
+                eprintln!("[{}] Failed to send transaction for block {}: {}", name, slot, e);
+                shared.bus.publish(Event::TransferStatus {
+                    source: keypair_copy.pubkey().to_string(),
+                    destination: self.destination.to_string(),
+                    lamports: amount,
+                    signature: None,
+                    status: TransferState::Failed,
+                    error: Some(format!("{:#}", e)),
+                    pipeline: Some(name.clone()),
+                });
+            }
+        }
+    }
+}
+
+/// The `--emit` event of a stream update
+fn update_event(event: &StreamEvent, pipeline: &str) -> Event {
+    let pipeline = Some(pipeline.to_string());
+    match event {
+        StreamEvent::Block { slot, transactions } => Event::BlockUpdate {
+            slot: *slot,
+            transactions: *transactions,
+            pipeline,
+        },
+        StreamEvent::Account { pubkey, lamports, data } => Event::AccountUpdate {
+            pubkey: pubkey.clone(),
+            lamports: *lamports,
+            data_len: data.len(),
+            pipeline,
+        },
+        StreamEvent::Transaction { signature, slot } => Event::TransactionUpdate {
+            signature: signature.clone(),
+            slot: *slot,
+            pipeline,
+        },
+    }
+}
+
+/// Log messages of a streamed transaction. The stream can run ahead of the RPC node, so the
+/// lookup is retried a few times before giving up.
+async fn transaction_logs(client: &RpcClient, signature: &str) -> Result<Vec<String>> {
+    let signature = Signature::from_str(signature).context("Invalid transaction signature")?;
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Json),
+        commitment: Some(client.commitment()),
+        max_supported_transaction_version: Some(0),
     };
     
-    // Subscribe to updates
-    let mut stream = client
-        .subscribe(request)
-        .await
-        .context("Failed to subscribe to gRPC stream")?
-        .into_inner();
+    let mut attempt = 1;
+    loop {
+        match client.get_transaction_with_config(&signature, config) {
+            Ok(transaction) => {
+                let logs: Option<Vec<String>> = transaction
+                    .transaction
+                    .meta
+                    .and_then(|meta| meta.log_messages.into());
+                return Ok(logs.unwrap_or_default());
+            }
+            Err(_) if attempt < LOG_FETCH_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(LOG_FETCH_INTERVAL).await;
+            }
+            Err(e) => return Err(e).context("Failed to fetch transaction logs"),
+        }
+    }
+}
+
+/// Stream updates for one pipeline and send its transfer on every trigger (a block, or a
+/// transaction whose logs match the pipeline's log rule), until the stream ends
+async fn run_pipeline(pipeline: Pipeline, shared: Arc<Shared>) {
+    let name = pipeline.name.clone();
     
-    println!("Successfully subscribed to block updates");
+    // Create a channel for block, account and transaction notifications
+    let (tx, mut rx) = mpsc::channel::<StreamEvent>(100);
     
-    // Process incoming updates
-    while let Some(update) = stream.message().await? {
-        if let Some(Update::Block(block)) = update.update {
-            println!("New block detected: Slot {}", block.slot);
-            tx.send(block.slot).await.ok();
+    // Spawn a task to subscribe to the pipeline's updates
+    let grpc_name = name.clone();
+    let streams = pipeline.streams.clone();
+    let watchdog = pipeline.watchdog;
+    // A budgeted source wallet is streamed too, so its local balance follows every change
+    let source = pipeline.source_keypair.pubkey().to_string();
+    let amount_account = pipeline.amount.watched_account();
+    let mut accounts: Vec<String> = amount_account.map(str::to_string).into_iter().collect();
+    if pipeline.budget.is_some() && amount_account != Some(source.as_str()) {
+        accounts.push(source.clone());
+    }
+    let subscription = Subscription {
+        blocks: pipeline.logs.is_none(),
+        accounts,
+        program: pipeline.logs.as_ref().map(|(program, _)| program.clone()),
+    };
+    let rpc_client = shared.rpc_client.clone();
+    let grpc_task = tokio::spawn(async move {
+        if let Err(e) = subscribe_to_blocks(&grpc_name, &streams, &subscription, &rpc_client, tx, &watchdog).await {
+            eprintln!("[{}] Error in gRPC subscription: {}", grpc_name, e);
+        }
+    });
+    
+    let action = Action {
+        name: name.clone(),
+        shared: shared.clone(),
+        keypair_bytes: pipeline.source_keypair.to_bytes(),
+        source: pipeline.source_keypair.pubkey(),
+        destination: pipeline.destination,
+        payload: pipeline.payload.clone(),
+        cooldown: pipeline.cooldown.clone(),
+        fee_oracle: pipeline.fee_oracle.clone(),
+        compute_limit: pipeline.compute_limit,
+        budget: pipeline.budget.clone(),
+        metrics: pipeline.metrics.clone(),
+    };
+    let mut last_trigger: Option<u64> = None;
+    let mut account: Option<AccountSnapshot> = None;
+    let mut gaps = SlotGaps::default();
+    // Process notifications and send transactions
+    while let Some(event) = rx.recv().await {
+        if shared.tap {
+            shared.bus.publish(update_event(&event, &name));
+        }
+        let (slot, transactions, signature) = match event {
+            StreamEvent::Block { slot, transactions } => {
+                pipeline.metrics.blocks.fetch_add(1, Ordering::Relaxed);
+                let previous = gaps.last();
+                if let Some(missed) = gaps.observe(slot) {
+                    pipeline.metrics.slot_gaps.fetch_add(1, Ordering::Relaxed);
+                    pipeline.metrics.missed_slots.fetch_add(missed, Ordering::Relaxed);
+                    eprintln!("[{}] Slot gap: {} slot(s) missing between blocks {} and {}", name, missed, previous.unwrap_or_default(), slot);
+                }
+                if last_trigger.is_some_and(|last| slot.saturating_sub(last) < pipeline.slot_interval) {
+                    action.skipped(slot, SkipReason::SlotInterval);
+                    continue;
+                }
+                (slot, transactions, None)
+            }
+            StreamEvent::Account { pubkey, lamports, data } => {
+                if let Some(budget) = pipeline.budget.as_ref().filter(|_| pubkey == source) {
+                    budget.observe(lamports);
+                }
+                if amount_account == Some(pubkey.as_str()) {
+                    account = Some(AccountSnapshot { lamports, data });
+                }
+                continue;
+            }
+            StreamEvent::Transaction { signature, slot } => {
+                pipeline.metrics.transactions.fetch_add(1, Ordering::Relaxed);
+                (slot, 1, Some(signature))
+            }
+        };
+        
+        let amount = match pipeline.amount.amount(transactions, account.as_ref()) {
+            Some(amount) if amount > 0 => amount,
+            Some(_) => {
+                status!("[{}] Skipping slot {}: amount strategy gave 0 lamports", name, slot);
+                action.skipped(slot, SkipReason::ZeroAmount);
+                continue;
+            }
+            None => {
+                status!("[{}] Skipping slot {}: no usable update of the amount account yet", name, slot);
+                action.skipped(slot, SkipReason::NoAccountState);
+                continue;
+            }
+        };
+        
+        let action = action.clone();
+        match (signature, &pipeline.logs) {
+            // Logs are looked up per transaction, so matching runs in its own task too
+            (Some(signature), Some((_, matcher))) => {
+                let matcher = matcher.clone();
+                tokio::spawn(async move {
+                    let logs = match transaction_logs(&action.shared.rpc_client, &signature).await {
+                        Ok(logs) => logs,
+                        Err(e) => {
+                            eprintln!("[{}] Skipping transaction {}: {:#}", action.name, signature, e);
+                            action.skipped(slot, SkipReason::LogsUnavailable);
+                            return;
+                        }
+                    };
+                    match matcher.find(&logs) {
+                        Some(line) => {
+                            status!("[{}] Transaction {} matched: {}", action.name, signature, line);
+                            action.fire(slot, amount).await;
+                        }
+                        None => action.skipped(slot, SkipReason::LogsUnmatched),
+                    }
+                });
+            }
+            _ => {
+                last_trigger = Some(slot);
+                // Execute transaction in a separate task
+                tokio::spawn(async move { action.fire(slot, amount).await });
+                
+                // Add a small delay to avoid rate limiting
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
         }
     }
     
-    Ok(())
+    // The channel only closes once the gRPC task is done
+    let _ = grpc_task.await;
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+/// Name passed to the Windows service control manager; register the binary under it with `sc.exe create`
+#[cfg(windows)]
+const SERVICE_NAME: &str = "solana_geyser_subscription";
+
+fn main() -> Result<()> {
     let args = Args::parse();
+    
+    // The service control manager has to own the main thread while the daemon runs on another
+    #[cfg(windows)]
+    if args.service {
+        return service::run_as_service(SERVICE_NAME, move || runtime()?.block_on(run(args)))
+            .context("Failed to start as a Windows service; register the binary with sc.exe create first");
+    }
+    
+    runtime()?.block_on(run(args))
+}
+
+fn runtime() -> Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start the async runtime")
+}
+
+async fn run(args: Args) -> Result<()> {
+    if args.emit.is_some() && args.emit_file.is_none() {
+        STDOUT_IS_TAP.store(true, Ordering::Relaxed);
+    }
     let config_path = Path::new(&args.config);
     
     // Load configuration
     let config_file = File::open(config_path).context("Failed to open config file")?;
     let config: Config = serde_yaml::from_reader(config_file).context("Failed to parse config file")?;
+    let pipeline_configs = config.pipelines()?;
     
-    // Set up Solana client
-    let rpc_client = Arc::new(RpcClient::new_with_commitment(
-        "https://api.devnet.solana.com".to_string(),
-        CommitmentConfig::confirmed(),
-    ));
+    // Benchmark mode only streams, so it needs no RPC, keys or submission setup
+    if let Some(secs) = args.compare_endpoints {
+        let endpoints = configured_endpoints(&pipeline_configs, &args.grpc_endpoint);
+        return compare_endpoints(endpoints, &config, Duration::from_secs(secs)).await;
+    }
     
-    // Load source keypair
-    let source_keypair = load_keypair_from_secret(&config.source_wallet.secret_key)
-        .context("Failed to load source keypair")?;
+    // Unset values fall back to the user's solana-cli config
+    let cli_config = CliConfig::load()?;
+    let rpc_url = cli_config.rpc_url(args.rpc_url.as_deref(), "https://api.devnet.solana.com");
+    let commitment = cli_config.commitment(CommitmentConfig::confirmed());
     
-    // Parse destination pubkey
-    let destination = Pubkey::from_str(&config.destination_wallet)
-        .context("Failed to parse destination wallet address")?;
+    // Set up Solana client
+    let rpc_client = Arc::new(rpc::rpc_client(&rpc_url, commitment, config.rpc_limits));
     
-    // Create a channel for block notifications
-    let (tx, mut rx) = mpsc::channel::<u64>(100);
+    let safety = config.safety.with_flag(args.safe_mode);
+    if safety.safe_mode {
+        status!("Safe mode: simulating every transfer before sending");
+    }
     
-    // Spawn a task to subscribe to block updates
-    let grpc_task = tokio::spawn(async move {
-        if let Err(e) = subscribe_to_blocks(&args.grpc_endpoint, tx).await {
-            eprintln!("Error in gRPC subscription: {}", e);
+    let mut submitter = Submitter::new(&config.submission.clone().with_profile(args.submission))?;
+    if submitter.profile() == SubmissionProfile::Private {
+        status!("Private submission: sending only through {} relay(s)", config.submission.relays.len());
+    }
+    
+    // Latency-critical triggers can skip the RPC node's forwarding hop
+    if let Some(tpu) = config.submission.tpu.or(args.tpu.then(TpuConfig::default)) {
+        if submitter.profile() == SubmissionProfile::Private {
+            status!("TPU submission ignored: the private profile only sends through relays");
+        } else {
+            let ws_url = cli_config.websocket_url(None, &rpc_url);
+            let tpu_rpc = Arc::new(rpc::nonblocking_rpc_client(&rpc_url, commitment, config.rpc_limits));
+            match submitter.connect_tpu(tpu_rpc, &ws_url, tpu).await {
+                Ok(()) => status!("TPU submission: sending to the leaders of the next {} slots, RPC as fallback", tpu.fanout_slots),
+                Err(e) => eprintln!("TPU submission unavailable, sending through RPC: {}", e),
+            }
         }
-    });
+    }
     
-    println!("Waiting for new blocks...");
-    println!("When a new block is detected, will send {} lamports from {} to {}",
-        config.amount_lamports,
-        config.source_wallet.address,
-        config.destination_wallet
-    );
+    // Every submitted transfer is appended to the audit log when one is configured
+    let audit = config.audit.open()?.map(Arc::new);
+    // and every triggered transaction gets a receipt signed by the operator key
+    let receipts = config.receipts.open()?.map(Arc::new);
+    if let Some(receipts) = &receipts {
+        status!("Signing action receipts with operator key {}", receipts.operator());
+    }
     
-    // Process block notifications and send transactions
-    while let Some(slot) = rx.recv().await {
-        println!("Processing block at slot: {}", slot);
+    // Resolve every pipeline's signer and destination (address book alias or raw address) up front
+    let book = AddressBook::load()?;
+    let mut pipelines = Vec::with_capacity(pipeline_configs.len());
+    for (name, pipeline) in pipeline_configs {
+        let source_keypair = match &pipeline.source_wallet {
+            Some(source) => load_keypair_from_secret(&source.secret_key)
+                .with_context(|| format!("Failed to load source keypair of pipeline {}", name))?,
+            None => cli_config
+                .keypair()?
+                .with_context(|| format!("Pipeline {} has no source_wallet and no solana-cli keypair was found", name))?,
+        };
+        
+        let source_address = pipeline
+            .source_wallet
+            .as_ref()
+            .map_or_else(|| source_keypair.pubkey().to_string(), |source| source.address.clone());
+        
+        let deposit = match &pipeline.deposit_program {
+            Some(deposit) => Some(DepositAction {
+                program_id: book
+                    .resolve(&deposit.program_id)
+                    .with_context(|| format!("Failed to parse deposit program id of pipeline {}", name))?,
+                deposit_account: book
+                    .resolve(&deposit.deposit_account)
+                    .with_context(|| format!("Failed to parse deposit account of pipeline {}", name))?,
+                operation: deposit.instruction,
+            }),
+            None => None,
+        };
+        
+        // Deposits land in the deposit account, so they need no destination of their own
+        let destination = match (&pipeline.destination_wallet, &deposit) {
+            (Some(wallet), _) => book
+                .resolve(wallet)
+                .with_context(|| format!("Failed to parse destination wallet address of pipeline {}", name))?,
+            (None, Some(deposit)) if deposit.operation == DepositOperation::Deposit => deposit.deposit_account,
+            (None, _) => anyhow::bail!("Pipeline {} needs destination_wallet", name),
+        };
+        
+        let mut amount = pipeline
+            .amount_strategy()
+            .with_context(|| format!("Pipeline {} needs amount or amount_lamports", name))?;
+        let logs = match &pipeline.logs {
+            Some(rule) => {
+                let program = book
+                    .resolve(&rule.program)
+                    .with_context(|| format!("Failed to parse log rule program of pipeline {}", name))?;
+                let matcher = rule
+                    .matcher()
+                    .with_context(|| format!("Invalid log rule regex in pipeline {}", name))?;
+                Some((program.to_string(), matcher))
+            }
+            None => None,
+        };
+        if let AmountStrategy::AccountField { account, .. } = &mut amount {
+            *account = book
+                .resolve(account)
+                .with_context(|| format!("Failed to parse amount account of pipeline {}", name))?
+                .to_string();
+        }
         
-        // Clone references for the async block
-        let rpc_client_clone = rpc_client.clone();
-        let keypair_bytes = source_keypair.to_bytes();
-        let destination_clone = destination;
-        let amount = config.amount_lamports;
+        let payload = match (&pipeline.transaction, deposit) {
+            (Some(_), Some(_)) => anyhow::bail!("Pipeline {} sets both transaction and deposit_program", name),
+            (Some(template), None) => Payload::Template(Arc::new(
+                template
+                    .compile(|value| Ok(book.resolve(value)?))
+                    .with_context(|| format!("Invalid transaction template of pipeline {}", name))?,
+            )),
+            (None, Some(deposit)) => Payload::Deposit(deposit),
+            (None, None) => Payload::Transfer,
+        };
         
-        // Execute transaction in a separate task
+        // Priority fees are only attached when the config enables the oracle
+        let fee_oracle = pipeline
+            .priority_fee
+            .map(|oracle_config| Arc::new(FeeOracle::new(&rpc_url, oracle_config)));
+        
+        let grpc_endpoint = pipeline.grpc_endpoint.unwrap_or_else(|| args.grpc_endpoint.clone());
+        let streams = std::iter::once(grpc_endpoint.clone())
+            .chain(pipeline.grpc_fallback_endpoints.unwrap_or_default())
+            .map(|endpoint| {
+                let resilience = Resilient::new(&endpoint, config.grpc_retry, config.grpc_circuit_breaker);
+                (endpoint, resilience)
+            })
+            .collect();
+        
+        let trigger = match &logs {
+            Some((program, _)) => format!("a transaction of {} logs a match", program),
+            None => "a new block is detected".to_string(),
+        };
+        match &payload {
+            Payload::Template(template) => status!("[{}] When {} on {}, will send a {}-instruction transaction template with amount {} from {}",
+                name,
+                trigger,
+                grpc_endpoint,
+                template.instruction_count(),
+                describe_amount(&amount),
+                book.display(&source_address)
+            ),
+            Payload::Deposit(deposit) => status!("[{}] When {} on {}, will {} {} {} deposit account {} of program {} (signer {})",
+                name,
+                trigger,
+                grpc_endpoint,
+                match deposit.operation {
+                    DepositOperation::Deposit => "deposit",
+                    DepositOperation::Withdraw => "withdraw",
+                },
+                describe_amount(&amount),
+                match deposit.operation {
+                    DepositOperation::Deposit => "into",
+                    DepositOperation::Withdraw => "from",
+                },
+                book.display(&deposit.deposit_account.to_string()),
+                book.display(&deposit.program_id.to_string()),
+                book.display(&source_address)
+            ),
+            Payload::Transfer => status!("[{}] When {} on {}, will send {} from {} to {}",
+                name,
+                trigger,
+                grpc_endpoint,
+                describe_amount(&amount),
+                book.display(&source_address),
+                book.display(&destination.to_string())
+            ),
+        }
+        
+        if let Some(budget) = &pipeline.budget {
+            status!("[{}] Keeping at least {} lamports in {}; triggers that would dip below are skipped", name, budget.reserve_lamports, book.display(&source_address));
+        }
+        
+        pipelines.push(Pipeline {
+            name,
+            slot_interval: pipeline.slot_interval.max(1),
+            source_keypair,
+            destination,
+            amount,
+            payload,
+            logs,
+            cooldown: pipeline.cooldown.map(|cooldown| Arc::new(Cooldown::new(cooldown))),
+            fee_oracle,
+            compute_limit: pipeline.compute_budget,
+            budget: pipeline.budget.map(|budget| Arc::new(Budget::new(budget))),
+            streams,
+            watchdog: config.stream_watchdog,
+            metrics: Arc::new(PipelineMetrics::default()),
+        });
+    }
+    
+    // Push events to dashboards over WebSocket when requested
+    let bus = EventBus::new();
+    if let Some(addr) = args.events_addr {
+        let server_bus = bus.clone();
         tokio::spawn(async move {
-            // Recreate keypair from bytes
-            let keypair_copy = match Keypair::from_bytes(&keypair_bytes) {
-                Ok(kp) => kp,
-                Err(e) => {
-                    eprintln!("Error recreating keypair: {}", e);
-                    return;
+            if let Err(e) = events::serve(addr, server_bus).await {
+                eprintln!("Event server on {} failed: {}", addr, e);
+            }
+        });
+        
+        let mut wallets: Vec<Pubkey> = Vec::new();
+        for pipeline in &pipelines {
+            for wallet in [pipeline.source_keypair.pubkey(), pipeline.destination] {
+                if !wallets.contains(&wallet) {
+                    wallets.push(wallet);
                 }
+            }
+        }
+        let balance_client = rpc::nonblocking_rpc_client(&rpc_url, commitment, config.rpc_limits);
+        tokio::spawn(watch_balances(balance_client, wallets, bus.clone()));
+        
+        status!("Pushing events on ws://{}", addr);
+    }
+    
+    // A stream tap for jq or vector; subscribed before any pipeline runs so nothing is missed
+    if args.emit.is_some() {
+        let tap = bus.subscribe();
+        let file = match &args.emit_file {
+            Some(path) => Some(
+                tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .with_context(|| format!("Failed to open {}", path.display()))?,
+            ),
+            None => None,
+        };
+        tokio::spawn(async move {
+            let written = match file {
+                Some(file) => events::write_ndjson(tap, file).await,
+                None => events::write_ndjson(tap, tokio::io::stdout()).await,
             };
-            
-            match send_transaction(&rpc_client_clone, &keypair_copy, &destination_clone, amount).await {
-                Ok(signature) => {
-                    println!("Transaction sent successfully for block {}", slot);
-                    println!("Signature: {}", signature);
-                }
-                Err(e) => {
-                    eprintln!("Failed to send transaction for block {}: {}", slot, e);
-                }
+            if let Err(e) = written {
+                eprintln!("NDJSON output failed: {}", e);
             }
         });
-        
-        // Add a small delay to avoid rate limiting
-        tokio::time::sleep(Duration::from_millis(100)).await;
     }
     
-    // Wait for the gRPC task to complete (this will likely never happen in normal operation)
-    grpc_task.await?;
+    let shared = Arc::new(Shared {
+        rpc_client,
+        safety,
+        submitter,
+        audit,
+        receipts,
+        bus,
+        tap: args.emit.is_some(),
+    });
+    
+    tokio::spawn(report_metrics(
+        pipelines
+            .iter()
+            .map(|pipeline| (pipeline.name.clone(), pipeline.metrics.clone()))
+            .collect(),
+        args.output,
+    ));
+    
+    status!("Waiting for new blocks on {} pipeline(s)...", pipelines.len());
+    if args.service {
+        service::ready(&format!("Waiting for new blocks on {} pipeline(s)", pipelines.len()));
+        if let Some(interval) = service::watchdog_interval() {
+            tokio::spawn(service::keep_alive(interval));
+        }
+    }
+    
+    // Each pipeline runs until its gRPC subscription fails (this will likely never happen in normal operation)
+    let tasks: Vec<_> = pipelines
+        .into_iter()
+        .map(|pipeline| tokio::spawn(run_pipeline(pipeline, shared.clone())))
+        .collect();
+    // Outside --service the process runs until killed, as before
+    let stop = async {
+        if args.service {
+            service::shutdown().await
+        } else {
+            std::future::pending().await
+        }
+    };
+    tokio::select! {
+        results = futures::future::join_all(tasks) => {
+            for task in results {
+                task?;
+            }
+        }
+        _ = stop => {
+            status!("Stop requested, shutting down");
+            service::stopping();
+        }
+    }
     
     Ok(())
 }
@@ -0,0 +1,338 @@
+//! `--service`: report readiness and stop cleanly under the platform's service manager,
+//! systemd's `sd_notify` protocol on Linux and the service control manager on Windows.
+
+use std::time::Duration;
+
+/// Tell the service manager that startup finished, with a human-readable status
+pub fn ready(status: &str) {
+    platform::notify(&format!("READY=1\nSTATUS={}", status));
+}
+
+/// Tell the service manager that the daemon is shutting down
+pub fn stopping() {
+    platform::notify("STOPPING=1");
+}
+
+/// How often the service manager expects a keep-alive, when it runs a watchdog
+pub fn watchdog_interval() -> Option<Duration> {
+    platform::watchdog_interval()
+}
+
+/// Send keep-alives at half the watchdog interval for as long as the runtime makes progress,
+/// so a wedged process is restarted
+pub async fn keep_alive(interval: Duration) {
+    let mut ticks = tokio::time::interval(interval / 2);
+    loop {
+        ticks.tick().await;
+        platform::notify("WATCHDOG=1");
+    }
+}
+
+/// Resolves once the service manager asks the daemon to stop, or on Ctrl-C
+pub async fn shutdown() {
+    platform::shutdown().await;
+}
+
+/// Lifecycle of the daemon as the Windows service control manager sees it
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    Starting,
+    Running,
+    Stopping,
+    Stopped,
+}
+
+/// What moves the daemon from one [`Phase`] to the next
+#[cfg_attr(not(windows), allow(dead_code))]
+#[derive(Debug, Clone, Copy)]
+enum Event<'a> {
+    /// A state from `ready` or `stopping`, in sd_notify form
+    Notify(&'a str),
+    /// The service manager asked the daemon to stop
+    StopRequested,
+    /// The daemon returned with this exit code
+    Exited(u32),
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl Phase {
+    /// The phase after `event`, or `None` when there is nothing new to report. Phases only
+    /// move forward, so a late `READY=1` cannot report a stopping daemon as running again.
+    fn next(self, event: Event) -> Option<Phase> {
+        let next = match event {
+            Event::Notify(state) if state.starts_with("READY=1") => Phase::Running,
+            Event::Notify(state) if state.starts_with("STOPPING=1") => Phase::Stopping,
+            Event::Notify(_) => return None,
+            Event::StopRequested => Phase::Stopping,
+            Event::Exited(_) => Phase::Stopped,
+        };
+        (next > self).then_some(next)
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::{os::unix::net::UnixDatagram, time::Duration};
+
+    /// Send `state` to `$NOTIFY_SOCKET`; does nothing when not started by systemd
+    pub fn notify(state: &str) {
+        let Some(path) = std::env::var_os("NOTIFY_SOCKET") else {
+            return;
+        };
+        if let Err(e) = send(&path, state) {
+            eprintln!("Failed to notify the service manager: {}", e);
+        }
+    }
+
+    fn send(path: &std::ffi::OsStr, state: &str) -> std::io::Result<usize> {
+        let socket = UnixDatagram::unbound()?;
+        // A leading '@' names a socket in the abstract namespace
+        #[cfg(target_os = "linux")]
+        if let Some(name) = path.as_encoded_bytes().strip_prefix(b"@") {
+            use std::os::{linux::net::SocketAddrExt, unix::net::SocketAddr};
+            let address = SocketAddr::from_abstract_name(name)?;
+            return socket.send_to_addr(state.as_bytes(), &address);
+        }
+        socket.send_to(state.as_bytes(), path)
+    }
+
+    pub fn watchdog_interval() -> Option<Duration> {
+        // A watchdog meant for another process of the unit is not ours to feed
+        if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+            if pid.parse() != Ok(std::process::id()) {
+                return None;
+            }
+        }
+        let micros: u64 = std::env::var("WATCHDOG_USEC").ok()?.parse().ok()?;
+        (micros > 0).then(|| Duration::from_micros(micros))
+    }
+
+    pub async fn shutdown() {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+            }
+            Err(e) => {
+                eprintln!("Cannot listen for SIGTERM, stopping on Ctrl-C only: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{Event, Phase};
+    use std::{
+        ffi::OsString,
+        sync::{Mutex, OnceLock},
+        time::Duration,
+    };
+    use tokio::sync::Notify;
+    use windows_service::{
+        define_windows_service,
+        service::{
+            ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus,
+            ServiceType,
+        },
+        service_control_handler::{self, ServiceControlHandlerResult, ServiceStatusHandle},
+        service_dispatcher,
+    };
+
+    type Run = Box<dyn FnOnce() -> anyhow::Result<()> + Send>;
+
+    /// What the service main runs; the dispatcher only hands it a plain function pointer
+    static RUN: Mutex<Option<Run>> = Mutex::new(None);
+    static NAME: OnceLock<String> = OnceLock::new();
+    /// Status handle and last reported phase of the running service, `None` when not running
+    /// as one
+    static STATUS: Mutex<Option<(ServiceStatusHandle, Phase)>> = Mutex::new(None);
+    static STOP: OnceLock<Notify> = OnceLock::new();
+
+    define_windows_service!(ffi_service_main, service_main);
+
+    fn stop_requested() -> &'static Notify {
+        STOP.get_or_init(Notify::new)
+    }
+
+    fn report(handle: &ServiceStatusHandle, phase: Phase, exit_code: u32) {
+        let (current_state, controls_accepted) = match phase {
+            Phase::Starting => (ServiceState::StartPending, ServiceControlAccept::empty()),
+            Phase::Running => (
+                ServiceState::Running,
+                ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+            ),
+            Phase::Stopping => (ServiceState::StopPending, ServiceControlAccept::empty()),
+            Phase::Stopped => (ServiceState::Stopped, ServiceControlAccept::empty()),
+        };
+        let status = ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state,
+            controls_accepted,
+            exit_code: match exit_code {
+                0 => ServiceExitCode::NO_ERROR,
+                code => ServiceExitCode::ServiceSpecific(code),
+            },
+            checkpoint: 0,
+            wait_hint: match phase {
+                Phase::Starting | Phase::Stopping => Duration::from_secs(30),
+                Phase::Running | Phase::Stopped => Duration::ZERO,
+            },
+            process_id: None,
+        };
+        if let Err(e) = handle.set_service_status(status) {
+            eprintln!("Failed to report the service status: {}", e);
+        }
+    }
+
+    /// Report the phase `event` moves the service to, if it moves it at all
+    fn advance(event: Event) {
+        let mut status = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+        let Some((handle, phase)) = status.as_mut() else {
+            return;
+        };
+        let Some(next) = phase.next(event) else {
+            return;
+        };
+        *phase = next;
+        let exit_code = match event {
+            Event::Exited(code) => code,
+            _ => 0,
+        };
+        report(handle, next, exit_code);
+    }
+
+    /// Maps `READY=1` and `STOPPING=1` onto service states; the rest has no equivalent
+    pub fn notify(state: &str) {
+        advance(Event::Notify(state));
+    }
+
+    pub fn watchdog_interval() -> Option<Duration> {
+        None
+    }
+
+    pub async fn shutdown() {
+        tokio::select! {
+            _ = stop_requested().notified() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+
+    fn handle_control(control: ServiceControl) -> ServiceControlHandlerResult {
+        match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                advance(Event::StopRequested);
+                // Stored as a permit when the daemon is not waiting yet
+                stop_requested().notify_one();
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        }
+    }
+
+    fn service_main(_arguments: Vec<OsString>) {
+        let name = NAME.get().expect("service name is set before dispatching");
+        let handle = match service_control_handler::register(name, handle_control) {
+            Ok(handle) => handle,
+            Err(e) => {
+                eprintln!("Failed to register the service control handler: {}", e);
+                return;
+            }
+        };
+        report(&handle, Phase::Starting, 0);
+        *STATUS.lock().unwrap_or_else(|e| e.into_inner()) = Some((handle, Phase::Starting));
+
+        let run = RUN.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let exit_code = match run.map(|run| run()) {
+            Some(Err(e)) => {
+                eprintln!("Error: {:#}", e);
+                1
+            }
+            _ => 0,
+        };
+        advance(Event::Exited(exit_code));
+    }
+
+    /// Hand the process to the service control manager and run `run` as service `name`,
+    /// returning once it has stopped. Fails when the process was not started as a service.
+    pub fn run_as_service(
+        name: &str,
+        run: impl FnOnce() -> anyhow::Result<()> + Send + 'static,
+    ) -> windows_service::Result<()> {
+        let name = NAME.get_or_init(|| name.to_string());
+        *RUN.lock().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(run));
+        // Blocks until the service has stopped
+        service_dispatcher::start(name, ffi_service_main)
+    }
+}
+
+#[cfg(windows)]
+pub use platform::run_as_service;
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use std::time::Duration;
+
+    pub fn notify(_state: &str) {}
+
+    pub fn watchdog_interval() -> Option<Duration> {
+        None
+    }
+
+    pub async fn shutdown() {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_daemon_starts_runs_and_stops() {
+        let phase = Phase::Starting;
+        let phase = phase
+            .next(Event::Notify("READY=1\nSTATUS=Waiting"))
+            .unwrap();
+        assert_eq!(phase, Phase::Running);
+        assert_eq!(phase.next(Event::Notify("WATCHDOG=1")), None);
+
+        let phase = phase.next(Event::StopRequested).unwrap();
+        assert_eq!(phase, Phase::Stopping);
+        // The daemon announces the shutdown it was asked for; that is already reported
+        assert_eq!(phase.next(Event::Notify("STOPPING=1")), None);
+        assert_eq!(phase.next(Event::Exited(0)), Some(Phase::Stopped));
+    }
+
+    #[test]
+    fn a_daemon_may_stop_on_its_own() {
+        let phase = Phase::Running.next(Event::Notify("STOPPING=1")).unwrap();
+        assert_eq!(phase, Phase::Stopping);
+        assert_eq!(phase.next(Event::StopRequested), None);
+        assert_eq!(phase.next(Event::Exited(1)), Some(Phase::Stopped));
+    }
+
+    #[test]
+    fn a_failed_start_stops_without_running() {
+        assert_eq!(Phase::Starting.next(Event::Exited(1)), Some(Phase::Stopped));
+        assert_eq!(
+            Phase::Starting.next(Event::StopRequested),
+            Some(Phase::Stopping)
+        );
+    }
+
+    #[test]
+    fn phases_never_move_backwards() {
+        assert_eq!(Phase::Stopping.next(Event::Notify("READY=1")), None);
+        assert_eq!(Phase::Stopped.next(Event::Notify("READY=1")), None);
+        assert_eq!(Phase::Stopped.next(Event::StopRequested), None);
+        assert_eq!(Phase::Stopped.next(Event::Exited(0)), None);
+    }
+}
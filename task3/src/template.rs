@@ -0,0 +1,240 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::Deserialize;
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+
+/// Instructions an action sends instead of a system transfer. Program ids and account keys
+/// are addresses, address book aliases or the `{source}` / `{destination}` placeholders;
+/// `data` is base64 with `{slot}`, `{amount}` (little-endian u64) and `{blockhash}` (32
+/// bytes) placeholders spliced in, e.g. `"AgAAAA=={amount}"`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TransactionTemplate {
+    pub instructions: Vec<InstructionTemplate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstructionTemplate {
+    pub program_id: String,
+    #[serde(default)]
+    pub accounts: Vec<AccountTemplate>,
+    #[serde(default)]
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AccountTemplate {
+    pub pubkey: String,
+    /// Only `{source}`, the pipeline's keypair, can sign
+    #[serde(default)]
+    pub signer: bool,
+    #[serde(default)]
+    pub writable: bool,
+}
+
+/// Values of one trigger that placeholders are filled with
+#[derive(Debug, Clone, Copy)]
+pub struct TemplateValues {
+    pub slot: u64,
+    pub amount: u64,
+    pub blockhash: Hash,
+    pub source: Pubkey,
+    pub destination: Pubkey,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Key {
+    Fixed(Pubkey),
+    Source,
+    Destination,
+}
+
+#[derive(Debug, Clone)]
+enum DataPart {
+    Bytes(Vec<u8>),
+    Slot,
+    Amount,
+    Blockhash,
+}
+
+/// A [`TransactionTemplate`] with its addresses resolved and its data decoded
+#[derive(Debug, Clone)]
+pub struct CompiledTemplate {
+    instructions: Vec<CompiledInstruction>,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledInstruction {
+    program_id: Key,
+    accounts: Vec<(Key, bool, bool)>,
+    data: Vec<DataPart>,
+}
+
+impl Key {
+    fn parse(value: &str, resolve: &impl Fn(&str) -> Result<Pubkey>) -> Result<Key> {
+        Ok(match value {
+            "{source}" => Key::Source,
+            "{destination}" => Key::Destination,
+            _ => Key::Fixed(resolve(value)?),
+        })
+    }
+
+    fn pubkey(self, values: &TemplateValues) -> Pubkey {
+        match self {
+            Key::Fixed(pubkey) => pubkey,
+            Key::Source => values.source,
+            Key::Destination => values.destination,
+        }
+    }
+}
+
+/// Split `data` into base64 chunks and placeholders
+fn parse_data(data: &str) -> Result<Vec<DataPart>> {
+    let mut parts = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (literal, placeholder) = match rest.find('{') {
+            Some(start) => {
+                let end = rest[start..]
+                    .find('}')
+                    .map(|end| start + end)
+                    .with_context(|| format!("Unclosed placeholder in data {:?}", data))?;
+                let placeholder = &rest[start + 1..end];
+                let literal = &rest[..start];
+                rest = &rest[end + 1..];
+                (literal, Some(placeholder))
+            }
+            None => {
+                let literal = rest;
+                rest = "";
+                (literal, None)
+            }
+        };
+
+        if !literal.is_empty() {
+            let bytes = BASE64
+                .decode(literal)
+                .with_context(|| format!("Invalid base64 {:?} in data", literal))?;
+            parts.push(DataPart::Bytes(bytes));
+        }
+        match placeholder {
+            Some("slot") => parts.push(DataPart::Slot),
+            Some("amount") => parts.push(DataPart::Amount),
+            Some("blockhash") => parts.push(DataPart::Blockhash),
+            Some(other) => bail!("Unknown placeholder {{{}}} in data", other),
+            None => {}
+        }
+    }
+    Ok(parts)
+}
+
+impl TransactionTemplate {
+    /// Resolve addresses with `resolve` and decode the data, so triggers only fill in values
+    pub fn compile(&self, resolve: impl Fn(&str) -> Result<Pubkey>) -> Result<CompiledTemplate> {
+        if self.instructions.is_empty() {
+            bail!("Transaction template has no instructions");
+        }
+        let instructions = self
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| {
+                let program_id = Key::parse(&instruction.program_id, &resolve)
+                    .with_context(|| format!("Invalid program_id of instruction {}", index))?;
+                let accounts = instruction
+                    .accounts
+                    .iter()
+                    .map(|account| {
+                        let key = Key::parse(&account.pubkey, &resolve).with_context(|| {
+                            format!(
+                                "Invalid account {} of instruction {}",
+                                account.pubkey, index
+                            )
+                        })?;
+                        if account.signer && !matches!(key, Key::Source) {
+                            bail!(
+                                "Account {} of instruction {} cannot sign, only {{source}} can",
+                                account.pubkey,
+                                index
+                            );
+                        }
+                        Ok((key, account.signer, account.writable))
+                    })
+                    .collect::<Result<_>>()?;
+                let data = parse_data(&instruction.data)
+                    .with_context(|| format!("Invalid data of instruction {}", index))?;
+                Ok(CompiledInstruction {
+                    program_id,
+                    accounts,
+                    data,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(CompiledTemplate { instructions })
+    }
+}
+
+impl CompiledTemplate {
+    pub fn instruction_count(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// The template's instructions for one trigger
+    pub fn instructions(&self, values: &TemplateValues) -> Vec<Instruction> {
+        self.instructions
+            .iter()
+            .map(|instruction| {
+                let accounts = instruction
+                    .accounts
+                    .iter()
+                    .map(|&(key, signer, writable)| {
+                        let pubkey = key.pubkey(values);
+                        if writable {
+                            AccountMeta::new(pubkey, signer)
+                        } else {
+                            AccountMeta::new_readonly(pubkey, signer)
+                        }
+                    })
+                    .collect();
+                let mut data = Vec::new();
+                for part in &instruction.data {
+                    match part {
+                        DataPart::Bytes(bytes) => data.extend_from_slice(bytes),
+                        DataPart::Slot => data.extend_from_slice(&values.slot.to_le_bytes()),
+                        DataPart::Amount => data.extend_from_slice(&values.amount.to_le_bytes()),
+                        DataPart::Blockhash => data.extend_from_slice(values.blockhash.as_ref()),
+                    }
+                }
+                Instruction {
+                    program_id: instruction.program_id.pubkey(values),
+                    accounts,
+                    data,
+                }
+            })
+            .collect()
+    }
+
+    /// Writable accounts of one trigger, for priority fee estimates
+    pub fn writable_accounts(&self, source: Pubkey, destination: Pubkey) -> Vec<Pubkey> {
+        let values = TemplateValues {
+            slot: 0,
+            amount: 0,
+            blockhash: Hash::default(),
+            source,
+            destination,
+        };
+        let mut accounts = vec![source];
+        for instruction in &self.instructions {
+            for &(key, _, writable) in &instruction.accounts {
+                let pubkey = key.pubkey(&values);
+                if writable && !accounts.contains(&pubkey) {
+                    accounts.push(pubkey);
+                }
+            }
+        }
+        accounts
+    }
+}
@@ -0,0 +1,38 @@
+use serde::Deserialize;
+use std::{future::Future, time::Duration};
+
+/// Restart a Geyser stream that has gone quiet. Servers send pings on idle streams, so a long
+/// silence means the connection stalled without closing.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct WatchdogConfig {
+    /// Seconds without any update, pings included, before the stream is restarted; 0
+    /// disables the watchdog
+    #[serde(default = "default_stale_after_secs")]
+    pub stale_after_secs: u64,
+}
+
+fn default_stale_after_secs() -> u64 {
+    30
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            stale_after_secs: default_stale_after_secs(),
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn stale_after(&self) -> Option<Duration> {
+        (self.stale_after_secs > 0).then(|| Duration::from_secs(self.stale_after_secs))
+    }
+
+    /// Output of `message`, or `None` when it does not complete within the threshold
+    pub async fn watch<T>(&self, message: impl Future<Output = T>) -> Option<T> {
+        match self.stale_after() {
+            Some(stale_after) => tokio::time::timeout(stale_after, message).await.ok(),
+            None => Some(message.await),
+        }
+    }
+}
@@ -2,9 +2,18 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
     msg,
     program_error::ProgramError,
     pubkey::Pubkey,
+    system_program,
+};
+
+use solana_program::{ed25519_program, sysvar};
+
+use crate::processor::{
+    config_address, program_data_address, pull_address, stats_address, voucher_address, voucher_message,
+    DepositAccount,
 };
 
 /// Client-side instructions for interacting with the deposit/withdraw program
@@ -21,6 +30,312 @@ pub enum DepositInstruction {
         /// Amount to withdraw in lamports
         amount: u64,
     },
+    
+    /// Create the program config PDA with the signer, the program's upgrade authority, as its admin
+    InitializeConfig {
+        /// Smallest deposit accepted, in lamports
+        min_deposit: u64,
+        /// Smallest withdrawal accepted, in lamports
+        min_withdraw: u64,
+    },
+    
+    /// Change the minimums in the program config; admin only
+    UpdateConfig {
+        min_deposit: u64,
+        min_withdraw: u64,
+    },
+    
+    /// Designate a key that may withdraw once the owner has been inactive; owner only
+    SetRecovery {
+        /// Recovery key, `None` to remove the designation
+        recovery: Option<Pubkey>,
+        /// Slots without owner activity before the recovery key may withdraw
+        inactivity_slots: u64,
+    },
+    
+    /// Let `payee` pull up to `amount` lamports until `expiry`, replacing any earlier
+    /// authorization of the same payee; owner only
+    AuthorizePull {
+        payee: Pubkey,
+        /// Total the payee may pull, in lamports; 0 revokes the authorization
+        amount: u64,
+        /// Cluster Unix timestamp from which pulls are refused
+        expiry: i64,
+    },
+    
+    /// Pull lamports from the account under an authorization; payee only
+    Pull {
+        /// Amount to pull in lamports
+        amount: u64,
+    },
+    
+    /// Create the program stats PDA; anyone may pay for it
+    InitializeStats,
+    
+    /// Withdraw `amount` lamports to the destination under a voucher the owner signed off-chain
+    /// (see [`voucher_message`]); anyone may submit it, right after an ed25519 program
+    /// instruction verifying the owner's signature, and pays for the redemption marker
+    RedeemVoucher {
+        /// Amount to withdraw in lamports
+        amount: u64,
+        /// Voucher id, redeemable once per deposit account
+        id: u64,
+        /// Cluster Unix timestamp from which the voucher is refused
+        expiry: i64,
+    },
+}
+
+/// Create a `Deposit` instruction moving `amount` lamports from `funder` into `deposit_account`
+pub fn deposit(
+    program_id: &Pubkey,
+    funder: &Pubkey,
+    deposit_account: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = DepositInstruction::Deposit { amount }
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(*deposit_account, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(config_address(program_id).0, false),
+            AccountMeta::new(stats_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Create a `Withdraw` instruction sending `amount` lamports from `deposit_account` to `destination`.
+/// `owner` may also be the account's recovery key once the owner has been inactive long enough.
+pub fn withdraw(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    deposit_account: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = DepositInstruction::Withdraw { amount }
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*deposit_account, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(config_address(program_id).0, false),
+            AccountMeta::new(stats_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Create an `InitializeConfig` instruction making `admin`, which must be the program's upgrade
+/// authority, the payer and admin of the config PDA
+pub fn initialize_config(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    min_deposit: u64,
+    min_withdraw: u64,
+) -> Instruction {
+    let data = DepositInstruction::InitializeConfig { min_deposit, min_withdraw }
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new(*admin, true),
+            AccountMeta::new(config_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(program_data_address(program_id), false),
+        ],
+    )
+}
+
+/// Create an `UpdateConfig` instruction setting new minimums, signed by the config admin
+pub fn update_config(
+    program_id: &Pubkey,
+    admin: &Pubkey,
+    min_deposit: u64,
+    min_withdraw: u64,
+) -> Instruction {
+    let data = DepositInstruction::UpdateConfig { min_deposit, min_withdraw }
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new_readonly(*admin, true),
+            AccountMeta::new(config_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Create a `SetRecovery` instruction designating `recovery` for `deposit_account`, or removing it
+pub fn set_recovery(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    deposit_account: &Pubkey,
+    recovery: Option<Pubkey>,
+    inactivity_slots: u64,
+) -> Instruction {
+    let data = DepositInstruction::SetRecovery { recovery, inactivity_slots }
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*deposit_account, false),
+        ],
+    )
+}
+
+/// Create an `AuthorizePull` instruction letting `payee` pull up to `amount` lamports from
+/// `deposit_account` until `expiry`
+pub fn authorize_pull(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    deposit_account: &Pubkey,
+    payee: &Pubkey,
+    amount: u64,
+    expiry: i64,
+) -> Instruction {
+    let data = DepositInstruction::AuthorizePull { payee: *payee, amount, expiry }
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(*deposit_account, false),
+            AccountMeta::new(pull_address(program_id, deposit_account, payee).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create a `Pull` instruction sending `amount` lamports from `deposit_account` to `destination`
+/// under `payee`'s authorization
+pub fn pull(
+    program_id: &Pubkey,
+    payee: &Pubkey,
+    deposit_account: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let data = DepositInstruction::Pull { amount }
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new_readonly(*payee, true),
+            AccountMeta::new(*deposit_account, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new(pull_address(program_id, deposit_account, payee).0, false),
+            AccountMeta::new_readonly(config_address(program_id).0, false),
+            AccountMeta::new(stats_address(program_id).0, false),
+        ],
+    )
+}
+
+/// Create an `InitializeStats` instruction creating the program stats PDA, paid for by `payer`
+pub fn initialize_stats(program_id: &Pubkey, payer: &Pubkey) -> Instruction {
+    let data = DepositInstruction::InitializeStats
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    Instruction::new_with_bytes(
+        *program_id,
+        &data,
+        vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(stats_address(program_id).0, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Create the ed25519 program instruction verifying `signature` by `signer` over `message`, laid
+/// out the way `RedeemVoucher` expects: public key, signature and message in its own data
+pub fn ed25519_verify(signer: &Pubkey, signature: &[u8; 64], message: &[u8]) -> Instruction {
+    const HEADER_LEN: u16 = 16;
+    let public_key_offset = HEADER_LEN;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+    
+    let mut data = Vec::with_capacity(message_offset as usize + message.len());
+    data.extend_from_slice(&[1, 0]);
+    for offset in [
+        signature_offset,
+        u16::MAX,
+        public_key_offset,
+        u16::MAX,
+        message_offset,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    
+    Instruction::new_with_bytes(ed25519_program::id(), &data, vec![])
+}
+
+/// Create the instructions redeeming voucher `id` of `deposit_account`: the ed25519 verification
+/// of the owner's `signature` over [`voucher_message`], then `RedeemVoucher`, paid for by `submitter`
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_voucher(
+    program_id: &Pubkey,
+    submitter: &Pubkey,
+    owner: &Pubkey,
+    deposit_account: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+    id: u64,
+    expiry: i64,
+    signature: &[u8; 64],
+) -> [Instruction; 2] {
+    let message = voucher_message(program_id, deposit_account, destination, amount, id, expiry);
+    let data = DepositInstruction::RedeemVoucher { amount, id, expiry }
+        .try_to_vec()
+        .expect("instruction serialization cannot fail");
+    
+    [
+        ed25519_verify(owner, signature, &message),
+        Instruction::new_with_bytes(
+            *program_id,
+            &data,
+            vec![
+                AccountMeta::new(*submitter, true),
+                AccountMeta::new(*deposit_account, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new(voucher_address(program_id, deposit_account, id).0, false),
+                AccountMeta::new_readonly(sysvar::instructions::id(), false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(config_address(program_id).0, false),
+                AccountMeta::new(stats_address(program_id).0, false),
+            ],
+        ),
+    ]
 }
 
 /// Helper function to check account balance
@@ -37,17 +352,24 @@ pub fn get_balance(
     }
     
     // Deserialize the deposit account data
-    let deposit_account_data = DepositAccount::try_from_slice(&deposit_account_info.data.borrow())?;
+    let deposit_account_data = DepositAccount::unpack(&deposit_account_info.data.borrow())?;
     
-    // Log the balance
+    // Log the balance and activity
     msg!("Account balance: {} lamports", deposit_account_data.balance);
+    msg!(
+        "Deposits: {}, withdrawals: {}, last activity: {}",
+        deposit_account_data.deposit_count,
+        deposit_account_data.withdraw_count,
+        deposit_account_data.last_activity_ts
+    );
+    if let Some(recovery) = deposit_account_data.recovery_key() {
+        msg!(
+            "Recovery key: {}, after {} slots of owner inactivity since slot {}",
+            recovery,
+            deposit_account_data.recovery_after_slots,
+            deposit_account_data.last_owner_slot
+        );
+    }
     
     Ok(())
 }
-
-/// Define the state of the deposit account
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub struct DepositAccount {
-    pub owner: Pubkey,
-    pub balance: u64,
-}
@@ -1,6 +1,11 @@
+#[cfg(not(feature = "no-entrypoint"))]
 mod entrypoint;
-mod instruction;
-mod processor;
+pub mod instruction;
+pub mod processor;
+pub mod validate_accounts;
 
 pub use instruction::DepositInstruction;
-pub use processor::DepositAccount;
+pub use processor::{
+    config_address, program_data_address, pull_address, stats_address, voucher_address,
+    voucher_message, DepositAccount, ProgramConfig, ProgramStats, PullAuthorization,
+};
@@ -1,24 +1,368 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    account_info::{next_account_info, AccountInfo},
+    account_info::AccountInfo,
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
     entrypoint::ProgramResult,
     msg,
     program_error::ProgramError,
+    program_utils::limited_deserialize,
     pubkey::Pubkey,
     program::invoke_signed,
     system_instruction,
+    clock::Clock,
     rent::Rent,
     sysvar::Sysvar,
 };
+#[cfg(feature = "voucher")]
+use solana_program::{ed25519_program, sysvar::instructions as instructions_sysvar};
 use thiserror::Error;
 
 use crate::instruction::DepositInstruction;
+use crate::validate_accounts::{self, validate_accounts};
 
 /// Define the state of the deposit account
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
 pub struct DepositAccount {
     pub owner: Pubkey,
     pub balance: u64,
+    /// Successful deposits and withdrawals since the account was created
+    pub deposit_count: u64,
+    pub withdraw_count: u64,
+    /// Cluster Unix timestamp of the latest deposit or withdrawal, 0 before the first one
+    pub last_activity_ts: i64,
+    /// Key allowed to withdraw once the owner has been inactive for `recovery_after_slots`;
+    /// `Pubkey::default()` when no recovery key is designated
+    pub recovery: Pubkey,
+    pub recovery_after_slots: u64,
+    /// Slot of the owner's latest deposit, withdrawal or recovery change
+    pub last_owner_slot: u64,
+}
+
+/// Seed of the program config PDA
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Address and bump of the program config PDA
+pub fn config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// Program-wide settings, stored in the PDA at [`config_address`]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct ProgramConfig {
+    /// Key allowed to change the settings: the program's upgrade authority, which initialized them
+    pub admin: Pubkey,
+    /// Smallest deposit accepted, in lamports; 0 accepts any amount
+    pub min_deposit: u64,
+    /// Smallest withdrawal accepted, in lamports, unless it empties the account; 0 accepts any amount
+    pub min_withdraw: u64,
+}
+
+impl ProgramConfig {
+    /// Serialized size of the config PDA data
+    pub const LEN: usize = 32 + 8 + 8;
+    
+    /// Settings from the config account passed to an instruction; no minimums until the
+    /// PDA is initialized
+    fn load(program_id: &Pubkey, config_info: &AccountInfo) -> Result<Self, ProgramError> {
+        if *config_info.key != config_address(program_id).0 {
+            return Err(DepositError::InvalidConfigAccount.into());
+        }
+        if config_info.data_is_empty() {
+            return Ok(Self::default());
+        }
+        if config_info.owner != program_id {
+            return Err(DepositError::InvalidConfigAccount.into());
+        }
+        Ok(Self::try_from_slice(&config_info.data.borrow())?)
+    }
+}
+
+/// Address of the account where the upgradeable loader keeps the code and upgrade authority
+/// of `program_id`
+pub fn program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
+/// Upgrade authority recorded in the program data account of `program_id`; None once the
+/// program was made immutable
+fn upgrade_authority(program_id: &Pubkey, program_data_info: &AccountInfo) -> Result<Option<Pubkey>, ProgramError> {
+    if *program_data_info.key != program_data_address(program_id)
+        || *program_data_info.owner != bpf_loader_upgradeable::id()
+    {
+        return Err(DepositError::UnexpectedAccount.into());
+    }
+    let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+    let data = program_data_info.data.borrow();
+    let metadata = data.get(..metadata_len).ok_or(ProgramError::InvalidAccountData)?;
+    match limited_deserialize(metadata, metadata_len as u64) {
+        Ok(UpgradeableLoaderState::ProgramData { upgrade_authority_address, .. }) => Ok(upgrade_authority_address),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Create the PDA `account_info`, signed for with `seeds`, with `space` bytes of data owned by
+/// the program and the rent-exempt minimum paid by `payer_info`. Anyone can send lamports to
+/// the address first, which makes `create_account` fail, so a funded account is topped up,
+/// allocated and assigned instead.
+fn create_pda<'a>(
+    program_id: &Pubkey,
+    payer_info: &AccountInfo<'a>,
+    account_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let required = Rent::get()?.minimum_balance(space);
+    let infos = [payer_info.clone(), account_info.clone(), system_program_info.clone()];
+    
+    if account_info.lamports() == 0 {
+        let instruction = system_instruction::create_account(
+            payer_info.key,
+            account_info.key,
+            required,
+            space as u64,
+            program_id,
+        );
+        return invoke_signed(&instruction, &infos, &[seeds]);
+    }
+    
+    let top_up = required.saturating_sub(account_info.lamports());
+    if top_up > 0 {
+        invoke_signed(&system_instruction::transfer(payer_info.key, account_info.key, top_up), &infos, &[])?;
+    }
+    invoke_signed(&system_instruction::allocate(account_info.key, space as u64), &infos, &[seeds])?;
+    invoke_signed(&system_instruction::assign(account_info.key, program_id), &infos, &[seeds])
+}
+
+/// Seed of the program stats PDA
+pub const STATS_SEED: &[u8] = b"stats";
+
+/// Address and bump of the program stats PDA
+pub fn stats_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[STATS_SEED], program_id)
+}
+
+/// Program-wide totals, stored in the PDA at [`stats_address`] and kept up to date by every
+/// deposit, withdrawal, pull and voucher; all of them are refused until it is initialized
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct ProgramStats {
+    /// Lamports ever deposited and withdrawn (pulls included)
+    pub total_deposited: u64,
+    pub total_withdrawn: u64,
+    /// Deposit accounts currently holding a non-zero balance
+    pub active_accounts: u64,
+}
+
+impl ProgramStats {
+    /// Serialized size of the stats PDA data
+    pub const LEN: usize = 8 + 8 + 8;
+    
+    /// Decode the data of the stats account fetched from the cluster
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        Self::try_from_slice(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+    
+    /// Lamports held by all deposit accounts together
+    pub fn total_value_locked(&self) -> u64 {
+        self.total_deposited.saturating_sub(self.total_withdrawn)
+    }
+    
+    /// Apply `change` to the stats account passed to an instruction, which fails until the
+    /// PDA is initialized so the totals never miss a movement of funds
+    fn record(
+        program_id: &Pubkey,
+        stats_info: &AccountInfo,
+        change: impl FnOnce(&mut Self),
+    ) -> ProgramResult {
+        if *stats_info.key != stats_address(program_id).0 {
+            return Err(DepositError::InvalidStatsAccount.into());
+        }
+        if stats_info.data_is_empty() {
+            return Err(DepositError::StatsNotInitialized.into());
+        }
+        if stats_info.owner != program_id {
+            return Err(DepositError::InvalidStatsAccount.into());
+        }
+        let mut stats = Self::try_from_slice(&stats_info.data.borrow())?;
+        change(&mut stats);
+        stats.serialize(&mut *stats_info.data.borrow_mut())?;
+        Ok(())
+    }
+    
+    fn deposited(&mut self, amount: u64, balance_before: u64) {
+        self.total_deposited = self.total_deposited.saturating_add(amount);
+        if balance_before == 0 && amount > 0 {
+            self.active_accounts += 1;
+        }
+    }
+    
+    fn withdrawn(&mut self, amount: u64, balance_after: u64) {
+        self.total_withdrawn = self.total_withdrawn.saturating_add(amount);
+        if balance_after == 0 && amount > 0 {
+            self.active_accounts = self.active_accounts.saturating_sub(1);
+        }
+    }
+}
+
+/// Seed prefix of pull authorization PDAs, followed by the deposit account and the payee
+pub const PULL_SEED: &[u8] = b"pull";
+
+/// Address and bump of the PDA authorizing `payee` to pull from `deposit_account`
+pub fn pull_address(program_id: &Pubkey, deposit_account: &Pubkey, payee: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[PULL_SEED, deposit_account.as_ref(), payee.as_ref()], program_id)
+}
+
+/// Amount a payee may still pull from a deposit account, stored in the PDA at [`pull_address`]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct PullAuthorization {
+    pub deposit_account: Pubkey,
+    pub payee: Pubkey,
+    /// Lamports left to pull; each pull lowers it
+    pub remaining: u64,
+    /// Cluster Unix timestamp from which no more pulls are accepted
+    pub expiry: i64,
+}
+
+impl PullAuthorization {
+    /// Serialized size of the pull authorization PDA data
+    pub const LEN: usize = 32 + 32 + 8 + 8;
+}
+
+/// Seed prefix of voucher redemption PDAs, followed by the deposit account and the voucher id
+pub const VOUCHER_SEED: &[u8] = b"voucher";
+
+/// Prefix of every voucher message, so no other message the owner signs can pass for one
+pub const VOUCHER_DOMAIN: &[u8] = b"solana_deposit_withdraw:voucher";
+
+/// Address and bump of the PDA marking voucher `id` of `deposit_account` as redeemed
+pub fn voucher_address(program_id: &Pubkey, deposit_account: &Pubkey, id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VOUCHER_SEED, deposit_account.as_ref(), &id.to_le_bytes()], program_id)
+}
+
+/// Message the owner of `deposit_account` signs off-chain to let anyone withdraw `amount`
+/// lamports to `destination` until `expiry`; `id` tells apart vouchers with the same terms
+pub fn voucher_message(
+    program_id: &Pubkey,
+    deposit_account: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+    id: u64,
+    expiry: i64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(VOUCHER_DOMAIN.len() + 3 * 32 + 3 * 8);
+    message.extend_from_slice(VOUCHER_DOMAIN);
+    message.extend_from_slice(program_id.as_ref());
+    message.extend_from_slice(deposit_account.as_ref());
+    message.extend_from_slice(destination.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(&id.to_le_bytes());
+    message.extend_from_slice(&expiry.to_le_bytes());
+    message
+}
+
+/// Check that the instruction right before the current one is an ed25519 program instruction
+/// verifying a single signature by `signer` over `message`, with every part carried in its own
+/// data. The ed25519 program fails the whole transaction on a bad signature, so a match here
+/// means `signer` signed `message`.
+#[cfg(feature = "voucher")]
+fn verify_ed25519_signature(
+    instructions_info: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> ProgramResult {
+    let current = instructions_sysvar::load_current_index_checked(instructions_info)?;
+    let previous = current.checked_sub(1).ok_or(DepositError::InvalidVoucherSignature)?;
+    let instruction = instructions_sysvar::load_instruction_at_checked(previous as usize, instructions_info)?;
+    if instruction.program_id != ed25519_program::id() {
+        return Err(DepositError::InvalidVoucherSignature.into());
+    }
+    
+    // Header: signature count and padding, then seven u16 offsets for the one signature
+    let data = &instruction.data;
+    if data.len() < 16 || data[0] != 1 {
+        return Err(DepositError::InvalidVoucherSignature.into());
+    }
+    let offset = |index: usize| u16::from_le_bytes([data[2 + 2 * index], data[3 + 2 * index]]);
+    let (public_key_offset, message_offset, message_size) = (offset(2) as usize, offset(4) as usize, offset(5) as usize);
+    // Offsets into other instructions would let the verified key or message differ from this data
+    if offset(1) != u16::MAX || offset(3) != u16::MAX || offset(6) != u16::MAX {
+        return Err(DepositError::InvalidVoucherSignature.into());
+    }
+    
+    let public_key = data.get(public_key_offset..public_key_offset + 32);
+    let signed = data.get(message_offset..message_offset + message_size);
+    if public_key != Some(signer.as_ref()) || signed != Some(message) {
+        return Err(DepositError::InvalidVoucherSignature.into());
+    }
+    Ok(())
+}
+
+impl DepositAccount {
+    /// Serialized size of the account data the program expects to be allocated
+    pub const LEN: usize = 32 + 8 + 8 + 8 + 8 + 32 + 8 + 8;
+    
+    /// Size of accounts allocated before the activity and recovery fields: owner and balance only
+    pub const LEGACY_LEN: usize = 32 + 8;
+    
+    /// Decode the data of a deposit account fetched from the cluster; a legacy account reads
+    /// with zeroed counters and no recovery key
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() == Self::LEGACY_LEN {
+            let (owner, balance) =
+                <(Pubkey, u64)>::try_from_slice(data).map_err(|_| ProgramError::InvalidAccountData)?;
+            return Ok(Self { owner, balance, ..Self::default() });
+        }
+        Self::try_from_slice(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+    
+    /// Whether the account still has the legacy layout, which only a deposit migrates
+    fn is_legacy(account_info: &AccountInfo) -> bool {
+        account_info.data_len() == Self::LEGACY_LEN
+    }
+    
+    /// Write the state back in the layout the account has, so a legacy account keeps only
+    /// its owner and balance
+    fn store(&self, account_info: &AccountInfo) -> ProgramResult {
+        let mut data = account_info.data.borrow_mut();
+        if data.len() == Self::LEGACY_LEN {
+            (self.owner, self.balance).serialize(&mut *data)?;
+        } else {
+            self.serialize(&mut *data)?;
+        }
+        Ok(())
+    }
+    
+    /// Seconds without a deposit or withdrawal as of `now` (a Unix timestamp), `None` for an
+    /// account that never had any
+    pub fn idle_secs(&self, now: i64) -> Option<i64> {
+        (self.last_activity_ts > 0).then(|| now.saturating_sub(self.last_activity_ts))
+    }
+    
+    /// Whether the account has seen no activity for at least `max_idle_secs` as of `now`;
+    /// never-used accounts count as dormant
+    pub fn is_dormant(&self, now: i64, max_idle_secs: i64) -> bool {
+        self.idle_secs(now).map_or(true, |idle| idle >= max_idle_secs)
+    }
+    
+    /// The designated recovery key, if any
+    pub fn recovery_key(&self) -> Option<Pubkey> {
+        (self.recovery != Pubkey::default()).then_some(self.recovery)
+    }
+    
+    /// Whether `key` may withdraw as of `slot`: the owner always, the recovery key once the
+    /// owner has been inactive for `recovery_after_slots`
+    pub fn may_withdraw(&self, key: &Pubkey, slot: u64) -> Result<(), ProgramError> {
+        if self.owner == *key {
+            return Ok(());
+        }
+        if self.recovery_key() != Some(*key) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if slot.saturating_sub(self.last_owner_slot) < self.recovery_after_slots {
+            return Err(DepositError::RecoveryNotYetAvailable.into());
+        }
+        Ok(())
+    }
 }
 
 /// Error types for the deposit/withdraw program
@@ -32,6 +376,69 @@ pub enum DepositError {
     
     #[error("Invalid instruction data")]
     InvalidInstructionData,
+    
+    #[error("Deposit below the configured minimum")]
+    DepositBelowMinimum,
+    
+    #[error("Withdrawal below the configured minimum")]
+    WithdrawBelowMinimum,
+    
+    #[error("Not the program config account")]
+    InvalidConfigAccount,
+    
+    #[error("Only the config admin may change the program config")]
+    Unauthorized,
+    
+    #[error("The owner has not been inactive long enough for recovery")]
+    RecoveryNotYetAvailable,
+    
+    #[error("A recovery key needs a non-zero inactivity period")]
+    InvalidRecoveryPeriod,
+    
+    #[error("Not the pull authorization account of this deposit account and payee")]
+    InvalidPullAccount,
+    
+    #[error("The pull authorization has expired")]
+    PullExpired,
+    
+    #[error("Pull exceeds the remaining authorized amount")]
+    PullExceedsAuthorization,
+    
+    #[error("Not the program stats account")]
+    InvalidStatsAccount,
+    
+    #[error("No ed25519 signature by the deposit owner over this voucher precedes the instruction")]
+    InvalidVoucherSignature,
+    
+    #[error("The voucher has expired")]
+    VoucherExpired,
+    
+    #[error("The voucher has already been redeemed")]
+    VoucherAlreadyRedeemed,
+    
+    #[error("Not the redemption account of this deposit account and voucher id")]
+    InvalidVoucherAccount,
+    
+    #[error("The instruction belongs to a subsystem left out of this build of the program")]
+    InstructionDisabled,
+    
+    #[error("Account must be writable")]
+    AccountNotWritable,
+    
+    #[error("Account does not hold the rent-exempt minimum")]
+    NotRentExempt,
+    
+    #[error("Not the program or sysvar account the instruction expects")]
+    UnexpectedAccount,
+    
+    #[error("Only the program's upgrade authority may initialize the program config")]
+    NotUpgradeAuthority,
+    
+    #[error("The program stats must be initialized before funds move")]
+    StatsNotInitialized,
+    
+    #[error("The deposit account has the legacy layout; a deposit migrates it")]
+    LegacyAccountLayout,
 }
 
 impl From<DepositError> for ProgramError {
@@ -60,6 +467,40 @@ impl Processor {
             DepositInstruction::Withdraw { amount } => {
                 Self::process_withdraw(program_id, accounts, amount)
             },
+            DepositInstruction::InitializeConfig { min_deposit, min_withdraw } => {
+                Self::process_initialize_config(program_id, accounts, min_deposit, min_withdraw)
+            },
+            DepositInstruction::UpdateConfig { min_deposit, min_withdraw } => {
+                Self::process_update_config(program_id, accounts, min_deposit, min_withdraw)
+            },
+            #[cfg(feature = "recovery")]
+            DepositInstruction::SetRecovery { recovery, inactivity_slots } => {
+                Self::process_set_recovery(program_id, accounts, recovery, inactivity_slots)
+            },
+            #[cfg(feature = "pull")]
+            DepositInstruction::AuthorizePull { payee, amount, expiry } => {
+                Self::process_authorize_pull(program_id, accounts, payee, amount, expiry)
+            },
+            #[cfg(feature = "pull")]
+            DepositInstruction::Pull { amount } => {
+                Self::process_pull(program_id, accounts, amount)
+            },
+            DepositInstruction::InitializeStats => {
+                Self::process_initialize_stats(program_id, accounts)
+            },
+            #[cfg(feature = "voucher")]
+            DepositInstruction::RedeemVoucher { amount, id, expiry } => {
+                Self::process_redeem_voucher(program_id, accounts, amount, id, expiry)
+            },
+            // Optional subsystems compiled out keep their instructions, so clients stay compatible
+            #[cfg(not(feature = "recovery"))]
+            DepositInstruction::SetRecovery { .. } => Err(DepositError::InstructionDisabled.into()),
+            #[cfg(not(feature = "pull"))]
+            DepositInstruction::AuthorizePull { .. } | DepositInstruction::Pull { .. } => {
+                Err(DepositError::InstructionDisabled.into())
+            },
+            #[cfg(not(feature = "voucher"))]
+            DepositInstruction::RedeemVoucher { .. } => Err(DepositError::InstructionDisabled.into()),
         }
     }
 
@@ -69,28 +510,37 @@ impl Processor {
         accounts: &[AccountInfo],
         amount: u64,
     ) -> ProgramResult {
-        // Get the account iterator
-        let account_info_iter = &mut accounts.iter();
-        
-        // Get the accounts
-        let funder_info = next_account_info(account_info_iter)?;
-        let deposit_account_info = next_account_info(account_info_iter)?;
+        // Get the accounts; the funder signed and the deposit account is ours and rent-exempt
+        let [funder_info, deposit_account_info, _system_program_info, config_info, stats_info] =
+            validate_accounts(program_id, accounts, &validate_accounts::DEPOSIT)?;
         
-        // Verify the deposit account is owned by our program
-        if deposit_account_info.owner != program_id {
-            return Err(DepositError::IncorrectProgramId.into());
+        // Reject dust deposits
+        let config = ProgramConfig::load(program_id, config_info)?;
+        if amount < config.min_deposit {
+            msg!("Deposit of {} lamports is below the minimum of {}", amount, config.min_deposit);
+            return Err(DepositError::DepositBelowMinimum.into());
         }
         
-        // Verify the funder signed the transaction
-        if !funder_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+        // The totals only add up if they count every deposit from the first one on
+        if stats_info.data_is_empty() {
+            return Err(DepositError::StatsNotInitialized.into());
         }
         
+        // A legacy account grows to the current layout, the funder paying the extra rent
+        let legacy = DepositAccount::is_legacy(deposit_account_info);
+        let top_up = if legacy {
+            let rent = Rent::get()?;
+            rent.minimum_balance(DepositAccount::LEN)
+                .saturating_sub(rent.minimum_balance(DepositAccount::LEGACY_LEN))
+        } else {
+            0
+        };
+        
         // Transfer SOL from funder to deposit account
         let instruction = system_instruction::transfer(
             funder_info.key,
             deposit_account_info.key,
-            amount,
+            amount.checked_add(top_up).ok_or(DepositError::InsufficientFunds)?,
         );
         
         invoke_signed(
@@ -100,21 +550,30 @@ impl Processor {
         )?;
         
         // Update the deposit account state
-        let mut deposit_account_data = if deposit_account_info.data_len() > 0 {
-            DepositAccount::try_from_slice(&deposit_account_info.data.borrow())?
-        } else {
-            // Initialize new account
-            DepositAccount {
-                owner: *funder_info.key,
-                balance: 0,
-            }
-        };
+        let mut deposit_account_data = DepositAccount::unpack(&deposit_account_info.data.borrow())?;
+        if legacy {
+            deposit_account_info.realloc(DepositAccount::LEN, true)?;
+            msg!("Deposit account migrated to the current layout");
+        }
+        
+        // A freshly allocated account is all zeroes: the first funder becomes its owner
+        if deposit_account_data.owner == Pubkey::default() {
+            deposit_account_data.owner = *funder_info.key;
+        }
         
-        // Update balance
+        // Update balance and activity; only the owner's own deposits hold off recovery
+        let clock = Clock::get()?;
+        let balance_before = deposit_account_data.balance;
         deposit_account_data.balance += amount;
+        deposit_account_data.deposit_count += 1;
+        deposit_account_data.last_activity_ts = clock.unix_timestamp;
+        if deposit_account_data.owner == *funder_info.key {
+            deposit_account_data.last_owner_slot = clock.slot;
+        }
         
         // Serialize the updated state back to the account
-        deposit_account_data.serialize(&mut *deposit_account_info.data.borrow_mut())?;
+        deposit_account_data.store(deposit_account_info)?;
+        ProgramStats::record(program_id, stats_info, |stats| stats.deposited(amount, balance_before))?;
         
         msg!("Deposit successful: {} lamports", amount);
         
@@ -127,37 +586,29 @@ impl Processor {
         accounts: &[AccountInfo],
         amount: u64,
     ) -> ProgramResult {
-        // Get the account iterator
-        let account_info_iter = &mut accounts.iter();
-        
-        // Get the accounts
-        let owner_info = next_account_info(account_info_iter)?;
-        let deposit_account_info = next_account_info(account_info_iter)?;
-        let destination_info = next_account_info(account_info_iter)?;
-        
-        // Verify the deposit account is owned by our program
-        if deposit_account_info.owner != program_id {
-            return Err(DepositError::IncorrectProgramId.into());
-        }
-        
-        // Verify the owner signed the transaction
-        if !owner_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
+        // Get the accounts; the signer is the owner or, after its inactivity period, the recovery key
+        let [owner_info, deposit_account_info, destination_info, config_info, stats_info] =
+            validate_accounts(program_id, accounts, &validate_accounts::WITHDRAW)?;
         
         // Deserialize the deposit account data
-        let mut deposit_account_data = DepositAccount::try_from_slice(&deposit_account_info.data.borrow())?;
+        let mut deposit_account_data = DepositAccount::unpack(&deposit_account_info.data.borrow())?;
         
-        // Verify the owner is authorized
-        if deposit_account_data.owner != *owner_info.key {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        // Verify the owner is authorized, or the recovery key once the owner went inactive
+        let clock = Clock::get()?;
+        deposit_account_data.may_withdraw(owner_info.key, clock.slot)?;
         
         // Check if there are sufficient funds
         if deposit_account_data.balance < amount {
             return Err(DepositError::InsufficientFunds.into());
         }
         
+        // Reject dust withdrawals, except one emptying the account so no dust is stranded
+        let config = ProgramConfig::load(program_id, config_info)?;
+        if amount < config.min_withdraw && amount != deposit_account_data.balance {
+            msg!("Withdrawal of {} lamports is below the minimum of {}", amount, config.min_withdraw);
+            return Err(DepositError::WithdrawBelowMinimum.into());
+        }
+        
         // Calculate the rent-exempt amount that must remain in the account
         let rent = Rent::get()?;
         let min_balance = rent.minimum_balance(deposit_account_info.data_len());
@@ -171,18 +622,909 @@ impl Processor {
             return Err(DepositError::InsufficientFunds.into());
         }
         
-        // Update the deposit account balance
+        // Update the deposit account balance and activity
         deposit_account_data.balance -= amount;
+        deposit_account_data.withdraw_count += 1;
+        deposit_account_data.last_activity_ts = clock.unix_timestamp;
+        if deposit_account_data.owner == *owner_info.key {
+            deposit_account_data.last_owner_slot = clock.slot;
+        }
         
         // Transfer lamports from deposit account to destination
         **deposit_account_info.lamports.borrow_mut() -= amount;
         **destination_info.lamports.borrow_mut() += amount;
         
         // Serialize the updated state back to the account
-        deposit_account_data.serialize(&mut *deposit_account_info.data.borrow_mut())?;
+        deposit_account_data.store(deposit_account_info)?;
+        let balance_after = deposit_account_data.balance;
+        ProgramStats::record(program_id, stats_info, |stats| stats.withdrawn(amount, balance_after))?;
         
         msg!("Withdrawal successful: {} lamports", amount);
         
         Ok(())
     }
+    
+    // Process an initialize config instruction
+    fn process_initialize_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        min_deposit: u64,
+        min_withdraw: u64,
+    ) -> ProgramResult {
+        // The admin pays for the config account and becomes its only editor
+        let [admin_info, config_info, system_program_info, program_data_info] =
+            validate_accounts(program_id, accounts, &validate_accounts::INITIALIZE_CONFIG)?;
+        
+        // Only whoever deployed the program may claim it, or anyone could front-run the deployer
+        if upgrade_authority(program_id, program_data_info)? != Some(*admin_info.key) {
+            return Err(DepositError::NotUpgradeAuthority.into());
+        }
+        
+        let (config_key, bump) = config_address(program_id);
+        if *config_info.key != config_key {
+            return Err(DepositError::InvalidConfigAccount.into());
+        }
+        if !config_info.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        
+        // Create the PDA, signing for it with its seeds
+        create_pda(
+            program_id,
+            admin_info,
+            config_info,
+            system_program_info,
+            ProgramConfig::LEN,
+            &[CONFIG_SEED, &[bump]],
+        )?;
+        
+        let config = ProgramConfig {
+            admin: *admin_info.key,
+            min_deposit,
+            min_withdraw,
+        };
+        config.serialize(&mut *config_info.data.borrow_mut())?;
+        
+        msg!("Config initialized: min deposit {}, min withdraw {}", min_deposit, min_withdraw);
+        
+        Ok(())
+    }
+
+    // Process an update config instruction
+    fn process_update_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        min_deposit: u64,
+        min_withdraw: u64,
+    ) -> ProgramResult {
+        let [admin_info, config_info] =
+            validate_accounts(program_id, accounts, &validate_accounts::UPDATE_CONFIG)?;
+        
+        if config_info.data_is_empty() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        let mut config = ProgramConfig::load(program_id, config_info)?;
+        if config.admin != *admin_info.key {
+            return Err(DepositError::Unauthorized.into());
+        }
+        
+        config.min_deposit = min_deposit;
+        config.min_withdraw = min_withdraw;
+        config.serialize(&mut *config_info.data.borrow_mut())?;
+        
+        msg!("Config updated: min deposit {}, min withdraw {}", min_deposit, min_withdraw);
+        
+        Ok(())
+    }
+
+    // Process a set recovery instruction
+    #[cfg(feature = "recovery")]
+    fn process_set_recovery(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        recovery: Option<Pubkey>,
+        inactivity_slots: u64,
+    ) -> ProgramResult {
+        let [owner_info, deposit_account_info] =
+            validate_accounts(program_id, accounts, &validate_accounts::SET_RECOVERY)?;
+        
+        let mut deposit_account_data = DepositAccount::unpack(&deposit_account_info.data.borrow())?;
+        if deposit_account_data.owner != *owner_info.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        
+        // A legacy account has no room for the recovery fields
+        if DepositAccount::is_legacy(deposit_account_info) {
+            return Err(DepositError::LegacyAccountLayout.into());
+        }
+        
+        // A zero period would hand the account to the recovery key right away
+        if recovery.is_some() && inactivity_slots == 0 {
+            return Err(DepositError::InvalidRecoveryPeriod.into());
+        }
+        
+        // Changing the designation is owner activity too
+        deposit_account_data.recovery = recovery.unwrap_or_default();
+        deposit_account_data.recovery_after_slots = if recovery.is_some() { inactivity_slots } else { 0 };
+        deposit_account_data.last_owner_slot = Clock::get()?.slot;
+        deposit_account_data.store(deposit_account_info)?;
+        
+        match recovery {
+            Some(recovery) => msg!("Recovery key {} after {} inactive slots", recovery, inactivity_slots),
+            None => msg!("Recovery key cleared"),
+        }
+        
+        Ok(())
+    }
+
+    // Process an authorize pull instruction
+    #[cfg(feature = "pull")]
+    fn process_authorize_pull(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        payee: Pubkey,
+        amount: u64,
+        expiry: i64,
+    ) -> ProgramResult {
+        let [owner_info, deposit_account_info, pull_info, system_program_info] =
+            validate_accounts(program_id, accounts, &validate_accounts::AUTHORIZE_PULL)?;
+        
+        let deposit_account_data = DepositAccount::unpack(&deposit_account_info.data.borrow())?;
+        if deposit_account_data.owner != *owner_info.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        
+        let (pull_key, bump) = pull_address(program_id, deposit_account_info.key, &payee);
+        if *pull_info.key != pull_key {
+            return Err(DepositError::InvalidPullAccount.into());
+        }
+        
+        // The owner pays for the PDA the first time; later authorizations replace the terms
+        if pull_info.data_is_empty() {
+            create_pda(
+                program_id,
+                owner_info,
+                pull_info,
+                system_program_info,
+                PullAuthorization::LEN,
+                &[PULL_SEED, deposit_account_info.key.as_ref(), payee.as_ref(), &[bump]],
+            )?;
+        } else if pull_info.owner != program_id {
+            return Err(DepositError::InvalidPullAccount.into());
+        }
+        
+        let authorization = PullAuthorization {
+            deposit_account: *deposit_account_info.key,
+            payee,
+            remaining: amount,
+            expiry,
+        };
+        authorization.serialize(&mut *pull_info.data.borrow_mut())?;
+        
+        msg!("Pull authorized: {} may pull {} lamports until {}", payee, amount, expiry);
+        
+        Ok(())
+    }
+
+    // Process a pull instruction
+    #[cfg(feature = "pull")]
+    fn process_pull(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let [payee_info, deposit_account_info, destination_info, pull_info, config_info, stats_info] =
+            validate_accounts(program_id, accounts, &validate_accounts::PULL)?;
+        
+        // Only the payee's own authorization for this deposit account counts
+        if *pull_info.key != pull_address(program_id, deposit_account_info.key, payee_info.key).0
+            || pull_info.owner != program_id
+        {
+            return Err(DepositError::InvalidPullAccount.into());
+        }
+        let mut authorization = PullAuthorization::try_from_slice(&pull_info.data.borrow())?;
+        
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= authorization.expiry {
+            return Err(DepositError::PullExpired.into());
+        }
+        if amount > authorization.remaining {
+            msg!("Pull of {} lamports exceeds the remaining {}", amount, authorization.remaining);
+            return Err(DepositError::PullExceedsAuthorization.into());
+        }
+        
+        let mut deposit_account_data = DepositAccount::unpack(&deposit_account_info.data.borrow())?;
+        if deposit_account_data.balance < amount {
+            return Err(DepositError::InsufficientFunds.into());
+        }
+        
+        // A pull is held to the same minimum as a withdrawal
+        let config = ProgramConfig::load(program_id, config_info)?;
+        if amount < config.min_withdraw && amount != deposit_account_data.balance {
+            msg!("Pull of {} lamports is below the minimum of {}", amount, config.min_withdraw);
+            return Err(DepositError::WithdrawBelowMinimum.into());
+        }
+        
+        // Ensure the account will remain rent-exempt after the pull
+        let rent = Rent::get()?;
+        let min_balance = rent.minimum_balance(deposit_account_info.data_len());
+        let available_for_withdrawal = deposit_account_info.lamports()
+            .checked_sub(min_balance)
+            .ok_or(DepositError::InsufficientFunds)?;
+        if amount > available_for_withdrawal {
+            return Err(DepositError::InsufficientFunds.into());
+        }
+        
+        // A pull is a withdrawal, but not owner activity
+        authorization.remaining -= amount;
+        deposit_account_data.balance -= amount;
+        deposit_account_data.withdraw_count += 1;
+        deposit_account_data.last_activity_ts = clock.unix_timestamp;
+        
+        **deposit_account_info.lamports.borrow_mut() -= amount;
+        **destination_info.lamports.borrow_mut() += amount;
+        
+        deposit_account_data.store(deposit_account_info)?;
+        authorization.serialize(&mut *pull_info.data.borrow_mut())?;
+        let balance_after = deposit_account_data.balance;
+        ProgramStats::record(program_id, stats_info, |stats| stats.withdrawn(amount, balance_after))?;
+        
+        msg!("Pull successful: {} lamports, {} remaining", amount, authorization.remaining);
+        
+        Ok(())
+    }
+
+    // Process a redeem voucher instruction
+    #[cfg(feature = "voucher")]
+    fn process_redeem_voucher(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        id: u64,
+        expiry: i64,
+    ) -> ProgramResult {
+        // The submitter only pays for the redemption marker; the owner's signature is the authority
+        let [
+            submitter_info,
+            deposit_account_info,
+            destination_info,
+            voucher_info,
+            instructions_info,
+            system_program_info,
+            config_info,
+            stats_info,
+        ] = validate_accounts(program_id, accounts, &validate_accounts::REDEEM_VOUCHER)?;
+        
+        let mut deposit_account_data = DepositAccount::unpack(&deposit_account_info.data.borrow())?;
+        let message = voucher_message(
+            program_id,
+            deposit_account_info.key,
+            destination_info.key,
+            amount,
+            id,
+            expiry,
+        );
+        verify_ed25519_signature(instructions_info, &deposit_account_data.owner, &message)?;
+        
+        let clock = Clock::get()?;
+        if clock.unix_timestamp >= expiry {
+            return Err(DepositError::VoucherExpired.into());
+        }
+        
+        let (voucher_key, bump) = voucher_address(program_id, deposit_account_info.key, id);
+        if *voucher_info.key != voucher_key {
+            return Err(DepositError::InvalidVoucherAccount.into());
+        }
+        if voucher_info.owner == program_id {
+            return Err(DepositError::VoucherAlreadyRedeemed.into());
+        }
+        
+        if deposit_account_data.balance < amount {
+            return Err(DepositError::InsufficientFunds.into());
+        }
+        
+        let config = ProgramConfig::load(program_id, config_info)?;
+        if amount < config.min_withdraw && amount != deposit_account_data.balance {
+            msg!("Withdrawal of {} lamports is below the minimum of {}", amount, config.min_withdraw);
+            return Err(DepositError::WithdrawBelowMinimum.into());
+        }
+        
+        // Ensure the account will remain rent-exempt after the redemption
+        let rent = Rent::get()?;
+        let min_balance = rent.minimum_balance(deposit_account_info.data_len());
+        let available_for_withdrawal = deposit_account_info.lamports()
+            .checked_sub(min_balance)
+            .ok_or(DepositError::InsufficientFunds)?;
+        if amount > available_for_withdrawal {
+            return Err(DepositError::InsufficientFunds.into());
+        }
+        
+        // An empty account owned by the program marks the voucher as redeemed
+        create_pda(
+            program_id,
+            submitter_info,
+            voucher_info,
+            system_program_info,
+            0,
+            &[VOUCHER_SEED, deposit_account_info.key.as_ref(), &id.to_le_bytes(), &[bump]],
+        )?;
+        
+        // The owner may have signed the voucher long ago, so this is not owner activity
+        deposit_account_data.balance -= amount;
+        deposit_account_data.withdraw_count += 1;
+        deposit_account_data.last_activity_ts = clock.unix_timestamp;
+        
+        **deposit_account_info.lamports.borrow_mut() -= amount;
+        **destination_info.lamports.borrow_mut() += amount;
+        
+        deposit_account_data.store(deposit_account_info)?;
+        let balance_after = deposit_account_data.balance;
+        ProgramStats::record(program_id, stats_info, |stats| stats.withdrawn(amount, balance_after))?;
+        
+        msg!("Voucher {} redeemed: {} lamports", id, amount);
+        
+        Ok(())
+    }
+    
+    // Process an initialize stats instruction
+    fn process_initialize_stats(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        // Anyone may pay for the stats account; it only ever holds what the program records
+        let [payer_info, stats_info, system_program_info] =
+            validate_accounts(program_id, accounts, &validate_accounts::INITIALIZE_STATS)?;
+        
+        let (stats_key, bump) = stats_address(program_id);
+        if *stats_info.key != stats_key {
+            return Err(DepositError::InvalidStatsAccount.into());
+        }
+        if !stats_info.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        
+        create_pda(
+            program_id,
+            payer_info,
+            stats_info,
+            system_program_info,
+            ProgramStats::LEN,
+            &[STATS_SEED, &[bump]],
+        )?;
+        ProgramStats::default().serialize(&mut *stats_info.data.borrow_mut())?;
+        
+        msg!("Stats initialized");
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction;
+    use solana_program::{
+        entrypoint::SUCCESS,
+        instruction::Instruction,
+        program_stubs::{set_syscall_stubs, SyscallStubs},
+        system_program,
+    };
+    use std::{collections::HashMap, sync::Once};
+    
+    /// Slot and Unix timestamp of the stubbed clock
+    const SLOT: u64 = 1_000;
+    const NOW: i64 = 1_700_000_000;
+    
+    /// Answers the sysvar syscalls the program makes with the default rent and a fixed clock
+    struct Sysvars;
+    
+    impl SyscallStubs for Sysvars {
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { var_addr.cast::<Rent>().write(Rent::default()) };
+            SUCCESS
+        }
+        
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { slot: SLOT, unix_timestamp: NOW, ..Clock::default() };
+            unsafe { var_addr.cast::<Clock>().write(clock) };
+            SUCCESS
+        }
+    }
+    
+    #[derive(Clone)]
+    struct Account {
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+    }
+    
+    /// Accounts of one deployment of the program by address; any other address is an empty
+    /// system account
+    struct Cluster {
+        program_id: Pubkey,
+        admin: Pubkey,
+        accounts: HashMap<Pubkey, Account>,
+    }
+    
+    impl Cluster {
+        /// A deployment whose config has no minimums and whose stats are initialized
+        fn new() -> Self {
+            static STUBS: Once = Once::new();
+            STUBS.call_once(|| {
+                set_syscall_stubs(Box::new(Sysvars));
+            });
+            
+            let mut cluster = Self {
+                program_id: Pubkey::new_unique(),
+                admin: Pubkey::new_unique(),
+                accounts: HashMap::new(),
+            };
+            cluster.set_config(0);
+            cluster.store(stats_address(&cluster.program_id).0, ProgramStats::default().try_to_vec().unwrap());
+            cluster
+        }
+        
+        /// Hold `data` at `key`, owned by the program and rent-exempt
+        fn store(&mut self, key: Pubkey, data: Vec<u8>) {
+            let lamports = Rent::default().minimum_balance(data.len());
+            self.accounts.insert(key, Account { owner: self.program_id, lamports, data });
+        }
+        
+        fn set_config(&mut self, min_withdraw: u64) {
+            let config = ProgramConfig { admin: self.admin, min_deposit: 0, min_withdraw };
+            self.store(config_address(&self.program_id).0, config.try_to_vec().unwrap());
+        }
+        
+        /// A new deposit account in `state`, holding its balance on top of the rent-exempt minimum
+        fn deposit_account(&mut self, state: &DepositAccount) -> Pubkey {
+            let key = Pubkey::new_unique();
+            self.store(key, state.try_to_vec().unwrap());
+            self.accounts.get_mut(&key).unwrap().lamports += state.balance;
+            key
+        }
+        
+        fn deposit_state(&self, key: &Pubkey) -> DepositAccount {
+            DepositAccount::unpack(&self.accounts[key].data).unwrap()
+        }
+        
+        fn stats(&self) -> ProgramStats {
+            ProgramStats::unpack(&self.accounts[&stats_address(&self.program_id).0].data).unwrap()
+        }
+        
+        /// Run `instruction`, keeping the accounts it changed only when it succeeds
+        fn process(&mut self, instruction: &Instruction) -> ProgramResult {
+            let empty = Account { owner: system_program::ID, lamports: 0, data: vec![] };
+            let mut accounts: Vec<_> = instruction
+                .accounts
+                .iter()
+                .map(|meta| (meta, self.accounts.get(&meta.pubkey).unwrap_or(&empty).clone()))
+                .collect();
+            let infos: Vec<_> = accounts
+                .iter_mut()
+                .map(|(meta, account)| {
+                    AccountInfo::new(
+                        &meta.pubkey,
+                        meta.is_signer,
+                        meta.is_writable,
+                        &mut account.lamports,
+                        &mut account.data,
+                        &account.owner,
+                        false,
+                        0,
+                    )
+                })
+                .collect();
+            Processor::process(&self.program_id, &infos, &instruction.data)?;
+            
+            drop(infos);
+            for (meta, account) in accounts {
+                self.accounts.insert(meta.pubkey, account);
+            }
+            Ok(())
+        }
+    }
+    
+    fn owned_by(owner: Pubkey, balance: u64) -> DepositAccount {
+        DepositAccount { owner, balance, ..DepositAccount::default() }
+    }
+    
+    fn fails_with(error: DepositError) -> ProgramResult {
+        Err(error.into())
+    }
+    
+    #[test]
+    fn deposits_wait_for_the_stats() {
+        let mut cluster = Cluster::new();
+        let owner = Pubkey::new_unique();
+        let deposit_account = cluster.deposit_account(&owned_by(owner, 0));
+        cluster.accounts.remove(&stats_address(&cluster.program_id).0);
+        
+        let deposit = instruction::deposit(&cluster.program_id, &owner, &deposit_account, 1_000);
+        assert_eq!(cluster.process(&deposit), fails_with(DepositError::StatsNotInitialized));
+    }
+    
+    #[test]
+    fn withdrawals_wait_for_the_stats() {
+        let mut cluster = Cluster::new();
+        let owner = Pubkey::new_unique();
+        let deposit_account = cluster.deposit_account(&owned_by(owner, 1_000));
+        // Accounts of a deployment upgraded from a build without stats
+        let legacy_account = Pubkey::new_unique();
+        cluster.store(legacy_account, (owner, 1_000u64).try_to_vec().unwrap());
+        cluster.accounts.get_mut(&legacy_account).unwrap().lamports += 1_000;
+        cluster.accounts.remove(&stats_address(&cluster.program_id).0);
+        
+        for account in [deposit_account, legacy_account] {
+            let withdraw = instruction::withdraw(&cluster.program_id, &owner, &account, &owner, 400);
+            assert_eq!(cluster.process(&withdraw), fails_with(DepositError::StatsNotInitialized));
+            assert_eq!(cluster.deposit_state(&account).balance, 1_000);
+        }
+    }
+    
+    #[test]
+    fn withdrawals_by_anyone_but_the_owner_are_refused() {
+        let mut cluster = Cluster::new();
+        let owner = Pubkey::new_unique();
+        let deposit_account = cluster.deposit_account(&owned_by(owner, 1_000));
+        
+        let stranger = Pubkey::new_unique();
+        let withdraw = instruction::withdraw(&cluster.program_id, &stranger, &deposit_account, &stranger, 1_000);
+        assert_eq!(cluster.process(&withdraw), Err(ProgramError::InvalidAccountData));
+        
+        let mut unsigned = instruction::withdraw(&cluster.program_id, &owner, &deposit_account, &owner, 1_000);
+        unsigned.accounts[0].is_signer = false;
+        assert_eq!(cluster.process(&unsigned), Err(ProgramError::MissingRequiredSignature));
+    }
+    
+    #[test]
+    fn withdrawals_beyond_the_balance_are_refused() {
+        let mut cluster = Cluster::new();
+        let owner = Pubkey::new_unique();
+        let deposit_account = cluster.deposit_account(&owned_by(owner, 1_000));
+        
+        let withdraw = instruction::withdraw(&cluster.program_id, &owner, &deposit_account, &owner, 1_001);
+        assert_eq!(cluster.process(&withdraw), fails_with(DepositError::InsufficientFunds));
+        
+        // Lamports the balance does not account for are not withdrawable past the rent reserve
+        cluster.accounts.get_mut(&deposit_account).unwrap().lamports -= 1;
+        let withdraw = instruction::withdraw(&cluster.program_id, &owner, &deposit_account, &owner, 1_000);
+        assert_eq!(cluster.process(&withdraw), fails_with(DepositError::InsufficientFunds));
+    }
+    
+    #[test]
+    fn withdrawals_below_the_minimum_are_refused_unless_they_empty_the_account() {
+        let mut cluster = Cluster::new();
+        cluster.set_config(500);
+        let owner = Pubkey::new_unique();
+        let deposit_account = cluster.deposit_account(&owned_by(owner, 1_000));
+        
+        let withdraw = instruction::withdraw(&cluster.program_id, &owner, &deposit_account, &owner, 499);
+        assert_eq!(cluster.process(&withdraw), fails_with(DepositError::WithdrawBelowMinimum));
+        
+        let withdraw = instruction::withdraw(&cluster.program_id, &owner, &deposit_account, &owner, 1_000);
+        assert_eq!(cluster.process(&withdraw), Ok(()));
+        let state = cluster.deposit_state(&deposit_account);
+        assert_eq!((state.balance, state.withdraw_count, state.last_owner_slot), (0, 1, SLOT));
+        assert_eq!(cluster.accounts[&owner].lamports, 1_000);
+        assert_eq!(cluster.stats().total_withdrawn, 1_000);
+    }
+    
+    #[test]
+    fn withdrawals_need_the_program_stats_account() {
+        let mut cluster = Cluster::new();
+        let owner = Pubkey::new_unique();
+        let deposit_account = cluster.deposit_account(&owned_by(owner, 1_000));
+        
+        let mut withdraw = instruction::withdraw(&cluster.program_id, &owner, &deposit_account, &owner, 1_000);
+        withdraw.accounts[4].pubkey = Pubkey::new_unique();
+        assert_eq!(cluster.process(&withdraw), fails_with(DepositError::InvalidStatsAccount));
+        
+        let mut withdraw = instruction::withdraw(&cluster.program_id, &owner, &deposit_account, &owner, 1_000);
+        withdraw.accounts[3].pubkey = Pubkey::new_unique();
+        assert_eq!(cluster.process(&withdraw), fails_with(DepositError::InvalidConfigAccount));
+    }
+    
+    #[test]
+    fn legacy_accounts_withdraw_in_their_own_layout() {
+        let mut cluster = Cluster::new();
+        let owner = Pubkey::new_unique();
+        let deposit_account = Pubkey::new_unique();
+        cluster.store(deposit_account, (owner, 1_000u64).try_to_vec().unwrap());
+        cluster.accounts.get_mut(&deposit_account).unwrap().lamports += 1_000;
+        
+        let withdraw = instruction::withdraw(&cluster.program_id, &owner, &deposit_account, &owner, 400);
+        assert_eq!(cluster.process(&withdraw), Ok(()));
+        assert_eq!(cluster.accounts[&deposit_account].data.len(), DepositAccount::LEGACY_LEN);
+        assert_eq!(cluster.deposit_state(&deposit_account).balance, 600);
+    }
+    
+    #[cfg(feature = "recovery")]
+    #[test]
+    fn recovery_keys_wait_out_the_owners_inactivity() {
+        let mut cluster = Cluster::new();
+        let recovery = Pubkey::new_unique();
+        let state = DepositAccount {
+            recovery,
+            recovery_after_slots: 100,
+            last_owner_slot: SLOT - 99,
+            ..owned_by(Pubkey::new_unique(), 1_000)
+        };
+        let deposit_account = cluster.deposit_account(&state);
+        
+        let withdraw = instruction::withdraw(&cluster.program_id, &recovery, &deposit_account, &recovery, 1_000);
+        assert_eq!(cluster.process(&withdraw), fails_with(DepositError::RecoveryNotYetAvailable));
+        
+        cluster.store(deposit_account, DepositAccount { last_owner_slot: SLOT - 100, ..state }.try_to_vec().unwrap());
+        cluster.accounts.get_mut(&deposit_account).unwrap().lamports += 1_000;
+        assert_eq!(cluster.process(&withdraw), Ok(()));
+    }
+    
+    #[cfg(feature = "recovery")]
+    #[test]
+    fn only_the_owner_sets_a_recovery_key_with_a_waiting_period() {
+        let mut cluster = Cluster::new();
+        let owner = Pubkey::new_unique();
+        let deposit_account = cluster.deposit_account(&owned_by(owner, 0));
+        let recovery = Some(Pubkey::new_unique());
+        
+        let stranger = Pubkey::new_unique();
+        let set = instruction::set_recovery(&cluster.program_id, &stranger, &deposit_account, recovery, 100);
+        assert_eq!(cluster.process(&set), Err(ProgramError::InvalidAccountData));
+        
+        let set = instruction::set_recovery(&cluster.program_id, &owner, &deposit_account, recovery, 0);
+        assert_eq!(cluster.process(&set), fails_with(DepositError::InvalidRecoveryPeriod));
+        
+        let set = instruction::set_recovery(&cluster.program_id, &owner, &deposit_account, recovery, 100);
+        assert_eq!(cluster.process(&set), Ok(()));
+        let state = cluster.deposit_state(&deposit_account);
+        assert_eq!((state.recovery_key(), state.recovery_after_slots, state.last_owner_slot), (recovery, 100, SLOT));
+    }
+    
+    #[cfg(feature = "recovery")]
+    #[test]
+    fn legacy_accounts_cannot_hold_a_recovery_key() {
+        let mut cluster = Cluster::new();
+        let owner = Pubkey::new_unique();
+        let deposit_account = Pubkey::new_unique();
+        cluster.store(deposit_account, (owner, 0u64).try_to_vec().unwrap());
+        
+        let set = instruction::set_recovery(&cluster.program_id, &owner, &deposit_account, Some(Pubkey::new_unique()), 100);
+        assert_eq!(cluster.process(&set), fails_with(DepositError::LegacyAccountLayout));
+    }
+    
+    /// A deposit account of 1_000 lamports and `payee`'s authorization to pull `remaining` of
+    /// them until `expiry`
+    #[cfg(feature = "pull")]
+    fn authorized_pull(cluster: &mut Cluster, payee: &Pubkey, remaining: u64, expiry: i64) -> Pubkey {
+        let deposit_account = cluster.deposit_account(&owned_by(Pubkey::new_unique(), 1_000));
+        let authorization = PullAuthorization { deposit_account, payee: *payee, remaining, expiry };
+        let pull = pull_address(&cluster.program_id, &deposit_account, payee).0;
+        cluster.store(pull, authorization.try_to_vec().unwrap());
+        deposit_account
+    }
+    
+    #[cfg(feature = "pull")]
+    #[test]
+    fn pulls_stay_within_the_authorization() {
+        let mut cluster = Cluster::new();
+        let payee = Pubkey::new_unique();
+        let deposit_account = authorized_pull(&mut cluster, &payee, 600, NOW + 1);
+        
+        let pull = instruction::pull(&cluster.program_id, &payee, &deposit_account, &payee, 601);
+        assert_eq!(cluster.process(&pull), fails_with(DepositError::PullExceedsAuthorization));
+        
+        let pull = instruction::pull(&cluster.program_id, &payee, &deposit_account, &payee, 600);
+        assert_eq!(cluster.process(&pull), Ok(()));
+        let state = cluster.deposit_state(&deposit_account);
+        assert_eq!((state.balance, state.withdraw_count, state.last_owner_slot), (400, 1, 0));
+        
+        let pull = instruction::pull(&cluster.program_id, &payee, &deposit_account, &payee, 1);
+        assert_eq!(cluster.process(&pull), fails_with(DepositError::PullExceedsAuthorization));
+    }
+    
+    #[cfg(feature = "pull")]
+    #[test]
+    fn expired_pulls_are_refused() {
+        let mut cluster = Cluster::new();
+        let payee = Pubkey::new_unique();
+        let deposit_account = authorized_pull(&mut cluster, &payee, 600, NOW);
+        
+        let pull = instruction::pull(&cluster.program_id, &payee, &deposit_account, &payee, 600);
+        assert_eq!(cluster.process(&pull), fails_with(DepositError::PullExpired));
+    }
+    
+    #[cfg(feature = "pull")]
+    #[test]
+    fn payees_only_pull_under_their_own_authorization() {
+        let mut cluster = Cluster::new();
+        let payee = Pubkey::new_unique();
+        let deposit_account = authorized_pull(&mut cluster, &payee, 600, NOW + 1);
+        
+        let other = Pubkey::new_unique();
+        let mut pull = instruction::pull(&cluster.program_id, &other, &deposit_account, &other, 600);
+        pull.accounts[3].pubkey = pull_address(&cluster.program_id, &deposit_account, &payee).0;
+        assert_eq!(cluster.process(&pull), fails_with(DepositError::InvalidPullAccount));
+    }
+    
+    #[cfg(feature = "pull")]
+    #[test]
+    fn pulls_below_the_minimum_are_refused() {
+        let mut cluster = Cluster::new();
+        cluster.set_config(500);
+        let payee = Pubkey::new_unique();
+        let deposit_account = authorized_pull(&mut cluster, &payee, 1_000, NOW + 1);
+        
+        let pull = instruction::pull(&cluster.program_id, &payee, &deposit_account, &payee, 499);
+        assert_eq!(cluster.process(&pull), fails_with(DepositError::WithdrawBelowMinimum));
+    }
+    
+    /// Data of the instructions sysvar for a transaction of `instructions`, executing the one at
+    /// `current`
+    #[cfg(feature = "voucher")]
+    fn instructions_sysvar_data(instructions: &[Instruction], current: u16) -> Vec<u8> {
+        use solana_program::{instruction::AccountMeta, sysvar::instructions::{BorrowedAccountMeta, BorrowedInstruction}};
+        
+        let borrowed: Vec<_> = instructions
+            .iter()
+            .map(|instruction| BorrowedInstruction {
+                program_id: &instruction.program_id,
+                accounts: instruction
+                    .accounts
+                    .iter()
+                    .map(|AccountMeta { pubkey, is_signer, is_writable }| BorrowedAccountMeta {
+                        pubkey,
+                        is_signer: *is_signer,
+                        is_writable: *is_writable,
+                    })
+                    .collect(),
+                data: &instruction.data,
+            })
+            .collect();
+        let mut data = instructions_sysvar::construct_instructions_data(&borrowed);
+        instructions_sysvar::store_current_index(&mut data, current);
+        data
+    }
+    
+    /// Redeem voucher `id` of a deposit account of 1_000 lamports, signed by `signer`, in a
+    /// transaction with the instructions sysvar set up; returns the redemption instruction
+    #[cfg(feature = "voucher")]
+    fn redeem(cluster: &mut Cluster, signer: Option<&Pubkey>, amount: u64, id: u64, expiry: i64) -> Instruction {
+        let owner = Pubkey::new_unique();
+        let deposit_account = cluster.deposit_account(&owned_by(owner, 1_000));
+        let submitter = Pubkey::new_unique();
+        let [verify, redeem] = instruction::redeem_voucher(
+            &cluster.program_id,
+            &submitter,
+            signer.unwrap_or(&owner),
+            &deposit_account,
+            &submitter,
+            amount,
+            id,
+            expiry,
+            &[0; 64],
+        );
+        let data = instructions_sysvar_data(&[verify, redeem.clone()], 1);
+        cluster.accounts.insert(instructions_sysvar::ID, Account { owner: solana_program::sysvar::ID, lamports: 0, data });
+        redeem
+    }
+    
+    #[cfg(feature = "voucher")]
+    #[test]
+    fn vouchers_need_the_owners_signature_right_before_them() {
+        let mut cluster = Cluster::new();
+        
+        let forged = redeem(&mut cluster, Some(&Pubkey::new_unique()), 1_000, 1, NOW + 1);
+        assert_eq!(cluster.process(&forged), fails_with(DepositError::InvalidVoucherSignature));
+        
+        // Alone in its transaction, nothing verified a signature
+        let alone = redeem(&mut cluster, None, 1_000, 1, NOW + 1);
+        let data = instructions_sysvar_data(std::slice::from_ref(&alone), 0);
+        cluster.accounts.get_mut(&instructions_sysvar::ID).unwrap().data = data;
+        assert_eq!(cluster.process(&alone), fails_with(DepositError::InvalidVoucherSignature));
+        
+        let mut spoofed = redeem(&mut cluster, None, 1_000, 1, NOW + 1);
+        spoofed.accounts[4].pubkey = Pubkey::new_unique();
+        assert_eq!(cluster.process(&spoofed), fails_with(DepositError::UnexpectedAccount));
+    }
+    
+    #[cfg(feature = "voucher")]
+    #[test]
+    fn expired_or_redeemed_vouchers_are_refused() {
+        let mut cluster = Cluster::new();
+        
+        let expired = redeem(&mut cluster, None, 1_000, 1, NOW);
+        assert_eq!(cluster.process(&expired), fails_with(DepositError::VoucherExpired));
+        
+        let redeemed = redeem(&mut cluster, None, 1_000, 1, NOW + 1);
+        cluster.store(redeemed.accounts[3].pubkey, vec![]);
+        assert_eq!(cluster.process(&redeemed), fails_with(DepositError::VoucherAlreadyRedeemed));
+    }
+    
+    #[cfg(feature = "voucher")]
+    #[test]
+    fn vouchers_follow_the_withdrawal_rules() {
+        let mut cluster = Cluster::new();
+        cluster.set_config(500);
+        
+        let dust = redeem(&mut cluster, None, 499, 1, NOW + 1);
+        assert_eq!(cluster.process(&dust), fails_with(DepositError::WithdrawBelowMinimum));
+        
+        let overdrawn = redeem(&mut cluster, None, 1_001, 1, NOW + 1);
+        assert_eq!(cluster.process(&overdrawn), fails_with(DepositError::InsufficientFunds));
+    }
+    
+    /// Program data account of `program_id` recording `authority` as its upgrade authority
+    fn program_data(authority: Option<Pubkey>) -> Account {
+        // Bincode layout of `UpgradeableLoaderState::ProgramData`: variant, slot, optional key
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        match authority {
+            Some(authority) => {
+                data.push(1);
+                data.extend_from_slice(authority.as_ref());
+            },
+            None => data.extend_from_slice(&[0; 33]),
+        }
+        Account { owner: bpf_loader_upgradeable::ID, lamports: 0, data }
+    }
+    
+    #[test]
+    fn only_the_upgrade_authority_initializes_the_config() {
+        let mut cluster = Cluster::new();
+        cluster.accounts.remove(&config_address(&cluster.program_id).0);
+        let authority = cluster.admin;
+        cluster.accounts.insert(program_data_address(&cluster.program_id), program_data(Some(authority)));
+        
+        let stranger = Pubkey::new_unique();
+        let initialize = instruction::initialize_config(&cluster.program_id, &stranger, 0, 0);
+        assert_eq!(cluster.process(&initialize), fails_with(DepositError::NotUpgradeAuthority));
+        
+        // Another program's data account names its own authority
+        let mut initialize = instruction::initialize_config(&cluster.program_id, &authority, 0, 0);
+        initialize.accounts[3].pubkey = Pubkey::new_unique();
+        cluster.accounts.insert(initialize.accounts[3].pubkey, program_data(Some(authority)));
+        assert_eq!(cluster.process(&initialize), fails_with(DepositError::UnexpectedAccount));
+        
+        // Nobody may claim the config of an immutable program
+        cluster.accounts.insert(program_data_address(&cluster.program_id), program_data(None));
+        let initialize = instruction::initialize_config(&cluster.program_id, &authority, 0, 0);
+        assert_eq!(cluster.process(&initialize), fails_with(DepositError::NotUpgradeAuthority));
+    }
+    
+    #[test]
+    fn the_config_is_initialized_once() {
+        let mut cluster = Cluster::new();
+        let authority = cluster.admin;
+        cluster.accounts.insert(program_data_address(&cluster.program_id), program_data(Some(authority)));
+        
+        let initialize = instruction::initialize_config(&cluster.program_id, &authority, 0, 0);
+        assert_eq!(cluster.process(&initialize), Err(ProgramError::AccountAlreadyInitialized));
+    }
+    
+    #[test]
+    fn only_the_admin_updates_an_initialized_config() {
+        let mut cluster = Cluster::new();
+        
+        let update = instruction::update_config(&cluster.program_id, &Pubkey::new_unique(), 0, 500);
+        assert_eq!(cluster.process(&update), fails_with(DepositError::Unauthorized));
+        
+        let update = instruction::update_config(&cluster.program_id, &cluster.admin, 0, 500);
+        assert_eq!(cluster.process(&update), Ok(()));
+        
+        cluster.accounts.remove(&config_address(&cluster.program_id).0);
+        assert_eq!(cluster.process(&update), Err(ProgramError::UninitializedAccount));
+    }
+    
+    #[test]
+    fn the_stats_are_initialized_once_at_their_address() {
+        let mut cluster = Cluster::new();
+        let payer = Pubkey::new_unique();
+        
+        let initialize = instruction::initialize_stats(&cluster.program_id, &payer);
+        assert_eq!(cluster.process(&initialize), Err(ProgramError::AccountAlreadyInitialized));
+        
+        let mut initialize = instruction::initialize_stats(&cluster.program_id, &payer);
+        initialize.accounts[1].pubkey = Pubkey::new_unique();
+        assert_eq!(cluster.process(&initialize), fails_with(DepositError::InvalidStatsAccount));
+    }
 }
@@ -0,0 +1,330 @@
+use solana_program::{
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey, rent::Rent,
+    system_program, sysvar, sysvar::Sysvar,
+};
+
+use crate::processor::DepositError;
+
+/// What an instruction requires of one of its accounts
+#[derive(Debug, Clone, Copy)]
+pub struct Expect {
+    signer: bool,
+    writable: bool,
+    program_owned: bool,
+    rent_exempt: bool,
+    key: Option<Pubkey>,
+}
+
+impl Expect {
+    /// Any read-only account; the processor checks PDAs against their derived address
+    pub const ANY: Self = Self {
+        signer: false,
+        writable: false,
+        program_owned: false,
+        rent_exempt: false,
+        key: None,
+    };
+
+    /// Signed the transaction
+    pub const fn signer(self) -> Self {
+        Self {
+            signer: true,
+            ..self
+        }
+    }
+
+    /// Passed as writable
+    pub const fn writable(self) -> Self {
+        Self {
+            writable: true,
+            ..self
+        }
+    }
+
+    /// Owned by the program being invoked
+    pub const fn program_owned(self) -> Self {
+        Self {
+            program_owned: true,
+            ..self
+        }
+    }
+
+    /// Holds at least the rent-exempt minimum for its data
+    pub const fn rent_exempt(self) -> Self {
+        Self {
+            rent_exempt: true,
+            ..self
+        }
+    }
+
+    /// Is exactly `key`, such as a program or sysvar the instruction calls into
+    pub const fn key(self, key: Pubkey) -> Self {
+        Self {
+            key: Some(key),
+            ..self
+        }
+    }
+
+    fn check(
+        &self,
+        program_id: &Pubkey,
+        account: &AccountInfo,
+        rent: Option<&Rent>,
+    ) -> Result<(), ProgramError> {
+        if self.key.map_or(false, |key| key != *account.key) {
+            return Err(DepositError::UnexpectedAccount.into());
+        }
+        if self.signer && !account.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if self.writable && !account.is_writable {
+            return Err(DepositError::AccountNotWritable.into());
+        }
+        if self.program_owned && account.owner != program_id {
+            return Err(DepositError::IncorrectProgramId.into());
+        }
+        if let Some(rent) = rent.filter(|_| self.rent_exempt) {
+            if !rent.is_exempt(account.lamports(), account.data_len()) {
+                return Err(DepositError::NotRentExempt.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Check the first `N` accounts passed to an instruction against its expectations, in order,
+/// and return them; extra accounts are ignored
+pub fn validate_accounts<'a, 'info, const N: usize>(
+    program_id: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+    expected: &[Expect; N],
+) -> Result<&'a [AccountInfo<'info>; N], ProgramError> {
+    let accounts: &[AccountInfo; N] = accounts
+        .get(..N)
+        .ok_or(ProgramError::NotEnoughAccountKeys)?
+        .try_into()
+        .expect("slice has exactly N accounts");
+    let rent = if expected.iter().any(|expect| expect.rent_exempt) {
+        Some(Rent::get()?)
+    } else {
+        None
+    };
+    for (account, expect) in accounts.iter().zip(expected) {
+        expect.check(program_id, account, rent.as_ref())?;
+    }
+    Ok(accounts)
+}
+
+/// A deposit account the instruction changes
+const DEPOSIT_ACCOUNT: Expect = Expect::ANY.writable().program_owned().rent_exempt();
+const SYSTEM_PROGRAM: Expect = Expect::ANY.key(system_program::ID);
+/// The stats PDA, written once initialized
+const STATS: Expect = Expect::ANY.writable();
+
+/// Funder, deposit account, system program, config, stats
+pub const DEPOSIT: [Expect; 5] = [
+    Expect::ANY.signer().writable(),
+    DEPOSIT_ACCOUNT,
+    SYSTEM_PROGRAM,
+    Expect::ANY,
+    STATS,
+];
+
+/// Owner or recovery key, deposit account, destination, config, stats
+pub const WITHDRAW: [Expect; 5] = [
+    Expect::ANY.signer(),
+    DEPOSIT_ACCOUNT,
+    Expect::ANY.writable(),
+    Expect::ANY,
+    STATS,
+];
+
+/// Admin paying for the config, config, system program, program data holding the upgrade authority
+pub const INITIALIZE_CONFIG: [Expect; 4] = [
+    Expect::ANY.signer().writable(),
+    Expect::ANY.writable(),
+    SYSTEM_PROGRAM,
+    Expect::ANY,
+];
+
+/// Admin, config
+pub const UPDATE_CONFIG: [Expect; 2] = [Expect::ANY.signer(), Expect::ANY.writable()];
+
+/// Owner, deposit account
+pub const SET_RECOVERY: [Expect; 2] = [Expect::ANY.signer(), DEPOSIT_ACCOUNT];
+
+/// Owner paying for the authorization, deposit account (read only), authorization, system program
+pub const AUTHORIZE_PULL: [Expect; 4] = [
+    Expect::ANY.signer().writable(),
+    Expect::ANY.program_owned(),
+    Expect::ANY.writable(),
+    SYSTEM_PROGRAM,
+];
+
+/// Payee, deposit account, destination, authorization, config, stats
+pub const PULL: [Expect; 6] = [
+    Expect::ANY.signer(),
+    DEPOSIT_ACCOUNT,
+    Expect::ANY.writable(),
+    Expect::ANY.writable(),
+    Expect::ANY,
+    STATS,
+];
+
+/// Payer, stats, system program
+pub const INITIALIZE_STATS: [Expect; 3] = [
+    Expect::ANY.signer().writable(),
+    Expect::ANY.writable(),
+    SYSTEM_PROGRAM,
+];
+
+/// Submitter paying for the redemption marker, deposit account, destination, marker,
+/// instructions sysvar, system program, config, stats
+pub const REDEEM_VOUCHER: [Expect; 8] = [
+    Expect::ANY.signer().writable(),
+    DEPOSIT_ACCOUNT,
+    Expect::ANY.writable(),
+    Expect::ANY.writable(),
+    Expect::ANY.key(sysvar::instructions::ID),
+    SYSTEM_PROGRAM,
+    Expect::ANY,
+    STATS,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Storage behind an `AccountInfo`
+    struct TestAccount {
+        key: Pubkey,
+        owner: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        is_signer: bool,
+        is_writable: bool,
+    }
+
+    impl TestAccount {
+        /// A signed, writable and rent-exempt account of `program_id` with `len` bytes of data
+        fn new(program_id: &Pubkey, len: usize) -> Self {
+            Self {
+                key: Pubkey::new_unique(),
+                owner: *program_id,
+                lamports: Rent::default().minimum_balance(len),
+                data: vec![0; len],
+                is_signer: true,
+                is_writable: true,
+            }
+        }
+
+        fn info(&mut self) -> AccountInfo<'_> {
+            AccountInfo::new(
+                &self.key,
+                self.is_signer,
+                self.is_writable,
+                &mut self.lamports,
+                &mut self.data,
+                &self.owner,
+                false,
+                0,
+            )
+        }
+    }
+
+    fn check(expect: Expect, program_id: &Pubkey, account: &mut TestAccount) -> Result<(), ProgramError> {
+        expect.check(program_id, &account.info(), Some(&Rent::default()))
+    }
+
+    #[test]
+    fn accounts_meeting_every_expectation_pass() {
+        let program_id = Pubkey::new_unique();
+        let mut account = TestAccount::new(&program_id, 40);
+        let expect = Expect::ANY.signer().writable().program_owned().rent_exempt().key(account.key);
+        assert_eq!(check(expect, &program_id, &mut account), Ok(()));
+    }
+
+    #[test]
+    fn missing_signers_are_rejected() {
+        let program_id = Pubkey::new_unique();
+        let mut account = TestAccount::new(&program_id, 0);
+        account.is_signer = false;
+        assert_eq!(
+            check(Expect::ANY.signer(), &program_id, &mut account),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+        assert_eq!(check(Expect::ANY.writable(), &program_id, &mut account), Ok(()));
+    }
+
+    #[test]
+    fn read_only_accounts_are_rejected_where_writes_are_expected() {
+        let program_id = Pubkey::new_unique();
+        let mut account = TestAccount::new(&program_id, 0);
+        account.is_writable = false;
+        assert_eq!(
+            check(Expect::ANY.writable(), &program_id, &mut account),
+            Err(DepositError::AccountNotWritable.into())
+        );
+        assert_eq!(check(Expect::ANY.signer(), &program_id, &mut account), Ok(()));
+    }
+
+    #[test]
+    fn accounts_of_other_programs_are_rejected() {
+        let program_id = Pubkey::new_unique();
+        let mut account = TestAccount::new(&Pubkey::new_unique(), 0);
+        assert_eq!(
+            check(Expect::ANY.program_owned(), &program_id, &mut account),
+            Err(DepositError::IncorrectProgramId.into())
+        );
+        assert_eq!(check(Expect::ANY, &program_id, &mut account), Ok(()));
+    }
+
+    #[test]
+    fn accounts_below_the_rent_exempt_minimum_are_rejected() {
+        let program_id = Pubkey::new_unique();
+        let mut account = TestAccount::new(&program_id, 40);
+        account.lamports -= 1;
+        assert_eq!(
+            check(Expect::ANY.rent_exempt(), &program_id, &mut account),
+            Err(DepositError::NotRentExempt.into())
+        );
+        assert_eq!(check(Expect::ANY.program_owned(), &program_id, &mut account), Ok(()));
+    }
+
+    #[test]
+    fn other_keys_are_rejected_where_a_program_or_sysvar_is_expected() {
+        let program_id = Pubkey::new_unique();
+        let mut account = TestAccount::new(&program_id, 0);
+        assert_eq!(
+            check(SYSTEM_PROGRAM, &program_id, &mut account),
+            Err(DepositError::UnexpectedAccount.into())
+        );
+        account.key = system_program::ID;
+        assert_eq!(check(SYSTEM_PROGRAM, &program_id, &mut account), Ok(()));
+    }
+
+    #[test]
+    fn accounts_are_checked_in_order_and_extras_ignored() {
+        let program_id = Pubkey::new_unique();
+        let mut admin = TestAccount::new(&program_id, 0);
+        let mut config = TestAccount::new(&program_id, 0);
+        let mut extra = TestAccount::new(&program_id, 0);
+        let (admin_key, config_key) = (admin.key, config.key);
+
+        let accounts = [admin.info(), config.info(), extra.info()];
+        let [admin_info, config_info] = validate_accounts(&program_id, &accounts, &UPDATE_CONFIG).unwrap();
+        assert_eq!((*admin_info.key, *config_info.key), (admin_key, config_key));
+
+        assert_eq!(
+            validate_accounts(&program_id, &accounts[..1], &UPDATE_CONFIG).map(|_| ()),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        // Passed the other way round, the config in the admin's place has not signed
+        let mut config_first = accounts[1].clone();
+        config_first.is_signer = false;
+        assert_eq!(
+            validate_accounts(&program_id, &[config_first, accounts[0].clone()], &UPDATE_CONFIG).map(|_| ()),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use anyhow::{bail, Context, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+};
+use std::{
+    net::TcpListener,
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+use tempfile::TempDir;
+
+/// Overrides the `solana-test-validator` binary used by the tests
+pub const VALIDATOR_BIN_ENV: &str = "SOLANA_TEST_VALIDATOR";
+
+/// Overrides the path of the compiled task4 program loaded into the validator
+pub const DEPOSIT_PROGRAM_SO_ENV: &str = "DEPOSIT_PROGRAM_SO";
+
+/// Validators bind fixed gossip/faucet ports, so only one runs at a time per test binary
+static VALIDATOR_LOCK: Mutex<()> = Mutex::new(());
+
+/// A `solana-test-validator` process with the task4 deposit program preloaded as an upgradeable
+/// program. The process is killed and its ledger removed when the value is dropped.
+pub struct TestValidator {
+    process: Child,
+    rpc_url: String,
+    deposit_program_id: Pubkey,
+    deposit_program_authority: Keypair,
+    _ledger: TempDir,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl TestValidator {
+    /// Start a fresh validator and wait until its RPC endpoint is serving requests
+    pub fn start() -> Result<Self> {
+        let guard = VALIDATOR_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let program_so = deposit_program_path();
+        if !program_so.exists() {
+            bail!(
+                "Deposit program not found at {}; build it with `cargo build-sbf --manifest-path task4/Cargo.toml` or set {}",
+                program_so.display(),
+                DEPOSIT_PROGRAM_SO_ENV
+            );
+        }
+
+        let ledger = tempfile::tempdir().context("Failed to create ledger directory")?;
+        let rpc_port = free_port()?;
+        let faucet_port = free_port()?;
+        let deposit_program_id = Keypair::new().pubkey();
+        let deposit_program_authority = Keypair::new();
+
+        let binary = std::env::var(VALIDATOR_BIN_ENV)
+            .unwrap_or_else(|_| "solana-test-validator".to_string());
+
+        let process = Command::new(&binary)
+            .arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(ledger.path())
+            .args(["--rpc-port", &rpc_port.to_string()])
+            .args(["--faucet-port", &faucet_port.to_string()])
+            .arg("--upgradeable-program")
+            .arg(deposit_program_id.to_string())
+            .arg(&program_so)
+            .arg(deposit_program_authority.pubkey().to_string())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Failed to start {} (is it on PATH?)", binary))?;
+
+        let validator = TestValidator {
+            process,
+            rpc_url: format!("http://127.0.0.1:{}", rpc_port),
+            deposit_program_id,
+            deposit_program_authority,
+            _ledger: ledger,
+            _guard: guard,
+        };
+
+        validator.wait_until_ready(Duration::from_secs(60))?;
+        validator.airdrop(
+            &validator.deposit_program_authority.pubkey(),
+            LAMPORTS_PER_SOL,
+        )?;
+
+        Ok(validator)
+    }
+
+    pub fn rpc_url(&self) -> &str {
+        &self.rpc_url
+    }
+
+    /// Program id the task4 deposit program was loaded under
+    pub fn deposit_program_id(&self) -> Pubkey {
+        self.deposit_program_id
+    }
+
+    /// Funded upgrade authority of the deposit program, the only key that may initialize its
+    /// config
+    pub fn deposit_program_authority(&self) -> &Keypair {
+        &self.deposit_program_authority
+    }
+
+    /// A confirmed-commitment RPC client pointed at this validator
+    pub fn client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+    }
+
+    /// Generate an ephemeral wallet and fund it from the validator faucet
+    pub fn fund_new_wallet(&self, lamports: u64) -> Result<Keypair> {
+        let wallet = Keypair::new();
+        self.airdrop(&wallet.pubkey(), lamports)?;
+        Ok(wallet)
+    }
+
+    fn airdrop(&self, wallet: &Pubkey, lamports: u64) -> Result<()> {
+        let signature = self
+            .client()
+            .request_airdrop(wallet, lamports)
+            .context("Failed to request airdrop")?;
+        self.wait_for_confirmation(&signature)
+    }
+
+    /// Block until `signature` is confirmed, failing after 30 seconds
+    pub fn wait_for_confirmation(&self, signature: &Signature) -> Result<()> {
+        let client = self.client();
+        let deadline = Instant::now() + Duration::from_secs(30);
+
+        while Instant::now() < deadline {
+            if client.confirm_transaction(signature).unwrap_or(false) {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(250));
+        }
+
+        bail!("Transaction {} was not confirmed in time", signature)
+    }
+
+    fn wait_until_ready(&self, timeout: Duration) -> Result<()> {
+        let client = self.client();
+        let deadline = Instant::now() + timeout;
+
+        while Instant::now() < deadline {
+            if client.get_health().is_ok() && client.get_slot().unwrap_or(0) > 0 {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+
+        bail!(
+            "solana-test-validator did not become ready at {}",
+            self.rpc_url
+        )
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+fn deposit_program_path() -> PathBuf {
+    match std::env::var(DEPOSIT_PROGRAM_SO_ENV) {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../task4/target/deploy/solana_deposit_withdraw.so"),
+    }
+}
+
+fn free_port() -> Result<u16> {
+    let listener = TcpListener::bind("127.0.0.1:0").context("Failed to find a free port")?;
+    Ok(listener.local_addr()?.port())
+}
@@ -0,0 +1,852 @@
+//! End-to-end flows against a local `solana-test-validator`.
+//!
+//! These are ignored by default; run them with `cargo test -- --ignored` after building
+//! the task4 program with `cargo build-sbf --manifest-path task4/Cargo.toml`.
+
+use borsh::BorshDeserialize;
+use solana_common::{
+    ComputeBudget, ComputeBudgetConfig, SafetyConfig, SubmissionConfig, Submitter,
+};
+use solana_deposit_withdraw::{
+    config_address, instruction, processor::DepositError, pull_address, stats_address,
+    voucher_message, DepositAccount, ProgramConfig, ProgramStats, PullAuthorization,
+};
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_instruction, system_program,
+    transaction::{Transaction, TransactionError},
+};
+use solana_test_support::TestValidator;
+use solana_token_transfer::transfer::{send_transaction, SendPolicy};
+use std::str::FromStr;
+
+/// Start a validator whose deposit program is ready for deposits, its stats PDA initialized
+fn start_deployment() -> TestValidator {
+    let validator = TestValidator::start().unwrap();
+    let client = validator.client();
+    let payer = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::initialize_stats(
+            &validator.deposit_program_id(),
+            &payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+    validator
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore]
+async fn task2_transfer_reaches_destination() {
+    let validator = TestValidator::start().unwrap();
+    let client = validator.client();
+
+    let source = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let destination = Keypair::new().pubkey();
+
+    let (signature, _) = send_transaction(
+        &client,
+        (&source).into(),
+        &destination,
+        5_000_000,
+        None,
+        &ComputeBudget::default(),
+        &SendPolicy {
+            safety: SafetyConfig::default(),
+            submitter: Submitter::new(&SubmissionConfig::default()).unwrap(),
+            deadline: None,
+            fee_ceiling: None,
+        },
+    )
+    .await
+    .unwrap();
+    validator
+        .wait_for_confirmation(&Signature::from_str(&signature).unwrap())
+        .unwrap();
+
+    assert_eq!(client.get_balance(&destination).unwrap(), 5_000_000);
+}
+
+#[test]
+#[ignore]
+fn task4_deposit_and_withdraw() {
+    let validator = start_deployment();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let owner = validator.fund_new_wallet(2 * LAMPORTS_PER_SOL).unwrap();
+    let deposit_account = Keypair::new();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+
+    // Allocate the program-owned deposit account and fund it in one transaction
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                rent,
+                DepositAccount::LEN as u64,
+                &program_id,
+            ),
+            instruction::deposit(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                500_000_000,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, &deposit_account],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    let state = DepositAccount::try_from_slice(
+        &client.get_account_data(&deposit_account.pubkey()).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(state.owner, owner.pubkey());
+    assert_eq!(state.balance, 500_000_000);
+    assert_eq!((state.deposit_count, state.withdraw_count), (1, 0));
+    assert!(state.last_activity_ts > 0);
+    let deposited_at = state.last_activity_ts;
+
+    // Withdraw part of the deposit to a fresh wallet under a simulated compute unit limit
+    let destination = Keypair::new().pubkey();
+    let budget = ComputeBudget {
+        unit_price: None,
+        limit: Some(ComputeBudgetConfig::default()),
+    };
+    let instructions = budget
+        .apply(
+            &client,
+            &owner.pubkey(),
+            vec![instruction::withdraw(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                &destination,
+                200_000_000,
+            )],
+        )
+        .unwrap();
+    assert_eq!(instructions.len(), 2);
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&owner.pubkey()),
+        &[&owner],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    let state =
+        DepositAccount::unpack(&client.get_account_data(&deposit_account.pubkey()).unwrap())
+            .unwrap();
+    assert_eq!(state.balance, 300_000_000);
+    assert_eq!((state.deposit_count, state.withdraw_count), (1, 1));
+    assert!(state.last_activity_ts >= deposited_at);
+    assert_eq!(client.get_balance(&destination).unwrap(), 200_000_000);
+    assert_eq!(
+        client.get_balance(&deposit_account.pubkey()).unwrap(),
+        rent + 300_000_000
+    );
+}
+
+#[test]
+#[ignore]
+fn task4_withdraw_rejects_non_owner() {
+    let validator = start_deployment();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let owner = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let intruder = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let deposit_account = Keypair::new();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                rent,
+                DepositAccount::LEN as u64,
+                &program_id,
+            ),
+            instruction::deposit(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                100_000_000,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, &deposit_account],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::withdraw(
+            &program_id,
+            &intruder.pubkey(),
+            &deposit_account.pubkey(),
+            &intruder.pubkey(),
+            100_000_000,
+        )],
+        Some(&intruder.pubkey()),
+        &[&intruder],
+        client.get_latest_blockhash().unwrap(),
+    );
+    assert!(client.send_and_confirm_transaction(&transaction).is_err());
+}
+
+#[test]
+#[ignore]
+fn task4_rejects_dust_below_configured_minimums() {
+    let validator = start_deployment();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let owner = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let admin = validator.deposit_program_authority();
+    let deposit_account = Keypair::new();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+
+    // Only the upgrade authority may claim the config
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::initialize_config(
+            &program_id,
+            &owner.pubkey(),
+            0,
+            0,
+        )],
+        Some(&owner.pubkey()),
+        &[&owner],
+        client.get_latest_blockhash().unwrap(),
+    );
+    let error = client
+        .send_and_confirm_transaction(&transaction)
+        .unwrap_err();
+    assert_eq!(
+        error.get_transaction_error(),
+        Some(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(DepositError::NotUpgradeAuthority as u32)
+        ))
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize_config(&program_id, &admin.pubkey(), 1_000_000, 1_000_000),
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                rent,
+                DepositAccount::LEN as u64,
+                &program_id,
+            ),
+            instruction::deposit(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                10_000_000,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, admin, &deposit_account],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    let config = ProgramConfig::try_from_slice(
+        &client
+            .get_account_data(&config_address(&program_id).0)
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(config.admin, admin.pubkey());
+
+    let dust_deposit =
+        instruction::deposit(&program_id, &owner.pubkey(), &deposit_account.pubkey(), 500);
+    let dust_withdraw = instruction::withdraw(
+        &program_id,
+        &owner.pubkey(),
+        &deposit_account.pubkey(),
+        &owner.pubkey(),
+        500,
+    );
+    for dust in [dust_deposit, dust_withdraw] {
+        let transaction = Transaction::new_signed_with_payer(
+            &[dust],
+            Some(&owner.pubkey()),
+            &[&owner],
+            client.get_latest_blockhash().unwrap(),
+        );
+        assert!(client.send_and_confirm_transaction(&transaction).is_err());
+    }
+
+    // Emptying the account is allowed whatever the minimum
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::update_config(&program_id, &admin.pubkey(), 1_000_000, 20_000_000),
+            instruction::withdraw(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                &owner.pubkey(),
+                10_000_000,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, admin],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+    let state =
+        DepositAccount::unpack(&client.get_account_data(&deposit_account.pubkey()).unwrap())
+            .unwrap();
+    assert_eq!(state.balance, 0);
+}
+
+#[test]
+#[ignore]
+fn task4_recovery_key_withdraws_after_owner_inactivity() {
+    let validator = start_deployment();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let owner = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let recovery = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let deposit_account = Keypair::new();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                rent,
+                DepositAccount::LEN as u64,
+                &program_id,
+            ),
+            instruction::deposit(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                100_000_000,
+            ),
+            instruction::set_recovery(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                Some(recovery.pubkey()),
+                20,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, &deposit_account],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+    let state =
+        DepositAccount::unpack(&client.get_account_data(&deposit_account.pubkey()).unwrap())
+            .unwrap();
+    assert_eq!(state.recovery_key(), Some(recovery.pubkey()));
+
+    let recover = || {
+        Transaction::new_signed_with_payer(
+            &[instruction::withdraw(
+                &program_id,
+                &recovery.pubkey(),
+                &deposit_account.pubkey(),
+                &recovery.pubkey(),
+                100_000_000,
+            )],
+            Some(&recovery.pubkey()),
+            &[&recovery],
+            client.get_latest_blockhash().unwrap(),
+        )
+    };
+
+    // The owner was just active
+    assert!(client.send_and_confirm_transaction(&recover()).is_err());
+
+    while client.get_slot().unwrap() < state.last_owner_slot + state.recovery_after_slots {
+        std::thread::sleep(std::time::Duration::from_millis(400));
+    }
+    client.send_and_confirm_transaction(&recover()).unwrap();
+    let state =
+        DepositAccount::unpack(&client.get_account_data(&deposit_account.pubkey()).unwrap())
+            .unwrap();
+    assert_eq!(state.balance, 0);
+}
+
+#[test]
+#[ignore]
+fn task4_payee_pulls_up_to_authorized_amount() {
+    let validator = start_deployment();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let owner = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let payee = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let deposit_account = Keypair::new();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+    let expiry = client.get_block_time(client.get_slot().unwrap()).unwrap() + 3_600;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                rent,
+                DepositAccount::LEN as u64,
+                &program_id,
+            ),
+            instruction::deposit(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                100_000_000,
+            ),
+            instruction::authorize_pull(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                &payee.pubkey(),
+                50_000_000,
+                expiry,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, &deposit_account],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    let pull = |amount| {
+        Transaction::new_signed_with_payer(
+            &[instruction::pull(
+                &program_id,
+                &payee.pubkey(),
+                &deposit_account.pubkey(),
+                &payee.pubkey(),
+                amount,
+            )],
+            Some(&payee.pubkey()),
+            &[&payee],
+            client.get_latest_blockhash().unwrap(),
+        )
+    };
+    client
+        .send_and_confirm_transaction(&pull(30_000_000))
+        .unwrap();
+    assert!(client
+        .send_and_confirm_transaction(&pull(30_000_000))
+        .is_err());
+    client
+        .send_and_confirm_transaction(&pull(20_000_000))
+        .unwrap();
+
+    let authorization = PullAuthorization::try_from_slice(
+        &client
+            .get_account_data(
+                &pull_address(&program_id, &deposit_account.pubkey(), &payee.pubkey()).0,
+            )
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(authorization.remaining, 0);
+    let state =
+        DepositAccount::unpack(&client.get_account_data(&deposit_account.pubkey()).unwrap())
+            .unwrap();
+    assert_eq!(state.balance, 50_000_000);
+}
+
+#[test]
+#[ignore]
+fn task4_stats_track_value_locked() {
+    let validator = TestValidator::start().unwrap();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let owner = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let accounts = [Keypair::new(), Keypair::new()];
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+
+    // Deposits are refused until the stats count them
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &accounts[0].pubkey(),
+                rent,
+                DepositAccount::LEN as u64,
+                &program_id,
+            ),
+            instruction::deposit(
+                &program_id,
+                &owner.pubkey(),
+                &accounts[0].pubkey(),
+                100_000_000,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, &accounts[0]],
+        client.get_latest_blockhash().unwrap(),
+    );
+    assert_eq!(
+        client
+            .send_and_confirm_transaction(&transaction)
+            .unwrap_err()
+            .get_transaction_error(),
+        Some(TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(DepositError::StatsNotInitialized as u32)
+        ))
+    );
+
+    let mut instructions = vec![instruction::initialize_stats(&program_id, &owner.pubkey())];
+    for account in &accounts {
+        instructions.push(system_instruction::create_account(
+            &owner.pubkey(),
+            &account.pubkey(),
+            rent,
+            DepositAccount::LEN as u64,
+            &program_id,
+        ));
+        instructions.push(instruction::deposit(
+            &program_id,
+            &owner.pubkey(),
+            &account.pubkey(),
+            100_000_000,
+        ));
+    }
+    instructions.push(instruction::withdraw(
+        &program_id,
+        &owner.pubkey(),
+        &accounts[0].pubkey(),
+        &owner.pubkey(),
+        100_000_000,
+    ));
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&owner.pubkey()),
+        &[&owner, &accounts[0], &accounts[1]],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    let stats = ProgramStats::unpack(
+        &client
+            .get_account_data(&stats_address(&program_id).0)
+            .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(stats.total_deposited, 200_000_000);
+    assert_eq!(stats.total_withdrawn, 100_000_000);
+    assert_eq!(stats.active_accounts, 1);
+    assert_eq!(stats.total_value_locked(), 100_000_000);
+}
+
+#[test]
+#[ignore]
+fn task4_legacy_accounts_migrate_on_deposit() {
+    let validator = start_deployment();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let owner = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let deposit_account = Keypair::new();
+    let legacy_rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEGACY_LEN)
+        .unwrap();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+
+    // An account allocated with the owner + balance layout of earlier builds
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                legacy_rent,
+                DepositAccount::LEGACY_LEN as u64,
+                &program_id,
+            ),
+            instruction::deposit(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                100_000_000,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, &deposit_account],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    let account = client.get_account(&deposit_account.pubkey()).unwrap();
+    assert_eq!(account.data.len(), DepositAccount::LEN);
+    assert_eq!(account.lamports, rent + 100_000_000);
+    let state = DepositAccount::unpack(&account.data).unwrap();
+    assert_eq!(state.owner, owner.pubkey());
+    assert_eq!(state.balance, 100_000_000);
+    assert_eq!(state.deposit_count, 1);
+}
+
+#[test]
+#[ignore]
+fn task4_prefunded_pdas_still_initialize() {
+    let validator = TestValidator::start().unwrap();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let griefer = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let admin = validator.deposit_program_authority();
+    let config = config_address(&program_id).0;
+    let stats = stats_address(&program_id).0;
+
+    // Lamports sent to a PDA before it is created must not block its creation
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::transfer(&griefer.pubkey(), &config, 1_000_000),
+            system_instruction::transfer(&griefer.pubkey(), &stats, 1),
+        ],
+        Some(&griefer.pubkey()),
+        &[&griefer],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            instruction::initialize_config(&program_id, &admin.pubkey(), 1_000, 1_000),
+            instruction::initialize_stats(&program_id, &admin.pubkey()),
+        ],
+        Some(&admin.pubkey()),
+        &[admin],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    for (address, len) in [(config, ProgramConfig::LEN), (stats, ProgramStats::LEN)] {
+        let account = client.get_account(&address).unwrap();
+        assert_eq!(account.owner, program_id);
+        assert_eq!(account.data.len(), len);
+        let rent = client.get_minimum_balance_for_rent_exemption(len).unwrap();
+        assert!(account.lamports >= rent);
+    }
+}
+
+#[test]
+#[ignore]
+fn task4_anyone_redeems_owner_signed_voucher_once() {
+    let validator = start_deployment();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let owner = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let submitters = [
+        validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap(),
+        validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap(),
+    ];
+    let destination = Keypair::new();
+    let deposit_account = Keypair::new();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+    let expiry = client.get_block_time(client.get_slot().unwrap()).unwrap() + 3_600;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            system_instruction::create_account(
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                rent,
+                DepositAccount::LEN as u64,
+                &program_id,
+            ),
+            instruction::deposit(
+                &program_id,
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                100_000_000,
+            ),
+        ],
+        Some(&owner.pubkey()),
+        &[&owner, &deposit_account],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+    let owner_lamports = client.get_balance(&owner.pubkey()).unwrap();
+
+    // Signed off-chain; the owner sends nothing from here on
+    let message = voucher_message(
+        &program_id,
+        &deposit_account.pubkey(),
+        &destination.pubkey(),
+        40_000_000,
+        1,
+        expiry,
+    );
+    let signature: [u8; 64] = owner.sign_message(&message).into();
+
+    let redeem = |submitter: &Keypair, amount| {
+        Transaction::new_signed_with_payer(
+            &instruction::redeem_voucher(
+                &program_id,
+                &submitter.pubkey(),
+                &owner.pubkey(),
+                &deposit_account.pubkey(),
+                &destination.pubkey(),
+                amount,
+                1,
+                expiry,
+                &signature,
+            ),
+            Some(&submitter.pubkey()),
+            &[submitter],
+            client.get_latest_blockhash().unwrap(),
+        )
+    };
+    // Terms other than the signed ones fail the signature check
+    assert!(client
+        .send_and_confirm_transaction(&redeem(&submitters[0], 50_000_000))
+        .is_err());
+    client
+        .send_and_confirm_transaction(&redeem(&submitters[0], 40_000_000))
+        .unwrap();
+    // A different submitter makes a different transaction, rejected by the redemption marker
+    assert!(client
+        .send_and_confirm_transaction(&redeem(&submitters[1], 40_000_000))
+        .is_err());
+
+    assert_eq!(
+        client.get_balance(&destination.pubkey()).unwrap(),
+        40_000_000
+    );
+    assert_eq!(client.get_balance(&owner.pubkey()).unwrap(), owner_lamports);
+    let state =
+        DepositAccount::unpack(&client.get_account_data(&deposit_account.pubkey()).unwrap())
+            .unwrap();
+    assert_eq!(state.balance, 60_000_000);
+}
+
+#[test]
+#[ignore]
+fn task4_deposit_rejects_accounts_violating_expectations() {
+    let validator = start_deployment();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let funder = validator.fund_new_wallet(LAMPORTS_PER_SOL).unwrap();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+
+    // Create a fresh deposit account holding `lamports`, owned by `owner`, and deposit into it
+    // with the instruction as altered by `alter`; the deposit must fail with `expected`
+    let attempt = |lamports: u64,
+                   owner: &Pubkey,
+                   alter: &dyn Fn(&mut Instruction),
+                   expected: InstructionError| {
+        let deposit_account = Keypair::new();
+        let mut deposit = instruction::deposit(
+            &program_id,
+            &funder.pubkey(),
+            &deposit_account.pubkey(),
+            100_000_000,
+        );
+        alter(&mut deposit);
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::create_account(
+                    &funder.pubkey(),
+                    &deposit_account.pubkey(),
+                    lamports,
+                    DepositAccount::LEN as u64,
+                    owner,
+                ),
+                deposit,
+            ],
+            Some(&funder.pubkey()),
+            &[&funder, &deposit_account],
+            client.get_latest_blockhash().unwrap(),
+        );
+        let error = client
+            .send_and_confirm_transaction(&transaction)
+            .unwrap_err()
+            .get_transaction_error();
+        assert_eq!(error, Some(TransactionError::InstructionError(1, expected)));
+    };
+    let custom = |error: DepositError| InstructionError::Custom(error as u32);
+
+    // Too few accounts
+    attempt(
+        rent,
+        &program_id,
+        &|deposit| deposit.accounts.truncate(4),
+        InstructionError::NotEnoughAccountKeys,
+    );
+    // A funder that did not sign; the fee payer signs, so another wallet stands in
+    let bystander = Pubkey::new_unique();
+    attempt(
+        rent,
+        &program_id,
+        &|deposit| {
+            deposit.accounts[0].pubkey = bystander;
+            deposit.accounts[0].is_signer = false;
+        },
+        InstructionError::MissingRequiredSignature,
+    );
+    // A deposit account passed read-only
+    attempt(
+        rent,
+        &program_id,
+        &|deposit| deposit.accounts[1].is_writable = false,
+        custom(DepositError::AccountNotWritable),
+    );
+    // A deposit account the program does not own
+    attempt(
+        rent,
+        &system_program::ID,
+        &|_| {},
+        custom(DepositError::IncorrectProgramId),
+    );
+    // A deposit account below the rent-exempt minimum
+    attempt(
+        rent - 1,
+        &program_id,
+        &|_| {},
+        custom(DepositError::NotRentExempt),
+    );
+    // Another program in place of the system program
+    attempt(
+        rent,
+        &program_id,
+        &|deposit| deposit.accounts[2].pubkey = Pubkey::new_unique(),
+        custom(DepositError::UnexpectedAccount),
+    );
+}
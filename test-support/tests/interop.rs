@@ -0,0 +1,123 @@
+//! The workspace crates working as one system against a local `solana-test-validator`: task2's
+//! batch sender funds task4 deposit accounts and task1 reads the result.
+//!
+//! Built only with the `interop` feature; run it with `cargo test --features interop` after
+//! building the task4 program with `cargo build-sbf --manifest-path task4/Cargo.toml`.
+#![cfg(feature = "interop")]
+
+use solana_balance_checker::BalanceFetcher;
+use solana_client::nonblocking::rpc_client::RpcClient as NonblockingRpcClient;
+use solana_common::{ComputeBudget, SafetyConfig, SubmissionConfig, Submitter};
+use solana_deposit_withdraw::{instruction, DepositAccount};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::LAMPORTS_PER_SOL,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use solana_test_support::TestValidator;
+use solana_token_transfer::transfer::{pack_transfers, send_batch, sign_batch, SendPolicy};
+use std::str::FromStr;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn task2_batches_fund_task4_deposits_seen_by_task1() {
+    let validator = TestValidator::start().unwrap();
+    let client = validator.client();
+    let program_id = validator.deposit_program_id();
+
+    let funder = validator.fund_new_wallet(2 * LAMPORTS_PER_SOL).unwrap();
+    let deposit_accounts: Vec<Keypair> = (0..12).map(|_| Keypair::new()).collect();
+    let amounts: Vec<u64> = (1..=12).map(|n| n * 10_000_000).collect();
+    let rent = client
+        .get_minimum_balance_for_rent_exemption(DepositAccount::LEN)
+        .unwrap();
+
+    // Deposits need the stats PDA
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::initialize_stats(&program_id, &funder.pubkey())],
+        Some(&funder.pubkey()),
+        &[&funder],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    // The deposit accounts sign their own creation, so they are created up front
+    for accounts in deposit_accounts.chunks(4) {
+        let instructions: Vec<_> = accounts
+            .iter()
+            .map(|account| {
+                system_instruction::create_account(
+                    &funder.pubkey(),
+                    &account.pubkey(),
+                    rent,
+                    DepositAccount::LEN as u64,
+                    &program_id,
+                )
+            })
+            .collect();
+        let mut signers = vec![&funder];
+        signers.extend(accounts);
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&funder.pubkey()),
+            &signers,
+            client.get_latest_blockhash().unwrap(),
+        );
+        client.send_and_confirm_transaction(&transaction).unwrap();
+    }
+
+    // Every deposit is one transfer of the task2 batch sender
+    let transfers: Vec<Vec<_>> = deposit_accounts
+        .iter()
+        .zip(&amounts)
+        .map(|(account, &amount)| {
+            vec![instruction::deposit(
+                &program_id,
+                &funder.pubkey(),
+                &account.pubkey(),
+                amount,
+            )]
+        })
+        .collect();
+    let runs = pack_transfers(&funder.pubkey(), None, &transfers);
+    assert!(runs.len() < transfers.len(), "deposits were not batched");
+
+    let policy = SendPolicy {
+        safety: SafetyConfig::default(),
+        submitter: Submitter::new(&SubmissionConfig::default()).unwrap(),
+        deadline: None,
+        fee_ceiling: None,
+    };
+    for run in runs {
+        let lamports = amounts[run.clone()].iter().sum();
+        let batch = sign_batch(
+            &client,
+            (&funder).into(),
+            transfers[run].to_vec(),
+            &ComputeBudget::default(),
+        )
+        .unwrap();
+        let (signature, _) = send_batch(&client, &batch, lamports, &policy)
+            .await
+            .unwrap();
+        validator
+            .wait_for_confirmation(&Signature::from_str(&signature).unwrap())
+            .unwrap();
+    }
+
+    // task1 sees each account's rent reserve plus its deposit
+    let fetcher = BalanceFetcher::new(NonblockingRpcClient::new_with_commitment(
+        validator.rpc_url().to_string(),
+        CommitmentConfig::confirmed(),
+    ));
+    let addresses: Vec<_> = deposit_accounts.iter().map(Keypair::pubkey).collect();
+    let lamports = fetcher.fetch_lamports(&addresses).await;
+    for (address, amount) in addresses.iter().zip(&amounts) {
+        assert_eq!(lamports[address].as_ref().unwrap(), &(rent + amount));
+
+        let state = DepositAccount::unpack(&client.get_account_data(address).unwrap()).unwrap();
+        assert_eq!(state.owner, funder.pubkey());
+        assert_eq!(state.balance, *amount);
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_common::cli_config::CLI_CONFIG_ENV;
+use solana_deposit_withdraw::instruction;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
+    signature::{write_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+/// Lamports requested for the wallet paying for the program deployment
+const DEPLOYER_AIRDROP_LAMPORTS: u64 = 2_000_000_000;
+
+/// Lamports each generated config transfers per send
+const CONFIG_AMOUNT_LAMPORTS: u64 = 1_000;
+
+/// solana-cli style config pointing every binary at the bootstrapped cluster
+#[derive(Debug, Serialize)]
+struct CliConfigFile<'a> {
+    json_rpc_url: &'a str,
+    keypair_path: String,
+    commitment: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Task1Config<'a> {
+    wallets: &'a [String],
+}
+
+#[derive(Debug, Serialize)]
+struct SourceWallet {
+    address: String,
+    secret_key: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Task2Config<'a> {
+    source_wallets: Vec<SourceWallet>,
+    destination_wallets: &'a [String],
+    amount_lamports: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Task3Config<'a> {
+    source_wallet: SourceWallet,
+    destination_wallet: &'a str,
+    amount_lamports: u64,
+}
+
+/// Everything `bootstrap` produced
+#[derive(Debug)]
+pub struct Environment {
+    pub wallets: Vec<String>,
+    pub keypair_files: Vec<PathBuf>,
+    pub config_files: Vec<PathBuf>,
+    pub program_id: Option<Pubkey>,
+}
+
+/// Generate `count` wallets in `out_dir`, fund each with `airdrop_lamports` and write
+/// task1/task2/task3 configs using them. The first half of the wallets are task2 sources,
+/// the rest destinations; task3 sends from the first wallet to the last one. A solana-cli
+/// config for `rpc_url` with the first wallet as default keypair is written alongside.
+pub fn create_wallets(
+    client: &RpcClient,
+    rpc_url: &str,
+    count: usize,
+    airdrop_lamports: u64,
+    out_dir: &Path,
+) -> Result<Environment> {
+    if count < 2 {
+        bail!("Bootstrap needs at least 2 wallets (a source and a destination)");
+    }
+    std::fs::create_dir_all(out_dir).context("Failed to create output directory")?;
+
+    let mut keypairs = Vec::with_capacity(count);
+    let mut keypair_files = Vec::with_capacity(count);
+    for index in 0..count {
+        let keypair = Keypair::new();
+        let path = out_dir.join(format!("wallet-{}.json", index));
+        write_keypair_file(&keypair, &path)
+            .map_err(|e| anyhow!("Failed to write keypair file {}: {}", path.display(), e))?;
+
+        airdrop(client, &keypair.pubkey(), airdrop_lamports)?;
+        println!("Funded wallet {} ({})", index, keypair.pubkey());
+
+        keypairs.push(keypair);
+        keypair_files.push(path);
+    }
+
+    let wallets: Vec<String> = keypairs.iter().map(|k| k.pubkey().to_string()).collect();
+    let source_wallet = |keypair: &Keypair| SourceWallet {
+        address: keypair.pubkey().to_string(),
+        secret_key: bs58::encode(keypair.to_bytes()).into_string(),
+    };
+    let split = count / 2;
+
+    let config_files = vec![
+        write_yaml(
+            &out_dir.join("cli-config.yml"),
+            &CliConfigFile {
+                json_rpc_url: rpc_url,
+                keypair_path: absolute(&keypair_files[0]).display().to_string(),
+                commitment: "confirmed",
+            },
+        )?,
+        write_yaml(
+            &out_dir.join("task1.yaml"),
+            &Task1Config { wallets: &wallets },
+        )?,
+        write_yaml(
+            &out_dir.join("task2.yaml"),
+            &Task2Config {
+                source_wallets: keypairs[..split].iter().map(source_wallet).collect(),
+                destination_wallets: &wallets[split..],
+                amount_lamports: CONFIG_AMOUNT_LAMPORTS,
+            },
+        )?,
+        write_yaml(
+            &out_dir.join("task3.yaml"),
+            &Task3Config {
+                source_wallet: source_wallet(&keypairs[0]),
+                destination_wallet: &wallets[count - 1],
+                amount_lamports: CONFIG_AMOUNT_LAMPORTS,
+            },
+        )?,
+    ];
+
+    Ok(Environment {
+        wallets,
+        keypair_files,
+        config_files,
+        program_id: None,
+    })
+}
+
+/// Deploy the task4 program with `solana program deploy`, paid by a freshly funded
+/// deployer key written to `out_dir`, and initialize its stats so it accepts deposits.
+/// Returns the program id.
+pub fn deploy_program(
+    client: &RpcClient,
+    rpc_url: &str,
+    program_so: &Path,
+    out_dir: &Path,
+) -> Result<Pubkey> {
+    if !program_so.exists() {
+        bail!(
+            "Program binary {} not found; build it with `cargo build-sbf --manifest-path task4/Cargo.toml`",
+            program_so.display()
+        );
+    }
+
+    let deployer = Keypair::new();
+    let deployer_file = out_dir.join("deployer.json");
+    write_keypair_file(&deployer, &deployer_file)
+        .map_err(|e| anyhow!("Failed to write keypair file {}: {}", deployer_file.display(), e))?;
+    airdrop(client, &deployer.pubkey(), DEPLOYER_AIRDROP_LAMPORTS)?;
+
+    let program = Keypair::new();
+    let program_file = out_dir.join("deposit-program.json");
+    write_keypair_file(&program, &program_file)
+        .map_err(|e| anyhow!("Failed to write keypair file {}: {}", program_file.display(), e))?;
+
+    let output = Command::new("solana")
+        .arg("program")
+        .arg("deploy")
+        .arg("--url")
+        .arg(rpc_url)
+        .arg("--keypair")
+        .arg(&deployer_file)
+        .arg("--program-id")
+        .arg(&program_file)
+        .arg(program_so)
+        .output()
+        .context("Failed to run `solana program deploy` (is the solana CLI installed?)")?;
+
+    if !output.status.success() {
+        bail!(
+            "solana program deploy failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    // Output looks like "Program Id: <base58>"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let program_id = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Program Id:"))
+        .map(str::trim)
+        .map(Pubkey::from_str)
+        .transpose()
+        .context("Invalid program id in deploy output")?
+        .unwrap_or_else(|| program.pubkey());
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction::initialize_stats(&program_id, &deployer.pubkey())],
+        Some(&deployer.pubkey()),
+        &[&deployer],
+        client.get_latest_blockhash()?,
+    );
+    client
+        .send_and_confirm_transaction(&transaction)
+        .context("Failed to initialize the deposit program stats")?;
+
+    Ok(program_id)
+}
+
+/// Print the commands to run against the generated environment
+pub fn print_quickstart(env: &Environment, rpc_url: &str, out_dir: &Path) {
+    println!("\nEnvironment ready in {}", out_dir.display());
+    println!("Wallets:");
+    for (wallet, file) in env.wallets.iter().zip(&env.keypair_files) {
+        println!("  {}  {}", wallet, file.display());
+    }
+    println!("Configs:");
+    for file in &env.config_files {
+        println!("  {}", file.display());
+    }
+    if let Some(program_id) = env.program_id {
+        println!("Deposit program: {}", program_id);
+    }
+
+    println!("\nQuickstart ({}):", rpc_url);
+    println!(
+        "  export {}={}",
+        CLI_CONFIG_ENV,
+        absolute(&out_dir.join("cli-config.yml")).display()
+    );
+    println!(
+        "  (cd task1 && cargo run -- --config {})",
+        absolute(&out_dir.join("task1.yaml")).display()
+    );
+    println!(
+        "  (cd task2 && cargo run -- --config {})",
+        absolute(&out_dir.join("task2.yaml")).display()
+    );
+    println!(
+        "  (cd task3 && cargo run -- --config {})",
+        absolute(&out_dir.join("task3.yaml")).display()
+    );
+    if let Some(program_id) = env.program_id {
+        println!(
+            "  (cd toolkit && cargo run -- decode-tx <signature> --deposit-program {})",
+            program_id
+        );
+    }
+}
+
+fn airdrop(client: &RpcClient, pubkey: &Pubkey, lamports: u64) -> Result<()> {
+    let signature = client
+        .request_airdrop(pubkey, lamports)
+        .with_context(|| format!("Airdrop to {} failed (devnet faucets are rate limited)", pubkey))?;
+    client
+        .poll_for_signature_with_commitment(&signature, CommitmentConfig::confirmed())
+        .with_context(|| format!("Airdrop to {} was not confirmed", pubkey))?;
+    Ok(())
+}
+
+fn write_yaml<T: Serialize>(path: &Path, value: &T) -> Result<PathBuf> {
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    serde_yaml::to_writer(file, value).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path.to_path_buf())
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
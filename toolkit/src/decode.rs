@@ -0,0 +1,475 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshDeserialize;
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
+use solana_common::AddressBook;
+use solana_deposit_withdraw::DepositInstruction;
+use solana_sdk::{
+    borsh1::try_from_slice_unchecked,
+    commitment_config::CommitmentConfig,
+    compute_budget::{self, ComputeBudgetInstruction},
+    native_token::lamports_to_sol,
+    pubkey::Pubkey,
+    signature::Signature,
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::VersionedTransaction,
+};
+use solana_transaction_status::{
+    UiLoadedAddresses, UiTransactionEncoding, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+use spl_token::instruction::TokenInstruction;
+use std::str::FromStr;
+
+/// SPL Memo program ids (v2 and the legacy v1 deployment)
+const MEMO_PROGRAM_IDS: [&str; 2] = [
+    "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+    "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo",
+];
+
+/// SPL Token-2022 program id (shares the base instruction layout with SPL Token)
+const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+
+/// A transaction together with its execution status when it was fetched from the chain
+pub struct DecodedTransaction {
+    pub transaction: VersionedTransaction,
+    pub slot: Option<u64>,
+    pub meta: Option<UiTransactionStatusMeta>,
+}
+
+/// Load a transaction either by signature (fetched over RPC) or from a base64-encoded blob
+pub fn load_transaction(client: &RpcClient, input: &str) -> Result<DecodedTransaction> {
+    if let Ok(signature) = Signature::from_str(input) {
+        return fetch_transaction(client, &signature);
+    }
+
+    let bytes = STANDARD
+        .decode(input.trim())
+        .context("Input is neither a transaction signature nor base64")?;
+
+    let transaction: VersionedTransaction =
+        bincode::deserialize(&bytes).context("Failed to deserialize transaction")?;
+
+    Ok(DecodedTransaction {
+        transaction,
+        slot: None,
+        meta: None,
+    })
+}
+
+pub fn fetch_transaction(client: &RpcClient, signature: &Signature) -> Result<DecodedTransaction> {
+    let config = RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::Base64),
+        commitment: Some(CommitmentConfig::confirmed()),
+        max_supported_transaction_version: Some(0),
+    };
+
+    let confirmed = client
+        .get_transaction_with_config(signature, config)
+        .context("Failed to fetch transaction")?;
+
+    let transaction = confirmed
+        .transaction
+        .transaction
+        .decode()
+        .context("Failed to decode transaction returned by RPC")?;
+
+    Ok(DecodedTransaction {
+        transaction,
+        slot: Some(confirmed.slot),
+        meta: confirmed.transaction.meta,
+    })
+}
+
+/// Print a human-readable breakdown of the transaction
+pub fn print_transaction(
+    decoded: &DecodedTransaction,
+    deposit_program: Option<&Pubkey>,
+    book: &AddressBook,
+) {
+    let transaction = &decoded.transaction;
+    let keys = account_keys(decoded);
+
+    if let Some(signature) = transaction.signatures.first() {
+        println!("Transaction: {}", signature);
+    }
+    if let Some(slot) = decoded.slot {
+        println!("Slot: {}", slot);
+    }
+
+    if let Some(meta) = &decoded.meta {
+        match &meta.err {
+            Some(err) => println!("Status: Failed ({:?})", err),
+            None => println!("Status: Success"),
+        }
+        println!("Fee: {} lamports", meta.fee);
+    } else {
+        println!("Status: Not submitted (decoded offline)");
+    }
+
+    println!(
+        "Signers: {}",
+        transaction.message.header().num_required_signatures
+    );
+    println!(
+        "Recent blockhash: {}",
+        transaction.message.recent_blockhash()
+    );
+
+    println!("\nInstructions:");
+    for (index, instruction) in transaction.message.instructions().iter().enumerate() {
+        let program_id = keys
+            .get(instruction.program_id_index as usize)
+            .copied()
+            .unwrap_or_default();
+        let accounts: Vec<Pubkey> = instruction
+            .accounts
+            .iter()
+            .map(|i| keys.get(*i as usize).copied().unwrap_or_default())
+            .collect();
+
+        let program = KnownProgram::resolve(&program_id, deposit_program);
+
+        println!("  #{} {} ({})", index, program.name(), program_id);
+        println!(
+            "     {}",
+            describe_instruction(program, &instruction.data, &accounts, book)
+        );
+    }
+
+    let Some(meta) = &decoded.meta else {
+        return;
+    };
+
+    println!("\nBalance changes:");
+    for (index, (pre, post)) in meta
+        .pre_balances
+        .iter()
+        .zip(&meta.post_balances)
+        .enumerate()
+    {
+        if pre == post {
+            continue;
+        }
+        let address = keys
+            .get(index)
+            .map(|k| book.display(&k.to_string()))
+            .unwrap_or_default();
+        let delta = *post as i128 - *pre as i128;
+        println!(
+            "  {:<44} {} SOL -> {} SOL ({:+} lamports)",
+            address,
+            lamports_to_sol(*pre),
+            lamports_to_sol(*post),
+            delta
+        );
+    }
+
+    let pre_tokens: Option<&Vec<UiTransactionTokenBalance>> =
+        meta.pre_token_balances.as_ref().into();
+    let post_tokens: Option<&Vec<UiTransactionTokenBalance>> =
+        meta.post_token_balances.as_ref().into();
+    if let Some(post_tokens) = post_tokens.filter(|balances| !balances.is_empty()) {
+        println!("\nToken balance changes:");
+        for post in post_tokens {
+            let pre_amount = pre_tokens
+                .and_then(|balances| {
+                    balances
+                        .iter()
+                        .find(|b| b.account_index == post.account_index)
+                })
+                .and_then(|b| b.ui_token_amount.ui_amount)
+                .unwrap_or(0.0);
+            let post_amount = post.ui_token_amount.ui_amount.unwrap_or(0.0);
+            if pre_amount == post_amount {
+                continue;
+            }
+            let address = keys
+                .get(post.account_index as usize)
+                .map(|k| book.display(&k.to_string()))
+                .unwrap_or_default();
+            println!(
+                "  {:<44} mint {} {} -> {}",
+                address, post.mint, pre_amount, post_amount
+            );
+        }
+    }
+
+    let logs: Option<&Vec<String>> = meta.log_messages.as_ref().into();
+    if let Some(logs) = logs {
+        println!("\nLogs:");
+        for line in logs {
+            println!("  {}", line);
+        }
+    }
+}
+
+/// A lamport movement made by a System program instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SolTransfer {
+    pub from: Pubkey,
+    pub to: Pubkey,
+    pub lamports: u64,
+}
+
+/// System program transfers among the transaction's top-level instructions
+pub fn sol_transfers(decoded: &DecodedTransaction) -> Vec<SolTransfer> {
+    let keys = account_keys(decoded);
+    let message = &decoded.transaction.message;
+    let key = |index: Option<&u8>| index.and_then(|i| keys.get(*i as usize)).copied();
+
+    message
+        .instructions()
+        .iter()
+        .filter(|ix| key(Some(&ix.program_id_index)) == Some(system_program::id()))
+        .filter_map(|ix| {
+            let (from, to, lamports) = match bincode::deserialize(&ix.data).ok()? {
+                SystemInstruction::Transfer { lamports } => {
+                    (ix.accounts.first(), ix.accounts.get(1), lamports)
+                }
+                SystemInstruction::TransferWithSeed { lamports, .. } => {
+                    (ix.accounts.first(), ix.accounts.get(2), lamports)
+                }
+                _ => return None,
+            };
+
+            Some(SolTransfer {
+                from: key(from)?,
+                to: key(to)?,
+                lamports,
+            })
+        })
+        .collect()
+}
+
+/// Static account keys followed by any addresses loaded from lookup tables
+fn account_keys(decoded: &DecodedTransaction) -> Vec<Pubkey> {
+    let mut keys = decoded.transaction.message.static_account_keys().to_vec();
+
+    let loaded: Option<&UiLoadedAddresses> = decoded
+        .meta
+        .as_ref()
+        .and_then(|meta| meta.loaded_addresses.as_ref().into());
+    if let Some(loaded) = loaded {
+        for address in loaded.writable.iter().chain(&loaded.readonly) {
+            if let Ok(pubkey) = Pubkey::from_str(address) {
+                keys.push(pubkey);
+            }
+        }
+    }
+
+    keys
+}
+
+/// Programs the decoder knows how to interpret
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum KnownProgram {
+    System,
+    Token,
+    Token2022,
+    Memo,
+    ComputeBudget,
+    Deposit,
+    Unknown,
+}
+
+impl KnownProgram {
+    fn resolve(program_id: &Pubkey, deposit_program: Option<&Pubkey>) -> Self {
+        let id = program_id.to_string();
+        if *program_id == system_program::id() {
+            Self::System
+        } else if *program_id == spl_token::id() {
+            Self::Token
+        } else if id == TOKEN_2022_PROGRAM_ID {
+            Self::Token2022
+        } else if MEMO_PROGRAM_IDS.contains(&id.as_str()) {
+            Self::Memo
+        } else if *program_id == compute_budget::id() {
+            Self::ComputeBudget
+        } else if Some(program_id) == deposit_program {
+            Self::Deposit
+        } else {
+            Self::Unknown
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::System => "System Program",
+            Self::Token => "SPL Token",
+            Self::Token2022 => "SPL Token-2022",
+            Self::Memo => "SPL Memo",
+            Self::ComputeBudget => "Compute Budget",
+            Self::Deposit => "Deposit Program",
+            Self::Unknown => "Unknown Program",
+        }
+    }
+}
+
+fn describe_instruction(
+    program: KnownProgram,
+    data: &[u8],
+    accounts: &[Pubkey],
+    book: &AddressBook,
+) -> String {
+    let account = |index: usize| {
+        accounts
+            .get(index)
+            .map(|k| book.display(&k.to_string()))
+            .unwrap_or_else(|| "?".to_string())
+    };
+
+    match program {
+        KnownProgram::System => match bincode::deserialize::<SystemInstruction>(data) {
+            Ok(SystemInstruction::Transfer { lamports }) => format!(
+                "Transfer {} lamports ({} SOL) from {} to {}",
+                lamports,
+                lamports_to_sol(lamports),
+                account(0),
+                account(1)
+            ),
+            Ok(SystemInstruction::CreateAccount {
+                lamports,
+                space,
+                owner,
+            }) => format!(
+                "Create account {} funded by {} with {} lamports, {} bytes, owner {}",
+                account(1),
+                account(0),
+                lamports,
+                space,
+                owner
+            ),
+            Ok(SystemInstruction::AdvanceNonceAccount) => format!(
+                "Advance nonce account {} (authority {})",
+                account(0),
+                account(2)
+            ),
+            Ok(other) => format!("{:?}", other),
+            Err(_) => "Unrecognized system instruction".to_string(),
+        },
+        KnownProgram::Token | KnownProgram::Token2022 => match TokenInstruction::unpack(data) {
+            Ok(TokenInstruction::Transfer { amount }) => format!(
+                "Transfer {} base units from {} to {} (authority {})",
+                amount,
+                account(0),
+                account(1),
+                account(2)
+            ),
+            Ok(TokenInstruction::TransferChecked { amount, decimals }) => format!(
+                "Transfer {} base units ({} decimals) of mint {} from {} to {} (authority {})",
+                amount,
+                decimals,
+                account(1),
+                account(0),
+                account(2),
+                account(3)
+            ),
+            Ok(other) => format!("{:?}", other),
+            Err(_) => "Unrecognized token instruction".to_string(),
+        },
+        KnownProgram::Memo => format!("Memo: \"{}\"", String::from_utf8_lossy(data)),
+        KnownProgram::ComputeBudget => {
+            match try_from_slice_unchecked::<ComputeBudgetInstruction>(data) {
+                Ok(ComputeBudgetInstruction::SetComputeUnitLimit(units)) => {
+                    format!("Set compute unit limit to {}", units)
+                }
+                Ok(ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports)) => {
+                    format!(
+                        "Set compute unit price to {} micro-lamports",
+                        micro_lamports
+                    )
+                }
+                Ok(other) => format!("{:?}", other),
+                Err(_) => "Unrecognized compute budget instruction".to_string(),
+            }
+        }
+        KnownProgram::Deposit => match DepositInstruction::try_from_slice(data) {
+            Ok(DepositInstruction::Deposit { amount }) => format!(
+                "Deposit {} lamports from {} into {}",
+                amount,
+                account(0),
+                account(1)
+            ),
+            Ok(DepositInstruction::Withdraw { amount }) => format!(
+                "Withdraw {} lamports from {} to {} (owner {})",
+                amount,
+                account(1),
+                account(2),
+                account(0)
+            ),
+            Ok(DepositInstruction::InitializeConfig {
+                min_deposit,
+                min_withdraw,
+            }) => format!(
+                "Initialize config {} (admin {}): min deposit {} lamports, min withdraw {} lamports",
+                account(1),
+                account(0),
+                min_deposit,
+                min_withdraw
+            ),
+            Ok(DepositInstruction::UpdateConfig {
+                min_deposit,
+                min_withdraw,
+            }) => format!(
+                "Update config {} (admin {}): min deposit {} lamports, min withdraw {} lamports",
+                account(1),
+                account(0),
+                min_deposit,
+                min_withdraw
+            ),
+            Ok(DepositInstruction::SetRecovery {
+                recovery: Some(recovery),
+                inactivity_slots,
+            }) => format!(
+                "Set recovery key of {} to {} after {} inactive slots (owner {})",
+                account(1),
+                recovery,
+                inactivity_slots,
+                account(0)
+            ),
+            Ok(DepositInstruction::SetRecovery { recovery: None, .. }) => format!(
+                "Clear recovery key of {} (owner {})",
+                account(1),
+                account(0)
+            ),
+            Ok(DepositInstruction::AuthorizePull {
+                payee,
+                amount,
+                expiry,
+            }) => format!(
+                "Authorize {} to pull up to {} lamports from {} until {} (owner {})",
+                payee,
+                amount,
+                account(1),
+                expiry,
+                account(0)
+            ),
+            Ok(DepositInstruction::Pull { amount }) => format!(
+                "Pull {} lamports from {} to {} (payee {})",
+                amount,
+                account(1),
+                account(2),
+                account(0)
+            ),
+            Ok(DepositInstruction::InitializeStats) => format!(
+                "Initialize stats {} (payer {})",
+                account(1),
+                account(0)
+            ),
+            Ok(DepositInstruction::RedeemVoucher { amount, id, expiry }) => format!(
+                "Redeem voucher {} of {}: {} lamports to {}, valid until {} (submitter {})",
+                id,
+                account(1),
+                amount,
+                account(2),
+                expiry,
+                account(0)
+            ),
+            Err(_) => "Unrecognized deposit program instruction".to_string(),
+        },
+        KnownProgram::Unknown => {
+            format!("{} bytes of data, {} accounts", data.len(), accounts.len())
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::{Context, Result};
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, instruction::Instruction, message::Message,
+    program_pack::Pack, pubkey::Pubkey, transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+
+/// Compute units assumed when simulation is unavailable (covers a handful of simple instructions)
+const FALLBACK_COMPUTE_UNITS: u64 = 1_000;
+
+/// Expected cost of landing a single transaction
+#[derive(Debug, Clone, Copy)]
+pub struct FeeEstimate {
+    /// Signature fee returned by getFeeForMessage
+    pub base_fee: u64,
+    /// Compute units consumed in simulation
+    pub compute_units: u64,
+    /// Median recent prioritization fee for the written accounts, in micro-lamports per CU
+    pub priority_fee_per_cu: u64,
+    /// Rent for accounts the transaction creates (e.g. a missing destination ATA)
+    pub rent: u64,
+}
+
+impl FeeEstimate {
+    /// Priority fee in lamports for the simulated compute units
+    pub fn priority_fee(&self) -> u64 {
+        (self.compute_units * self.priority_fee_per_cu).div_ceil(1_000_000)
+    }
+
+    /// Total lamports spent by the payer on top of the transferred amount
+    pub fn total(&self) -> u64 {
+        self.base_fee + self.priority_fee() + self.rent
+    }
+}
+
+/// Build the instructions of an SPL token transfer, creating the destination ATA when missing.
+/// Returns the instructions and the rent the new ATA would require.
+pub fn token_transfer_instructions(
+    client: &RpcClient,
+    owner: &Pubkey,
+    recipient: &Pubkey,
+    mint: &Pubkey,
+    amount: u64,
+) -> Result<(Vec<Instruction>, u64)> {
+    let mint_account = client
+        .get_account(mint)
+        .context("Failed to fetch mint account")?;
+    let mint_state = spl_token::state::Mint::unpack(&mint_account.data)
+        .context("Account is not a token mint")?;
+
+    let source_ata = get_associated_token_address(owner, mint);
+    let destination_ata = get_associated_token_address(recipient, mint);
+
+    let mut instructions = Vec::new();
+    let mut rent = 0;
+
+    let destination_exists = client
+        .get_account_with_commitment(&destination_ata, CommitmentConfig::confirmed())
+        .context("Failed to look up destination token account")?
+        .value
+        .is_some();
+    if !destination_exists {
+        instructions.push(create_associated_token_account_idempotent(
+            owner,
+            recipient,
+            mint,
+            &spl_token::id(),
+        ));
+        rent = client
+            .get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)
+            .context("Failed to get rent exemption for token account")?;
+    }
+
+    instructions.push(
+        spl_token::instruction::transfer_checked(
+            &spl_token::id(),
+            &source_ata,
+            mint,
+            &destination_ata,
+            owner,
+            &[],
+            amount,
+            mint_state.decimals,
+        )
+        .context("Failed to build token transfer instruction")?,
+    );
+
+    Ok((instructions, rent))
+}
+
+/// Estimate the cost of a transaction made of `instructions` paid by `payer`
+pub fn estimate(
+    client: &RpcClient,
+    payer: &Pubkey,
+    instructions: &[Instruction],
+) -> Result<FeeEstimate> {
+    let blockhash = client
+        .get_latest_blockhash()
+        .context("Failed to get recent blockhash")?;
+    let message = Message::new_with_blockhash(instructions, Some(payer), &blockhash);
+
+    let base_fee = client
+        .get_fee_for_message(&message)
+        .context("Failed to get fee for message")?;
+
+    // Simulate unsigned to learn the compute units the transaction would consume
+    let simulation = client.simulate_transaction_with_config(
+        &Transaction::new_unsigned(message.clone()),
+        RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            commitment: Some(CommitmentConfig::confirmed()),
+            ..RpcSimulateTransactionConfig::default()
+        },
+    );
+    let compute_units = match simulation {
+        Ok(response) => response
+            .value
+            .units_consumed
+            .unwrap_or(FALLBACK_COMPUTE_UNITS),
+        Err(e) => {
+            eprintln!(
+                "Simulation failed, assuming {} CU: {}",
+                FALLBACK_COMPUTE_UNITS, e
+            );
+            FALLBACK_COMPUTE_UNITS
+        }
+    };
+
+    let writable_accounts: Vec<Pubkey> = message
+        .account_keys
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| message.is_writable(*index))
+        .map(|(_, key)| *key)
+        .collect();
+
+    let mut recent_fees: Vec<u64> = client
+        .get_recent_prioritization_fees(&writable_accounts)
+        .context("Failed to get recent prioritization fees")?
+        .into_iter()
+        .map(|fee| fee.prioritization_fee)
+        .collect();
+    recent_fees.sort_unstable();
+    let priority_fee_per_cu = recent_fees.get(recent_fees.len() / 2).copied().unwrap_or(0);
+
+    Ok(FeeEstimate {
+        base_fee,
+        compute_units,
+        priority_fee_per_cu,
+        rent: 0,
+    })
+}
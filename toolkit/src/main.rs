@@ -0,0 +1,893 @@
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+use solana_common::{audit, receipt, rpc, AddressBook, CliConfig, RpcLimits};
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+    signature::{read_keypair_file, write_keypair_file, Keypair, Signer},
+};
+use solana_token_transfer::{
+    manifest::SignedManifest,
+    token::{TokenMint, TokenTransfers},
+    transfer::transfer_instructions,
+    Config as TransferConfig, Plan,
+};
+use std::{collections::HashMap, fs, path::Path};
+use vault::Vault;
+
+mod bootstrap;
+mod decode;
+mod fees;
+mod reconcile;
+mod rotate;
+mod simulate;
+mod snapshot;
+mod vault;
+
+const DEFAULT_RPC_URL: &str = "https://api.devnet.solana.com";
+
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Solana RPC endpoint [default: solana-cli config, else devnet]
+    #[arg(short, long, global = true)]
+    rpc_url: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Fetch or parse a transaction and print a human-readable breakdown
+    DecodeTx {
+        /// Transaction signature or base64-encoded serialized transaction
+        input: String,
+
+        /// Program id of the deployed task4 deposit program
+        #[arg(long)]
+        deposit_program: Option<String>,
+    },
+
+    /// Estimate the cost of a transfer, or of every transfer in a task2 batch
+    EstimateFee {
+        /// task2 config file describing the batch to estimate
+        #[arg(long, conflicts_with_all = ["from", "to"])]
+        config: Option<String>,
+
+        /// Source wallet (alias or address) of a single transfer
+        #[arg(long, requires = "to")]
+        from: Option<String>,
+
+        /// Destination wallet (alias or address) of a single transfer
+        #[arg(long, requires = "from")]
+        to: Option<String>,
+
+        /// Amount in lamports, or in token base units with --mint (defaults to the config amount)
+        #[arg(long)]
+        amount: Option<u64>,
+
+        /// Estimate an SPL token transfer of this mint instead of a SOL transfer
+        #[arg(long)]
+        mint: Option<String>,
+    },
+
+    /// Convert a finance CSV (source, destination, amount, memo) into a task2 plan
+    ImportPlan {
+        /// CSV file with a header row; `amount` is in SOL, or use an `amount_lamports` column
+        csv: String,
+
+        /// Where to write the YAML plan for `task2 --plan`
+        #[arg(long, default_value = "plan.yaml")]
+        output: String,
+
+        /// task2 config to check that every source wallet has a configured key
+        #[arg(long)]
+        config: Option<String>,
+    },
+
+    /// Replace wallets with fresh keypairs, sweeping all SOL, SPL Token and Token-2022 balances to them
+    RotateKeys {
+        /// task2 config whose source wallets are rotated
+        #[arg(long)]
+        config: Option<String>,
+
+        /// Keypair file of a wallet to rotate (repeatable)
+        #[arg(long = "keypair")]
+        keypairs: Vec<String>,
+
+        /// Encrypted vault the new keys are added to, created if missing
+        #[arg(long, default_value = "keys.vault")]
+        vault: String,
+
+        /// Environment variable holding the vault passphrase
+        #[arg(long, default_value = vault::PASSPHRASE_ENV)]
+        passphrase_env: String,
+
+        /// Write the new keys as UNENCRYPTED keypair files to this directory instead of the vault
+        #[arg(long, conflicts_with = "vault")]
+        plaintext_out_dir: Option<String>,
+
+        /// Where to write the signed rotation report
+        #[arg(long, default_value = "rotation-report.json")]
+        report: String,
+    },
+
+    /// Write one key of the rotation vault to a keypair file, e.g. for a task2 config
+    VaultExport {
+        /// Address (or alias) of the key to export
+        address: String,
+
+        /// Keypair file to write
+        #[arg(long)]
+        output: String,
+
+        /// Encrypted vault holding the key
+        #[arg(long, default_value = "keys.vault")]
+        vault: String,
+
+        /// Environment variable holding the vault passphrase
+        #[arg(long, default_value = vault::PASSPHRASE_ENV)]
+        passphrase_env: String,
+    },
+
+    /// Verify the hash chain and signatures of a task2/task3 audit log
+    VerifyAudit {
+        /// Audit log file
+        log: String,
+
+        /// Require every entry to be signed by this audit key (alias or address)
+        #[arg(long)]
+        auditor: Option<String>,
+    },
+
+    /// Verify the hashes and operator signatures of a task3 action receipt log
+    VerifyReceipts {
+        /// Receipt log file
+        log: String,
+
+        /// Require every receipt to be signed by this operator key (alias or address)
+        #[arg(long)]
+        operator: Option<String>,
+    },
+
+    /// Verify a signed task2 run manifest and, with --audit, that the audited transfers
+    /// of the run match what the manifest approved
+    VerifyManifest {
+        /// Signed manifest file written by task2
+        manifest: String,
+
+        /// Require the manifest to be signed by this operator key (alias or address)
+        #[arg(long)]
+        operator: Option<String>,
+
+        /// Audit log the run's transfers were recorded in
+        #[arg(long)]
+        audit: Option<String>,
+    },
+
+    /// Cross-check an audit log against on-chain history and flag missing, failed,
+    /// mismatched, duplicated or unexpected movements
+    Reconcile {
+        /// Audit log written by task2/task3
+        log: String,
+
+        /// Maximum signatures fetched per wallet when scanning history
+        #[arg(long, default_value_t = 1000)]
+        history_limit: usize,
+    },
+
+    /// Export every account of a deployed task4 program as a canonical, hashed JSON or CSV
+    /// snapshot, e.g. for audits or to plan a migration
+    Snapshot {
+        /// Program id of the deployed task4 deposit program (alias or address)
+        program: String,
+
+        /// Snapshot file, written as CSV or JSON depending on its extension
+        #[arg(long, default_value = "snapshot.json")]
+        output: String,
+
+        /// Refuse to snapshot from a node that has not reached this slot yet
+        #[arg(long)]
+        min_slot: Option<u64>,
+    },
+
+    /// Run every transaction of a task2 config or saved plan through simulateTransaction and
+    /// report the expected outcomes and total cost, without sending anything
+    SimulateBatch {
+        /// task2 config whose transfers (matrix, distribution or top-up) are simulated; also
+        /// supplies the token, memos, batching and stake settings for --plan
+        #[arg(long, required_unless_present = "plan")]
+        config: Option<String>,
+
+        /// Saved plan (YAML, or a source,destination,amount,memo CSV) simulated instead
+        #[arg(long)]
+        plan: Option<String>,
+
+        /// Commitment of the bank the transactions are simulated against
+        #[arg(long, default_value = "confirmed")]
+        commitment: CommitmentLevel,
+
+        /// Refuse to simulate against a node that has not reached this slot yet
+        #[arg(long)]
+        min_slot: Option<u64>,
+
+        /// Report file, written as CSV or JSON depending on its extension
+        #[arg(long, default_value = "simulation.json")]
+        output: String,
+    },
+
+    /// Create funded devnet wallets, task1/task2/task3 configs using them and deploy task4
+    Bootstrap {
+        /// Number of wallets to generate (first half are task2 sources, the rest destinations)
+        #[arg(long, default_value_t = 4)]
+        wallets: usize,
+
+        /// SOL airdropped to every generated wallet
+        #[arg(long, default_value_t = 1.0)]
+        airdrop_sol: f64,
+
+        /// Directory receiving keypairs and configs
+        #[arg(long, default_value = "bootstrap")]
+        out_dir: String,
+
+        /// Compiled task4 program to deploy
+        #[arg(long, default_value = "../task4/target/deploy/solana_deposit_withdraw.so")]
+        program: String,
+
+        /// Only create wallets and configs
+        #[arg(long)]
+        skip_deploy: bool,
+    },
+
+    /// Manage the shared address book of aliases
+    Address {
+        #[command(subcommand)]
+        action: AddressAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AddressAction {
+    /// Register an alias for an address
+    Add {
+        /// Alias to register (letters, digits, '_' and '-')
+        alias: String,
+
+        /// Base58 address the alias points to
+        address: String,
+    },
+
+    /// Remove an alias
+    Remove {
+        /// Alias to remove
+        alias: String,
+    },
+
+    /// List all registered aliases
+    List,
+
+    /// Resolve an alias or validate a raw address
+    Resolve {
+        /// Alias or base58 address
+        value: String,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    // Unset flags fall back to the user's solana-cli config
+    let cli_config = CliConfig::load()?;
+    let rpc_url = cli_config.rpc_url(args.rpc_url.as_deref(), DEFAULT_RPC_URL);
+    let client = rpc::rpc_client(
+        &rpc_url,
+        cli_config.commitment(CommitmentConfig::confirmed()),
+        RpcLimits::default(),
+    );
+
+    let mut book = AddressBook::load()?;
+
+    match args.command {
+        Command::DecodeTx {
+            input,
+            deposit_program,
+        } => {
+            let deposit_program = deposit_program
+                .map(|id| book.resolve(&id))
+                .transpose()
+                .context("Failed to parse deposit program id")?;
+
+            let decoded = decode::load_transaction(&client, &input)?;
+            decode::print_transaction(&decoded, deposit_program.as_ref(), &book);
+        }
+        Command::EstimateFee {
+            config,
+            from,
+            to,
+            amount,
+            mint,
+        } => {
+            // Collect the (source, destination, amount) triples to estimate
+            let transfers: Vec<(Pubkey, Pubkey, u64)> = match (config, from, to) {
+                (Some(config_path), _, _) => {
+                    let config = TransferConfig::load(Path::new(&config_path))?;
+                    let mut transfers = Vec::new();
+                    for transfer in config.planned_transfers()? {
+                        transfers.push((
+                            book.resolve(&transfer.source)?,
+                            book.resolve(&transfer.destination)?,
+                            amount.unwrap_or(transfer.amount_lamports),
+                        ));
+                    }
+                    transfers
+                }
+                (None, Some(from), Some(to)) => {
+                    vec![(
+                        book.resolve(&from)?,
+                        book.resolve(&to)?,
+                        amount.unwrap_or(1000),
+                    )]
+                }
+                _ => anyhow::bail!("Either --config or both --from and --to are required"),
+            };
+
+            let mint = mint
+                .map(|mint| book.resolve(&mint))
+                .transpose()
+                .context("Failed to parse mint")?;
+
+            // SOL transfer costs only depend on the payer, so estimate once per source
+            let mut per_source: HashMap<Pubkey, fees::FeeEstimate> = HashMap::new();
+            let mut total_fees = 0;
+            let mut total_amount = 0;
+
+            for (source, destination, amount) in &transfers {
+                let estimate = match &mint {
+                    Some(mint) => {
+                        let (instructions, rent) = fees::token_transfer_instructions(
+                            &client,
+                            source,
+                            destination,
+                            mint,
+                            *amount,
+                        )?;
+                        fees::FeeEstimate {
+                            rent,
+                            ..fees::estimate(&client, source, &instructions)?
+                        }
+                    }
+                    None => match per_source.get(source) {
+                        Some(estimate) => *estimate,
+                        None => {
+                            let instructions =
+                                transfer_instructions(source, destination, *amount, None);
+                            let estimate = fees::estimate(&client, source, &instructions)?;
+                            per_source.insert(*source, estimate);
+                            estimate
+                        }
+                    },
+                };
+
+                println!(
+                    "{} -> {}",
+                    book.display(&source.to_string()),
+                    book.display(&destination.to_string())
+                );
+                println!("  Base fee:      {} lamports", estimate.base_fee);
+                println!(
+                    "  Priority fee:  {} lamports ({} micro-lamports/CU x {} CU)",
+                    estimate.priority_fee(),
+                    estimate.priority_fee_per_cu,
+                    estimate.compute_units
+                );
+                if estimate.rent > 0 {
+                    println!(
+                        "  Account rent:  {} lamports (new token account)",
+                        estimate.rent
+                    );
+                }
+                println!(
+                    "  Total cost:    {} lamports ({} SOL)",
+                    estimate.total(),
+                    lamports_to_sol(estimate.total())
+                );
+
+                total_fees += estimate.total();
+                total_amount += amount;
+            }
+
+            println!("\nSummary:");
+            println!("Transfers: {}", transfers.len());
+            match mint {
+                Some(mint) => {
+                    println!("Total transferred: {} base units of {}", total_amount, mint)
+                }
+                None => println!(
+                    "Total transferred: {} lamports ({} SOL)",
+                    total_amount,
+                    lamports_to_sol(total_amount)
+                ),
+            }
+            println!(
+                "Total expected cost: {} lamports ({} SOL)",
+                total_fees,
+                lamports_to_sol(total_fees)
+            );
+        }
+        Command::ImportPlan {
+            csv,
+            output,
+            config,
+        } => {
+            let plan = Plan::from_csv(Path::new(&csv), &book)?;
+
+            if let Some(config_path) = config {
+                let config = TransferConfig::load(Path::new(&config_path))?;
+                let mut configured = Vec::new();
+                for source in &config.source_wallets {
+                    configured.push(book.resolve(&source.address)?.to_string());
+                }
+
+                let mut missing: Vec<&str> = plan
+                    .transfers
+                    .iter()
+                    .map(|t| t.source.as_str())
+                    .filter(|source| !configured.iter().any(|c| c == source))
+                    .collect();
+                missing.sort_unstable();
+                missing.dedup();
+                if !missing.is_empty() {
+                    anyhow::bail!(
+                        "No secret key in {} for source wallet(s): {}",
+                        config_path,
+                        missing.join(", ")
+                    );
+                }
+            }
+
+            plan.save(Path::new(&output))?;
+
+            println!(
+                "Imported {} transfers ({} SOL in total) into {}",
+                plan.transfers.len(),
+                lamports_to_sol(plan.total_lamports()),
+                output
+            );
+        }
+        Command::RotateKeys {
+            config,
+            keypairs,
+            vault,
+            passphrase_env,
+            plaintext_out_dir,
+            report,
+        } => {
+            // Collect the wallets to rotate from the task2 config and keypair files
+            let mut old_keys: Vec<Keypair> = Vec::new();
+            if let Some(config_path) = &config {
+                let config = TransferConfig::load(Path::new(config_path))?;
+                for source in &config.source_wallets {
+                    old_keys.push(source.keypair().with_context(
+                        || format!("Failed to load keypair for {}", source.address),
+                    )?);
+                }
+            }
+            for path in &keypairs {
+                old_keys.push(
+                    read_keypair_file(path)
+                        .map_err(|e| anyhow::anyhow!("Failed to read keypair {}: {}", path, e))?,
+                );
+            }
+            if old_keys.is_empty() {
+                anyhow::bail!("Nothing to rotate: pass --config and/or --keypair");
+            }
+
+            // New keys go to the encrypted vault unless plaintext files were asked for
+            let mut opened_vault = None;
+            let mut store = match &plaintext_out_dir {
+                Some(out_dir) => rotate::KeyStore::Plaintext(Path::new(out_dir)),
+                None => {
+                    let passphrase = vault::passphrase(&passphrase_env)?;
+                    rotate::KeyStore::Vault(
+                        opened_vault.insert(Vault::open_or_create(Path::new(&vault), &passphrase)?),
+                    )
+                }
+            };
+
+            let mut rotations = Vec::new();
+            let mut new_keys = Vec::new();
+            for old in &old_keys {
+                let (new, mut rotation) = rotate::rotate_wallet(&client, old, &mut store)?;
+
+                // Aliases only follow the key once all funds have moved
+                if rotation.error.is_none() {
+                    rotation.aliases = book.repoint(&rotation.old_address, &new.pubkey());
+                }
+
+                println!(
+                    "{} -> {}",
+                    book.display(&rotation.old_address),
+                    rotation.new_address
+                );
+                println!("  Stored in:     {}", rotation.stored_in.display());
+                println!("  Token accounts swept: {}", rotation.tokens.len());
+                println!(
+                    "  SOL swept:     {} lamports ({} SOL)",
+                    rotation.sol_swept,
+                    lamports_to_sol(rotation.sol_swept)
+                );
+                if let Some(error) = &rotation.error {
+                    println!("  Error:         {}", error);
+                }
+
+                rotations.push(rotation);
+                new_keys.push(new);
+            }
+            book.save()?;
+
+            let keys: Vec<(&Keypair, &'static str)> = old_keys
+                .iter()
+                .map(|key| (key, "old"))
+                .chain(new_keys.iter().map(|key| (key, "new")))
+                .collect();
+            let signed = rotate::signed_report(rotations, &keys)?;
+            fs::write(&report, serde_json::to_string_pretty(&signed)?)
+                .context("Failed to write rotation report")?;
+
+            let failed = signed
+                .rotations
+                .iter()
+                .filter(|r| r.error.is_some())
+                .count();
+            println!("\nSummary:");
+            println!("Wallets rotated: {}", signed.rotations.len() - failed);
+            println!("Failed rotations: {}", failed);
+            println!("Signed report written to {}", report);
+            if config.is_some() {
+                match &plaintext_out_dir {
+                    Some(out_dir) => println!(
+                        "Replace the secret keys in the task2 config with the new keypairs from {}",
+                        out_dir
+                    ),
+                    None => println!(
+                        "Replace the secret keys in the task2 config with the new keys (toolkit vault-export <address> --output <file>)"
+                    ),
+                }
+            }
+        }
+        Command::VaultExport {
+            address,
+            output,
+            vault,
+            passphrase_env,
+        } => {
+            let address = book.resolve(&address)?;
+            let passphrase = vault::passphrase(&passphrase_env)?;
+            let keypair = Vault::open_or_create(Path::new(&vault), &passphrase)?.keypair(&address)?;
+            write_keypair_file(&keypair, &output)
+                .map_err(|e| anyhow::anyhow!("Failed to write keypair file {}: {}", output, e))?;
+            println!("Wrote the key of {} to {}", address, output);
+        }
+        Command::VerifyAudit { log, auditor } => {
+            let auditor = auditor
+                .map(|auditor| book.resolve(&auditor))
+                .transpose()
+                .context("Failed to parse auditor key")?;
+
+            let entries = audit::verify(Path::new(&log), auditor.as_ref())?;
+
+            println!("Audit log {} verified: {} entries", log, entries.len());
+            if let Some(last) = entries.last() {
+                println!("Head: entry {} hash {}", last.seq, last.hash);
+                println!("Signed by: {}", book.display(&last.auditor));
+            }
+        }
+        Command::VerifyReceipts { log, operator } => {
+            let operator = operator
+                .map(|operator| book.resolve(&operator))
+                .transpose()
+                .context("Failed to parse operator key")?;
+
+            let receipts = receipt::verify(Path::new(&log), operator.as_ref())?;
+
+            println!("Receipt log {} verified: {} receipts", log, receipts.len());
+            for signed in &receipts {
+                let receipt = &signed.receipt;
+                println!(
+                    "{}  {} slot {}  {} lamports {} -> {}  signed by {}",
+                    receipt.signature,
+                    receipt.rule,
+                    receipt.trigger_slot,
+                    receipt.lamports,
+                    book.display(&receipt.source),
+                    book.display(&receipt.destination),
+                    book.display(&signed.operator)
+                );
+            }
+        }
+        Command::VerifyManifest {
+            manifest,
+            operator,
+            audit: audit_log,
+        } => {
+            let operator = operator
+                .map(|operator| book.resolve(&operator))
+                .transpose()
+                .context("Failed to parse operator key")?;
+
+            let signed = SignedManifest::load(Path::new(&manifest))?;
+            signed.verify(operator.as_ref())?;
+            println!(
+                "Manifest {} verified: {} transfers approved by {} at {}",
+                signed.hash,
+                signed.manifest.transfers.len(),
+                book.display(&signed.operator),
+                signed.manifest.created_at
+            );
+
+            if let Some(audit_log) = audit_log {
+                let entries = audit::verify(Path::new(&audit_log), None)?;
+                let check = signed.check(&entries);
+                for entry in &check.unapproved {
+                    println!(
+                        "UNAPPROVED  entry {} {} lamports {} -> {}",
+                        entry.seq,
+                        entry.record.lamports,
+                        book.display(&entry.record.source),
+                        book.display(&entry.record.destination)
+                    );
+                }
+                for transfer in &check.unsent {
+                    println!(
+                        "UNSENT      {} lamports {} -> {}",
+                        transfer.lamports,
+                        book.display(&transfer.source),
+                        book.display(&transfer.destination)
+                    );
+                }
+                if !check.unapproved.is_empty() {
+                    anyhow::bail!(
+                        "{} audited transfers were not approved by the manifest",
+                        check.unapproved.len()
+                    );
+                }
+                println!("Every audited transfer of the run matches the manifest");
+            }
+        }
+        Command::Reconcile { log, history_limit } => {
+            // Reconciling against a tampered log would be meaningless, so verify it first
+            let entries = audit::verify(Path::new(&log), None)?;
+            let findings = reconcile::reconcile(&client, &entries, history_limit)?;
+
+            for finding in &findings {
+                match finding {
+                    reconcile::Finding::Missing { entry } => println!(
+                        "MISSING     entry {} {} (not found on-chain)",
+                        entry.seq, entry.record.signature
+                    ),
+                    reconcile::Finding::Failed { entry, error } => println!(
+                        "FAILED      entry {} {}: {}",
+                        entry.seq, entry.record.signature, error
+                    ),
+                    reconcile::Finding::Mismatched { entry, actual } => {
+                        println!(
+                            "MISMATCHED  entry {} {}: logged {} lamports {} -> {}",
+                            entry.seq,
+                            entry.record.signature,
+                            entry.record.lamports,
+                            book.display(&entry.record.source),
+                            book.display(&entry.record.destination)
+                        );
+                        for transfer in actual {
+                            println!(
+                                "              on-chain {} lamports {} -> {}",
+                                transfer.lamports,
+                                book.display(&transfer.from.to_string()),
+                                book.display(&transfer.to.to_string())
+                            );
+                        }
+                    }
+                    reconcile::Finding::DuplicateEntry { signature, count } => {
+                        println!("DUPLICATE   {} logged {} times", signature, count)
+                    }
+                    reconcile::Finding::Duplicated {
+                        signature,
+                        transfer,
+                    } => println!(
+                        "DUPLICATED  {} repeats {} lamports {} -> {}",
+                        signature,
+                        transfer.lamports,
+                        book.display(&transfer.from.to_string()),
+                        book.display(&transfer.to.to_string())
+                    ),
+                    reconcile::Finding::Unexpected {
+                        signature,
+                        transfer,
+                    } => println!(
+                        "UNEXPECTED  {} moved {} lamports {} -> {}",
+                        signature,
+                        transfer.lamports,
+                        book.display(&transfer.from.to_string()),
+                        book.display(&transfer.to.to_string())
+                    ),
+                }
+            }
+
+            println!("\nSummary:");
+            println!("Audit entries: {}", entries.len());
+            println!("Discrepancies: {}", findings.len());
+            if !findings.is_empty() {
+                anyhow::bail!("Reconciliation found {} discrepancies", findings.len());
+            }
+        }
+        Command::Snapshot {
+            program,
+            output,
+            min_slot,
+        } => {
+            let program_id = book
+                .resolve(&program)
+                .context("Failed to parse deposit program id")?;
+
+            let snapshot = snapshot::take(&client, &program_id, min_slot)?;
+            snapshot.write(Path::new(&output))?;
+
+            println!(
+                "Snapshot of {} at slot {}: {} accounts",
+                book.display(&snapshot.program_id),
+                snapshot.slot,
+                snapshot.accounts.len()
+            );
+            for (kind, count) in snapshot.counts() {
+                if count > 0 {
+                    println!("  {:<8} {}", kind, count);
+                }
+            }
+            println!(
+                "Deposit balances: {} lamports ({} SOL)",
+                snapshot.deposit_balance(),
+                lamports_to_sol(snapshot.deposit_balance())
+            );
+            println!("Hash: {}", snapshot.hash);
+            println!("Written to {}", output);
+        }
+        Command::SimulateBatch {
+            config,
+            plan,
+            commitment,
+            min_slot,
+            output,
+        } => {
+            let mut config = match config {
+                Some(config_path) => TransferConfig::load(Path::new(&config_path))?,
+                None => serde_yaml::from_str("{}").expect("an empty task2 config is valid"),
+            };
+
+            // Token amounts are in base units of the mint, as in task2
+            let token = match &config.token_mint {
+                Some(mint) => {
+                    let mint = TokenMint::fetch(&client, &book.resolve(mint)?)?;
+                    if let Some(amount) = &config.amount_tokens {
+                        config.amount_lamports = mint.parse_amount(amount)?;
+                    }
+                    Some(TokenTransfers {
+                        mint,
+                        create_destination_account: config.create_destination_ata,
+                    })
+                }
+                None => None,
+            };
+
+            let transfers = match (&plan, &config.top_up) {
+                (Some(plan_path), _) => Plan::load_any(Path::new(plan_path), &book)?.transfers,
+                (None, Some(top_up)) => top_up.plan(&client, &book, &config.source_addresses())?,
+                (None, None) => config.planned_transfers()?,
+            };
+            let memos = config.memos(&book)?;
+            let options = simulate::BatchOptions {
+                token: token.as_ref(),
+                stake_vote_account: config
+                    .stake_vote_account
+                    .as_deref()
+                    .map(|vote_account| book.resolve(vote_account))
+                    .transpose()?,
+                batch_transfers: config.batch_transfers,
+                memos: &memos,
+            };
+            let transactions = simulate::plan_transactions(&transfers, &book, &options)?;
+
+            let simulation = simulate::run(&client, &transactions, commitment, min_slot)?;
+            simulation.write(Path::new(&output))?;
+
+            for transaction in &simulation.transactions {
+                let destinations = match transaction.destinations.as_slice() {
+                    [destination] => book.display(destination),
+                    destinations => format!("{} destinations", destinations.len()),
+                };
+                println!(
+                    "{:<8} {} -> {}: {} (fee {} lamports, {} CU)",
+                    if transaction.succeeded() { "OK" } else { "FAIL" },
+                    book.display(&transaction.source),
+                    destinations,
+                    transaction.amount,
+                    transaction.fee_lamports,
+                    transaction
+                        .units_consumed
+                        .map_or("?".to_string(), |units| units.to_string())
+                );
+                if let Some(error) = &transaction.error {
+                    println!("         {} ({})", error, transaction.outcome);
+                }
+            }
+
+            let unit = if token.is_some() { "base units" } else { "lamports" };
+            println!("\nSummary:");
+            println!(
+                "Transfers: {} in {} transactions, simulated at {} commitment",
+                transfers.len(),
+                simulation.transactions.len(),
+                simulation.commitment
+            );
+            println!("Expected failures: {}", simulation.failures());
+            println!("Expected transferred: {} {}", simulation.total_amount(), unit);
+            println!(
+                "Expected fees: {} lamports ({} SOL), priority fees not included",
+                simulation.total_fees(),
+                lamports_to_sol(simulation.total_fees())
+            );
+            println!("Written to {}", output);
+            if simulation.failures() > 0 {
+                anyhow::bail!("{} transactions would fail", simulation.failures());
+            }
+        }
+        Command::Bootstrap {
+            wallets,
+            airdrop_sol,
+            out_dir,
+            program,
+            skip_deploy,
+        } => {
+            let out_dir = Path::new(&out_dir);
+            let mut env =
+                bootstrap::create_wallets(&client, &rpc_url, wallets, sol_to_lamports(airdrop_sol), out_dir)?;
+
+            if !skip_deploy {
+                println!("Deploying {}...", program);
+                env.program_id = Some(bootstrap::deploy_program(
+                    &client,
+                    &rpc_url,
+                    Path::new(&program),
+                    out_dir,
+                )?);
+            }
+
+            bootstrap::print_quickstart(&env, &rpc_url, out_dir);
+        }
+        Command::Address { action } => match action {
+            AddressAction::Add { alias, address } => {
+                let pubkey = book.add(&alias, &address)?;
+                book.save()?;
+                println!("Added {} -> {}", alias, pubkey);
+            }
+            AddressAction::Remove { alias } => {
+                let address = book.remove(&alias)?;
+                book.save()?;
+                println!("Removed {} (was {})", alias, address);
+            }
+            AddressAction::List => {
+                println!("{:<24} {:<44}", "Alias", "Address");
+                for (alias, address) in book.entries() {
+                    println!("{:<24} {:<44}", alias, address);
+                }
+            }
+            AddressAction::Resolve { value } => {
+                println!("{}", book.resolve(&value)?);
+            }
+        },
+    }
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+use crate::decode::{self, SolTransfer};
+use anyhow::{Context, Result};
+use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_common::audit::AuditEntry;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+/// A discrepancy between the audit log and the chain
+#[derive(Debug)]
+pub enum Finding {
+    /// Logged as submitted but the transaction never landed
+    Missing { entry: AuditEntry },
+    /// Landed but failed on-chain
+    Failed { entry: AuditEntry, error: String },
+    /// Landed but did not move the logged amount between the logged wallets
+    Mismatched {
+        entry: AuditEntry,
+        actual: Vec<SolTransfer>,
+    },
+    /// The same signature was logged more than once
+    DuplicateEntry { signature: String, count: usize },
+    /// An expected movement landed again under a signature the log doesn't know
+    Duplicated {
+        signature: Signature,
+        transfer: SolTransfer,
+    },
+    /// An outgoing movement from an audited wallet that matches nothing in the log
+    Unexpected {
+        signature: Signature,
+        transfer: SolTransfer,
+    },
+}
+
+/// Check every audit entry against its on-chain transaction, then scan the history of every
+/// audited source wallet since the first entry for movements the log doesn't account for.
+/// `history_limit` caps the signatures fetched per wallet.
+pub fn reconcile(
+    client: &RpcClient,
+    entries: &[AuditEntry],
+    history_limit: usize,
+) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+
+    let mut by_signature: HashMap<&str, Vec<&AuditEntry>> = HashMap::new();
+    for entry in entries {
+        by_signature
+            .entry(entry.record.signature.as_str())
+            .or_default()
+            .push(entry);
+    }
+    let mut duplicates: Vec<(&str, usize)> = by_signature
+        .iter()
+        .filter(|(_, logged)| logged.len() > 1)
+        .map(|(signature, logged)| (*signature, logged.len()))
+        .collect();
+    duplicates.sort_unstable();
+    findings.extend(
+        duplicates
+            .into_iter()
+            .map(|(signature, count)| Finding::DuplicateEntry {
+                signature: signature.to_string(),
+                count,
+            }),
+    );
+
+    // Logged transfers: did each one land, succeed and move what the log says?
+    let mut expected: HashSet<SolTransfer> = HashSet::new();
+    let mut checked = HashSet::new();
+    for entry in entries {
+        if !checked.insert(entry.record.signature.as_str()) {
+            continue;
+        }
+
+        let transfer = expected_transfer(entry)?;
+        expected.insert(transfer);
+
+        let signature = Signature::from_str(&entry.record.signature)
+            .with_context(|| format!("Invalid signature in audit entry {}", entry.seq))?;
+
+        let landed = client
+            .get_signature_statuses_with_history(&[signature])
+            .context("Failed to fetch signature status")?
+            .value
+            .first()
+            .is_some_and(|status| status.is_some());
+        if !landed {
+            findings.push(Finding::Missing {
+                entry: entry.clone(),
+            });
+            continue;
+        }
+
+        let decoded = decode::fetch_transaction(client, &signature)?;
+
+        if let Some(error) = decoded.meta.as_ref().and_then(|meta| meta.err.as_ref()) {
+            findings.push(Finding::Failed {
+                entry: entry.clone(),
+                error: error.to_string(),
+            });
+            continue;
+        }
+
+        let actual = decode::sol_transfers(&decoded);
+        if !actual.contains(&transfer) {
+            findings.push(Finding::Mismatched {
+                entry: entry.clone(),
+                actual,
+            });
+        }
+    }
+
+    // On-chain history: anything sent from an audited wallet that the log doesn't know about
+    let since = entries
+        .iter()
+        .filter_map(|entry| chrono::DateTime::parse_from_rfc3339(&entry.timestamp).ok())
+        .map(|timestamp| timestamp.timestamp())
+        .min();
+    let wallets: HashSet<Pubkey> = expected.iter().map(|transfer| transfer.from).collect();
+
+    for wallet in wallets {
+        let history = client
+            .get_signatures_for_address_with_config(
+                &wallet,
+                GetConfirmedSignaturesForAddress2Config {
+                    limit: Some(history_limit),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    ..GetConfirmedSignaturesForAddress2Config::default()
+                },
+            )
+            .with_context(|| format!("Failed to fetch history of {}", wallet))?;
+
+        for status in history {
+            let in_window = match (since, status.block_time) {
+                (Some(since), Some(block_time)) => block_time >= since,
+                _ => true,
+            };
+            if !in_window
+                || status.err.is_some()
+                || by_signature.contains_key(status.signature.as_str())
+            {
+                continue;
+            }
+
+            let signature = Signature::from_str(&status.signature)
+                .context("RPC returned an invalid signature")?;
+            let decoded = decode::fetch_transaction(client, &signature)?;
+
+            for transfer in decode::sol_transfers(&decoded) {
+                if transfer.from != wallet {
+                    continue;
+                }
+
+                findings.push(if expected.contains(&transfer) {
+                    Finding::Duplicated {
+                        signature,
+                        transfer,
+                    }
+                } else {
+                    Finding::Unexpected {
+                        signature,
+                        transfer,
+                    }
+                });
+            }
+        }
+    }
+
+    Ok(findings)
+}
+
+fn expected_transfer(entry: &AuditEntry) -> Result<SolTransfer> {
+    Ok(SolTransfer {
+        from: Pubkey::from_str(&entry.record.source)
+            .with_context(|| format!("Invalid source in audit entry {}", entry.seq))?,
+        to: Pubkey::from_str(&entry.record.destination)
+            .with_context(|| format!("Invalid destination in audit entry {}", entry.seq))?,
+        lamports: entry.record.lamports,
+    })
+}
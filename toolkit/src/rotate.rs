@@ -0,0 +1,381 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    message::Message,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account as TokenAccount, AccountState, Mint},
+};
+use std::path::{Path, PathBuf};
+
+use crate::vault::Vault;
+
+/// Byte offset of the owner field in an SPL token account
+const TOKEN_ACCOUNT_OWNER_OFFSET: usize = 32;
+
+/// Programs whose token accounts are swept: SPL Token and Token-2022
+const TOKEN_PROGRAMS: [Pubkey; 2] = [spl_token::ID, spl_token_2022::ID];
+
+#[derive(Debug, Serialize)]
+pub struct TokenSweep {
+    /// SPL Token or Token-2022
+    pub token_program: String,
+    pub mint: String,
+    pub token_account: String,
+    pub amount: u64,
+    pub signature: String,
+}
+
+/// Outcome of rotating one wallet
+#[derive(Debug, Serialize)]
+pub struct Rotation {
+    pub old_address: String,
+    pub new_address: String,
+    /// Vault, or plaintext keypair file, holding the new key
+    pub stored_in: PathBuf,
+    pub tokens: Vec<TokenSweep>,
+    pub sol_swept: u64,
+    pub sol_signature: Option<String>,
+    pub aliases: Vec<String>,
+    pub error: Option<String>,
+}
+
+/// Signature over the serialized rotations by one of the keys involved
+#[derive(Debug, Serialize)]
+pub struct Attestation {
+    pub signer: String,
+    pub role: &'static str,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RotationReport {
+    pub created_at: String,
+    pub rotations: Vec<Rotation>,
+    /// Every old and new key signs the JSON encoding of `rotations`, proving both ends took part
+    pub attestations: Vec<Attestation>,
+}
+
+/// Where new keypairs are persisted
+pub enum KeyStore<'a> {
+    /// The encrypted vault, saved after every new key
+    Vault(&'a mut Vault),
+    /// Unencrypted keypair files in a directory, only on explicit request
+    Plaintext(&'a Path),
+}
+
+impl KeyStore<'_> {
+    fn store(&mut self, keypair: &Keypair) -> Result<PathBuf> {
+        match self {
+            KeyStore::Vault(vault) => {
+                vault.insert(keypair);
+                vault.save()?;
+                Ok(vault.path().to_path_buf())
+            }
+            KeyStore::Plaintext(out_dir) => {
+                let keypair_file = out_dir.join(format!("{}.json", keypair.pubkey()));
+                std::fs::create_dir_all(out_dir)
+                    .context("Failed to create keypair output directory")?;
+                solana_sdk::signature::write_keypair_file(keypair, &keypair_file).map_err(|e| {
+                    anyhow!(
+                        "Failed to write keypair file {}: {}",
+                        keypair_file.display(),
+                        e
+                    )
+                })?;
+                Ok(keypair_file)
+            }
+        }
+    }
+}
+
+/// Generate a replacement for `old`, persist it to `store` and move all SOL and tokens (SPL Token
+/// and Token-2022) over.
+/// The new keypair is stored before any funds move so they can never land on a lost key.
+pub fn rotate_wallet(
+    client: &RpcClient,
+    old: &Keypair,
+    store: &mut KeyStore,
+) -> Result<(Keypair, Rotation)> {
+    let new = Keypair::new();
+    let stored_in = store.store(&new)?;
+
+    let mut rotation = Rotation {
+        old_address: old.pubkey().to_string(),
+        new_address: new.pubkey().to_string(),
+        stored_in,
+        tokens: Vec::new(),
+        sol_swept: 0,
+        sol_signature: None,
+        aliases: Vec::new(),
+        error: None,
+    };
+
+    // Tokens first: their fees and reclaimed rent are settled before the final SOL sweep
+    let result = sweep_tokens(client, old, &new.pubkey(), &mut rotation)
+        .and_then(|_| sweep_sol(client, old, &new.pubkey(), &mut rotation));
+    if let Err(e) = result {
+        rotation.error = Some(format!("{:#}", e));
+    }
+
+    Ok((new, rotation))
+}
+
+fn sweep_tokens(
+    client: &RpcClient,
+    old: &Keypair,
+    new: &Pubkey,
+    rotation: &mut Rotation,
+) -> Result<()> {
+    for program in TOKEN_PROGRAMS {
+        // Token-2022 accounts grow with their extensions, so only classic ones have a fixed size
+        let mut filters = vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            TOKEN_ACCOUNT_OWNER_OFFSET,
+            old.pubkey().to_bytes().to_vec(),
+        ))];
+        if program == spl_token::ID {
+            filters.push(RpcFilterType::DataSize(TokenAccount::LEN as u64));
+        }
+        let accounts = client
+            .get_program_accounts_with_config(
+                &program,
+                RpcProgramAccountsConfig {
+                    filters: Some(filters),
+                    account_config: RpcAccountInfoConfig {
+                        commitment: Some(CommitmentConfig::confirmed()),
+                        ..RpcAccountInfoConfig::default()
+                    },
+                    ..RpcProgramAccountsConfig::default()
+                },
+            )
+            .context("Failed to list token accounts")?;
+
+        for (address, account) in accounts {
+            let token_account = StateWithExtensions::<TokenAccount>::unpack(&account.data)
+                .with_context(|| format!("Failed to parse token account {}", address))?
+                .base;
+
+            if token_account.state == AccountState::Frozen {
+                bail!("Token account {} is frozen and cannot be swept", address);
+            }
+
+            let mut instructions = Vec::new();
+
+            // Wrapped SOL is unwrapped by closing the account; other tokens move to the new
+            // owner's ATA under the same token program
+            if token_account.amount > 0 && !token_account.is_native() {
+                let mint_account = client
+                    .get_account(&token_account.mint)
+                    .context("Failed to fetch mint account")?;
+                let mint = StateWithExtensions::<Mint>::unpack(&mint_account.data)
+                    .context("Account is not a token mint")?
+                    .base;
+
+                instructions.push(create_associated_token_account_idempotent(
+                    &old.pubkey(),
+                    new,
+                    &token_account.mint,
+                    &program,
+                ));
+                instructions.push(spl_token_2022::instruction::transfer_checked(
+                    &program,
+                    &address,
+                    &token_account.mint,
+                    &get_associated_token_address_with_program_id(
+                        new,
+                        &token_account.mint,
+                        &program,
+                    ),
+                    &old.pubkey(),
+                    &[],
+                    token_account.amount,
+                    mint.decimals,
+                )?);
+            }
+
+            // Close the emptied account so its rent goes to the new wallet too
+            instructions.push(spl_token_2022::instruction::close_account(
+                &program,
+                &address,
+                new,
+                &old.pubkey(),
+                &[],
+            )?);
+
+            let signature = send(client, old, &instructions)
+                .with_context(|| format!("Failed to sweep token account {}", address))?;
+
+            rotation.tokens.push(TokenSweep {
+                token_program: program.to_string(),
+                mint: token_account.mint.to_string(),
+                token_account: address.to_string(),
+                amount: token_account.amount,
+                signature,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn sweep_sol(
+    client: &RpcClient,
+    old: &Keypair,
+    new: &Pubkey,
+    rotation: &mut Rotation,
+) -> Result<()> {
+    let balance = client
+        .get_balance(&old.pubkey())
+        .context("Failed to get balance")?;
+
+    let blockhash = client
+        .get_latest_blockhash()
+        .context("Failed to get recent blockhash")?;
+    let message = Message::new_with_blockhash(
+        &[system_instruction::transfer(&old.pubkey(), new, balance)],
+        Some(&old.pubkey()),
+        &blockhash,
+    );
+    let fee = client
+        .get_fee_for_message(&message)
+        .context("Failed to get fee for message")?;
+
+    if balance <= fee {
+        return Ok(());
+    }
+
+    let amount = balance - fee;
+    let signature = send(
+        client,
+        old,
+        &[system_instruction::transfer(&old.pubkey(), new, amount)],
+    )
+    .context("Failed to sweep SOL")?;
+
+    rotation.sol_swept = amount;
+    rotation.sol_signature = Some(signature);
+
+    Ok(())
+}
+
+fn send(client: &RpcClient, payer: &Keypair, instructions: &[Instruction]) -> Result<String> {
+    let blockhash = client
+        .get_latest_blockhash()
+        .context("Failed to get recent blockhash")?;
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&payer.pubkey()),
+        &[payer],
+        blockhash,
+    );
+
+    let signature = client
+        .send_and_confirm_transaction(&transaction)
+        .context("Failed to send transaction")?;
+
+    Ok(signature.to_string())
+}
+
+/// Build the report and have every old and new key sign the rotations it describes
+pub fn signed_report(
+    rotations: Vec<Rotation>,
+    keys: &[(&Keypair, &'static str)],
+) -> Result<RotationReport> {
+    let payload = serde_json::to_vec(&rotations).context("Failed to serialize rotations")?;
+
+    let attestations = keys
+        .iter()
+        .map(|(keypair, role)| Attestation {
+            signer: keypair.pubkey().to_string(),
+            role,
+            signature: keypair.sign_message(&payload).to_string(),
+        })
+        .collect();
+
+    Ok(RotationReport {
+        created_at: chrono::Utc::now().to_rfc3339(),
+        rotations,
+        attestations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::Signature;
+    use std::str::FromStr;
+
+    fn rotation(old: &Keypair, new: &Keypair) -> Rotation {
+        Rotation {
+            old_address: old.pubkey().to_string(),
+            new_address: new.pubkey().to_string(),
+            stored_in: PathBuf::from("keys.vault"),
+            tokens: Vec::new(),
+            sol_swept: 1_000_000,
+            sol_signature: Some(Signature::default().to_string()),
+            aliases: vec!["treasury".to_string()],
+            error: None,
+        }
+    }
+
+    /// Whether `attestation` is a valid signature of its signer over `rotations`
+    fn verifies(attestation: &Attestation, rotations: &[Rotation]) -> bool {
+        let payload = serde_json::to_vec(rotations).unwrap();
+        let signer = Pubkey::from_str(&attestation.signer).unwrap();
+        Signature::from_str(&attestation.signature)
+            .unwrap()
+            .verify(signer.as_ref(), &payload)
+    }
+
+    #[test]
+    fn every_old_and_new_key_attests_the_rotations() {
+        let (old, new) = (Keypair::new(), Keypair::new());
+        let report =
+            signed_report(vec![rotation(&old, &new)], &[(&old, "old"), (&new, "new")]).unwrap();
+
+        let signers: Vec<_> = report
+            .attestations
+            .iter()
+            .map(|attestation| (attestation.signer.clone(), attestation.role))
+            .collect();
+        assert_eq!(
+            signers,
+            [
+                (old.pubkey().to_string(), "old"),
+                (new.pubkey().to_string(), "new")
+            ]
+        );
+        for attestation in &report.attestations {
+            assert!(verifies(attestation, &report.rotations));
+        }
+    }
+
+    #[test]
+    fn attestations_break_when_the_rotations_change() {
+        let (old, new) = (Keypair::new(), Keypair::new());
+        let mut report =
+            signed_report(vec![rotation(&old, &new)], &[(&old, "old"), (&new, "new")]).unwrap();
+
+        report.rotations[0].new_address = Keypair::new().pubkey().to_string();
+        for attestation in &report.attestations {
+            assert!(!verifies(attestation, &report.rotations));
+        }
+    }
+}
@@ -0,0 +1,336 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
+use solana_common::AddressBook;
+use solana_sdk::{
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    instruction::Instruction,
+    message::Message,
+    pubkey::Pubkey,
+    transaction::Transaction,
+};
+use solana_token_transfer::{
+    token::TokenTransfers,
+    transfer::{pack_transfers, stake_instructions, transfer_instructions},
+    FailureClass, PlannedTransfer, TransferKind, TransferMemos,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// One transaction task2 would send
+#[derive(Debug, Clone)]
+pub struct PlannedTransaction {
+    pub source: Pubkey,
+    pub destinations: Vec<Pubkey>,
+    /// Lamports, or token base units with a mint, the transaction moves
+    pub amount: u64,
+    pub instructions: Vec<Instruction>,
+}
+
+/// How task2 would build the transactions of a run
+pub struct BatchOptions<'a> {
+    pub token: Option<&'a TokenTransfers>,
+    pub stake_vote_account: Option<Pubkey>,
+    pub batch_transfers: bool,
+    pub memos: &'a TransferMemos,
+}
+
+/// Group `transfers` into the transactions task2 would send: one per transfer, or with
+/// batching each source's transfers (stake ones aside) packed as tightly as they fit.
+/// Durable nonces are left out, so every transaction uses a recent blockhash.
+pub fn plan_transactions(
+    transfers: &[PlannedTransfer],
+    book: &AddressBook,
+    options: &BatchOptions,
+) -> Result<Vec<PlannedTransaction>> {
+    let mut transactions = Vec::new();
+    let mut by_source: Vec<(Pubkey, Vec<PlannedTransaction>)> = Vec::new();
+
+    for transfer in transfers {
+        let source = book
+            .resolve(&transfer.source)
+            .with_context(|| format!("Invalid source {}", transfer.source))?;
+        let destination = book
+            .resolve(&transfer.destination)
+            .with_context(|| format!("Invalid destination {}", transfer.destination))?;
+        let amount = transfer.amount_lamports;
+
+        if transfer.kind == TransferKind::Stake {
+            if options.token.is_some() {
+                bail!(
+                    "Stake transfer {} -> {} cannot be made with token_mint set",
+                    transfer.source,
+                    transfer.destination
+                );
+            }
+            let Some(vote_account) = options.stake_vote_account else {
+                bail!(
+                    "Stake transfer {} -> {} needs stake_vote_account in the config",
+                    transfer.source,
+                    transfer.destination
+                );
+            };
+            // Simulated without signatures, so any fresh address stands in for the stake account
+            let stake_account = Pubkey::new_unique();
+            transactions.push(PlannedTransaction {
+                source,
+                destinations: vec![destination],
+                amount,
+                instructions: stake_instructions(
+                    &source,
+                    &stake_account,
+                    &destination,
+                    &vote_account,
+                    amount,
+                ),
+            });
+            continue;
+        }
+
+        let memo = transfer
+            .memo
+            .as_deref()
+            .or_else(|| options.memos.memo_for(&destination));
+        let instructions = match options.token {
+            Some(token) => token.instructions(&source, &destination, amount, memo),
+            None => transfer_instructions(&source, &destination, amount, memo),
+        };
+        let single = PlannedTransaction {
+            source,
+            destinations: vec![destination],
+            amount,
+            instructions,
+        };
+        if !options.batch_transfers {
+            transactions.push(single);
+            continue;
+        }
+        match by_source
+            .iter_mut()
+            .find(|(batch_source, _)| *batch_source == source)
+        {
+            Some((_, singles)) => singles.push(single),
+            None => by_source.push((source, vec![single])),
+        }
+    }
+
+    for (source, singles) in by_source {
+        let instructions: Vec<Vec<Instruction>> = singles
+            .iter()
+            .map(|single| single.instructions.clone())
+            .collect();
+        for run in pack_transfers(&source, None, &instructions) {
+            let singles = &singles[run];
+            transactions.push(PlannedTransaction {
+                source,
+                destinations: singles
+                    .iter()
+                    .flat_map(|single| single.destinations.iter().copied())
+                    .collect(),
+                amount: singles.iter().map(|single| single.amount).sum(),
+                instructions: singles
+                    .iter()
+                    .flat_map(|single| single.instructions.iter().cloned())
+                    .collect(),
+            });
+        }
+    }
+    Ok(transactions)
+}
+
+/// Expected outcome of one transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulatedTransaction {
+    pub source: String,
+    pub destinations: Vec<String>,
+    pub amount: u64,
+    /// Slot of the bank the transaction was simulated against
+    pub slot: u64,
+    /// `success`, or the class of the error the transaction would fail with
+    pub outcome: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Base fee of the message; priority fees come on top
+    pub fee_lamports: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub units_consumed: Option<u64>,
+    pub logs: Vec<String>,
+}
+
+impl SimulatedTransaction {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Expected outcomes of every transaction of a run
+#[derive(Debug, Serialize)]
+pub struct BatchSimulation {
+    pub commitment: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_slot: Option<u64>,
+    pub transactions: Vec<SimulatedTransaction>,
+}
+
+/// Run every transaction through `simulateTransaction` against the bank at `commitment`,
+/// which must have reached `min_slot` when given. Transactions are simulated unsigned and
+/// independently of one another, so nothing is sent and no keys are needed.
+pub fn run(
+    client: &RpcClient,
+    transactions: &[PlannedTransaction],
+    commitment: CommitmentLevel,
+    min_slot: Option<u64>,
+) -> Result<BatchSimulation> {
+    let blockhash = client
+        .get_latest_blockhash()
+        .context("Failed to get recent blockhash")?;
+
+    let mut simulated = Vec::with_capacity(transactions.len());
+    for transaction in transactions {
+        let message = Message::new_with_blockhash(
+            &transaction.instructions,
+            Some(&transaction.source),
+            &blockhash,
+        );
+        let fee_lamports = client
+            .get_fee_for_message(&message)
+            .context("Failed to get fee for message")?;
+        let response = client
+            .simulate_transaction_with_config(
+                &Transaction::new_unsigned(message),
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(CommitmentConfig { commitment }),
+                    min_context_slot: min_slot,
+                    ..RpcSimulateTransactionConfig::default()
+                },
+            )
+            .context("Failed to simulate transaction")?;
+
+        let result = response.value;
+        simulated.push(SimulatedTransaction {
+            source: transaction.source.to_string(),
+            destinations: transaction
+                .destinations
+                .iter()
+                .map(Pubkey::to_string)
+                .collect(),
+            amount: transaction.amount,
+            slot: response.context.slot,
+            outcome: result.err.as_ref().map_or("success", |error| {
+                FailureClass::from_transaction_error(error).as_str()
+            }),
+            error: result.err.map(|error| error.to_string()),
+            fee_lamports,
+            units_consumed: result.units_consumed,
+            logs: result.logs.unwrap_or_default(),
+        });
+    }
+
+    Ok(BatchSimulation {
+        commitment: format!("{:?}", commitment).to_lowercase(),
+        min_slot,
+        transactions: simulated,
+    })
+}
+
+impl BatchSimulation {
+    /// Transactions that would fail
+    pub fn failures(&self) -> usize {
+        self.transactions
+            .iter()
+            .filter(|transaction| !transaction.succeeded())
+            .count()
+    }
+
+    /// Amount moved by the transactions that would succeed
+    pub fn total_amount(&self) -> u64 {
+        self.transactions
+            .iter()
+            .filter(|transaction| transaction.succeeded())
+            .map(|transaction| transaction.amount)
+            .sum()
+    }
+
+    /// Base fees of every transaction; a failed transaction that lands still pays its fee
+    pub fn total_fees(&self) -> u64 {
+        self.transactions
+            .iter()
+            .map(|transaction| transaction.fee_lamports)
+            .sum()
+    }
+
+    /// Write the simulation as JSON, or for a `.csv` path as one row per transaction
+    /// (destinations separated by spaces), then a blank line and `metric,value` rows
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let csv = match extension.as_deref() {
+            Some("csv") => true,
+            Some("json") => false,
+            _ => bail!("Report {} must end in .csv or .json", path.display()),
+        };
+
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create report {}", path.display()))?;
+        let mut file = BufWriter::new(file);
+        if !csv {
+            serde_json::to_writer_pretty(&mut file, self)?;
+            writeln!(file)?;
+            return file.flush().context("Failed to write report");
+        }
+
+        let mut writer = csv::Writer::from_writer(&mut file);
+        writer.write_record([
+            "source",
+            "destinations",
+            "amount",
+            "slot",
+            "outcome",
+            "error",
+            "fee_lamports",
+            "units_consumed",
+            "logs",
+        ])?;
+        for transaction in &self.transactions {
+            writer.write_record([
+                transaction.source.clone(),
+                transaction.destinations.join(" "),
+                transaction.amount.to_string(),
+                transaction.slot.to_string(),
+                transaction.outcome.to_string(),
+                transaction.error.clone().unwrap_or_default(),
+                transaction.fee_lamports.to_string(),
+                transaction
+                    .units_consumed
+                    .map_or(String::new(), |units| units.to_string()),
+                transaction.logs.join("\n"),
+            ])?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        // A bare empty line; an empty record would be written as `""`
+        writeln!(file)?;
+        let mut writer = csv::Writer::from_writer(&mut file);
+        writer.write_record(["metric", "value"])?;
+        writer.write_record(["commitment", &self.commitment])?;
+        if let Some(min_slot) = self.min_slot {
+            writer.write_record(["min_slot", &min_slot.to_string()])?;
+        }
+        writer.write_record(["transactions", &self.transactions.len().to_string()])?;
+        writer.write_record(["failures", &self.failures().to_string()])?;
+        writer.write_record(["total_amount", &self.total_amount().to_string()])?;
+        writer.write_record(["total_fees_lamports", &self.total_fees().to_string()])?;
+        writer.flush()?;
+        drop(writer);
+
+        file.flush().context("Failed to write report")
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::BorshDeserialize;
+use serde::Serialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_request::RpcRequest,
+    rpc_response::{OptionalContext, RpcKeyedAccount},
+};
+use solana_deposit_withdraw::{
+    config_address, stats_address, DepositAccount, ProgramConfig, ProgramStats, PullAuthorization,
+};
+use solana_sdk::{
+    account::Account,
+    hash::{hash, Hash},
+    pubkey::Pubkey,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Decoded state of one task4 account
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AccountState {
+    Deposit {
+        owner: String,
+        balance: u64,
+        deposit_count: u64,
+        withdraw_count: u64,
+        last_activity_ts: i64,
+        recovery: Option<String>,
+        recovery_after_slots: u64,
+        last_owner_slot: u64,
+    },
+    Config {
+        admin: String,
+        min_deposit: u64,
+        min_withdraw: u64,
+    },
+    Stats {
+        total_deposited: u64,
+        total_withdrawn: u64,
+        active_accounts: u64,
+    },
+    Pull {
+        deposit_account: String,
+        payee: String,
+        remaining: u64,
+        expiry: i64,
+    },
+    /// Empty account marking a voucher as redeemed
+    Voucher,
+    /// Data of a size or layout no task4 state has, kept as base64
+    Unknown { data: String },
+}
+
+impl AccountState {
+    fn kind(&self) -> &'static str {
+        match self {
+            AccountState::Deposit { .. } => "deposit",
+            AccountState::Config { .. } => "config",
+            AccountState::Stats { .. } => "stats",
+            AccountState::Pull { .. } => "pull",
+            AccountState::Voucher => "voucher",
+            AccountState::Unknown { .. } => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotAccount {
+    pub address: String,
+    pub lamports: u64,
+    #[serde(flatten)]
+    pub state: AccountState,
+}
+
+/// Every account owned by a task4 deployment, sorted by address
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub program_id: String,
+    /// Slot the node served the accounts at
+    pub slot: u64,
+    /// Hash of the canonical JSON of the program id and accounts; the slot is left out so
+    /// snapshots of unchanged state hash the same
+    pub hash: String,
+    pub accounts: Vec<SnapshotAccount>,
+}
+
+/// The hashed part of a snapshot, serialized in a fixed field order
+#[derive(Serialize)]
+struct HashedFields<'a> {
+    program_id: &'a str,
+    accounts: &'a [SnapshotAccount],
+}
+
+fn compute_hash(program_id: &str, accounts: &[SnapshotAccount]) -> Hash {
+    let fields = HashedFields {
+        program_id,
+        accounts,
+    };
+    hash(&serde_json::to_vec(&fields).expect("snapshot accounts serialize"))
+}
+
+/// Decode `account` by its address (config and stats PDAs) or data size (an empty one being a
+/// redeemed voucher)
+fn decode(program_id: &Pubkey, address: &Pubkey, account: &Account) -> AccountState {
+    let data = account.data.as_slice();
+    let unknown = || AccountState::Unknown {
+        data: STANDARD.encode(data),
+    };
+
+    if *address == config_address(program_id).0 {
+        return match ProgramConfig::try_from_slice(data) {
+            Ok(config) => AccountState::Config {
+                admin: config.admin.to_string(),
+                min_deposit: config.min_deposit,
+                min_withdraw: config.min_withdraw,
+            },
+            Err(_) => unknown(),
+        };
+    }
+    if *address == stats_address(program_id).0 {
+        return match ProgramStats::unpack(data) {
+            Ok(stats) => AccountState::Stats {
+                total_deposited: stats.total_deposited,
+                total_withdrawn: stats.total_withdrawn,
+                active_accounts: stats.active_accounts,
+            },
+            Err(_) => unknown(),
+        };
+    }
+
+    match data.len() {
+        DepositAccount::LEN | DepositAccount::LEGACY_LEN => match DepositAccount::unpack(data) {
+            Ok(deposit) => AccountState::Deposit {
+                owner: deposit.owner.to_string(),
+                balance: deposit.balance,
+                deposit_count: deposit.deposit_count,
+                withdraw_count: deposit.withdraw_count,
+                last_activity_ts: deposit.last_activity_ts,
+                recovery: deposit.recovery_key().map(|key| key.to_string()),
+                recovery_after_slots: deposit.recovery_after_slots,
+                last_owner_slot: deposit.last_owner_slot,
+            },
+            Err(_) => unknown(),
+        },
+        PullAuthorization::LEN => match PullAuthorization::try_from_slice(data) {
+            Ok(pull) => AccountState::Pull {
+                deposit_account: pull.deposit_account.to_string(),
+                payee: pull.payee.to_string(),
+                remaining: pull.remaining,
+                expiry: pull.expiry,
+            },
+            Err(_) => unknown(),
+        },
+        0 => AccountState::Voucher,
+        _ => unknown(),
+    }
+}
+
+/// Read every account owned by `program_id` in a single `getProgramAccounts` call, so they
+/// all reflect the same slot, and decode them. With `min_slot`, the node must have reached it.
+pub fn take(client: &RpcClient, program_id: &Pubkey, min_slot: Option<u64>) -> Result<Snapshot> {
+    let config = RpcProgramAccountsConfig {
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            commitment: Some(client.commitment()),
+            min_context_slot: min_slot,
+            ..RpcAccountInfoConfig::default()
+        },
+        with_context: Some(true),
+        ..RpcProgramAccountsConfig::default()
+    };
+    let response: OptionalContext<Vec<RpcKeyedAccount>> = client
+        .send(
+            RpcRequest::GetProgramAccounts,
+            serde_json::json!([program_id.to_string(), config]),
+        )
+        .context("Failed to list the program's accounts")?;
+    let OptionalContext::Context(response) = response else {
+        bail!("The node did not report the slot of the program's accounts");
+    };
+
+    let mut accounts = Vec::with_capacity(response.value.len());
+    for keyed in response.value {
+        let address: Pubkey = keyed
+            .pubkey
+            .parse()
+            .with_context(|| format!("Invalid account address {}", keyed.pubkey))?;
+        let account: Account = keyed
+            .account
+            .decode()
+            .ok_or_else(|| anyhow!("Failed to decode account {}", address))?;
+        accounts.push(SnapshotAccount {
+            address: address.to_string(),
+            lamports: account.lamports,
+            state: decode(program_id, &address, &account),
+        });
+    }
+    accounts.sort_by(|a, b| a.address.cmp(&b.address));
+
+    let program_id = program_id.to_string();
+    Ok(Snapshot {
+        hash: compute_hash(&program_id, &accounts).to_string(),
+        program_id,
+        slot: response.context.slot,
+        accounts,
+    })
+}
+
+impl Snapshot {
+    /// Accounts of each kind, in a fixed order
+    pub fn counts(&self) -> Vec<(&'static str, usize)> {
+        ["deposit", "pull", "voucher", "config", "stats", "unknown"]
+            .into_iter()
+            .map(|kind| {
+                let count = self
+                    .accounts
+                    .iter()
+                    .filter(|account| account.state.kind() == kind)
+                    .count();
+                (kind, count)
+            })
+            .collect()
+    }
+
+    /// Sum of the recorded balances of all deposit accounts
+    pub fn deposit_balance(&self) -> u64 {
+        self.accounts
+            .iter()
+            .map(|account| match account.state {
+                AccountState::Deposit { balance, .. } => balance,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Write the snapshot as JSON, or for a `.csv` path as one `address,kind,lamports,state`
+    /// row per account (`state` holding the decoded fields as JSON), then a blank line and
+    /// `metric,value` rows with the program id, slot and hash
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        let csv = match extension.as_deref() {
+            Some("csv") => true,
+            Some("json") => false,
+            _ => bail!("Snapshot {} must end in .csv or .json", path.display()),
+        };
+
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create snapshot {}", path.display()))?;
+        let mut file = BufWriter::new(file);
+        if !csv {
+            serde_json::to_writer_pretty(&mut file, self)?;
+            writeln!(file)?;
+            return file.flush().context("Failed to write snapshot");
+        }
+
+        let mut writer = csv::Writer::from_writer(&mut file);
+        writer.write_record(["address", "kind", "lamports", "state"])?;
+        for account in &self.accounts {
+            let mut state = serde_json::to_value(&account.state)?;
+            if let Some(fields) = state.as_object_mut() {
+                fields.remove("kind");
+            }
+            writer.write_record([
+                account.address.clone(),
+                account.state.kind().to_string(),
+                account.lamports.to_string(),
+                state.to_string(),
+            ])?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        // A bare empty line; an empty record would be written as `""`
+        writeln!(file)?;
+        let mut writer = csv::Writer::from_writer(&mut file);
+        writer.write_record(["metric", "value"])?;
+        writer.write_record(["program_id", &self.program_id])?;
+        writer.write_record(["slot", &self.slot.to_string()])?;
+        writer.write_record(["hash", &self.hash])?;
+        writer.write_record(["accounts", &self.accounts.len().to_string()])?;
+        writer.flush()?;
+        drop(writer);
+
+        file.flush().context("Failed to write snapshot")
+    }
+}
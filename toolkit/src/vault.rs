@@ -0,0 +1,214 @@
+use aes_gcm_siv::{
+    aead::{Aead, NewAead},
+    Aes256GcmSiv, Key, Nonce,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::Hmac;
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Environment variable holding the vault passphrase unless `--passphrase-env` names another
+pub const PASSPHRASE_ENV: &str = "TOOLKIT_VAULT_PASSPHRASE";
+
+/// PBKDF2-HMAC-SHA256 rounds deriving the encryption key of a new vault
+const ROUNDS: u32 = 600_000;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// On-disk form of a vault: the secret keys are only ever written encrypted
+#[derive(Debug, Serialize, Deserialize)]
+struct VaultFile {
+    /// PBKDF2-HMAC-SHA256 rounds deriving the AES-256-GCM-SIV key from the passphrase
+    rounds: u32,
+    salt: String,
+    nonce: String,
+    /// JSON map of address to secret key bytes, encrypted
+    ciphertext: String,
+}
+
+/// Passphrase-encrypted store of keypairs by address
+pub struct Vault {
+    path: PathBuf,
+    rounds: u32,
+    salt: [u8; SALT_LEN],
+    cipher: Aes256GcmSiv,
+    keys: BTreeMap<String, Vec<u8>>,
+}
+
+impl Vault {
+    /// Decrypt the vault at `path`, or start an empty one there if the file does not exist
+    pub fn open_or_create(path: &Path, passphrase: &str) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::create(path, passphrase, ROUNDS));
+        }
+
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read vault {}", path.display()))?;
+        let file: VaultFile = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid vault {}", path.display()))?;
+        let salt: [u8; SALT_LEN] = decode(&file.salt, "salt")?;
+        let nonce: [u8; NONCE_LEN] = decode(&file.nonce, "nonce")?;
+        let ciphertext = STANDARD
+            .decode(&file.ciphertext)
+            .context("Invalid vault ciphertext")?;
+
+        let cipher = derive_cipher(passphrase, &salt, file.rounds);
+        let plaintext = cipher
+            .decrypt(&Nonce::from(nonce), ciphertext.as_ref())
+            .map_err(|_| anyhow!("Wrong passphrase for vault {}", path.display()))?;
+        let keys = serde_json::from_slice(&plaintext).context("Invalid vault contents")?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            rounds: file.rounds,
+            salt,
+            cipher,
+            keys,
+        })
+    }
+
+    fn create(path: &Path, passphrase: &str, rounds: u32) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            path: path.to_path_buf(),
+            rounds,
+            salt,
+            cipher: derive_cipher(passphrase, &salt, rounds),
+            keys: BTreeMap::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add `keypair`, replacing any key stored under its address; call `save` to persist it
+    pub fn insert(&mut self, keypair: &Keypair) {
+        self.keys
+            .insert(keypair.pubkey().to_string(), keypair.to_bytes().to_vec());
+    }
+
+    pub fn keypair(&self, address: &Pubkey) -> Result<Keypair> {
+        let bytes = self
+            .keys
+            .get(&address.to_string())
+            .with_context(|| format!("No key for {} in the vault", address))?;
+        Keypair::from_bytes(bytes).map_err(|e| anyhow!("Invalid key for {}: {}", address, e))
+    }
+
+    /// Encrypt the keys under a fresh nonce and replace the vault file with them
+    pub fn save(&self) -> Result<()> {
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let plaintext = serde_json::to_vec(&self.keys).context("Failed to serialize vault")?;
+        let ciphertext = self
+            .cipher
+            .encrypt(&Nonce::from(nonce), plaintext.as_ref())
+            .map_err(|_| anyhow!("Failed to encrypt vault"))?;
+
+        let file = VaultFile {
+            rounds: self.rounds,
+            salt: STANDARD.encode(self.salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+
+        // Write next to the vault and rename over it, so a crash never leaves it half-written
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).context("Failed to create vault directory")?;
+        }
+        let contents = serde_json::to_string_pretty(&file).context("Failed to serialize vault")?;
+        let temp = self.path.with_extension("tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&temp)
+            .and_then(|mut out| out.write_all(contents.as_bytes()))
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, &self.path)
+            .with_context(|| format!("Failed to replace vault {}", self.path.display()))
+    }
+}
+
+/// The passphrase from the environment variable `var`
+pub fn passphrase(var: &str) -> Result<String> {
+    match std::env::var(var) {
+        Ok(passphrase) if !passphrase.is_empty() => Ok(passphrase),
+        _ => bail!("Set {} to the vault passphrase", var),
+    }
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8], rounds: u32) -> Aes256GcmSiv {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, rounds, &mut key);
+    Aes256GcmSiv::new(&Key::from(key))
+}
+
+fn decode<const N: usize>(value: &str, field: &str) -> Result<[u8; N]> {
+    STANDARD
+        .decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .with_context(|| format!("Invalid vault {}", field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_vault(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("toolkit-vault-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn keys_round_trip_encrypted() {
+        let path = temp_vault("round-trip.vault");
+        let keypair = Keypair::new();
+        let mut vault = Vault::create(&path, "correct horse", 1_000);
+        vault.insert(&keypair);
+        vault.save().unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(&keypair.pubkey().to_string()));
+        assert!(!contents.contains(&bs58::encode(keypair.to_bytes()).into_string()));
+
+        let reopened = Vault::open_or_create(&path, "correct horse").unwrap();
+        assert_eq!(reopened.rounds, 1_000);
+        let stored = reopened.keypair(&keypair.pubkey()).unwrap();
+        assert_eq!(stored.to_bytes(), keypair.to_bytes());
+        assert!(reopened.keypair(&Pubkey::new_unique()).is_err());
+    }
+
+    #[test]
+    fn a_wrong_passphrase_is_refused() {
+        let path = temp_vault("wrong-passphrase.vault");
+        let mut vault = Vault::create(&path, "correct horse", 1_000);
+        vault.insert(&Keypair::new());
+        vault.save().unwrap();
+
+        let error = Vault::open_or_create(&path, "battery staple")
+            .err()
+            .unwrap();
+        assert!(error.to_string().starts_with("Wrong passphrase"));
+    }
+}